@@ -1,8 +1,8 @@
-use actix_web::{get, web, App, HttpResponse, HttpServer};
+use actix_web::{get, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer};
 use openlibx402_actix::{
-    create_payment_request, payment_required_response, PaymentExtractor, PaymentRequirement,
-    X402Config, X402State,
+    PaymentExtractor, PaymentRequirement, RequirePayment, X402Config, X402Protection, X402State,
 };
+use openlibx402_core::PaymentAuthorization;
 use serde::{Deserialize, Serialize};
 
 /// Example data structure for premium content
@@ -29,94 +29,65 @@ async fn index() -> HttpResponse {
 
 /// Basic tier endpoint - requires $0.01 payment
 #[get("/basic")]
-async fn basic_tier(
-    state: web::Data<X402State>,
-    auth: Option<PaymentExtractor>,
-) -> HttpResponse {
-    match auth {
-        Some(_) => {
-            // Payment verified
-            HttpResponse::Ok().json(PremiumData {
-                message: "Access granted to basic tier".to_string(),
-                data: vec![
-                    "Basic data point 1".to_string(),
-                    "Basic data point 2".to_string(),
-                ],
-                tier: "basic".to_string(),
-            })
-        }
-        None => {
-            // No payment, return 402
-            let requirement =
-                PaymentRequirement::new("0.01").with_description("Access to basic tier data");
-            let payment_request = create_payment_request(&state.config, &requirement, "/basic");
-            payment_required_response(payment_request)
-        }
-    }
+async fn basic_tier(_auth: RequirePayment<1>) -> HttpResponse {
+    // Only reached once the payment is verified; unpaid requests get a 402
+    HttpResponse::Ok().json(PremiumData {
+        message: "Access granted to basic tier".to_string(),
+        data: vec![
+            "Basic data point 1".to_string(),
+            "Basic data point 2".to_string(),
+        ],
+        tier: "basic".to_string(),
+    })
 }
 
 /// Premium tier endpoint - requires $0.10 payment
-#[get("/premium")]
-async fn premium_tier(
-    state: web::Data<X402State>,
-    auth: Option<PaymentExtractor>,
-) -> HttpResponse {
-    match auth {
-        Some(_) => {
-            // Payment verified
-            HttpResponse::Ok().json(PremiumData {
-                message: "Access granted to premium tier".to_string(),
-                data: vec![
-                    "Premium insight 1".to_string(),
-                    "Premium insight 2".to_string(),
-                    "Premium insight 3".to_string(),
-                    "Exclusive data point".to_string(),
-                ],
-                tier: "premium".to_string(),
-            })
-        }
-        None => {
-            // No payment, return 402
-            let requirement = PaymentRequirement::new("0.10")
-                .with_description("Access to premium tier data")
-                .with_expires_in(600);
-            let payment_request = create_payment_request(&state.config, &requirement, "/premium");
-            payment_required_response(payment_request)
-        }
-    }
+///
+/// Priced by the `PaymentRequirement` registered for the route in `main`.
+async fn premium_tier(_auth: PaymentExtractor) -> HttpResponse {
+    HttpResponse::Ok().json(PremiumData {
+        message: "Access granted to premium tier".to_string(),
+        data: vec![
+            "Premium insight 1".to_string(),
+            "Premium insight 2".to_string(),
+            "Premium insight 3".to_string(),
+            "Exclusive data point".to_string(),
+        ],
+        tier: "premium".to_string(),
+    })
 }
 
 /// Enterprise tier endpoint - requires $1.00 payment
 #[get("/enterprise")]
-async fn enterprise_tier(
-    state: web::Data<X402State>,
-    auth: Option<PaymentExtractor>,
-) -> HttpResponse {
-    match auth {
-        Some(_) => {
-            // Payment verified
-            HttpResponse::Ok().json(PremiumData {
-                message: "Access granted to enterprise tier".to_string(),
-                data: vec![
-                    "Enterprise analytics 1".to_string(),
-                    "Enterprise analytics 2".to_string(),
-                    "Enterprise analytics 3".to_string(),
-                    "Enterprise analytics 4".to_string(),
-                    "Confidential market data".to_string(),
-                    "Advanced predictions".to_string(),
-                ],
-                tier: "enterprise".to_string(),
-            })
-        }
-        None => {
-            // No payment, return 402
-            let requirement = PaymentRequirement::new("1.00")
-                .with_description("Access to enterprise tier data and analytics");
-            let payment_request =
-                create_payment_request(&state.config, &requirement, "/enterprise");
-            payment_required_response(payment_request)
-        }
-    }
+async fn enterprise_tier(_auth: RequirePayment<100>) -> HttpResponse {
+    HttpResponse::Ok().json(PremiumData {
+        message: "Access granted to enterprise tier".to_string(),
+        data: vec![
+            "Enterprise analytics 1".to_string(),
+            "Enterprise analytics 2".to_string(),
+            "Enterprise analytics 3".to_string(),
+            "Enterprise analytics 4".to_string(),
+            "Confidential market data".to_string(),
+            "Advanced predictions".to_string(),
+        ],
+        tier: "enterprise".to_string(),
+    })
+}
+
+/// API endpoint - paid through the middleware protecting the `/api` scope
+async fn api_data(req: HttpRequest) -> HttpResponse {
+    // The middleware only lets verified payments through
+    let payer = req
+        .extensions()
+        .get::<PaymentAuthorization>()
+        .map(|authorization| authorization.public_key.clone())
+        .unwrap_or_default();
+
+    HttpResponse::Ok().json(PremiumData {
+        message: format!("API access paid by {}", payer),
+        data: vec![format!("Data for {}", req.path())],
+        tier: "api".to_string(),
+    })
 }
 
 /// Health check endpoint
@@ -135,6 +106,7 @@ async fn main() -> std::io::Result<()> {
         network: "solana-devnet".to_string(),
         rpc_url: None,
         auto_verify: true,
+        ..Default::default()
     };
 
     let state = web::Data::new(X402State {
@@ -151,15 +123,38 @@ async fn main() -> std::io::Result<()> {
     println!("  - GET  /basic     : Basic tier ($0.01)");
     println!("  - GET  /premium   : Premium tier ($0.10)");
     println!("  - GET  /enterprise: Enterprise tier ($1.00)");
+    println!("  - GET  /api/*     : Every API route ($0.05, via middleware)");
     println!("  - GET  /health    : Health check");
     println!("\nServer running at http://127.0.0.1:8080\n");
 
     HttpServer::new(move || {
+        // Charge one price for the whole `/api` scope
+        let protection = X402Protection::scope(
+            PaymentRequirement::new("0.05").with_description("Access to the data API"),
+        )
+        .config(config.clone())
+        .build()
+        .expect("valid X402 configuration");
+
         App::new()
             .app_data(state.clone())
+            .service(
+                web::scope("/api")
+                    .wrap(protection)
+                    .route("/quotes", web::get().to(api_data))
+                    .route("/news", web::get().to(api_data)),
+            )
             .service(index)
             .service(basic_tier)
-            .service(premium_tier)
+            .service(
+                web::resource("/premium")
+                    .app_data(web::Data::new(
+                        PaymentRequirement::new("0.10")
+                            .with_description("Access to premium tier data")
+                            .with_expires_in(600),
+                    ))
+                    .route(web::get().to(premium_tier)),
+            )
             .service(enterprise_tier)
             .service(health)
     })
@@ -0,0 +1,132 @@
+use axum::{routing::get, Extension, Json, Router};
+use openlibx402_axum::{
+    PaymentExtractor, PaymentRequirement, RequirePayment, X402Config, X402State,
+};
+use openlibx402_core::{MemoryStore, NonceStore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Example data structure for premium content
+#[derive(Debug, Serialize, Deserialize)]
+struct PremiumData {
+    message: String,
+    data: Vec<String>,
+    tier: String,
+}
+
+/// Example data structure for basic content
+#[derive(Debug, Serialize, Deserialize)]
+struct BasicData {
+    message: String,
+}
+
+/// Free endpoint - no payment required
+async fn index() -> Json<BasicData> {
+    Json(BasicData {
+        message: "Welcome to the X402 Axum example server!".to_string(),
+    })
+}
+
+/// Basic tier endpoint - requires $0.01 payment
+async fn basic_tier(_auth: RequirePayment<1>) -> Json<PremiumData> {
+    // Only reached once the payment is verified; unpaid requests get a 402
+    Json(PremiumData {
+        message: "Access granted to basic tier".to_string(),
+        data: vec![
+            "Basic data point 1".to_string(),
+            "Basic data point 2".to_string(),
+        ],
+        tier: "basic".to_string(),
+    })
+}
+
+/// Premium tier endpoint - requires $0.10 payment
+///
+/// Priced by the `PaymentRequirement` attached to the route in `main`.
+async fn premium_tier(_auth: PaymentExtractor) -> Json<PremiumData> {
+    Json(PremiumData {
+        message: "Access granted to premium tier".to_string(),
+        data: vec![
+            "Premium insight 1".to_string(),
+            "Premium insight 2".to_string(),
+            "Premium insight 3".to_string(),
+            "Exclusive data point".to_string(),
+        ],
+        tier: "premium".to_string(),
+    })
+}
+
+/// Enterprise tier endpoint - requires $1.00 payment
+async fn enterprise_tier(auth: RequirePayment<100>) -> Json<PremiumData> {
+    Json(PremiumData {
+        message: format!(
+            "Access granted to enterprise tier for {}",
+            auth.authorization.public_key
+        ),
+        data: vec![
+            "Enterprise analytics 1".to_string(),
+            "Enterprise analytics 2".to_string(),
+            "Enterprise analytics 3".to_string(),
+            "Enterprise analytics 4".to_string(),
+            "Confidential market data".to_string(),
+            "Advanced predictions".to_string(),
+        ],
+        tier: "enterprise".to_string(),
+    })
+}
+
+/// Health check endpoint
+async fn health() -> &'static str {
+    "OK"
+}
+
+#[tokio::main]
+async fn main() {
+    // Configure X402
+    // NOTE: Replace these with your actual Solana wallet addresses
+    let config = X402Config {
+        payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(), // USDC on Devnet
+        network: "solana-devnet".to_string(),
+        rpc_url: None,
+        auto_verify: true,
+        ..Default::default()
+    };
+
+    // Reject payments presented twice
+    let nonces: Arc<dyn NonceStore> = Arc::new(MemoryStore::new());
+    let state = X402State::new(config.clone()).with_nonce_store(nonces);
+
+    println!("\n🌐 Starting Axum X402 Example Server");
+    println!("====================================");
+    println!("Payment Address: {}", config.payment_address);
+    println!("Token Mint: {}", config.token_mint);
+    println!("Network: {}", config.network);
+    println!("\nEndpoints:");
+    println!("  - GET  /          : Free endpoint (no payment)");
+    println!("  - GET  /basic     : Basic tier ($0.01)");
+    println!("  - GET  /premium   : Premium tier ($0.10)");
+    println!("  - GET  /enterprise: Enterprise tier ($1.00)");
+    println!("  - GET  /health    : Health check");
+    println!("\nServer running at http://127.0.0.1:8080\n");
+
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/basic", get(basic_tier))
+        .route(
+            "/premium",
+            get(premium_tier).layer(Extension(
+                PaymentRequirement::new("0.10")
+                    .with_description("Access to premium tier data")
+                    .with_expires_in(600),
+            )),
+        )
+        .route("/enterprise", get(enterprise_tier))
+        .route("/health", get(health))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:8080")
+        .await
+        .expect("port 8080 is free");
+    axum::serve(listener, app).await.expect("server runs");
+}
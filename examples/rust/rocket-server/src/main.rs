@@ -1,7 +1,5 @@
-use openlibx402_rocket::{
-    create_payment_request, PaymentGuard, PaymentRequirement, PaymentRequiredResponse, X402Config,
-};
-use rocket::{get, routes, serde::json::Json, State};
+use openlibx402_rocket::{PaymentGuard, PaymentRequirement, PricingTable, X402Config, X402Fairing};
+use rocket::{get, routes, serde::json::Json};
 use serde::{Deserialize, Serialize};
 
 /// Example data structure for premium content
@@ -27,85 +25,51 @@ fn index() -> Json<BasicData> {
 }
 
 /// Basic tier endpoint - requires $0.01 payment
+///
+/// Unpaid requests never reach the handler: `X402Fairing` answers them with a
+/// 402 carrying the payment request for the route's price.
 #[get("/basic")]
-fn basic_tier(config: &State<X402Config>, _auth: Option<PaymentGuard>) -> Result<Json<PremiumData>, PaymentRequiredResponse> {
-    match _auth {
-        Some(_) => {
-            // Payment verified
-            Ok(Json(PremiumData {
-                message: "Access granted to basic tier".to_string(),
-                data: vec![
-                    "Basic data point 1".to_string(),
-                    "Basic data point 2".to_string(),
-                ],
-                tier: "basic".to_string(),
-            }))
-        }
-        None => {
-            // No payment, return 402
-            let requirement = PaymentRequirement::new("0.01")
-                .with_description("Access to basic tier data");
-            let payment_request = create_payment_request(config, &requirement, "/basic");
-            Err(PaymentRequiredResponse { payment_request })
-        }
-    }
+fn basic_tier(_auth: PaymentGuard) -> Json<PremiumData> {
+    Json(PremiumData {
+        message: "Access granted to basic tier".to_string(),
+        data: vec![
+            "Basic data point 1".to_string(),
+            "Basic data point 2".to_string(),
+        ],
+        tier: "basic".to_string(),
+    })
 }
 
 /// Premium tier endpoint - requires $0.10 payment
 #[get("/premium")]
-fn premium_tier(config: &State<X402Config>, _auth: Option<PaymentGuard>) -> Result<Json<PremiumData>, PaymentRequiredResponse> {
-    match _auth {
-        Some(_) => {
-            // Payment verified
-            Ok(Json(PremiumData {
-                message: "Access granted to premium tier".to_string(),
-                data: vec![
-                    "Premium insight 1".to_string(),
-                    "Premium insight 2".to_string(),
-                    "Premium insight 3".to_string(),
-                    "Exclusive data point".to_string(),
-                ],
-                tier: "premium".to_string(),
-            }))
-        }
-        None => {
-            // No payment, return 402
-            let requirement = PaymentRequirement::new("0.10")
-                .with_description("Access to premium tier data")
-                .with_expires_in(600);
-            let payment_request = create_payment_request(config, &requirement, "/premium");
-            Err(PaymentRequiredResponse { payment_request })
-        }
-    }
+fn premium_tier(_auth: PaymentGuard) -> Json<PremiumData> {
+    Json(PremiumData {
+        message: "Access granted to premium tier".to_string(),
+        data: vec![
+            "Premium insight 1".to_string(),
+            "Premium insight 2".to_string(),
+            "Premium insight 3".to_string(),
+            "Exclusive data point".to_string(),
+        ],
+        tier: "premium".to_string(),
+    })
 }
 
 /// Enterprise tier endpoint - requires $1.00 payment
 #[get("/enterprise")]
-fn enterprise_tier(config: &State<X402Config>, _auth: Option<PaymentGuard>) -> Result<Json<PremiumData>, PaymentRequiredResponse> {
-    match _auth {
-        Some(_) => {
-            // Payment verified
-            Ok(Json(PremiumData {
-                message: "Access granted to enterprise tier".to_string(),
-                data: vec![
-                    "Enterprise analytics 1".to_string(),
-                    "Enterprise analytics 2".to_string(),
-                    "Enterprise analytics 3".to_string(),
-                    "Enterprise analytics 4".to_string(),
-                    "Confidential market data".to_string(),
-                    "Advanced predictions".to_string(),
-                ],
-                tier: "enterprise".to_string(),
-            }))
-        }
-        None => {
-            // No payment, return 402
-            let requirement = PaymentRequirement::new("1.00")
-                .with_description("Access to enterprise tier data and analytics");
-            let payment_request = create_payment_request(config, &requirement, "/enterprise");
-            Err(PaymentRequiredResponse { payment_request })
-        }
-    }
+fn enterprise_tier(_auth: PaymentGuard) -> Json<PremiumData> {
+    Json(PremiumData {
+        message: "Access granted to enterprise tier".to_string(),
+        data: vec![
+            "Enterprise analytics 1".to_string(),
+            "Enterprise analytics 2".to_string(),
+            "Enterprise analytics 3".to_string(),
+            "Enterprise analytics 4".to_string(),
+            "Confidential market data".to_string(),
+            "Advanced predictions".to_string(),
+        ],
+        tier: "enterprise".to_string(),
+    })
 }
 
 /// Health check endpoint
@@ -124,6 +88,7 @@ async fn main() -> Result<(), rocket::Error> {
         network: "solana-devnet".to_string(),
         rpc_url: None,
         auto_verify: true,
+        ..Default::default()
     };
 
     println!("\n🚀 Starting Rocket X402 Example Server");
@@ -138,8 +103,27 @@ async fn main() -> Result<(), rocket::Error> {
     println!("  - GET  /enterprise: Enterprise tier ($1.00)");
     println!("  - GET  /health    : Health check\n");
 
+    // Route prices, enforced before any handler runs
+    let pricing = PricingTable::new()
+        .route(
+            "/basic",
+            PaymentRequirement::new("0.01").with_description("Access to basic tier data"),
+        )
+        .route(
+            "/premium",
+            PaymentRequirement::new("0.10")
+                .with_description("Access to premium tier data")
+                .with_expires_in(600),
+        )
+        .route(
+            "/enterprise",
+            PaymentRequirement::new("1.00")
+                .with_description("Access to enterprise tier data and analytics"),
+        );
+
     let _rocket = rocket::build()
         .manage(config)
+        .attach(X402Fairing::new(pricing))
         .mount("/", routes![index, basic_tier, premium_tier, enterprise_tier, health])
         .launch()
         .await?;
@@ -26,6 +26,7 @@
 //!         network: "solana-devnet".to_string(),
 //!         rpc_url: None,
 //!         auto_verify: true,
+//!         ..Default::default()
 //!     };
 //!
 //!     HttpServer::new(move || {
@@ -40,13 +41,34 @@
 //! ```
 
 use actix_web::{
-    dev::Payload, error::ResponseError, http::StatusCode, Error, FromRequest, HttpRequest,
-    HttpResponse,
+    dev::Payload,
+    error::{InternalError, ResponseError},
+    http::{header, StatusCode},
+    web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
 };
 use chrono::{Duration, Utc};
-use openlibx402_core::{PaymentAuthorization, PaymentRequest};
-use std::future::{ready, Ready};
-use uuid::Uuid;
+use futures_util::future::LocalBoxFuture;
+use openlibx402_core::{
+    negotiation::PROBLEM_JSON_CONTENT_TYPE, ChallengeFormat, NonceStore, PassAuthorization,
+    PassIssuer, PaymentAuthorization, PaymentIdGenerator, PaymentProblem, PaymentRequest,
+    RentExemptionReport, Settlement, SettlementTracker, SolanaPaymentProcessor, UuidGenerator,
+    VerificationOptions, X402Error, X402Result, AUTHORIZATION_HEADER, DEFAULT_EXPIRES_IN,
+    PASS_HEADER, PAYMENT_REQUEST_HEADER,
+};
+use std::{
+    future::{ready, Ready},
+    sync::Arc,
+};
+
+pub mod middleware;
+pub mod pricing;
+pub mod protection;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_support;
+
+pub use middleware::{ResponseTransformer, X402Middleware};
+pub use pricing::PricingTable;
+pub use protection::{X402Protection, X402ProtectionBuilder, DEFAULT_REPLAY_WINDOW};
 
 /// Global X402 configuration
 #[derive(Debug, Clone)]
@@ -63,8 +85,103 @@ pub struct X402Config {
     /// Optional custom RPC URL
     pub rpc_url: Option<String>,
 
+    /// Commitment payments must reach to be accepted: `processed`,
+    /// `confirmed` or `finalized` (default: confirmed)
+    pub commitment: Option<String>,
+
     /// Whether to verify payments on-chain
     pub auto_verify: bool,
+
+    /// Accept payments up to this many basis points below the required amount (default: 0)
+    pub amount_tolerance_bps: u16,
+
+    /// Token symbol shown in the `X-Payment-Price` header (default: "USDC")
+    pub token_symbol: String,
+
+    /// Source of payment IDs for issued payment requests (default: UUIDv4)
+    pub payment_ids: Arc<dyn PaymentIdGenerator>,
+
+    /// Facilitator advertised to payers for settling payments (default: none)
+    pub facilitator_url: Option<String>,
+
+    /// Header payers send authorizations in (default: `X-Payment-Authorization`)
+    pub authorization_header: String,
+
+    /// Oldest authorization accepted, by its `timestamp` (default: the
+    /// requirement's `expires_in`)
+    pub max_authorization_age: Option<std::time::Duration>,
+}
+
+impl Default for X402Config {
+    fn default() -> Self {
+        Self {
+            payment_address: String::new(),
+            token_mint: String::new(),
+            network: "solana-devnet".to_string(),
+            rpc_url: None,
+            commitment: None,
+            auto_verify: true,
+            amount_tolerance_bps: 0,
+            token_symbol: "USDC".to_string(),
+            payment_ids: Arc::new(UuidGenerator),
+            facilitator_url: None,
+            authorization_header: AUTHORIZATION_HEADER.to_string(),
+            max_authorization_age: None,
+        }
+    }
+}
+
+impl X402Config {
+    /// Create a payment processor for the configured network, RPC URL and
+    /// commitment
+    ///
+    /// Fails with a configuration error if `commitment` isn't a known level.
+    pub fn payment_processor(&self) -> X402Result<SolanaPaymentProcessor> {
+        let rpc_url = self
+            .rpc_url
+            .as_deref()
+            .unwrap_or_else(|| SolanaPaymentProcessor::default_rpc_url(&self.network));
+        let commitment = self
+            .commitment
+            .as_deref()
+            .map(SolanaPaymentProcessor::parse_commitment)
+            .transpose()?;
+        Ok(SolanaPaymentProcessor::new(rpc_url, commitment))
+    }
+
+    /// Value of the `X-Payment-Price` header for `requirement`, e.g. `0.10 USDC`
+    pub fn advertised_price(&self, requirement: &PaymentRequirement) -> String {
+        format!("{} {}", requirement.amount, self.token_symbol)
+    }
+
+    /// Verification policy derived from this configuration
+    pub fn verification_options(&self) -> VerificationOptions {
+        VerificationOptions::default().with_amount_tolerance_bps(self.amount_tolerance_bps)
+    }
+
+    /// Oldest authorization accepted for `requirement`
+    ///
+    /// `max_authorization_age` if set, otherwise how long the requirement's
+    /// payment requests stay payable ([`DEFAULT_EXPIRES_IN`] without one).
+    pub fn authorization_age_limit(
+        &self,
+        requirement: Option<&PaymentRequirement>,
+    ) -> std::time::Duration {
+        self.max_authorization_age
+            .unwrap_or_else(|| requirement_ttl(requirement))
+    }
+
+    /// Check that the recipient wallet and its token account are rent-exempt
+    ///
+    /// Run this at startup, before binding the server, to catch recipients that
+    /// would make payments fail in production. With `strict` set a recipient
+    /// that isn't rent-exempt is an error; otherwise the problems are listed in
+    /// the report's `warnings()`.
+    pub async fn check_rent_exemption(&self, strict: bool) -> X402Result<RentExemptionReport> {
+        self.payment_processor()?
+            .ensure_rent_exemption(&self.payment_address, &self.token_mint, strict)
+            .await
+    }
 }
 
 /// Application state containing X402 configuration
@@ -84,6 +201,12 @@ pub struct PaymentRequirement {
 
     /// Expiration time in seconds (default: 300)
     pub expires_in: i64,
+
+    /// Answer retries of a paid request with the first response (default: false)
+    pub cache_result: bool,
+
+    /// Resources one payment of `amount` unlocks together (default: none)
+    pub bundle: Vec<String>,
 }
 
 impl PaymentRequirement {
@@ -93,6 +216,8 @@ impl PaymentRequirement {
             amount: amount.to_string(),
             description: None,
             expires_in: 300,
+            cache_result: false,
+            bundle: Vec::new(),
         }
     }
 
@@ -107,38 +232,328 @@ impl PaymentRequirement {
         self.expires_in = seconds;
         self
     }
+
+    /// Sell `resources` as a bundle: one payment of `amount` unlocks each once
+    ///
+    /// Register the bundle with [`PricingTable::bundle`]. A challenge for any
+    /// of the resources lists them all, and its payment is accepted once at
+    /// each of them until the challenge expires. Only applies when payments
+    /// are verified by [`X402Protection`].
+    pub fn with_bundle(mut self, resources: &[&str]) -> Self {
+        self.bundle = resources.iter().map(|r| r.to_string()).collect();
+        self
+    }
+
+    /// Serve a retry carrying an already used authorization from the first response
+    ///
+    /// For expensive handlers: a client that lost the response to a network
+    /// drop gets it again instead of a 409, and the handler doesn't run twice.
+    /// Only successful responses are kept, for the replay window, and only
+    /// when payments are verified by [`X402Protection`].
+    pub fn with_cached_result(mut self) -> Self {
+        self.cache_result = true;
+        self
+    }
 }
 
 /// Extractor that enforces payment requirements
+///
+/// Requests without an `X-Payment-Authorization` header are refused with a
+/// 402. The route's [`PaymentRequirement`] is read from its app data (as a
+/// `web::Data<PaymentRequirement>`), or else from the [`X402Middleware`]
+/// that priced the request. Next to a `web::Data<X402State>`, the 402
+/// carries a `PaymentRequest` for the request path, negotiated like
+/// [`payment_required_response_for`]:
+///
+/// ```rust,no_run
+/// use actix_web::{web, App, HttpResponse};
+/// use openlibx402_actix::{PaymentExtractor, PaymentRequirement};
+///
+/// async fn premium(auth: PaymentExtractor) -> HttpResponse {
+///     HttpResponse::Ok().body(auth.authorization.payment_id)
+/// }
+///
+/// let app = App::new().service(
+///     web::resource("/premium")
+///         .app_data(web::Data::new(PaymentRequirement::new("0.10")))
+///         .route(web::get().to(premium)),
+/// );
+/// ```
+///
+/// When the app data holds a `web::Data<X402State>` with `auto_verify` set,
+/// the authorization must pay the configured recipient and token, and its
+/// transfer is verified on-chain through the config's RPC endpoint (or a
+/// `web::Data<SolanaPaymentProcessor>`, if registered) before the handler
+/// runs. The transfer must cover the route's requirement, whose amount is
+/// exposed as `verified_amount`; a route without one is refused with a 500
+/// rather than trusting the amount the authorization claims. Failed
+/// verification is a 400.
+///
+/// Authorizations older than the config's
+/// [`authorization_age_limit`](X402Config::authorization_age_limit) are
+/// refused with a 402, before any verification, so a settled payment can't be
+/// presented again after its payment request has expired. Without a
+/// `web::Data<X402State>` the limit is the requirement's `expires_in`.
+///
+/// When the app data holds a `web::Data<dyn NonceStore>`, the signature of the
+/// transaction that paid (the authorization's `signature` without
+/// `auto_verify`) is recorded there for [`DEFAULT_REPLAY_WINDOW`] and a reused
+/// one is rejected with 409 Conflict, whatever `payment_id` it comes with.
+/// Register a shared store such as `RedisStore` to reject replays across server
+/// instances:
+///
+/// ```rust,no_run
+/// use actix_web::{web, App};
+/// use openlibx402_core::{MemoryStore, NonceStore};
+/// use std::sync::Arc;
+///
+/// let store: Arc<dyn NonceStore> = Arc::new(MemoryStore::new());
+/// let app = App::new().app_data(web::Data::from(store));
+/// ```
 pub struct PaymentExtractor {
     pub authorization: PaymentAuthorization,
+
+    /// Amount confirmed on-chain, or `None` if verification is off
+    pub verified_amount: Option<String>,
 }
 
 impl FromRequest for PaymentExtractor {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let requirement = req
+            .app_data::<web::Data<PaymentRequirement>>()
+            .map(|requirement| requirement.as_ref().clone())
+            .or_else(|| req.extensions().get::<PaymentRequirement>().cloned());
+        extract_payment(req, requirement)
+    }
+}
+
+/// Extractor for endpoints priced at `CENTS` hundredths of a token
+///
+/// Works like [`PaymentExtractor`] with a [`PaymentRequirement`] fixed in the
+/// handler's signature, so no route app data is needed: `RequirePayment<10>`
+/// charges 0.10. Unpaid requests get a 402 with a `PaymentRequest` for the
+/// request path, built from the `web::Data<X402State>` in the app data.
+///
+/// ```rust,no_run
+/// use actix_web::HttpResponse;
+/// use openlibx402_actix::RequirePayment;
+///
+/// async fn premium(auth: RequirePayment<10>) -> HttpResponse {
+///     HttpResponse::Ok().body(auth.authorization.payment_id)
+/// }
+/// ```
+pub struct RequirePayment<const CENTS: u64> {
+    pub authorization: PaymentAuthorization,
+
+    /// Amount confirmed on-chain, or `None` if verification is off
+    pub verified_amount: Option<String>,
+}
+
+impl<const CENTS: u64> RequirePayment<CENTS> {
+    /// Requirement the extractor charges
+    pub fn requirement() -> PaymentRequirement {
+        PaymentRequirement::new(&format!("{}.{:02}", CENTS / 100, CENTS % 100))
+    }
+}
+
+impl<const CENTS: u64> FromRequest for RequirePayment<CENTS> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let payment = extract_payment(req, Some(Self::requirement()));
+        Box::pin(async move {
+            let PaymentExtractor {
+                authorization,
+                verified_amount,
+            } = payment.await?;
+            Ok(RequirePayment {
+                authorization,
+                verified_amount,
+            })
+        })
+    }
+}
+
+/// Extract and check the payment on `req`, charging `requirement` if known
+fn extract_payment(
+    req: &HttpRequest,
+    requirement: Option<PaymentRequirement>,
+) -> LocalBoxFuture<'static, Result<PaymentExtractor, Error>> {
+    // Get payment authorization header
+    let header_name = req
+        .app_data::<web::Data<X402State>>()
+        .map_or(AUTHORIZATION_HEADER, |state| {
+            state.config.authorization_header.as_str()
+        });
+    let auth_header = match req.headers().get(header_name) {
+        Some(h) => match h.to_str() {
+            Ok(s) => s,
+            Err(_) => {
+                return Box::pin(ready(Err(PaymentError::InvalidHeader.into())));
+            }
+        },
+        None => {
+            return Box::pin(ready(Err(payment_required(req, requirement.as_ref()))));
+        }
+    };
+
+    // Parse authorization
+    let authorization = match PaymentAuthorization::from_header_value(auth_header) {
+        Ok(auth) => auth,
+        Err(e) => {
+            return Box::pin(ready(Err(PaymentError::InvalidAuthorization(
+                e.to_string(),
+            )
+            .into())))
+        }
+    };
+
+    let state = req.app_data::<web::Data<X402State>>();
+    let max_age = match state {
+        Some(state) => state.config.authorization_age_limit(requirement.as_ref()),
+        None => requirement_ttl(requirement.as_ref()),
+    };
+    if let Err(e) = authorization.check_age(max_age) {
+        return Box::pin(ready(Err(PaymentError::Expired(e.to_string()).into())));
+    }
+
+    let config = state
+        .map(|state| state.config.clone())
+        .filter(|config| config.auto_verify);
+    let processor = req.app_data::<web::Data<SolanaPaymentProcessor>>().cloned();
+    let nonces = req.app_data::<web::Data<dyn NonceStore>>().cloned();
+    Box::pin(async move {
+        let mut verified_amount = None;
+        let mut paid_by = authorization.signature.clone();
+        if let Some(config) = config {
+            let processor = match processor {
+                Some(processor) => processor.into_inner(),
+                None => Arc::new(
+                    config
+                        .payment_processor()
+                        .map_err(actix_web::error::ErrorInternalServerError)?,
+                ),
+            };
+            // The claimed amount is the client's word, so there is nothing to verify against
+            let Some(requirement) = &requirement else {
+                return Err(actix_web::error::ErrorInternalServerError(
+                    X402Error::Configuration(
+                        "auto_verify needs a PaymentRequirement for the route".to_string(),
+                    ),
+                ));
+            };
+            let required = &requirement.amount;
+            paid_by = verify_authorization(&config, &processor, &authorization, required)
+                .await
+                .map_err(|e| PaymentError::InvalidAuthorization(e.to_string()))?;
+            verified_amount = Some(required.clone());
+        }
+
+        if let Some(nonces) = nonces {
+            let retain_until =
+                Utc::now() + Duration::from_std(DEFAULT_REPLAY_WINDOW).unwrap_or(Duration::MAX);
+            let fresh = nonces
+                .check_and_record(&paid_by, retain_until)
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            if !fresh {
+                return Err(PaymentError::Replayed(paid_by).into());
+            }
+        }
+
+        Ok(PaymentExtractor {
+            authorization,
+            verified_amount,
+        })
+    })
+}
+
+/// How long payment requests for `requirement` stay payable
+fn requirement_ttl(requirement: Option<&PaymentRequirement>) -> std::time::Duration {
+    requirement.map_or(DEFAULT_EXPIRES_IN, |requirement| {
+        std::time::Duration::from_secs(requirement.expires_in.max(0) as u64)
+    })
+}
+
+/// 402 refusing `req`, challenging for `requirement` when it and the config are known
+fn payment_required(req: &HttpRequest, requirement: Option<&PaymentRequirement>) -> Error {
+    let state = req.app_data::<web::Data<X402State>>();
+    match (state, requirement) {
+        (Some(state), Some(requirement)) => {
+            let payment_request = create_payment_request(&state.config, requirement, req.path());
+            InternalError::from_response(
+                PaymentError::Required,
+                payment_required_response_for(req, payment_request),
+            )
+            .into()
+        }
+        _ => PaymentError::Required.into(),
+    }
+}
+
+/// Verify `authorization` pays `config`'s recipient at least `required`
+///
+/// Returns the candidate signature that made the payment.
+async fn verify_authorization(
+    config: &X402Config,
+    processor: &SolanaPaymentProcessor,
+    authorization: &PaymentAuthorization,
+    required: &str,
+) -> X402Result<String> {
+    if authorization.payment_address != config.payment_address
+        || authorization.asset_address != config.token_mint
+    {
+        return Err(X402Error::PaymentVerification(
+            "Payment was not made to this server's address and token".to_string(),
+        ));
+    }
+
+    processor
+        .verify_payment_signature(authorization, required, &config.verification_options())
+        .await
+}
+
+/// Extractor that admits requests presenting a valid pass
+///
+/// Checks the `X-Payment-Pass` header against the `PassIssuer` in the app
+/// data: the signature, the expiry and that the pass covers the request path.
+/// No RPC call is made.
+pub struct PassExtractor {
+    pub pass: PassAuthorization,
+}
+
+impl FromRequest for PassExtractor {
     type Error = Error;
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
-        // Get payment authorization header
-        let auth_header = match req.headers().get("X-Payment-Authorization") {
+        let issuer = match req.app_data::<web::Data<PassIssuer>>() {
+            Some(issuer) => issuer,
+            None => {
+                return ready(Err(actix_web::error::ErrorInternalServerError(
+                    "PassExtractor requires PassIssuer app data",
+                )))
+            }
+        };
+        let header = match req.headers().get(PASS_HEADER) {
             Some(h) => match h.to_str() {
                 Ok(s) => s,
-                Err(_) => {
-                    return ready(Err(PaymentError::InvalidHeader.into()));
-                }
+                Err(_) => return ready(Err(PaymentError::InvalidHeader.into())),
             },
-            None => {
-                return ready(Err(PaymentError::Required.into()));
-            }
+            None => return ready(Err(PaymentError::Required.into())),
         };
 
-        // Parse authorization
-        match PaymentAuthorization::from_header_value(auth_header) {
-            Ok(auth) => {
-                // TODO: Verify payment if auto_verify is enabled
-                ready(Ok(PaymentExtractor { authorization: auth }))
-            }
-            Err(e) => ready(Err(PaymentError::InvalidAuthorization(e.to_string()).into())),
+        let pass = match PassAuthorization::from_header_value(header) {
+            Ok(pass) => pass,
+            Err(e) => return ready(Err(PaymentError::InvalidAuthorization(e.to_string()).into())),
+        };
+        match issuer.verify(&pass, req.path()) {
+            Ok(()) => ready(Ok(PassExtractor { pass })),
+            Err(e) => ready(Err(PaymentError::PassRejected(e.to_string()).into())),
         }
     }
 }
@@ -149,6 +564,9 @@ pub enum PaymentError {
     Required,
     InvalidHeader,
     InvalidAuthorization(String),
+    PassRejected(String),
+    Replayed(String),
+    Expired(String),
 }
 
 impl std::fmt::Display for PaymentError {
@@ -159,59 +577,88 @@ impl std::fmt::Display for PaymentError {
             PaymentError::InvalidAuthorization(msg) => {
                 write!(f, "Invalid payment authorization: {}", msg)
             }
+            PaymentError::PassRejected(msg) => write!(f, "Pass rejected: {}", msg),
+            PaymentError::Replayed(signature) => {
+                write!(f, "Transaction {} was already used", signature)
+            }
+            PaymentError::Expired(msg) => write!(f, "{}", msg),
         }
     }
 }
 
-impl ResponseError for PaymentError {
-    fn status_code(&self) -> StatusCode {
+impl PaymentError {
+    /// Core error this rejection corresponds to
+    fn as_x402(&self) -> X402Error {
         match self {
-            PaymentError::Required => StatusCode::PAYMENT_REQUIRED,
+            PaymentError::Required => X402Error::PaymentRequired(self.to_string()),
             PaymentError::InvalidHeader | PaymentError::InvalidAuthorization(_) => {
-                StatusCode::BAD_REQUEST
+                X402Error::InvalidPaymentAuthorization(self.to_string())
             }
+            PaymentError::PassRejected(_) => X402Error::PaymentVerification(self.to_string()),
+            PaymentError::Replayed(_) => X402Error::PaymentReplayed(self.to_string()),
+            PaymentError::Expired(_) => X402Error::PaymentExpired(self.to_string()),
         }
     }
+}
+
+impl ResponseError for PaymentError {
+    fn status_code(&self) -> StatusCode {
+        status_code(&self.as_x402())
+    }
 
     fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
         match self {
-            PaymentError::Required => {
-                HttpResponse::PaymentRequired().json(serde_json::json!({
-                    "error": "Payment required",
-                    "message": "This endpoint requires payment"
-                }))
-            }
-            _ => HttpResponse::BadRequest().json(serde_json::json!({
+            PaymentError::Required => response.json(serde_json::json!({
+                "error": "Payment required",
+                "message": "This endpoint requires payment"
+            })),
+            PaymentError::Replayed(_) => response.json(serde_json::json!({
+                "error": self.to_string(),
+                "code": "PAYMENT_REPLAYED"
+            })),
+            PaymentError::Expired(_) => response.json(serde_json::json!({
+                "error": self.to_string(),
+                "code": "PAYMENT_EXPIRED"
+            })),
+            _ => response.json(serde_json::json!({
                 "error": self.to_string()
             })),
         }
     }
 }
 
+/// Status code for a request rejected with `error`
+pub(crate) fn status_code(error: &X402Error) -> StatusCode {
+    StatusCode::from_u16(error.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 /// Create a payment request for an endpoint
 pub fn create_payment_request(
     config: &X402Config,
     requirement: &PaymentRequirement,
     resource: &str,
 ) -> PaymentRequest {
-    let expires_at = Utc::now() + Duration::seconds(requirement.expires_in);
-    let payment_id = Uuid::new_v4().to_string();
-    let nonce = Uuid::new_v4().to_string();
-
-    let mut request = PaymentRequest::new(
-        requirement.amount.clone(),
-        config.token_mint.clone(),
-        config.payment_address.clone(),
-        config.network.clone(),
-        expires_at,
-        nonce,
-        payment_id,
-        resource.to_string(),
-    );
+    let mut request = PaymentRequest::builder()
+        .amount(requirement.amount.as_str())
+        .asset_address(config.token_mint.as_str())
+        .payment_address(config.payment_address.as_str())
+        .network(config.network.as_str())
+        .expires_at(Utc::now() + Duration::seconds(requirement.expires_in))
+        .payment_id(config.payment_ids.generate())
+        .resource(resource)
+        .build()
+        .expect("request with all required fields");
 
     if let Some(desc) = &requirement.description {
         request = request.with_description(desc.clone());
     }
+    if let Some(url) = &config.facilitator_url {
+        request = request.with_facilitator_url(url.clone());
+    }
+    if !requirement.bundle.is_empty() {
+        request = request.with_resources(requirement.bundle.clone());
+    }
 
     request
 }
@@ -221,6 +668,65 @@ pub fn payment_required_response(payment_request: PaymentRequest) -> HttpRespons
     HttpResponse::PaymentRequired().json(payment_request)
 }
 
+/// Create a 402 Payment Required response in the form the client asked for
+///
+/// Inspects the request's `Accept` header and responds with the payment request
+/// as plain JSON (default), `application/problem+json`, or an empty body with
+/// the base64-encoded request in the `X-Payment-Request` header.
+pub fn payment_required_response_for(
+    req: &HttpRequest,
+    payment_request: PaymentRequest,
+) -> HttpResponse {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|h| h.to_str().ok());
+
+    match ChallengeFormat::from_accept(accept) {
+        ChallengeFormat::Json => payment_required_response(payment_request),
+        ChallengeFormat::ProblemJson => HttpResponse::PaymentRequired()
+            .content_type(PROBLEM_JSON_CONTENT_TYPE)
+            .json(PaymentProblem::new(payment_request)),
+        ChallengeFormat::HeaderOnly => match payment_request.to_base64() {
+            Ok(encoded) => HttpResponse::PaymentRequired()
+                .insert_header((PAYMENT_REQUEST_HEADER, encoded))
+                .finish(),
+            Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": e.to_string()
+            })),
+        },
+    }
+}
+
+/// Create a 202 Accepted response for a newly issued settlement
+///
+/// Use this when a paid request starts long-running work. The body is the
+/// pending settlement and the `Location` header points at its `/settle/{id}`
+/// endpoint.
+pub fn settlement_issued_response(settlement: &Settlement) -> HttpResponse {
+    HttpResponse::Accepted()
+        .insert_header((header::LOCATION, settlement.poll_path()))
+        .json(settlement)
+}
+
+/// Create the response for a `/settle/{id}` poll
+///
+/// Pending settlements return 202 Accepted, complete or failed ones 200 OK,
+/// and unknown IDs 404 Not Found.
+pub fn settlement_status_response(
+    tracker: &SettlementTracker,
+    settlement_id: &str,
+) -> HttpResponse {
+    match tracker.get(settlement_id) {
+        Some(settlement) if settlement.is_pending() => HttpResponse::Accepted().json(settlement),
+        Some(settlement) => HttpResponse::Ok().json(settlement),
+        None => HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Unknown settlement",
+            "settlement_id": settlement_id
+        })),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -244,6 +750,7 @@ mod tests {
             network: "solana-devnet".to_string(),
             rpc_url: None,
             auto_verify: false,
+            ..Default::default()
         };
 
         let requirement = PaymentRequirement::new("0.10");
@@ -253,4 +760,586 @@ mod tests {
         assert_eq!(request.payment_address, config.payment_address);
         assert_eq!(request.asset_address, config.token_mint);
     }
+
+    #[test]
+    fn test_create_payment_request_uses_configured_ids() {
+        let config = X402Config {
+            payment_ids: Arc::new(openlibx402_core::MonotonicGenerator::new("node-1")),
+            ..Default::default()
+        };
+        let requirement = PaymentRequirement::new("0.10");
+
+        let first = create_payment_request(&config, &requirement, "/premium");
+        let second = create_payment_request(&config, &requirement, "/premium");
+        assert!(first.payment_id.starts_with("node-1-"));
+        assert!(first.payment_id.ends_with("-0000000000"));
+        assert!(second.payment_id.ends_with("-0000000001"));
+    }
+
+    fn test_payment_request() -> PaymentRequest {
+        let config = X402Config {
+            payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            network: "solana-devnet".to_string(),
+            rpc_url: None,
+            auto_verify: false,
+            ..Default::default()
+        };
+        create_payment_request(&config, &PaymentRequirement::new("0.10"), "/api/premium")
+    }
+
+    async fn negotiate(accept: Option<&str>) -> (HttpResponse, PaymentRequest) {
+        let mut req = actix_web::test::TestRequest::default();
+        if let Some(accept) = accept {
+            req = req.insert_header((header::ACCEPT, accept));
+        }
+        let payment_request = test_payment_request();
+        let response =
+            payment_required_response_for(&req.to_http_request(), payment_request.clone());
+        (response, payment_request)
+    }
+
+    #[actix_web::test]
+    async fn test_negotiate_json() {
+        for accept in [None, Some("application/json"), Some("*/*")] {
+            let (response, expected) = negotiate(accept).await;
+            assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+            assert_eq!(
+                response.headers().get(header::CONTENT_TYPE).unwrap(),
+                "application/json"
+            );
+            let body = actix_web::body::to_bytes(response.into_body())
+                .await
+                .unwrap();
+            let parsed: PaymentRequest = serde_json::from_slice(&body).unwrap();
+            assert_eq!(parsed, expected);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_negotiate_problem_json() {
+        let (response, expected) = negotiate(Some("application/problem+json")).await;
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let problem: PaymentProblem = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.status, 402);
+        assert_eq!(problem.payment_request, expected);
+    }
+
+    #[actix_web::test]
+    async fn test_negotiate_header_only() {
+        let (response, expected) = negotiate(Some("text/plain")).await;
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        let encoded = response
+            .headers()
+            .get(PAYMENT_REQUEST_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(PaymentRequest::from_base64(&encoded).unwrap(), expected);
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_extractors_challenge_with_payment_request() {
+        use crate::test_support::MockPayments;
+        use actix_web::{test, App};
+
+        async fn paid(auth: RequirePayment<10>) -> HttpResponse {
+            HttpResponse::Ok().body(auth.verified_amount.unwrap_or_default())
+        }
+        async fn extracted(auth: PaymentExtractor) -> HttpResponse {
+            HttpResponse::Ok().body(auth.verified_amount.unwrap_or_default())
+        }
+
+        let payments = MockPayments::new();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(X402State {
+                    config: payments.config().clone(),
+                }))
+                .app_data(web::Data::new(payments.processor()))
+                .route("/const", web::get().to(paid))
+                .service(
+                    web::resource("/routed")
+                        .app_data(web::Data::new(
+                            PaymentRequirement::new("0.25").with_description("Routed"),
+                        ))
+                        .route(web::get().to(extracted)),
+                )
+                .route("/unpriced", web::get().to(extracted)),
+        )
+        .await;
+        let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+        let with_auth = |uri: &str, authorization: &PaymentAuthorization| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header((
+                    "X-Payment-Authorization",
+                    authorization.to_header_value().unwrap(),
+                ))
+                .to_request()
+        };
+
+        for (uri, amount) in [("/const", "0.10"), ("/routed", "0.25")] {
+            let res = test::call_service(&app, get(uri)).await;
+            assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+            let request: PaymentRequest = test::read_body_json(res).await;
+            assert_eq!(request.max_amount_required, amount);
+            assert_eq!(request.resource, uri);
+            assert_eq!(request.payment_address, payments.config().payment_address);
+
+            let authorization = payments.pay(&request).await.unwrap();
+            let res = test::call_service(&app, with_auth(uri, &authorization)).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(test::read_body(res).await, amount);
+        }
+
+        // Payments must cover the price, not just what they claim
+        let res = test::call_service(&app, get("/routed")).await;
+        let mut request: PaymentRequest = test::read_body_json(res).await;
+        request.max_amount_required = "0.05".to_string();
+        let underpaid = payments.pay(&request).await.unwrap();
+        let res = test::call_service(&app, with_auth("/const", &underpaid)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        // Without a known price the 402 has no payment request to offer
+        let res = test::call_service(&app, get("/unpriced")).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["error"], "Payment required");
+    }
+
+    #[actix_web::test]
+    async fn test_settlement_responses() {
+        let tracker = SettlementTracker::new();
+        let authorization = PaymentAuthorization::new(
+            "payment123".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "signature".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        );
+        let issued = tracker.issue(&authorization);
+
+        let response = settlement_issued_response(&issued);
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            issued.poll_path().as_str()
+        );
+
+        let response = settlement_status_response(&tracker, &issued.settlement_id);
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        tracker.confirm_payment(&issued.settlement_id).unwrap();
+        tracker
+            .complete(&issued.settlement_id, serde_json::json!("done"))
+            .unwrap();
+        let response = settlement_status_response(&tracker, &issued.settlement_id);
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let settlement = Settlement::from_json(std::str::from_utf8(&body).unwrap()).unwrap();
+        assert_eq!(settlement.result, Some(serde_json::json!("done")));
+
+        let response = settlement_status_response(&tracker, "unknown");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn verified(payment: PaymentExtractor) -> HttpResponse {
+        HttpResponse::Ok().body(payment.verified_amount.unwrap_or_default())
+    }
+
+    #[actix_web::test]
+    async fn test_payment_extractor_verifies_on_chain() {
+        use actix_web::{test, App};
+        use openlibx402_core::rpc::mock::{MockRpcBackend, TransactionFixture};
+        use solana_sdk::{pubkey::Pubkey, signature::Signature};
+        use std::sync::Arc;
+
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(signature).transfer(mint, recipient, 100_000));
+        let processor = web::Data::new(SolanaPaymentProcessor::with_backend(
+            Arc::new(backend),
+            None,
+        ));
+        let config = X402Config {
+            payment_address: recipient.to_string(),
+            token_mint: mint.to_string(),
+            ..Default::default()
+        };
+        let app = |auto_verify: bool| {
+            let state = X402State {
+                config: X402Config {
+                    auto_verify,
+                    ..config.clone()
+                },
+            };
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(processor.clone())
+                .service(
+                    web::resource("/premium")
+                        .app_data(web::Data::new(PaymentRequirement::new("0.10")))
+                        .route(web::get().to(verified)),
+                )
+                .route("/unpriced", web::get().to(verified))
+        };
+        let paid_at = |uri: &str, signature: &Signature| {
+            let header = PaymentAuthorization::new(
+                "pay_1".to_string(),
+                "0.10".to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            )
+            .to_header_value()
+            .unwrap();
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("X-Payment-Authorization", header))
+                .to_request()
+        };
+        let paid = |signature: &Signature| paid_at("/premium", signature);
+
+        let service = test::init_service(app(true)).await;
+        let res = test::call_service(&service, paid(&signature)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, "0.10");
+
+        // Without a requirement there is no price to verify the claimed amount against
+        let res = test::call_service(&service, paid_at("/unpriced", &signature)).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let res = test::call_service(&service, paid(&Signature::new_unique())).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert!(body["error"].as_str().unwrap().contains("not found"));
+
+        // Without auto_verify the authorization is only parsed
+        let service = test::init_service(app(false)).await;
+        let res = test::call_service(&service, paid(&Signature::new_unique())).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, "");
+    }
+
+    #[actix_web::test]
+    async fn test_payment_extractor_rejects_replay() {
+        use actix_web::{test, App};
+        use openlibx402_core::MemoryStore;
+        use std::sync::Arc;
+
+        let store: Arc<dyn NonceStore> = Arc::new(MemoryStore::new());
+        let app = test::init_service(App::new().app_data(web::Data::from(store)).route(
+            "/premium",
+            web::get().to(|_: PaymentExtractor| async { HttpResponse::Ok().finish() }),
+        ))
+        .await;
+        let paid = |payment_id: &str, signature: &str| {
+            let header = PaymentAuthorization::new(
+                payment_id.to_string(),
+                "0.10".to_string(),
+                "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            )
+            .to_header_value()
+            .unwrap();
+            test::TestRequest::get()
+                .uri("/premium")
+                .insert_header(("X-Payment-Authorization", header))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, paid("pay_1", "signature_1")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = test::call_service(&app, paid("pay_1", "signature_1")).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        // The same transfer presented under a fresh payment ID is still a replay
+        let res = test::call_service(&app, paid("pay_2", "signature_1")).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        let res = test::call_service(&app, paid("pay_2", "signature_2")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_payment_extractor_records_only_the_paying_signature() {
+        use actix_web::{test, App};
+        use openlibx402_core::rpc::mock::{MockRpcBackend, TransactionFixture};
+        use openlibx402_core::MemoryStore;
+        use solana_sdk::{pubkey::Pubkey, signature::Signature};
+        use std::sync::Arc;
+
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (own, other) = (Signature::new_unique(), Signature::new_unique());
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(own).transfer(mint, recipient, 100_000))
+            .with_fixture(TransactionFixture::new(other).transfer(mint, recipient, 100_000));
+        let processor = web::Data::new(SolanaPaymentProcessor::with_backend(
+            Arc::new(backend),
+            None,
+        ));
+        let state = X402State {
+            config: X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                auto_verify: true,
+                ..Default::default()
+            },
+        };
+        let store: Arc<dyn NonceStore> = Arc::new(MemoryStore::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .app_data(processor)
+                .app_data(web::Data::from(store))
+                .route(
+                    "/premium",
+                    web::get().to(|_: RequirePayment<10>| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+        let paid = |payment_id: &str, signature: &Signature, extra: Vec<String>| {
+            let header = PaymentAuthorization::new(
+                payment_id.to_string(),
+                "0.10".to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            )
+            .with_signatures(extra)
+            .to_header_value()
+            .unwrap();
+            test::TestRequest::get()
+                .uri("/premium")
+                .insert_header(("X-Payment-Authorization", header))
+                .to_request()
+        };
+
+        // Listing someone else's transfer as an extra candidate doesn't spend it
+        let res = test::call_service(&app, paid("pay_1", &own, vec![other.to_string()])).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, paid("pay_2", &other, vec![])).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = test::call_service(&app, paid("pay_3", &other, vec![])).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn test_payment_extractor_rejects_stale_authorization() {
+        use actix_web::{test, App};
+
+        let config = X402Config {
+            auto_verify: false,
+            max_authorization_age: Some(std::time::Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(X402State { config }))
+                .route(
+                    "/premium",
+                    web::get().to(|_: PaymentExtractor| async { HttpResponse::Ok().finish() }),
+                )
+                .service(
+                    web::resource("/priced")
+                        .app_data(web::Data::new(
+                            PaymentRequirement::new("0.10").with_expires_in(600),
+                        ))
+                        .route(
+                            web::get()
+                                .to(|_: PaymentExtractor| async { HttpResponse::Ok().finish() }),
+                        ),
+                ),
+        )
+        .await;
+        let paid = |uri: &str, age: i64| {
+            let mut authorization = PaymentAuthorization::new(
+                "pay_1".to_string(),
+                "0.10".to_string(),
+                "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+                "solana-devnet".to_string(),
+                "signature".to_string(),
+                "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            );
+            authorization.timestamp = Utc::now() - Duration::seconds(age);
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header((
+                    "X-Payment-Authorization",
+                    authorization.to_header_value().unwrap(),
+                ))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, paid("/premium", 10)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = test::call_service(&app, paid("/premium", 120)).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["code"], "PAYMENT_EXPIRED");
+        assert!(body["error"].as_str().unwrap().contains("accepted for 60s"));
+
+        // The configured age overrides the requirement's expiry
+        let res = test::call_service(&app, paid("/priced", 120)).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+
+        // Without a configured age, the requirement's expiry is the limit
+        let app = test::init_service(
+            App::new().service(
+                web::resource("/priced")
+                    .app_data(web::Data::new(
+                        PaymentRequirement::new("0.10").with_expires_in(600),
+                    ))
+                    .route(
+                        web::get().to(|_: PaymentExtractor| async { HttpResponse::Ok().finish() }),
+                    ),
+            ),
+        )
+        .await;
+        let res = test::call_service(&app, paid("/priced", 500)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, paid("/priced", 700)).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+    }
+
+    #[actix_web::test]
+    async fn test_payment_extractor_reads_configured_header() {
+        use actix_web::{test, App};
+
+        let config = X402Config {
+            auto_verify: false,
+            authorization_header: "X-PAYMENT".to_string(),
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(X402State { config }))
+                .route(
+                    "/premium",
+                    web::get().to(|_: PaymentExtractor| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+        let header = PaymentAuthorization::new(
+            "pay_1".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "signature".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        )
+        .to_header_value()
+        .unwrap();
+        let paid = |name: &str| {
+            test::TestRequest::get()
+                .uri("/premium")
+                .insert_header((name.to_string(), header.clone()))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, paid("X-PAYMENT")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // A payment in the default header is missing, not malformed
+        let res = test::call_service(&app, paid(AUTHORIZATION_HEADER)).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+    }
+
+    #[actix_web::test]
+    async fn test_pass_extractor() {
+        use actix_web::{test, App};
+
+        let issuer = PassIssuer::new(solana_sdk::signature::Keypair::new());
+        let config = X402Config {
+            payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            ..Default::default()
+        };
+        let offer = openlibx402_core::PassRequest::new(
+            create_payment_request(&config, &PaymentRequirement::new("5.00"), "/passes"),
+            "/reports/*",
+            Duration::hours(1),
+        );
+        let pass = issuer
+            .issue(
+                &offer,
+                &PaymentAuthorization::new(
+                    offer.payment_request.payment_id.clone(),
+                    "5.00".to_string(),
+                    config.payment_address.clone(),
+                    config.token_mint.clone(),
+                    "solana-devnet".to_string(),
+                    "signature".to_string(),
+                    "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+                ),
+            )
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(issuer))
+                .route(
+                    "/reports/{name}",
+                    web::get().to(|pass: PassExtractor| async move {
+                        HttpResponse::Ok().body(pass.pass.pass_id)
+                    }),
+                )
+                .route(
+                    "/admin",
+                    web::get().to(|_: PassExtractor| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+        let with_pass = |path: &str| {
+            test::TestRequest::get()
+                .uri(path)
+                .insert_header((PASS_HEADER, pass.to_header_value().unwrap()))
+                .to_request()
+        };
+
+        for path in ["/reports/q1", "/reports/q2"] {
+            let res = test::call_service(&app, with_pass(path)).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(test::read_body(res).await, pass.pass_id.as_bytes());
+        }
+
+        let res = test::call_service(&app, with_pass("/admin")).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+
+        let res = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/reports/q1").to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+    }
 }
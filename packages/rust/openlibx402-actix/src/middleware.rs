@@ -0,0 +1,524 @@
+use actix_web::{
+    body::{self, BoxBody, EitherBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorInternalServerError,
+    http::{
+        header::{HeaderName, HeaderValue},
+        Method, StatusCode,
+    },
+    Error, HttpMessage, HttpResponse,
+};
+use chrono::{DateTime, Utc};
+use futures_util::future::LocalBoxFuture;
+use openlibx402_core::{
+    metrics, ChallengeStore, Grant, GrantStore, MetricsRecorder, NonceStore, PaymentAuthorization,
+    PaymentReceipt, PaymentRequest, SolanaPaymentProcessor, VerificationOptions, X402Error,
+    X402Result, PRICE_HEADER,
+};
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    create_payment_request, payment_required_response_for, pricing::PricingTable,
+    PaymentRequirement, X402Config,
+};
+
+/// Hook rewriting the response to a request whose payment was verified
+///
+/// Receives the handler's response, the verified authorization and the
+/// receipt issued for it, e.g. to attach the receipt as a header or inside
+/// a JSON body.
+pub type ResponseTransformer =
+    Arc<dyn Fn(HttpResponse, &PaymentAuthorization, &PaymentReceipt) -> HttpResponse + Send + Sync>;
+
+/// On-chain verification and replay protection applied by [`X402Middleware`]
+pub(crate) struct PaymentVerifier {
+    pub(crate) processor: SolanaPaymentProcessor,
+    pub(crate) options: VerificationOptions,
+    pub(crate) nonces: Arc<dyn NonceStore>,
+    pub(crate) grants: Arc<dyn GrantStore>,
+    pub(crate) challenges: Arc<dyn ChallengeStore>,
+    pub(crate) replay_window: Duration,
+    pub(crate) metrics: Option<Arc<dyn MetricsRecorder>>,
+    pub(crate) transformer: Option<ResponseTransformer>,
+}
+
+impl PaymentVerifier {
+    /// Verify `authorization` pays `requirement` to the configured recipient,
+    /// then record the signature that paid so the payment can't be used again
+    ///
+    /// A bundle payment must answer a challenge this server issued that lists
+    /// `resource`, and is recorded as used for `resource` only. Authorizations
+    /// older than the config's age limit are refused before anything is
+    /// checked on-chain.
+    async fn verify(
+        &self,
+        config: &X402Config,
+        requirement: &PaymentRequirement,
+        authorization: &PaymentAuthorization,
+        resource: &str,
+    ) -> X402Result<()> {
+        authorization.check_age(config.authorization_age_limit(Some(requirement)))?;
+
+        if authorization.payment_address != config.payment_address
+            || authorization.asset_address != config.token_mint
+        {
+            return Err(X402Error::PaymentVerification(
+                "Payment was not made to this server's address and token".to_string(),
+            ));
+        }
+
+        let mut challenge = None;
+        if !requirement.bundle.is_empty() {
+            let issued = self
+                .challenges
+                .get(&authorization.payment_id)
+                .await?
+                .ok_or_else(|| {
+                    X402Error::PaymentVerification(format!(
+                        "Bundle payment {} was not issued here or has expired",
+                        authorization.payment_id
+                    ))
+                })?;
+            if !issued.covers(resource) {
+                return Err(X402Error::PaymentVerification(format!(
+                    "Bundle payment {} does not cover {}",
+                    authorization.payment_id, resource
+                )));
+            }
+            challenge = Some(issued);
+        }
+
+        let signature = self
+            .processor
+            .verify_payment_signature(authorization, &requirement.amount, &self.options)
+            .await?;
+
+        let retain_until = self.retain_until();
+        let fresh = match &challenge {
+            Some(challenge) => {
+                self.record_bundle_use(&signature, challenge, resource, retain_until)
+                    .await?
+            }
+            None => {
+                self.nonces
+                    .check_and_record(&signature, retain_until)
+                    .await?
+            }
+        };
+        if !fresh {
+            if let Some(metrics) = &self.metrics {
+                metrics.record_failure(metrics::VERIFY_PAYMENT, "PAYMENT_REPLAYED");
+            }
+            return Err(X402Error::PaymentReplayed(format!(
+                "Transaction {} was already used",
+                signature
+            )));
+        }
+        Ok(())
+    }
+
+    /// Record a use of the bundle payment `signature` at `resource`
+    ///
+    /// A bundle payment may be used once per resource it covers. Its first
+    /// use also records the bare signature, so the payment can't be spent at
+    /// routes outside the bundle, and a signature already spent elsewhere
+    /// uses up the whole bundle.
+    async fn record_bundle_use(
+        &self,
+        signature: &str,
+        challenge: &PaymentRequest,
+        resource: &str,
+        retain_until: DateTime<Utc>,
+    ) -> X402Result<bool> {
+        let first_use = self
+            .nonces
+            .check_and_record(
+                &format!("{}:{}", signature, challenge.payment_id),
+                retain_until,
+            )
+            .await?;
+        if first_use
+            && !self
+                .nonces
+                .check_and_record(signature, retain_until)
+                .await?
+        {
+            for covered in &challenge.resources {
+                self.nonces
+                    .check_and_record(&format!("{}:{}", signature, covered), retain_until)
+                    .await?;
+            }
+            return Ok(false);
+        }
+        self.nonces
+            .check_and_record(&format!("{}:{}", signature, resource), retain_until)
+            .await
+    }
+
+    /// End of the replay window for a payment used now
+    fn retain_until(&self) -> DateTime<Utc> {
+        Utc::now() + chrono::Duration::from_std(self.replay_window).unwrap_or(chrono::Duration::MAX)
+    }
+
+    /// Response recorded for an earlier request paid by `authorization`, if any
+    async fn cached_response(
+        &self,
+        authorization: &PaymentAuthorization,
+    ) -> X402Result<Option<HttpResponse>> {
+        let grant = self.grants.get_grant(&authorization.payment_id).await?;
+        Ok(grant
+            .filter(|grant| grant.matches(&authorization.signature))
+            .map(|grant| {
+                let status = StatusCode::from_u16(grant.status).unwrap_or(StatusCode::OK);
+                let mut response = HttpResponse::build(status);
+                for (name, value) in &grant.headers {
+                    response.append_header((name.as_str(), value.as_str()));
+                }
+                response.body(grant.body)
+            }))
+    }
+
+    /// Keep `res` as the result of the request paid by `authorization`
+    ///
+    /// The body is read into memory to be stored, and the response is
+    /// returned with it. Failing to store the grant doesn't fail the request.
+    async fn record_grant(
+        &self,
+        authorization: &PaymentAuthorization,
+        res: ServiceResponse<BoxBody>,
+    ) -> Result<ServiceResponse<BoxBody>, Error> {
+        let (req, response) = res.into_parts();
+        let (head, body) = response.into_parts();
+        let body = body::to_bytes(body)
+            .await
+            .map_err(|e| ErrorInternalServerError(e.to_string()))?;
+
+        let grant = Grant {
+            payment_id: authorization.payment_id.clone(),
+            signature: authorization.signature.clone(),
+            status: head.status().as_u16(),
+            headers: head
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: body.to_vec(),
+            expires_at: self.retain_until(),
+        };
+        // Without the grant, a retry is refused as a replay, as on routes
+        // that don't cache their result
+        let _ = self.grants.save_grant(&grant).await;
+
+        Ok(ServiceResponse::new(
+            req,
+            head.set_body(body).map_into_boxed_body(),
+        ))
+    }
+}
+
+/// Middleware that enforces a [`PricingTable`] on every request it wraps
+///
+/// Requests to paid paths without a well-formed `X-Payment-Authorization`
+/// header are answered with a 402 carrying a fresh `PaymentRequest`, negotiated
+/// from the `Accept` header. Paid requests that carry an authorization are
+/// passed on with the parsed [`PaymentAuthorization`] and the path's
+/// [`PaymentRequirement`] in the request extensions. Without verification (see
+/// [`X402Protection`](crate::X402Protection)) the authorization is not checked
+/// on-chain; handlers must verify it themselves.
+///
+/// Every response on a paid path carries an `X-Payment-Price` header. `HEAD`
+/// requests to paid paths are answered with an empty 200 carrying just that
+/// header, so crawlers can learn prices without being challenged.
+#[derive(Clone)]
+pub struct X402Middleware {
+    config: Arc<X402Config>,
+    pricing: Arc<PricingTable>,
+    verifier: Option<Arc<PaymentVerifier>>,
+}
+
+impl X402Middleware {
+    /// Create middleware charging according to `pricing`
+    pub fn new(config: X402Config, pricing: PricingTable) -> Self {
+        Self {
+            config: Arc::new(config),
+            pricing: Arc::new(pricing),
+            verifier: None,
+        }
+    }
+
+    /// Verify payments on-chain and reject replays before forwarding
+    pub(crate) fn with_verifier(mut self, verifier: PaymentVerifier) -> Self {
+        self.verifier = Some(Arc::new(verifier));
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for X402Middleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = X402MiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(X402MiddlewareService {
+            service: Rc::new(service),
+            config: self.config.clone(),
+            pricing: self.pricing.clone(),
+            verifier: self.verifier.clone(),
+        }))
+    }
+}
+
+/// Service produced by [`X402Middleware`]
+pub struct X402MiddlewareService<S> {
+    service: Rc<S>,
+    config: Arc<X402Config>,
+    pricing: Arc<PricingTable>,
+    verifier: Option<Arc<PaymentVerifier>>,
+}
+
+impl<S, B> Service<ServiceRequest> for X402MiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let requirement = match self.pricing.requirement_for(req.path()) {
+            Some(requirement) => requirement,
+            None => {
+                let service = self.service.clone();
+                return Box::pin(async move {
+                    service.call(req).await.map(|res| res.map_into_left_body())
+                });
+            }
+        };
+
+        let price = HeaderValue::from_str(&self.config.advertised_price(requirement)).ok();
+        if req.method() == Method::HEAD {
+            let response = HttpResponse::Ok().finish();
+            let res = with_price(req.into_response(response), price);
+            return Box::pin(async move { Ok(res.map_into_right_body()) });
+        }
+
+        let authorization = req
+            .headers()
+            .get(self.config.authorization_header.as_str())
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| PaymentAuthorization::from_header_value(h).ok());
+
+        match authorization {
+            Some(authorization) => {
+                let service = self.service.clone();
+                let config = self.config.clone();
+                let verifier = self.verifier.clone();
+                let requirement = requirement.clone();
+
+                Box::pin(async move {
+                    let mut granted = None;
+                    if let Some(verifier) = &verifier {
+                        if requirement.cache_result {
+                            let response = match verifier.cached_response(&authorization).await {
+                                Ok(cached) => cached,
+                                Err(e) => Some(rejection_response(&e)),
+                            };
+                            if let Some(response) = response {
+                                let res = with_price(req.into_response(response), price);
+                                return Ok(res.map_into_right_body());
+                            }
+                        }
+                        if let Err(e) = verifier
+                            .verify(&config, &requirement, &authorization, req.path())
+                            .await
+                        {
+                            let response = rejection_response(&e);
+                            let res = with_price(req.into_response(response), price);
+                            return Ok(res.map_into_right_body());
+                        }
+                        if let Some(transformer) = verifier.transformer.clone() {
+                            let receipt = PaymentReceipt::new(
+                                &authorization,
+                                &requirement.amount,
+                                req.path(),
+                            );
+                            granted = Some((transformer, authorization.clone(), receipt));
+                        }
+                    }
+
+                    req.extensions_mut().insert(authorization.clone());
+                    req.extensions_mut().insert(requirement.clone());
+                    let res = service.call(req).await?;
+                    let res = match granted {
+                        Some((transformer, authorization, receipt)) => {
+                            let (req, response) = res.map_into_boxed_body().into_parts();
+                            let response = transformer(response, &authorization, &receipt);
+                            ServiceResponse::new(req, response).map_into_right_body()
+                        }
+                        None => res.map_into_left_body(),
+                    };
+
+                    match verifier {
+                        Some(verifier) if requirement.cache_result && res.status().is_success() => {
+                            let res = verifier
+                                .record_grant(&authorization, res.map_into_boxed_body())
+                                .await?;
+                            Ok(with_price(res, price).map_into_right_body())
+                        }
+                        _ => Ok(with_price(res, price)),
+                    }
+                })
+            }
+            None => {
+                let payment_request = create_payment_request(&self.config, requirement, req.path());
+                let verifier = self.verifier.clone();
+                Box::pin(async move {
+                    // Bundle payments are checked against the challenge they answer
+                    if let Some(verifier) = verifier.filter(|_| payment_request.is_bundle()) {
+                        if let Err(e) = verifier.challenges.save(&payment_request).await {
+                            let res = with_price(req.into_response(rejection_response(&e)), price);
+                            return Ok(res.map_into_right_body());
+                        }
+                    }
+                    let response = payment_required_response_for(req.request(), payment_request);
+                    let res = with_price(req.into_response(response), price);
+                    Ok(res.map_into_right_body())
+                })
+            }
+        }
+    }
+}
+
+/// Advertise `price` on `res`
+fn with_price<B>(mut res: ServiceResponse<B>, price: Option<HeaderValue>) -> ServiceResponse<B> {
+    if let Some(price) = price {
+        let name = HeaderName::from_bytes(PRICE_HEADER.as_bytes()).expect("valid header name");
+        res.headers_mut().insert(name, price);
+    }
+    res
+}
+
+/// Response for a payment that failed verification
+fn rejection_response(error: &X402Error) -> HttpResponse {
+    let body = serde_json::json!({
+        "error": error.to_string(),
+        "code": error.code(),
+    });
+    HttpResponse::build(crate::status_code(error)).json(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PaymentRequirement;
+    use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+
+    fn test_config() -> X402Config {
+        X402Config {
+            payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            auto_verify: false,
+            ..Default::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_deny_by_default_requires_payment_for_unlisted_route() {
+        let pricing = PricingTable::new()
+            .free("/health")
+            .paid_by_default(PaymentRequirement::new("0.01"));
+        let app = test::init_service(
+            App::new()
+                .wrap(X402Middleware::new(test_config(), pricing))
+                .route("/health", web::get().to(HttpResponse::Ok))
+                .route("/unlisted", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/health").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/unlisted").to_request()).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+        let payment_request: openlibx402_core::PaymentRequest = test::read_body_json(res).await;
+        assert_eq!(payment_request.max_amount_required, "0.01");
+        assert_eq!(payment_request.resource, "/unlisted");
+    }
+
+    #[actix_web::test]
+    async fn test_responses_advertise_price() {
+        let pricing = PricingTable::new().route("/premium", PaymentRequirement::new("0.10"));
+        let app = test::init_service(
+            App::new()
+                .wrap(X402Middleware::new(test_config(), pricing))
+                .route("/premium", web::get().to(HttpResponse::Ok))
+                .route("/public", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let price = |res: &ServiceResponse<_>| {
+            res.headers()
+                .get(PRICE_HEADER)
+                .map(|h| h.to_str().unwrap().to_string())
+        };
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/premium").to_request()).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(price(&res).as_deref(), Some("0.10 USDC"));
+
+        // Probing the priced route is free and still advertises its price
+        let res = test::call_service(
+            &app,
+            test::TestRequest::default()
+                .method(Method::HEAD)
+                .uri("/premium")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(price(&res).as_deref(), Some("0.10 USDC"));
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/public").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(price(&res), None);
+    }
+
+    #[actix_web::test]
+    async fn test_opt_in_leaves_unlisted_routes_free() {
+        let pricing = PricingTable::new().route("/premium", PaymentRequirement::new("0.10"));
+        let app = test::init_service(
+            App::new()
+                .wrap(X402Middleware::new(test_config(), pricing))
+                .route("/premium", web::get().to(HttpResponse::Ok))
+                .route("/unlisted", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/unlisted").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/premium").to_request()).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+    }
+}
@@ -0,0 +1,145 @@
+use openlibx402_core::PaymentRequest;
+
+use crate::{create_payment_request, PaymentRequirement, X402Config};
+
+/// Route prices for payment-protected paths
+///
+/// Patterns are exact paths (`/premium`) or prefix wildcards (`/api/*`, which
+/// matches `/api` and everything below it). When several patterns match, the
+/// most specific one wins: exact paths beat wildcards, and longer prefixes beat
+/// shorter ones.
+///
+/// By default only listed routes are paid. In deny-by-default mode (see
+/// [`PricingTable::paid_by_default`]) every route is paid unless it is
+/// explicitly marked free.
+#[derive(Debug, Clone, Default)]
+pub struct PricingTable {
+    routes: Vec<(String, Option<PaymentRequirement>)>,
+    default_requirement: Option<PaymentRequirement>,
+}
+
+impl PricingTable {
+    /// Create an empty, opt-in pricing table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Charge `requirement` for paths matching `pattern`
+    pub fn route(mut self, pattern: &str, requirement: PaymentRequirement) -> Self {
+        self.routes.push((pattern.to_string(), Some(requirement)));
+        self
+    }
+
+    /// Charge a bundle requirement at each of its resources
+    ///
+    /// See [`PaymentRequirement::with_bundle`].
+    pub fn bundle(mut self, requirement: PaymentRequirement) -> Self {
+        for resource in &requirement.bundle {
+            self.routes
+                .push((resource.clone(), Some(requirement.clone())));
+        }
+        self
+    }
+
+    /// Serve paths matching `pattern` without payment
+    pub fn free(mut self, pattern: &str) -> Self {
+        self.routes.push((pattern.to_string(), None));
+        self
+    }
+
+    /// Charge `requirement` for every path that no pattern matches
+    pub fn paid_by_default(mut self, requirement: PaymentRequirement) -> Self {
+        self.default_requirement = Some(requirement);
+        self
+    }
+
+    /// Whether unmatched paths require payment
+    pub fn default_paid(&self) -> bool {
+        self.default_requirement.is_some()
+    }
+
+    /// Payment requirement for `path`, or `None` if it is free
+    pub fn requirement_for(&self, path: &str) -> Option<&PaymentRequirement> {
+        self.routes
+            .iter()
+            .filter_map(|(pattern, requirement)| {
+                specificity(pattern, path).map(|score| (score, requirement))
+            })
+            .max_by_key(|(score, _)| *score)
+            .map_or(self.default_requirement.as_ref(), |(_, requirement)| {
+                requirement.as_ref()
+            })
+    }
+
+    /// Payment request to answer an unpaid request for `path` with
+    ///
+    /// `None` when `path` is free or matches no priced route, i.e. the
+    /// request should be served (or 404) without asking for payment.
+    pub fn payment_request_for(&self, config: &X402Config, path: &str) -> Option<PaymentRequest> {
+        self.requirement_for(path)
+            .map(|requirement| create_payment_request(config, requirement, path))
+    }
+}
+
+/// How specifically `pattern` matches `path`, or `None` if it doesn't
+fn specificity(pattern: &str, path: &str) -> Option<usize> {
+    match pattern.strip_suffix("/*") {
+        Some(prefix) => {
+            let matches = path == prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'));
+            matches.then_some(prefix.len())
+        }
+        None => (pattern == path).then_some(usize::MAX),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opt_in_pricing() {
+        let pricing = PricingTable::new()
+            .route("/premium", PaymentRequirement::new("0.10"))
+            .route("/api/*", PaymentRequirement::new("0.05"))
+            .free("/api/health");
+
+        assert_eq!(pricing.requirement_for("/premium").unwrap().amount, "0.10");
+        assert_eq!(pricing.requirement_for("/api/data").unwrap().amount, "0.05");
+        assert!(pricing.requirement_for("/api/health").is_none());
+        assert!(pricing.requirement_for("/apiary").is_none());
+        assert!(pricing.requirement_for("/public").is_none());
+    }
+
+    #[test]
+    fn test_deny_by_default() {
+        let pricing = PricingTable::new()
+            .route("/premium", PaymentRequirement::new("0.10"))
+            .free("/health")
+            .paid_by_default(PaymentRequirement::new("0.01"));
+
+        assert!(pricing.default_paid());
+        assert_eq!(pricing.requirement_for("/premium").unwrap().amount, "0.10");
+        assert_eq!(pricing.requirement_for("/unlisted").unwrap().amount, "0.01");
+        assert!(pricing.requirement_for("/health").is_none());
+    }
+
+    #[test]
+    fn test_payment_request_for_tiers() {
+        let config = X402Config::default();
+        let pricing = PricingTable::new()
+            .route("/premium", PaymentRequirement::new("0.10"))
+            .route("/enterprise", PaymentRequirement::new("1.00"));
+
+        let premium = pricing.payment_request_for(&config, "/premium").unwrap();
+        assert_eq!(premium.max_amount_required, "0.10");
+        assert_eq!(premium.resource, "/premium");
+        assert_eq!(premium.payment_address, config.payment_address);
+        let enterprise = pricing.payment_request_for(&config, "/enterprise").unwrap();
+        assert_eq!(enterprise.max_amount_required, "1.00");
+        assert_ne!(premium.payment_id, enterprise.payment_id);
+        assert!(pricing.payment_request_for(&config, "/unknown").is_none());
+    }
+}
@@ -0,0 +1,601 @@
+use actix_web::HttpResponse;
+use openlibx402_core::{
+    ChallengeStore, GrantStore, MemoryStore, MetricsRecorder, NonceStore, PaymentAuthorization,
+    PaymentReceipt, SolanaPaymentProcessor, X402Error, X402Result,
+};
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    middleware::{PaymentVerifier, ResponseTransformer, X402Middleware},
+    pricing::PricingTable,
+    PaymentRequirement, X402Config,
+};
+
+/// How long used payments are remembered by default
+pub const DEFAULT_REPLAY_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Entry point for assembling payment protection in a few lines
+///
+/// ```rust,no_run
+/// use actix_web::App;
+/// use openlibx402_actix::{PaymentRequirement, X402Config, X402Protection};
+///
+/// # fn app(config: X402Config) -> openlibx402_core::X402Result<()> {
+/// let protection = X402Protection::builder()
+///     .config(config)
+///     .route("/premium", PaymentRequirement::new("0.10"))
+///     .free("/health")
+///     .build()?;
+///
+/// let app = App::new().wrap(protection);
+/// # Ok(())
+/// # }
+/// ```
+pub struct X402Protection;
+
+impl X402Protection {
+    /// Start building protection with the defaults listed on [`X402ProtectionBuilder`]
+    pub fn builder() -> X402ProtectionBuilder {
+        X402ProtectionBuilder::default()
+    }
+
+    /// Start building protection charging `requirement` for everything it wraps
+    ///
+    /// Meant for wrapping a `web::scope`, so every route in the scope costs the
+    /// same. Handlers read the verified payment from the request extensions:
+    ///
+    /// ```rust,no_run
+    /// use actix_web::{web, App, HttpMessage, HttpRequest, HttpResponse};
+    /// use openlibx402_actix::{PaymentRequirement, X402Config, X402Protection};
+    /// use openlibx402_core::PaymentAuthorization;
+    ///
+    /// async fn quote(req: HttpRequest) -> HttpResponse {
+    ///     let payer = req
+    ///         .extensions()
+    ///         .get::<PaymentAuthorization>()
+    ///         .map(|authorization| authorization.public_key.clone());
+    ///     HttpResponse::Ok().json(payer)
+    /// }
+    ///
+    /// # fn app(config: X402Config) -> openlibx402_core::X402Result<()> {
+    /// let api = web::scope("/api")
+    ///     .wrap(X402Protection::scope(PaymentRequirement::new("0.05")).config(config).build()?)
+    ///     .route("/quote", web::get().to(quote));
+    /// let app = App::new().service(api);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn scope(requirement: PaymentRequirement) -> X402ProtectionBuilder {
+        Self::builder().paid_by_default(requirement)
+    }
+}
+
+/// Builder for an [`X402Middleware`] with verification, replay protection and metrics
+///
+/// Defaults:
+/// - opt-in pricing (only listed routes are paid)
+/// - on-chain verification when `config.auto_verify` is set, through the
+///   config's RPC endpoint
+/// - an in-memory [`MemoryStore`] remembering used payments, and the
+///   results of routes [`with_cached_result`](PaymentRequirement::with_cached_result),
+///   for [`DEFAULT_REPLAY_WINDOW`]
+/// - an in-memory [`MemoryStore`] tracking issued bundle challenges
+/// - no metrics
+/// - verified responses passed on unchanged
+#[derive(Default)]
+pub struct X402ProtectionBuilder {
+    config: Option<X402Config>,
+    pricing: PricingTable,
+    nonce_store: Option<Arc<dyn NonceStore>>,
+    grant_store: Option<Arc<dyn GrantStore>>,
+    challenge_store: Option<Arc<dyn ChallengeStore>>,
+    payment_processor: Option<SolanaPaymentProcessor>,
+    metrics: Option<Arc<dyn MetricsRecorder>>,
+    replay_window: Option<Duration>,
+    transformer: Option<ResponseTransformer>,
+}
+
+impl X402ProtectionBuilder {
+    /// Recipient, token and network settings (required)
+    pub fn config(mut self, config: X402Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Replace the pricing table
+    pub fn pricing(mut self, pricing: PricingTable) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Charge `requirement` for paths matching `pattern`
+    pub fn route(mut self, pattern: &str, requirement: PaymentRequirement) -> Self {
+        self.pricing = self.pricing.route(pattern, requirement);
+        self
+    }
+
+    /// Charge a bundle requirement at each of its resources
+    ///
+    /// See [`PaymentRequirement::with_bundle`].
+    pub fn bundle(mut self, requirement: PaymentRequirement) -> Self {
+        self.pricing = self.pricing.bundle(requirement);
+        self
+    }
+
+    /// Serve paths matching `pattern` without payment
+    pub fn free(mut self, pattern: &str) -> Self {
+        self.pricing = self.pricing.free(pattern);
+        self
+    }
+
+    /// Charge `requirement` for every path that no pattern matches
+    pub fn paid_by_default(mut self, requirement: PaymentRequirement) -> Self {
+        self.pricing = self.pricing.paid_by_default(requirement);
+        self
+    }
+
+    /// Remember used payments in `store`, e.g. a shared `RedisStore`
+    pub fn nonce_store(mut self, store: Arc<dyn NonceStore>) -> Self {
+        self.nonce_store = Some(store);
+        self
+    }
+
+    /// Keep the results of paid requests in `store`, e.g. a shared `RedisStore`
+    ///
+    /// Only routes [`with_cached_result`](PaymentRequirement::with_cached_result)
+    /// are kept.
+    pub fn grant_store(mut self, store: Arc<dyn GrantStore>) -> Self {
+        self.grant_store = Some(store);
+        self
+    }
+
+    /// Track issued bundle challenges in `store`, e.g. a shared `RedisStore`
+    pub fn challenge_store(mut self, store: Arc<dyn ChallengeStore>) -> Self {
+        self.challenge_store = Some(store);
+        self
+    }
+
+    /// Verify payments through `processor` instead of one built from the config
+    pub fn payment_processor(mut self, processor: SolanaPaymentProcessor) -> Self {
+        self.payment_processor = Some(processor);
+        self
+    }
+
+    /// Report verifications, amounts collected, failures and replays to `recorder`
+    pub fn metrics(mut self, recorder: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(recorder);
+        self
+    }
+
+    /// How long a used payment is remembered and refused
+    pub fn replay_window(mut self, window: Duration) -> Self {
+        self.replay_window = Some(window);
+        self
+    }
+
+    /// Rewrite the responses to requests whose payment was verified
+    ///
+    /// `transformer` gets the handler's response together with the verified
+    /// authorization and a [`PaymentReceipt`] for it. Only applies when
+    /// payments are verified, i.e. `config.auto_verify` is set.
+    pub fn transform_response(
+        mut self,
+        transformer: impl Fn(HttpResponse, &PaymentAuthorization, &PaymentReceipt) -> HttpResponse
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.transformer = Some(Arc::new(transformer));
+        self
+    }
+
+    /// Assemble the middleware
+    ///
+    /// Fails if no config was given or it lacks a payment address or token mint.
+    pub fn build(self) -> X402Result<X402Middleware> {
+        let config = self.config.ok_or_else(|| {
+            X402Error::Configuration("X402Protection requires an X402Config".to_string())
+        })?;
+        if config.payment_address.is_empty() || config.token_mint.is_empty() {
+            return Err(X402Error::Configuration(
+                "X402Config must set payment_address and token_mint".to_string(),
+            ));
+        }
+
+        let auto_verify = config.auto_verify;
+        let options = config.verification_options();
+        let mut middleware = X402Middleware::new(config.clone(), self.pricing);
+
+        if auto_verify {
+            let mut processor = match self.payment_processor {
+                Some(processor) => processor,
+                None => config.payment_processor()?,
+            };
+            if let Some(metrics) = &self.metrics {
+                processor = processor.with_metrics(metrics.clone());
+            }
+
+            middleware = middleware.with_verifier(PaymentVerifier {
+                processor,
+                options,
+                nonces: self
+                    .nonce_store
+                    .unwrap_or_else(|| Arc::new(MemoryStore::new())),
+                grants: self
+                    .grant_store
+                    .unwrap_or_else(|| Arc::new(MemoryStore::new())),
+                challenges: self
+                    .challenge_store
+                    .unwrap_or_else(|| Arc::new(MemoryStore::new())),
+                replay_window: self.replay_window.unwrap_or(DEFAULT_REPLAY_WINDOW),
+                metrics: self.metrics,
+                transformer: self.transformer,
+            });
+        }
+
+        Ok(middleware)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+    use openlibx402_core::{
+        rpc::mock::{MockRpcBackend, TransactionFixture},
+        PaymentAuthorization,
+    };
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        verifications: Mutex<usize>,
+        collected: Mutex<u64>,
+        failures: Mutex<Vec<&'static str>>,
+    }
+
+    impl MetricsRecorder for RecordingMetrics {
+        fn record_verification(&self, _latency: Duration) {
+            *self.verifications.lock().unwrap() += 1;
+        }
+
+        fn record_payment_collected(&self, _mint: &str, amount: u64) {
+            *self.collected.lock().unwrap() += amount;
+        }
+
+        fn record_failure(&self, _operation: &'static str, reason: &'static str) {
+            self.failures.lock().unwrap().push(reason);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_transformer_attaches_receipt() {
+        use actix_web::{
+            body::MessageBody,
+            http::header::{HeaderName, HeaderValue},
+        };
+        use openlibx402_core::RECEIPT_HEADER;
+
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(signature).transfer(mint, recipient, 100_000));
+        let protection = X402Protection::builder()
+            .config(X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            })
+            .route("/premium", PaymentRequirement::new("0.10"))
+            .payment_processor(SolanaPaymentProcessor::with_backend(
+                Arc::new(backend),
+                None,
+            ))
+            .transform_response(|mut response, _authorization, receipt| {
+                let header = HeaderValue::from_str(&receipt.to_header_value().unwrap()).unwrap();
+                let name = HeaderName::from_bytes(RECEIPT_HEADER.as_bytes()).unwrap();
+                response.headers_mut().insert(name, header);
+                // Wrap JSON bodies with the receipt
+                let (response, body) = response.into_parts();
+                let data: serde_json::Value =
+                    serde_json::from_slice(&body.try_into_bytes().unwrap()).unwrap();
+                response.set_body(
+                    serde_json::json!({ "data": data, "receipt": receipt })
+                        .to_string()
+                        .boxed(),
+                )
+            })
+            .build()
+            .unwrap();
+        let app = test::init_service(App::new().wrap(protection).route(
+            "/premium",
+            web::get().to(|| async { HttpResponse::Ok().json(serde_json::json!({ "n": 1 })) }),
+        ))
+        .await;
+
+        let header = PaymentAuthorization::new(
+            "pay_1".to_string(),
+            "0.10".to_string(),
+            recipient.to_string(),
+            mint.to_string(),
+            "solana-devnet".to_string(),
+            signature.to_string(),
+            Pubkey::new_unique().to_string(),
+        )
+        .to_header_value()
+        .unwrap();
+        let res = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/premium")
+                .insert_header(("X-Payment-Authorization", header))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let receipt = PaymentReceipt::from_header_value(
+            res.headers().get(RECEIPT_HEADER).unwrap().to_str().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(receipt.payment_id, "pay_1");
+        assert_eq!(receipt.signature, signature.to_string());
+        assert_eq!(receipt.amount, "0.10");
+        assert_eq!(receipt.resource, "/premium");
+
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["data"], serde_json::json!({ "n": 1 }));
+        assert_eq!(body["receipt"]["payment_id"], "pay_1");
+
+        // Unpaid requests are challenged as before
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/premium").to_request()).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+        assert!(res.headers().get(RECEIPT_HEADER).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_build_requires_config() {
+        assert!(X402Protection::builder().build().is_err());
+        assert!(X402Protection::builder()
+            .config(X402Config::default())
+            .build()
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_build_rejects_unknown_commitment() {
+        let config = X402Config {
+            payment_address: Pubkey::new_unique().to_string(),
+            token_mint: Pubkey::new_unique().to_string(),
+            commitment: Some("final".to_string()),
+            ..Default::default()
+        };
+
+        let Err(error) = X402Protection::builder().config(config.clone()).build() else {
+            panic!("unknown commitment accepted");
+        };
+        assert!(matches!(error, X402Error::Configuration(_)));
+        assert!(error.to_string().contains("'final'"));
+
+        let config = X402Config {
+            commitment: Some("finalized".to_string()),
+            ..config
+        };
+        assert!(config.payment_processor().is_ok());
+        assert!(X402Protection::builder().config(config).build().is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_protection_enforces_payment_and_prevents_replay() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(signature).transfer(mint, recipient, 100_000));
+        let metrics = Arc::new(RecordingMetrics::default());
+
+        let protection = X402Protection::builder()
+            .config(X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            })
+            .route("/premium", PaymentRequirement::new("0.10"))
+            .payment_processor(SolanaPaymentProcessor::with_backend(
+                Arc::new(backend),
+                None,
+            ))
+            .metrics(metrics.clone())
+            .build()
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap(protection)
+                .route("/premium", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/premium").to_request()).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+
+        let header = PaymentAuthorization::new(
+            "pay_1".to_string(),
+            "0.10".to_string(),
+            recipient.to_string(),
+            mint.to_string(),
+            "solana-devnet".to_string(),
+            signature.to_string(),
+            Pubkey::new_unique().to_string(),
+        )
+        .to_header_value()
+        .unwrap();
+        let paid = || {
+            test::TestRequest::get()
+                .uri("/premium")
+                .insert_header(("X-Payment-Authorization", header.clone()))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, paid()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = test::call_service(&app, paid()).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        // Stale authorizations are refused before anything is checked on-chain
+        let mut stale = PaymentAuthorization::new(
+            "pay_2".to_string(),
+            "0.10".to_string(),
+            recipient.to_string(),
+            mint.to_string(),
+            "solana-devnet".to_string(),
+            Signature::new_unique().to_string(),
+            Pubkey::new_unique().to_string(),
+        );
+        stale.timestamp = chrono::Utc::now() - chrono::Duration::seconds(600);
+        let req = test::TestRequest::get()
+            .uri("/premium")
+            .insert_header(("X-Payment-Authorization", stale.to_header_value().unwrap()))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["code"], "PAYMENT_EXPIRED");
+
+        // The replay still verifies on-chain before its signature is refused
+        assert_eq!(*metrics.verifications.lock().unwrap(), 2);
+        assert_eq!(*metrics.collected.lock().unwrap(), 200_000);
+        assert_eq!(*metrics.failures.lock().unwrap(), vec!["PAYMENT_REPLAYED"]);
+    }
+
+    #[actix_web::test]
+    async fn test_scope_protection_charges_every_route_in_scope() {
+        use crate::test_support::MockPayments;
+        use actix_web::{HttpMessage, HttpRequest};
+        use openlibx402_core::PaymentRequest;
+
+        async fn payer(req: HttpRequest) -> HttpResponse {
+            match req.extensions().get::<PaymentAuthorization>() {
+                Some(authorization) => HttpResponse::Ok().body(authorization.public_key.clone()),
+                None => HttpResponse::InternalServerError().finish(),
+            }
+        }
+
+        let payments = MockPayments::new();
+        let protection = X402Protection::scope(PaymentRequirement::new("0.05"))
+            .config(payments.config().clone())
+            .payment_processor(payments.processor())
+            .build()
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .route("/health", web::get().to(HttpResponse::Ok))
+                .service(
+                    web::scope("/api")
+                        .wrap(protection)
+                        .route("/quote", web::get().to(payer))
+                        .route("/news", web::get().to(payer)),
+                ),
+        )
+        .await;
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/health").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        for uri in ["/api/quote", "/api/news"] {
+            let res =
+                test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+            let request: PaymentRequest = test::read_body_json(res).await;
+            assert_eq!(request.max_amount_required, "0.05");
+            assert_eq!(request.resource, uri);
+
+            let authorization = payments.pay(&request).await.unwrap();
+            let res = test::call_service(
+                &app,
+                test::TestRequest::get()
+                    .uri(uri)
+                    .insert_header((
+                        "X-Payment-Authorization",
+                        authorization.to_header_value().unwrap(),
+                    ))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(test::read_body(res).await, authorization.public_key);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_cached_result_is_served_to_retries() {
+        use crate::test_support::{payment_app, MockPayments};
+        use openlibx402_core::PaymentRequest;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let report = {
+            let runs = runs.clone();
+            move || {
+                let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                async move { HttpResponse::Ok().body(format!("report #{}", run)) }
+            }
+        };
+        let payments = MockPayments::new();
+        let pricing = PricingTable::new()
+            .route(
+                "/report",
+                PaymentRequirement::new("0.10").with_cached_result(),
+            )
+            .route("/quote", PaymentRequirement::new("0.10"));
+        let app = test::init_service(
+            payment_app(&payments, pricing)
+                .route("/report", web::get().to(report.clone()))
+                .route("/quote", web::get().to(report)),
+        )
+        .await;
+
+        let pay = |uri: &'static str| {
+            let (app, payments) = (&app, &payments);
+            async move {
+                let res =
+                    test::call_service(app, test::TestRequest::get().uri(uri).to_request()).await;
+                let request: PaymentRequest = test::read_body_json(res).await;
+                payments
+                    .pay(&request)
+                    .await
+                    .unwrap()
+                    .to_header_value()
+                    .unwrap()
+            }
+        };
+        let paid = |uri: &str, header: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("X-Payment-Authorization", header.to_string()))
+                .to_request()
+        };
+
+        let header = pay("/report").await;
+        let res = test::call_service(&app, paid("/report", &header)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, "report #1");
+
+        // The retry gets the first result without running the handler again
+        let res = test::call_service(&app, paid("/report", &header)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().contains_key(openlibx402_core::PRICE_HEADER));
+        assert_eq!(test::read_body(res).await, "report #1");
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // Routes without cached results still refuse the replay
+        let header = pay("/quote").await;
+        let res = test::call_service(&app, paid("/quote", &header)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, paid("/quote", &header)).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
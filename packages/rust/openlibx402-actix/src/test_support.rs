@@ -0,0 +1,179 @@
+//! In-process payment-protected apps for downstream tests
+//!
+//! Enabled by the `test-util` feature. [`MockPayments`] stands in for the
+//! chain: it pays challenges from a funded wallet on a
+//! [`MockRpcBackend`] and verifies them against the same backend, so tests
+//! exercise the full 402 → pay → retry flow without a cluster.
+//!
+//! ```rust,no_run
+//! use actix_web::{test, web, HttpResponse};
+//! use openlibx402_actix::{
+//!     test_support::{payment_app, MockPayments},
+//!     PaymentRequirement, PricingTable,
+//! };
+//!
+//! # async fn example() {
+//! let payments = MockPayments::new();
+//! let pricing = PricingTable::new().route("/premium", PaymentRequirement::new("0.10"));
+//! let app = test::init_service(
+//!     payment_app(&payments, pricing).route("/premium", web::get().to(HttpResponse::Ok)),
+//! )
+//! .await;
+//! # }
+//! ```
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    web, App, Error,
+};
+use openlibx402_core::{
+    rpc::mock::MockRpcBackend, PaymentAuthorization, PaymentRequest, SolanaPaymentProcessor,
+    X402Result,
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use std::sync::Arc;
+
+use crate::{pricing::PricingTable, X402Config, X402Protection, X402State};
+
+/// Decimals of the mock token mint
+const MOCK_TOKEN_DECIMALS: u8 = 6;
+
+/// Mock chain that pays and verifies challenges in memory
+///
+/// The recipient and token mint are fresh addresses, and the payer wallet
+/// holds enough of the token for any test.
+pub struct MockPayments {
+    backend: Arc<MockRpcBackend>,
+    payer: Keypair,
+    config: X402Config,
+}
+
+impl MockPayments {
+    /// Create a mock chain with a funded payer
+    pub fn new() -> Self {
+        let (recipient, mint, payer) = (Pubkey::new_unique(), Pubkey::new_unique(), Keypair::new());
+        let backend = MockRpcBackend::new()
+            .with_mint(mint, MOCK_TOKEN_DECIMALS)
+            .with_balance(payer.pubkey(), 1_000_000_000)
+            .with_associated_token_account(payer.pubkey(), mint, u64::MAX / 2)
+            .with_associated_token_account(recipient, mint, 0)
+            .with_confirmed_sends();
+
+        Self {
+            backend: Arc::new(backend),
+            payer,
+            config: X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Configuration of apps served by [`payment_app`]
+    pub fn config(&self) -> &X402Config {
+        &self.config
+    }
+
+    /// The mock RPC backend, for scripting failures or inspecting calls
+    pub fn backend(&self) -> &MockRpcBackend {
+        &self.backend
+    }
+
+    /// Payment processor reading the mock chain
+    pub fn processor(&self) -> SolanaPaymentProcessor {
+        SolanaPaymentProcessor::with_backend(self.backend.clone(), None)
+    }
+
+    /// Pay `request` from the funded wallet
+    ///
+    /// The transfer lands on the mock chain immediately, so the returned
+    /// authorization passes verification.
+    pub async fn pay(&self, request: &PaymentRequest) -> X402Result<PaymentAuthorization> {
+        self.processor().create_payment(request, &self.payer).await
+    }
+}
+
+impl Default for MockPayments {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build an app charging according to `pricing`, verified against `payments`
+///
+/// The app is wrapped in [`X402Protection`] and holds the [`X402State`] and
+/// payment processor that [`PaymentExtractor`](crate::PaymentExtractor)
+/// needs. Add routes for the paths under test.
+pub fn payment_app(
+    payments: &MockPayments,
+    pricing: PricingTable,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    let protection = X402Protection::builder()
+        .config(payments.config.clone())
+        .pricing(pricing)
+        .payment_processor(payments.processor())
+        .build()
+        .expect("mock config is complete");
+
+    App::new()
+        .app_data(web::Data::new(X402State {
+            config: payments.config.clone(),
+        }))
+        .app_data(web::Data::new(payments.processor()))
+        .wrap(protection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PaymentExtractor, PaymentRequirement};
+    use actix_web::{http::StatusCode, test, HttpResponse};
+
+    async fn premium(payment: PaymentExtractor) -> HttpResponse {
+        HttpResponse::Ok().body(payment.authorization.payment_id)
+    }
+
+    #[actix_web::test]
+    async fn test_payment_app_fixture() {
+        let payments = MockPayments::new();
+        let pricing = PricingTable::new().route("/premium", PaymentRequirement::new("0.10"));
+        let app = test::init_service(
+            payment_app(&payments, pricing).route("/premium", web::get().to(premium)),
+        )
+        .await;
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/premium").to_request()).await;
+        assert_eq!(res.status(), StatusCode::PAYMENT_REQUIRED);
+        let request: PaymentRequest = test::read_body_json(res).await;
+
+        let authorization = payments.pay(&request).await.unwrap();
+        let header = authorization.to_header_value().unwrap();
+        let paid = || {
+            test::TestRequest::get()
+                .uri("/premium")
+                .insert_header(("X-Payment-Authorization", header.clone()))
+                .to_request()
+        };
+        let res = test::call_service(&app, paid()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, request.payment_id);
+
+        // The same payment is not accepted twice
+        let res = test::call_service(&app, paid()).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,978 @@
+//! # OpenLibx402 Axum
+//!
+//! Axum web framework integration for the X402 payment protocol.
+//!
+//! This library provides extractors to protect Axum endpoints with payment
+//! requirements using the X402 protocol.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use axum::{routing::get, Extension, Json, Router};
+//! use openlibx402_axum::{PaymentExtractor, PaymentRequirement, X402Config, X402State};
+//!
+//! async fn premium_data(auth: PaymentExtractor) -> Json<serde_json::Value> {
+//!     Json(serde_json::json!({
+//!         "message": "Premium content!",
+//!         "payment_id": auth.authorization.payment_id
+//!     }))
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let config = X402Config {
+//!         payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+//!         token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+//!         network: "solana-devnet".to_string(),
+//!         rpc_url: None,
+//!         auto_verify: true,
+//!         ..Default::default()
+//!     };
+//!
+//!     let app = Router::new()
+//!         .route(
+//!             "/premium",
+//!             get(premium_data).layer(Extension(PaymentRequirement::new("0.10"))),
+//!         )
+//!         .with_state(X402State::new(config));
+//!
+//!     let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await.unwrap();
+//!     axum::serve(listener, app).await.unwrap();
+//! }
+//! ```
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use openlibx402_core::{
+    negotiation::PROBLEM_JSON_CONTENT_TYPE, ChallengeFormat, NonceStore, PaymentAuthorization,
+    PaymentIdGenerator, PaymentProblem, PaymentRequest, RentExemptionReport,
+    SolanaPaymentProcessor, UuidGenerator, VerificationOptions, X402Error, X402Result,
+    AUTHORIZATION_HEADER, DEFAULT_EXPIRES_IN, PAYMENT_REQUEST_HEADER,
+};
+use std::{fmt, sync::Arc};
+
+/// Global X402 configuration
+#[derive(Debug, Clone)]
+pub struct X402Config {
+    /// Wallet address to receive payments
+    pub payment_address: String,
+
+    /// SPL token mint address (USDC)
+    pub token_mint: String,
+
+    /// Solana network (e.g., "solana-devnet", "solana-mainnet")
+    pub network: String,
+
+    /// Optional custom RPC URL
+    pub rpc_url: Option<String>,
+
+    /// Commitment payments must reach to be accepted: `processed`,
+    /// `confirmed` or `finalized` (default: confirmed)
+    pub commitment: Option<String>,
+
+    /// Whether to verify payments on-chain
+    pub auto_verify: bool,
+
+    /// Accept payments up to this many basis points below the required amount (default: 0)
+    pub amount_tolerance_bps: u16,
+
+    /// Token symbol shown in the `X-Payment-Price` header (default: "USDC")
+    pub token_symbol: String,
+
+    /// Source of payment IDs for issued payment requests (default: UUIDv4)
+    pub payment_ids: Arc<dyn PaymentIdGenerator>,
+
+    /// Facilitator advertised to payers for settling payments (default: none)
+    pub facilitator_url: Option<String>,
+
+    /// Header payers send authorizations in (default: `X-Payment-Authorization`)
+    pub authorization_header: String,
+
+    /// Oldest authorization accepted, by its `timestamp` (default: the
+    /// requirement's `expires_in`)
+    pub max_authorization_age: Option<std::time::Duration>,
+}
+
+impl Default for X402Config {
+    fn default() -> Self {
+        Self {
+            payment_address: String::new(),
+            token_mint: String::new(),
+            network: "solana-devnet".to_string(),
+            rpc_url: None,
+            commitment: None,
+            auto_verify: true,
+            amount_tolerance_bps: 0,
+            token_symbol: "USDC".to_string(),
+            payment_ids: Arc::new(UuidGenerator),
+            facilitator_url: None,
+            authorization_header: AUTHORIZATION_HEADER.to_string(),
+            max_authorization_age: None,
+        }
+    }
+}
+
+impl X402Config {
+    /// Create a payment processor for the configured network, RPC URL and
+    /// commitment
+    ///
+    /// Fails with a configuration error if `commitment` isn't a known level.
+    pub fn payment_processor(&self) -> X402Result<SolanaPaymentProcessor> {
+        let rpc_url = self
+            .rpc_url
+            .as_deref()
+            .unwrap_or_else(|| SolanaPaymentProcessor::default_rpc_url(&self.network));
+        let commitment = self
+            .commitment
+            .as_deref()
+            .map(SolanaPaymentProcessor::parse_commitment)
+            .transpose()?;
+        Ok(SolanaPaymentProcessor::new(rpc_url, commitment))
+    }
+
+    /// Value of the `X-Payment-Price` header for `requirement`, e.g. `0.10 USDC`
+    pub fn advertised_price(&self, requirement: &PaymentRequirement) -> String {
+        format!("{} {}", requirement.amount, self.token_symbol)
+    }
+
+    /// Verification policy derived from this configuration
+    pub fn verification_options(&self) -> VerificationOptions {
+        VerificationOptions::default().with_amount_tolerance_bps(self.amount_tolerance_bps)
+    }
+
+    /// Oldest authorization accepted for `requirement`
+    ///
+    /// `max_authorization_age` if set, otherwise how long the requirement's
+    /// payment requests stay payable ([`DEFAULT_EXPIRES_IN`] without one).
+    pub fn authorization_age_limit(
+        &self,
+        requirement: Option<&PaymentRequirement>,
+    ) -> std::time::Duration {
+        self.max_authorization_age.unwrap_or_else(|| {
+            requirement.map_or(DEFAULT_EXPIRES_IN, |requirement| {
+                std::time::Duration::from_secs(requirement.expires_in.max(0) as u64)
+            })
+        })
+    }
+
+    /// Check that the recipient wallet and its token account are rent-exempt
+    ///
+    /// Run this at startup, before binding the server, to catch recipients that
+    /// would make payments fail in production. With `strict` set a recipient
+    /// that isn't rent-exempt is an error; otherwise the problems are listed in
+    /// the report's `warnings()`.
+    pub async fn check_rent_exemption(&self, strict: bool) -> X402Result<RentExemptionReport> {
+        self.payment_processor()?
+            .ensure_rent_exemption(&self.payment_address, &self.token_mint, strict)
+            .await
+    }
+}
+
+/// Router state the payment extractors read
+///
+/// Pass it to `Router::with_state`, or make it reachable from the app's own
+/// state with an `impl FromRef<AppState> for X402State`.
+#[derive(Clone)]
+pub struct X402State {
+    pub config: X402Config,
+    processor: Option<Arc<SolanaPaymentProcessor>>,
+    nonces: Option<Arc<dyn NonceStore>>,
+}
+
+impl X402State {
+    /// State verifying payments through a processor built from `config`
+    pub fn new(config: X402Config) -> Self {
+        Self {
+            config,
+            processor: None,
+            nonces: None,
+        }
+    }
+
+    /// Verify payments through `processor` instead of the config's RPC endpoint
+    pub fn with_payment_processor(mut self, processor: SolanaPaymentProcessor) -> Self {
+        self.processor = Some(Arc::new(processor));
+        self
+    }
+
+    /// Record the signature of each transaction that paid in `store` and
+    /// reject reuse
+    ///
+    /// Use a shared store such as `RedisStore` to reject replays across
+    /// server instances.
+    pub fn with_nonce_store(mut self, store: Arc<dyn NonceStore>) -> Self {
+        self.nonces = Some(store);
+        self
+    }
+}
+
+impl fmt::Debug for X402State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X402State")
+            .field("config", &self.config)
+            .field("nonces", &self.nonces.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Payment requirement configuration for a specific endpoint
+#[derive(Debug, Clone)]
+pub struct PaymentRequirement {
+    /// Amount required in USDC
+    pub amount: String,
+
+    /// Optional description
+    pub description: Option<String>,
+
+    /// Expiration time in seconds (default: 300)
+    pub expires_in: i64,
+}
+
+impl PaymentRequirement {
+    /// Create a new payment requirement
+    pub fn new(amount: &str) -> Self {
+        Self {
+            amount: amount.to_string(),
+            description: None,
+            expires_in: 300,
+        }
+    }
+
+    /// Set the description
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Set the expiration time
+    pub fn with_expires_in(mut self, seconds: i64) -> Self {
+        self.expires_in = seconds;
+        self
+    }
+}
+
+/// How long used payments are remembered by default
+pub const DEFAULT_REPLAY_WINDOW: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// Extractor that enforces payment requirements
+///
+/// Requests without an `X-Payment-Authorization` header are refused with a
+/// 402. When the route carries its [`PaymentRequirement`] as an `Extension`,
+/// the 402 carries a `PaymentRequest` for the request path, negotiated like
+/// [`payment_required_response_for`]:
+///
+/// ```rust,no_run
+/// use axum::{routing::get, Extension, Router};
+/// use openlibx402_axum::{PaymentExtractor, PaymentRequirement, X402Config, X402State};
+///
+/// async fn premium(auth: PaymentExtractor) -> String {
+///     auth.authorization.payment_id
+/// }
+///
+/// let app: Router = Router::new()
+///     .route(
+///         "/premium",
+///         get(premium).layer(Extension(PaymentRequirement::new("0.10"))),
+///     )
+///     .with_state(X402State::new(X402Config::default()));
+/// ```
+///
+/// Authorizations older than the config's
+/// [`authorization_age_limit`](X402Config::authorization_age_limit) are
+/// refused with a 402, before any verification, so a settled payment can't be
+/// presented again after its payment request has expired.
+///
+/// With `auto_verify` set, the authorization must pay the configured
+/// recipient and token, and its transfer is verified on-chain through the
+/// state's payment processor before the handler runs. The transfer must
+/// cover the route's requirement, whose amount is exposed as
+/// `verified_amount`; a route without one is refused with a 500 rather than
+/// trusting the amount the authorization claims. Failed verification is a
+/// 400.
+///
+/// With a nonce store in the state (see [`X402State::with_nonce_store`]),
+/// the signature of the transaction that paid (the authorization's
+/// `signature` without `auto_verify`) is recorded there for
+/// [`DEFAULT_REPLAY_WINDOW`] and a reused one is rejected with 409 Conflict,
+/// whatever `payment_id` it comes with.
+pub struct PaymentExtractor {
+    pub authorization: PaymentAuthorization,
+
+    /// Amount confirmed on-chain, or `None` if verification is off
+    pub verified_amount: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for PaymentExtractor
+where
+    X402State: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = PaymentError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let requirement = parts.extensions.get::<PaymentRequirement>().cloned();
+        extract_payment(parts, &X402State::from_ref(state), requirement).await
+    }
+}
+
+/// Extractor for endpoints priced at `CENTS` hundredths of a token
+///
+/// Works like [`PaymentExtractor`] with a [`PaymentRequirement`] fixed in the
+/// handler's signature, so no route extension is needed: `RequirePayment<10>`
+/// charges 0.10. Unpaid requests get a 402 with a `PaymentRequest` for the
+/// request path.
+///
+/// ```rust,no_run
+/// use openlibx402_axum::RequirePayment;
+///
+/// async fn premium(auth: RequirePayment<10>) -> String {
+///     auth.authorization.payment_id
+/// }
+/// ```
+pub struct RequirePayment<const CENTS: u64> {
+    pub authorization: PaymentAuthorization,
+
+    /// Amount confirmed on-chain, or `None` if verification is off
+    pub verified_amount: Option<String>,
+}
+
+impl<const CENTS: u64> RequirePayment<CENTS> {
+    /// Requirement the extractor charges
+    pub fn requirement() -> PaymentRequirement {
+        PaymentRequirement::new(&format!("{}.{:02}", CENTS / 100, CENTS % 100))
+    }
+}
+
+#[async_trait]
+impl<S, const CENTS: u64> FromRequestParts<S> for RequirePayment<CENTS>
+where
+    X402State: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = PaymentError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let PaymentExtractor {
+            authorization,
+            verified_amount,
+        } = extract_payment(
+            parts,
+            &X402State::from_ref(state),
+            Some(Self::requirement()),
+        )
+        .await?;
+        Ok(RequirePayment {
+            authorization,
+            verified_amount,
+        })
+    }
+}
+
+/// Extract and check the payment on `parts`, charging `requirement` if known
+async fn extract_payment(
+    parts: &Parts,
+    state: &X402State,
+    requirement: Option<PaymentRequirement>,
+) -> Result<PaymentExtractor, PaymentError> {
+    let config = &state.config;
+
+    // Get payment authorization header
+    let auth_header = match parts.headers.get(config.authorization_header.as_str()) {
+        Some(h) => h.to_str().map_err(|_| PaymentError::InvalidHeader)?,
+        None => return Err(payment_required(parts, config, requirement.as_ref())),
+    };
+
+    // Parse authorization
+    let authorization = PaymentAuthorization::from_header_value(auth_header)
+        .map_err(|e| PaymentError::InvalidAuthorization(e.to_string()))?;
+    authorization
+        .check_age(config.authorization_age_limit(requirement.as_ref()))
+        .map_err(|e| PaymentError::Expired(e.to_string()))?;
+
+    let mut verified_amount = None;
+    let mut paid_by = authorization.signature.clone();
+    if config.auto_verify {
+        // The claimed amount is the client's word, so there is nothing to verify against
+        let Some(requirement) = &requirement else {
+            return Err(PaymentError::Configuration(X402Error::Configuration(
+                "auto_verify needs a PaymentRequirement for the route".to_string(),
+            )));
+        };
+        let required = &requirement.amount;
+        let verified = match &state.processor {
+            Some(processor) => {
+                verify_authorization(config, processor, &authorization, required).await
+            }
+            None => {
+                let processor = config
+                    .payment_processor()
+                    .map_err(PaymentError::Configuration)?;
+                verify_authorization(config, &processor, &authorization, required).await
+            }
+        };
+        paid_by = verified.map_err(|e| PaymentError::InvalidAuthorization(e.to_string()))?;
+        verified_amount = Some(required.clone());
+    }
+
+    if let Some(nonces) = &state.nonces {
+        let retain_until =
+            Utc::now() + Duration::from_std(DEFAULT_REPLAY_WINDOW).unwrap_or(Duration::MAX);
+        let fresh = nonces
+            .check_and_record(&paid_by, retain_until)
+            .await
+            .map_err(PaymentError::Store)?;
+        if !fresh {
+            return Err(PaymentError::Replayed(paid_by));
+        }
+    }
+
+    Ok(PaymentExtractor {
+        authorization,
+        verified_amount,
+    })
+}
+
+/// 402 refusing the request in `parts`, challenging for `requirement` if known
+fn payment_required(
+    parts: &Parts,
+    config: &X402Config,
+    requirement: Option<&PaymentRequirement>,
+) -> PaymentError {
+    match requirement {
+        Some(requirement) => {
+            let payment_request = create_payment_request(config, requirement, parts.uri.path());
+            let accept = parts
+                .headers
+                .get(header::ACCEPT)
+                .and_then(|h| h.to_str().ok());
+            PaymentError::Challenge(
+                Box::new(payment_request),
+                ChallengeFormat::from_accept(accept),
+            )
+        }
+        None => PaymentError::Required,
+    }
+}
+
+/// Verify `authorization` pays `config`'s recipient at least `required`
+///
+/// Returns the candidate signature that made the payment.
+async fn verify_authorization(
+    config: &X402Config,
+    processor: &SolanaPaymentProcessor,
+    authorization: &PaymentAuthorization,
+    required: &str,
+) -> X402Result<String> {
+    if authorization.payment_address != config.payment_address
+        || authorization.asset_address != config.token_mint
+    {
+        return Err(X402Error::PaymentVerification(
+            "Payment was not made to this server's address and token".to_string(),
+        ));
+    }
+
+    processor
+        .verify_payment_signature(authorization, required, &config.verification_options())
+        .await
+}
+
+/// Rejection of the payment extractors
+#[derive(Debug)]
+pub enum PaymentError {
+    /// No payment, and no known price to challenge for
+    Required,
+
+    /// No payment; answered with a challenge for the route's price
+    Challenge(Box<PaymentRequest>, ChallengeFormat),
+
+    InvalidHeader,
+    InvalidAuthorization(String),
+    Expired(String),
+    Replayed(String),
+
+    /// The nonce store failed
+    Store(X402Error),
+
+    /// The server's `X402Config` is invalid
+    Configuration(X402Error),
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::Required | PaymentError::Challenge(..) => write!(f, "Payment required"),
+            PaymentError::InvalidHeader => write!(f, "Invalid authorization header"),
+            PaymentError::InvalidAuthorization(msg) => {
+                write!(f, "Invalid payment authorization: {}", msg)
+            }
+            PaymentError::Expired(msg) => write!(f, "{}", msg),
+            PaymentError::Replayed(signature) => {
+                write!(f, "Transaction {} was already used", signature)
+            }
+            PaymentError::Store(e) | PaymentError::Configuration(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+impl PaymentError {
+    /// Core error this rejection corresponds to
+    fn as_x402(&self) -> X402Error {
+        match self {
+            PaymentError::Required | PaymentError::Challenge(..) => {
+                X402Error::PaymentRequired(self.to_string())
+            }
+            PaymentError::InvalidHeader | PaymentError::InvalidAuthorization(_) => {
+                X402Error::InvalidPaymentAuthorization(self.to_string())
+            }
+            PaymentError::Expired(_) => X402Error::PaymentExpired(self.to_string()),
+            PaymentError::Replayed(_) => X402Error::PaymentReplayed(self.to_string()),
+            PaymentError::Store(e) | PaymentError::Configuration(e) => e.clone(),
+        }
+    }
+}
+
+impl IntoResponse for PaymentError {
+    fn into_response(self) -> Response {
+        let error = self.as_x402();
+        let status = match self {
+            PaymentError::Store(_) | PaymentError::Configuration(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => status_code(&error),
+        };
+        let message = self.to_string();
+        let body = match self {
+            PaymentError::Challenge(payment_request, format) => {
+                return challenge_response(*payment_request, format)
+            }
+            PaymentError::Required => serde_json::json!({
+                "error": "Payment required",
+                "message": "This endpoint requires payment"
+            }),
+            PaymentError::Expired(_) | PaymentError::Replayed(_) => serde_json::json!({
+                "error": message,
+                "code": error.code()
+            }),
+            _ => serde_json::json!({
+                "error": message
+            }),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+/// Status code for a request rejected with `error`
+fn status_code(error: &X402Error) -> StatusCode {
+    StatusCode::from_u16(error.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Create a payment request for an endpoint
+pub fn create_payment_request(
+    config: &X402Config,
+    requirement: &PaymentRequirement,
+    resource: &str,
+) -> PaymentRequest {
+    let mut request = PaymentRequest::builder()
+        .amount(requirement.amount.as_str())
+        .asset_address(config.token_mint.as_str())
+        .payment_address(config.payment_address.as_str())
+        .network(config.network.as_str())
+        .expires_at(Utc::now() + Duration::seconds(requirement.expires_in))
+        .payment_id(config.payment_ids.generate())
+        .resource(resource)
+        .build()
+        .expect("request with all required fields");
+
+    if let Some(desc) = &requirement.description {
+        request = request.with_description(desc.clone());
+    }
+    if let Some(url) = &config.facilitator_url {
+        request = request.with_facilitator_url(url.clone());
+    }
+
+    request
+}
+
+/// Helper function to create a 402 Payment Required response
+pub fn payment_required_response(payment_request: PaymentRequest) -> Response {
+    (StatusCode::PAYMENT_REQUIRED, Json(payment_request)).into_response()
+}
+
+/// Create a 402 Payment Required response in the form the client asked for
+///
+/// Inspects the request's `Accept` header and responds with the payment request
+/// as plain JSON (default), `application/problem+json`, or an empty body with
+/// the base64-encoded request in the `X-Payment-Request` header.
+pub fn payment_required_response_for(
+    headers: &HeaderMap,
+    payment_request: PaymentRequest,
+) -> Response {
+    let accept = headers.get(header::ACCEPT).and_then(|h| h.to_str().ok());
+    challenge_response(payment_request, ChallengeFormat::from_accept(accept))
+}
+
+/// 402 carrying `payment_request` in `format`
+fn challenge_response(payment_request: PaymentRequest, format: ChallengeFormat) -> Response {
+    match format {
+        ChallengeFormat::Json => payment_required_response(payment_request),
+        ChallengeFormat::ProblemJson => (
+            StatusCode::PAYMENT_REQUIRED,
+            [(header::CONTENT_TYPE, PROBLEM_JSON_CONTENT_TYPE)],
+            Json(PaymentProblem::new(payment_request)),
+        )
+            .into_response(),
+        ChallengeFormat::HeaderOnly => match payment_request.to_base64() {
+            Ok(encoded) => (
+                StatusCode::PAYMENT_REQUIRED,
+                [(PAYMENT_REQUEST_HEADER, encoded)],
+            )
+                .into_response(),
+            Err(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+                .into_response(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body},
+        http::Request,
+        routing::get,
+        Extension, Router,
+    };
+    use openlibx402_core::rpc::mock::{MockRpcBackend, TransactionFixture};
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
+    use tower::ServiceExt;
+
+    const RECIPIENT: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+    const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn test_config() -> X402Config {
+        X402Config {
+            payment_address: RECIPIENT.to_string(),
+            token_mint: MINT.to_string(),
+            auto_verify: false,
+            ..Default::default()
+        }
+    }
+
+    fn authorization(payment_id: &str, signature: &str) -> PaymentAuthorization {
+        PaymentAuthorization::new(
+            payment_id.to_string(),
+            "0.10".to_string(),
+            RECIPIENT.to_string(),
+            MINT.to_string(),
+            "solana-devnet".to_string(),
+            signature.to_string(),
+            RECIPIENT.to_string(),
+        )
+    }
+
+    fn get_request(uri: &str, authorization: Option<&PaymentAuthorization>) -> Request<Body> {
+        let mut request = Request::builder().uri(uri);
+        if let Some(authorization) = authorization {
+            request = request.header(
+                AUTHORIZATION_HEADER,
+                authorization.to_header_value().unwrap(),
+            );
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    async fn call(app: &Router, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    async fn verified(payment: PaymentExtractor) -> String {
+        payment.verified_amount.unwrap_or_default()
+    }
+
+    #[test]
+    fn test_create_payment_request() {
+        let requirement = PaymentRequirement::new("0.10")
+            .with_description("Premium data access")
+            .with_expires_in(600);
+        let request = create_payment_request(&test_config(), &requirement, "/api/premium");
+
+        assert_eq!(request.max_amount_required, "0.10");
+        assert_eq!(request.payment_address, RECIPIENT);
+        assert_eq!(request.asset_address, MINT);
+        assert_eq!(request.resource, "/api/premium");
+        assert_eq!(request.description.as_deref(), Some("Premium data access"));
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_challenge_format() {
+        let payment_request =
+            create_payment_request(&test_config(), &PaymentRequirement::new("0.10"), "/premium");
+        let negotiate = |accept: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, accept.parse().unwrap());
+            payment_required_response_for(&headers, payment_request.clone())
+        };
+
+        let response = negotiate("application/json");
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let parsed: PaymentRequest = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed, payment_request);
+
+        let response = negotiate("application/problem+json");
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            PROBLEM_JSON_CONTENT_TYPE
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: PaymentProblem = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.payment_request, payment_request);
+
+        let response = negotiate("text/plain");
+        let encoded = response.headers().get(PAYMENT_REQUEST_HEADER).unwrap();
+        assert_eq!(
+            PaymentRequest::from_base64(encoded.to_str().unwrap()).unwrap(),
+            payment_request
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extractors_challenge_with_payment_request() {
+        async fn fixed(payment: RequirePayment<10>) -> String {
+            payment.authorization.payment_id
+        }
+
+        let app = Router::new()
+            .route("/const", get(fixed))
+            .route(
+                "/routed",
+                get(verified).layer(Extension(
+                    PaymentRequirement::new("0.25").with_description("Routed"),
+                )),
+            )
+            .route("/unpriced", get(verified))
+            .with_state(X402State::new(test_config()));
+
+        for (uri, amount) in [("/const", "0.10"), ("/routed", "0.25")] {
+            let (status, body) = call(&app, get_request(uri, None)).await;
+            assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+            let request: PaymentRequest = serde_json::from_slice(&body).unwrap();
+            assert_eq!(request.max_amount_required, amount);
+            assert_eq!(request.resource, uri);
+            assert_eq!(request.payment_address, RECIPIENT);
+        }
+
+        // Without a known price the 402 has no payment request to offer
+        let (status, body) = call(&app, get_request("/unpriced", None)).await;
+        assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "Payment required");
+
+        // Without auto_verify the authorization is only parsed
+        let paid = authorization("pay_1", "signature");
+        let (status, body) = call(&app, get_request("/const", Some(&paid))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"pay_1");
+
+        let malformed = Request::builder()
+            .uri("/const")
+            .header(AUTHORIZATION_HEADER, "not base64")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = call(&app, malformed).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_payment_extractor_verifies_on_chain() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(signature).transfer(mint, recipient, 100_000));
+        let state = X402State::new(X402Config {
+            payment_address: recipient.to_string(),
+            token_mint: mint.to_string(),
+            ..Default::default()
+        })
+        .with_payment_processor(SolanaPaymentProcessor::with_backend(
+            Arc::new(backend),
+            None,
+        ));
+        let app = Router::new()
+            .route(
+                "/premium",
+                get(verified).layer(Extension(PaymentRequirement::new("0.10"))),
+            )
+            .route("/unpriced", get(verified))
+            .with_state(state);
+        let paid_at = |uri: &str, signature: &Signature| {
+            let authorization = PaymentAuthorization::new(
+                "pay_1".to_string(),
+                "0.10".to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            );
+            get_request(uri, Some(&authorization))
+        };
+        let paid = |signature: &Signature| paid_at("/premium", signature);
+
+        let (status, body) = call(&app, paid(&signature)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"0.10");
+
+        // Without a requirement there is no price to verify the claimed amount against
+        let (status, _) = call(&app, paid_at("/unpriced", &signature)).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let (status, body) = call(&app, paid(&Signature::new_unique())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_payment_extractor_rejects_replay() {
+        let store: Arc<dyn NonceStore> = Arc::new(openlibx402_core::MemoryStore::new());
+        let app = Router::new()
+            .route("/premium", get(verified))
+            .with_state(X402State::new(test_config()).with_nonce_store(store));
+        let paid = |payment_id: &str, signature: &str| {
+            get_request("/premium", Some(&authorization(payment_id, signature)))
+        };
+
+        let (status, _) = call(&app, paid("pay_1", "signature_1")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(&app, paid("pay_1", "signature_1")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "PAYMENT_REPLAYED");
+
+        // The same transfer presented under a fresh payment ID is still a replay
+        let (status, _) = call(&app, paid("pay_2", "signature_1")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = call(&app, paid("pay_2", "signature_2")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_payment_extractor_records_only_the_paying_signature() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (own, other) = (Signature::new_unique(), Signature::new_unique());
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(own).transfer(mint, recipient, 100_000))
+            .with_fixture(TransactionFixture::new(other).transfer(mint, recipient, 100_000));
+        let store: Arc<dyn NonceStore> = Arc::new(openlibx402_core::MemoryStore::new());
+        let state = X402State::new(X402Config {
+            payment_address: recipient.to_string(),
+            token_mint: mint.to_string(),
+            ..Default::default()
+        })
+        .with_payment_processor(SolanaPaymentProcessor::with_backend(
+            Arc::new(backend),
+            None,
+        ))
+        .with_nonce_store(store);
+        let app = Router::new()
+            .route(
+                "/premium",
+                get(verified).layer(Extension(PaymentRequirement::new("0.10"))),
+            )
+            .with_state(state);
+        let paid = |payment_id: &str, signature: &Signature, extra: Vec<String>| {
+            let authorization = PaymentAuthorization::new(
+                payment_id.to_string(),
+                "0.10".to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            )
+            .with_signatures(extra);
+            get_request("/premium", Some(&authorization))
+        };
+
+        // Listing someone else's transfer as an extra candidate doesn't spend it
+        let (status, _) = call(&app, paid("pay_1", &own, vec![other.to_string()])).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&app, paid("pay_2", &other, vec![])).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = call(&app, paid("pay_3", &other, vec![])).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_payment_extractor_rejects_stale_authorization() {
+        let app = Router::new()
+            .route(
+                "/premium",
+                get(verified).layer(Extension(PaymentRequirement::new("0.10"))),
+            )
+            .with_state(X402State::new(test_config()));
+        let aged = |age: i64| {
+            let mut authorization = authorization("pay_1", "signature");
+            authorization.timestamp = Utc::now() - Duration::seconds(age);
+            get_request("/premium", Some(&authorization))
+        };
+
+        let (status, _) = call(&app, aged(10)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(&app, aged(600)).await;
+        assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "PAYMENT_EXPIRED");
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("accepted for 300s"));
+    }
+
+    #[tokio::test]
+    async fn test_payment_extractor_reads_configured_header() {
+        let app = Router::new()
+            .route("/premium", get(verified))
+            .with_state(X402State::new(X402Config {
+                authorization_header: "X-PAYMENT".to_string(),
+                ..test_config()
+            }));
+        let header = authorization("pay_1", "signature")
+            .to_header_value()
+            .unwrap();
+        let paid = |name: &str| {
+            Request::builder()
+                .uri("/premium")
+                .header(name, header.clone())
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let (status, _) = call(&app, paid("X-PAYMENT")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // A payment in the default header is missing, not malformed
+        let (status, _) = call(&app, paid(AUTHORIZATION_HEADER)).await;
+        assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+    }
+}
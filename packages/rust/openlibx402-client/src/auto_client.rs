@@ -1,13 +1,67 @@
-use openlibx402_core::{PaymentRequest, X402Error, X402Result};
-use reqwest::{Response, StatusCode};
+use futures_util::{future::Shared, FutureExt};
+use openlibx402_core::{
+    PaymentAuthorization, PaymentEvent, PaymentRequest, SolanaPaymentProcessor, X402Error,
+    X402Result,
+};
+use rand::Rng;
+use reqwest::{header::HeaderMap, Response, StatusCode, Url};
 use solana_sdk::signature::Keypair;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot};
 
-use crate::client::X402Client;
+use crate::{body::ReplayableBody, client::X402Client};
+
+/// Decides whether a failed payment attempt should be retried
+///
+/// Called with the error and the number of payment attempts made so far
+/// (starting at 1), for every failed attempt. The policy alone decides which
+/// errors are retried; check [`X402Error::is_retryable`] to leave terminal
+/// errors such as insufficient funds alone. Retries are always capped by
+/// `max_retries`.
+pub type RetryPolicy = Arc<dyn Fn(&X402Error, u32) -> bool + Send + Sync>;
+
+/// Default retry policy: retry only when the server still answers 402 after payment
+///
+/// Terminal errors are never retried.
+pub fn default_retry_policy() -> RetryPolicy {
+    Arc::new(|error, _attempt| {
+        error.is_retryable() && matches!(error, X402Error::PaymentRequired(_))
+    })
+}
+
+/// What to do when a 402 challenge names a different resource than the one requested
+///
+/// A mismatch can mean a misconfigured server, or a man-in-the-middle
+/// pointing the payment at a cheaper endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResourceMismatch {
+    /// Pay anyway without comment
+    Ignore,
+
+    /// Log a warning with the `tracing` feature and pay anyway
+    #[default]
+    Warn,
+
+    /// Refuse to pay with `X402Error::InvalidPaymentRequest`
+    Reject,
+}
 
 /// Configuration options for the auto client
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct AutoClientOptions {
-    /// Maximum amount willing to pay automatically (in USDC)
+    /// Maximum amount willing to pay automatically, in whole units of the
+    /// asset asked for (e.g. "5.0" USDC)
+    ///
+    /// Compared exactly against each request's amount in the asset's base
+    /// units, so it may have no more decimal places than the asset supports.
     pub max_payment_amount: String,
 
     /// Whether to automatically retry after payment
@@ -15,6 +69,63 @@ pub struct AutoClientOptions {
 
     /// Maximum number of retry attempts
     pub max_retries: u32,
+
+    /// Which errors and attempts trigger a retry (see [`default_retry_policy`])
+    pub retry_policy: RetryPolicy,
+
+    /// Handling of challenges whose `resource` isn't the requested path
+    pub resource_mismatch: ResourceMismatch,
+
+    /// Timeout for each HTTP request made by [`X402AutoClient::new`]
+    pub request_timeout: Option<Duration>,
+
+    /// Connect timeout for each HTTP request made by [`X402AutoClient::new`]
+    pub connect_timeout: Option<Duration>,
+
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+
+    /// Factor the delay grows by after each retry
+    pub backoff_multiplier: f64,
+
+    /// Longest delay between retries
+    pub max_backoff: Duration,
+
+    /// Randomize each delay to between half and all of its scheduled length
+    ///
+    /// Spreads out retries from many clients failing at the same time.
+    pub jitter: bool,
+
+    /// Present a payment again on later requests for the same resource
+    ///
+    /// Authorizations are kept in memory until their payment request expires.
+    /// One the server refuses with a fresh 402 is dropped and paid anew.
+    pub reuse_authorizations: bool,
+
+    /// Least time a challenge must have left for a payment to be attempted
+    ///
+    /// Challenges expiring sooner fail with `X402Error::PaymentExpired`
+    /// instead of broadcasting a payment the server would then refuse.
+    pub min_payment_ttl: Duration,
+
+    /// Headers sent with every attempt, e.g. an API key
+    ///
+    /// They take precedence over the client's own default headers, except
+    /// that a payment authorization the client presents replaces any value
+    /// given here for the authorization header.
+    pub default_headers: HeaderMap,
+
+    /// Most the client may spend over its lifetime, in the smallest token unit
+    ///
+    /// Payments that would take [`X402AutoClient::total_spent`] past it are
+    /// refused with `X402Error::SpendLimitExceeded`, however small each is.
+    /// Amounts of different assets are added up as they are.
+    pub session_spend_limit: Option<u64>,
+
+    /// Channel to report the progress of each payment on
+    ///
+    /// See [`X402Client::with_payment_events`].
+    pub payment_events: Option<mpsc::Sender<PaymentEvent>>,
 }
 
 impl Default for AutoClientOptions {
@@ -23,17 +134,150 @@ impl Default for AutoClientOptions {
             max_payment_amount: "10.0".to_string(),
             auto_retry: true,
             max_retries: 3,
+            retry_policy: default_retry_policy(),
+            resource_mismatch: ResourceMismatch::default(),
+            request_timeout: None,
+            connect_timeout: None,
+            initial_backoff: Duration::from_millis(250),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(5),
+            jitter: false,
+            reuse_authorizations: false,
+            min_payment_ttl: Duration::from_secs(2),
+            default_headers: HeaderMap::new(),
+            session_spend_limit: None,
+            payment_events: None,
         }
     }
 }
 
+impl AutoClientOptions {
+    /// Scheduled delay before retry number `retry` (starting at 0), before jitter
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self.initial_backoff.as_secs_f64()
+            * self
+                .backoff_multiplier
+                .powi(retry.min(i32::MAX as u32) as i32);
+        if delay.is_finite() && delay < self.max_backoff.as_secs_f64() {
+            Duration::from_secs_f64(delay.max(0.0))
+        } else {
+            self.max_backoff
+        }
+    }
+}
+
+impl fmt::Debug for AutoClientOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoClientOptions")
+            .field("max_payment_amount", &self.max_payment_amount)
+            .field("auto_retry", &self.auto_retry)
+            .field("max_retries", &self.max_retries)
+            .field("resource_mismatch", &self.resource_mismatch)
+            .field("request_timeout", &self.request_timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("backoff_multiplier", &self.backoff_multiplier)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .field("reuse_authorizations", &self.reuse_authorizations)
+            .field("min_payment_ttl", &self.min_payment_ttl)
+            // Values may be credentials, so only names are shown
+            .field(
+                "default_headers",
+                &self.default_headers.keys().collect::<Vec<_>>(),
+            )
+            .field("session_spend_limit", &self.session_spend_limit)
+            .field("payment_events", &self.payment_events)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Final response of an auto client request, with the payment made for it
+#[derive(Debug)]
+pub struct DetailedResponse {
+    /// Response to the last request sent
+    pub response: Response,
+
+    /// Authorization of the payment this call made, if it paid
+    ///
+    /// `None` when no payment was needed, including when an authorization
+    /// kept from an earlier call was presented instead.
+    pub payment: Option<PaymentAuthorization>,
+
+    /// Payment request that was paid, if the call paid
+    pub request: Option<PaymentRequest>,
+
+    /// Payment attempts made, 0 when the first response wasn't a 402
+    pub attempts: u32,
+}
+
 /// X402 HTTP client with automatic payment handling
 ///
 /// This client automatically detects 402 Payment Required responses,
 /// creates and sends payments, and retries the original request.
+/// Concurrent requests for the same resource at the same price share one
+/// payment, if the server accepts it more than once.
+#[derive(Debug)]
 pub struct X402AutoClient {
     client: X402Client,
     options: AutoClientOptions,
+    authorizations: Mutex<HashMap<String, CachedAuthorization>>,
+    /// Payments being made, by resource, recipient, asset and amount
+    in_flight: Mutex<HashMap<String, InFlightPayment>>,
+    /// Amount paid so far, in the smallest token unit
+    spent: AtomicU64,
+}
+
+/// Result of a payment being made, awaited by every call for the same terms
+type InFlightPayment = Shared<oneshot::Receiver<X402Result<PaymentAuthorization>>>;
+
+/// Removes a payment from those in flight once it finishes or is dropped
+struct InFlightGuard<'a> {
+    in_flight: &'a Mutex<HashMap<String, InFlightPayment>>,
+    key: String,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key);
+    }
+}
+
+/// Payment obtained by [`X402AutoClient::pay`]
+enum Payment<'a> {
+    /// Made by this call; calls waiting for it are handed the authorization
+    /// once this call has presented it
+    Made(PaymentAuthorization, PaymentShare<'a>),
+
+    /// Made by a concurrent call, which has already presented it
+    Shared(PaymentAuthorization),
+}
+
+/// Hands a payment on to the calls waiting for it
+struct PaymentShare<'a> {
+    // Declared before `sender`, so waiting calls find the payment gone if
+    // this is dropped without sharing it
+    _guard: InFlightGuard<'a>,
+    sender: oneshot::Sender<X402Result<PaymentAuthorization>>,
+}
+
+impl PaymentShare<'_> {
+    /// Give the waiting calls `authorization` to present
+    fn release(self, authorization: &PaymentAuthorization) {
+        let _ = self.sender.send(Ok(authorization.clone()));
+    }
+}
+
+/// Authorization kept for reuse, with the request it paid
+#[derive(Debug, Clone)]
+struct CachedAuthorization {
+    request: PaymentRequest,
+    authorization: PaymentAuthorization,
+    /// Whether it may be presented more than once
+    reusable: bool,
 }
 
 impl X402AutoClient {
@@ -48,106 +292,622 @@ impl X402AutoClient {
         rpc_url: Option<&str>,
         options: Option<AutoClientOptions>,
     ) -> Self {
+        let options = options.unwrap_or_default();
+        let mut builder = X402Client::builder().keypair(keypair);
+        if let Some(rpc_url) = rpc_url {
+            builder = builder.rpc_url(rpc_url);
+        }
+        if let Some(timeout) = options.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        let client = builder
+            .build()
+            .expect("client with a keypair and no headers");
+        Self::from_client(client, Some(options))
+    }
+
+    /// Create an auto client wrapping an existing client
+    ///
+    /// Requests use `client`'s own timeouts; `request_timeout` and
+    /// `connect_timeout` in `options` are ignored. `payment_events`, if set,
+    /// replaces any channel `client` reports payments on.
+    pub fn from_client(client: X402Client, options: Option<AutoClientOptions>) -> Self {
+        let options = options.unwrap_or_default();
+        let client = match &options.payment_events {
+            Some(events) => client.with_payment_events(events.clone()),
+            None => client,
+        };
         Self {
-            client: X402Client::new(keypair, rpc_url),
-            options: options.unwrap_or_default(),
+            client,
+            options,
+            authorizations: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            spent: AtomicU64::new(0),
         }
     }
 
+    /// Forget all authorizations kept for reuse
+    pub fn clear_cache(&self) {
+        self.cached_authorizations().clear();
+    }
+
+    /// Amount paid so far, in the smallest token unit
+    ///
+    /// Counts every payment made since the client was created or
+    /// [`reset_spend`](Self::reset_spend) was last called.
+    pub fn total_spent(&self) -> u64 {
+        self.spent.load(Ordering::SeqCst)
+    }
+
+    /// Start counting spend towards `session_spend_limit` from zero again
+    pub fn reset_spend(&self) {
+        self.spent.store(0, Ordering::SeqCst);
+    }
+
     /// Make a GET request with automatic payment handling
     pub async fn get(&self, url: &str) -> X402Result<Response> {
-        self.request("GET", url, None).await
+        self.request_detailed("GET", url, None)
+            .await
+            .map(|detailed| detailed.response)
     }
 
     /// Make a POST request with automatic payment handling
-    pub async fn post(&self, url: &str, body: Option<String>) -> X402Result<Response> {
-        self.request("POST", url, body).await
+    pub async fn post(&self, url: &str, body: impl Into<ReplayableBody>) -> X402Result<Response> {
+        self.request_detailed("POST", url, body)
+            .await
+            .map(|detailed| detailed.response)
     }
 
-    /// Make an HTTP request with automatic payment handling
-    async fn request(&self, method: &str, url: &str, body: Option<String>) -> X402Result<Response> {
+    /// Make a PUT request with automatic payment handling
+    pub async fn put(&self, url: &str, body: impl Into<ReplayableBody>) -> X402Result<Response> {
+        self.request_detailed("PUT", url, body)
+            .await
+            .map(|detailed| detailed.response)
+    }
+
+    /// Make a PATCH request with automatic payment handling
+    pub async fn patch(&self, url: &str, body: impl Into<ReplayableBody>) -> X402Result<Response> {
+        self.request_detailed("PATCH", url, body)
+            .await
+            .map(|detailed| detailed.response)
+    }
+
+    /// Make a DELETE request with automatic payment handling
+    pub async fn delete(&self, url: &str) -> X402Result<Response> {
+        self.request_detailed("DELETE", url, None)
+            .await
+            .map(|detailed| detailed.response)
+    }
+
+    /// Make a GET request with automatic payment handling, for a body read as it arrives
+    ///
+    /// The paid response is returned before its body is read, so large
+    /// payloads can be consumed chunk by chunk with
+    /// [`Response::chunk`](reqwest::Response::chunk), or with `bytes_stream`
+    /// when reqwest's `stream` feature is enabled, instead of being buffered
+    /// whole. Only the 402 challenge's body is read along the way.
+    ///
+    /// The download is paid at most once: failures before paying are retried
+    /// as with [`get`](Self::get), but once a payment has been sent a failed
+    /// attempt is returned rather than retried with a new payment.
+    pub async fn get_stream(&self, url: &str) -> X402Result<Response> {
+        self.send("GET", url, ReplayableBody::Empty, true)
+            .await
+            .map(|detailed| detailed.response)
+    }
+
+    /// Make a GET request with automatic payment handling, reporting the payment made
+    pub async fn get_detailed(&self, url: &str) -> X402Result<DetailedResponse> {
+        self.request_detailed("GET", url, None).await
+    }
+
+    /// Make an HTTP request with automatic payment handling, reporting the payment made
+    ///
+    /// `method` is one of GET, POST, PUT, PATCH or DELETE. The result carries
+    /// the payment request paid and the authorization created for it, so
+    /// callers can log or reconcile what each call cost.
+    ///
+    /// Failed payment attempts are retried while `retry_policy` allows it and
+    /// fewer than `max_retries` attempts have been made. An attempt that
+    /// times out counts whether or not it got as far as paying. Retries wait
+    /// an exponentially growing backoff, or the error's
+    /// [`retry_after`](X402Error::retry_after) hint if longer. A re-issued
+    /// challenge asking for more than the first approved amount is never paid.
+    ///
+    /// The body is sent again with each retry; a [`ReplayableBody`] holding
+    /// `Bytes` is shared between attempts instead of copied.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "x402_request",
+            skip_all,
+            fields(method = %method, resource = url.split(['?', '#']).next().unwrap_or_default())
+        )
+    )]
+    pub async fn request_detailed(
+        &self,
+        method: &str,
+        url: &str,
+        body: impl Into<ReplayableBody>,
+    ) -> X402Result<DetailedResponse> {
+        self.send(method, url, body.into(), false).await
+    }
+
+    /// Run the payment flow, retrying failed attempts
+    ///
+    /// With `pay_once`, an attempt that may have sent a payment is never
+    /// followed by another.
+    async fn send(
+        &self,
+        method: &str,
+        url: &str,
+        body: ReplayableBody,
+        pay_once: bool,
+    ) -> X402Result<DetailedResponse> {
+        let mut attempts = 0;
         let mut retries = 0;
+        let mut approved_amount = None;
+        let mut paid = false;
 
         loop {
-            // Make initial request
-            let response = match method {
-                "GET" => self.client.get(url).await?,
-                "POST" => self.client.post(url, body.clone()).await?,
-                _ => {
-                    return Err(X402Error::Configuration(format!(
-                        "Unsupported HTTP method: {}",
-                        method
-                    )))
-                }
+            let attempts_before = attempts;
+            let error = match self
+                .attempt(
+                    method,
+                    url,
+                    &body,
+                    &mut attempts,
+                    &mut approved_amount,
+                    &mut paid,
+                )
+                .await
+            {
+                Ok(detailed) => return Ok(detailed),
+                Err(error) => error,
             };
 
-            // Check if payment is required
-            if response.status() == StatusCode::PAYMENT_REQUIRED {
-                // Check retry limit
-                if retries >= self.options.max_retries {
-                    return Err(X402Error::PaymentRequired(
-                        "Maximum retry attempts reached".to_string(),
-                    ));
+            if matches!(error, X402Error::Timeout { .. }) && attempts == attempts_before {
+                attempts += 1;
+            }
+
+            if (pay_once && paid)
+                || attempts >= self.options.max_retries
+                || !(self.options.retry_policy)(&error, attempts)
+            {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(attempts, code = error.code(), error = %error, "request failed");
+                return Err(error);
+            }
+
+            let mut delay = self.options.backoff(retries);
+            if self.options.jitter {
+                delay = delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+            }
+            if let Some(hint) = error.retry_after() {
+                delay = delay.max(hint);
+            }
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                attempts,
+                code = error.code(),
+                error = %error,
+                delay_ms = delay.as_millis() as u64,
+                "payment attempt failed, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            retries += 1;
+        }
+    }
+
+    /// Run one round of the payment flow
+    ///
+    /// Sends the request and, on a 402, pays and retries it with the payment
+    /// authorization. A retry that is still answered with 402 is reported as
+    /// `X402Error::PaymentRequired`. `paid` is set once a payment may have
+    /// been sent.
+    async fn attempt(
+        &self,
+        method: &str,
+        url: &str,
+        body: &ReplayableBody,
+        attempts: &mut u32,
+        approved_amount: &mut Option<u64>,
+        paid: &mut bool,
+    ) -> X402Result<DetailedResponse> {
+        // Make initial request, presenting an earlier payment if there is one
+        let cache_key = cache_key(url)?;
+        let cached = self.cached_authorization(&cache_key);
+        let response = self
+            .client
+            .request_with_headers(
+                method,
+                url,
+                body.clone(),
+                cached.as_ref(),
+                &self.options.default_headers,
+            )
+            .await?;
+
+        // Return successful or non-402 error responses
+        if response.status() != StatusCode::PAYMENT_REQUIRED {
+            return Ok(DetailedResponse {
+                response,
+                payment: None,
+                request: None,
+                attempts: *attempts,
+            });
+        }
+        if cached.is_some() {
+            self.cached_authorizations().remove(&cache_key);
+        }
+
+        // Check retry limit
+        if *attempts >= self.options.max_retries {
+            return Err(X402Error::PaymentRequired(
+                "Maximum retry attempts reached".to_string(),
+            ));
+        }
+        *attempts += 1;
+
+        // Parse payment request
+        let payment_request = self.client.parse_payment_request(response).await?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            payment_id = %payment_request.payment_id,
+            amount = %payment_request.max_amount_required,
+            network = %payment_request.network,
+            attempt = *attempts,
+            "payment required"
+        );
+
+        // Check the challenge is for the resource we asked for
+        self.check_resource(url, &payment_request)?;
+
+        // Check if amount is acceptable
+        let amount = self.check_payment_amount(&payment_request).await?;
+
+        // A re-issued challenge must not raise the price we already approved
+        let approved = *approved_amount.get_or_insert(amount);
+        if amount > approved {
+            return Err(X402Error::InvalidPaymentRequest(format!(
+                "Re-issued payment request raised the amount from {} to {} base units",
+                approved, amount
+            )));
+        }
+
+        // Don't pay a challenge that expires before the payment can land
+        let remaining = payment_request.remaining_ttl();
+        if remaining < self.options.min_payment_ttl {
+            return Err(X402Error::PaymentExpired(format!(
+                "Payment request {} expires in {:?}, too soon to pay",
+                payment_request.payment_id, remaining
+            )));
+        }
+
+        // Create and send payment, or wait for a concurrent call's
+        let (mut authorization, share) = match self.pay(url, &payment_request, amount).await {
+            Ok(Payment::Made(authorization, share)) => (authorization, Some(share)),
+            Ok(Payment::Shared(authorization)) => (authorization, None),
+            Err(error) => {
+                if is_maybe_sent(&error) {
+                    *paid = true;
                 }
-                retries += 1;
+                return Err(error);
+            }
+        };
 
-                // Parse payment request
-                let payment_request = self.client.parse_payment_request(response).await?;
+        *paid = true;
 
-                // Check if amount is acceptable
-                self.check_payment_amount(&payment_request)?;
+        // Retry request with payment authorization
+        let shared = share.is_none();
+        let retry = self
+            .client
+            .request_with_headers(
+                method,
+                url,
+                body.clone(),
+                Some(&authorization),
+                &self.options.default_headers,
+            )
+            .await;
+        if let Some(share) = share {
+            share.release(&authorization);
+        }
+        let mut retry_response = retry?;
 
-                // Create and send payment
-                let authorization = self.client.create_payment(&payment_request).await?;
+        // Servers that refuse replays accept another call's payment only
+        // when they serve its cached result, so pay this challenge otherwise
+        if shared
+            && matches!(
+                retry_response.status(),
+                StatusCode::PAYMENT_REQUIRED | StatusCode::CONFLICT
+            )
+        {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                payment_id = %payment_request.payment_id,
+                "shared payment refused, paying separately"
+            );
+            let remaining = payment_request.remaining_ttl();
+            if remaining < self.options.min_payment_ttl {
+                return Err(X402Error::PaymentExpired(format!(
+                    "Payment request {} expires in {:?}, too soon to pay",
+                    payment_request.payment_id, remaining
+                )));
+            }
+            authorization = self.make_payment(&payment_request, amount).await?;
+            retry_response = self
+                .client
+                .request_with_headers(
+                    method,
+                    url,
+                    body.clone(),
+                    Some(&authorization),
+                    &self.options.default_headers,
+                )
+                .await?;
+        }
 
-                // Retry request with payment authorization
-                let retry_response = match method {
-                    "GET" => self.client.get_with_auth(url, &authorization).await?,
-                    "POST" => {
-                        self.client
-                            .post_with_auth(url, body.clone(), &authorization)
-                            .await?
-                    }
-                    _ => unreachable!(),
-                };
+        if retry_response.status() == StatusCode::PAYMENT_REQUIRED {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                payment_id = %authorization.payment_id,
+                signature = %authorization.signature,
+                "server refused payment"
+            );
+            return Err(X402Error::PaymentRequired(
+                "Payment was not accepted by the server".to_string(),
+            ));
+        }
+
+        if retry_response.status().is_success() {
+            self.keep_authorization(
+                url,
+                cache_key,
+                payment_request.clone(),
+                authorization.clone(),
+            )?;
+        }
+
+        Ok(DetailedResponse {
+            response: retry_response,
+            payment: Some(authorization),
+            request: Some(payment_request),
+            attempts: *attempts,
+        })
+    }
+
+    /// Pay `request`, sharing one payment among concurrent calls like it
+    ///
+    /// Every 402 carries its own `payment_id`, so calls are matched on what
+    /// they pay for: the resource, recipient, asset and amount. The first
+    /// call makes the payment; calls for the same terms while it is under
+    /// way await it instead of broadcasting another transaction and counting
+    /// it against the spend limit again. They get the authorization only once
+    /// the first call has presented it, so a server caching the paid result
+    /// has it ready for them.
+    async fn pay(
+        &self,
+        url: &str,
+        request: &PaymentRequest,
+        amount: u64,
+    ) -> X402Result<Payment<'_>> {
+        let resource = parse_url(url)?
+            .join(&request.resource)
+            .map_err(|e| X402Error::InvalidPaymentRequest(format!("Invalid resource: {}", e)))?;
+        let key = format!(
+            "{} {} {} {}",
+            resource_key(&resource),
+            request.payment_address,
+            request.asset_address,
+            amount
+        );
 
-                // Check if retry was successful
-                if retry_response.status().is_success() {
-                    return Ok(retry_response);
+        loop {
+            let in_flight = {
+                let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+                match in_flight.get(&key) {
+                    Some(payment) => Err(payment.clone()),
+                    None => {
+                        let (sender, receiver) = oneshot::channel();
+                        in_flight.insert(key.clone(), receiver.shared());
+                        Ok(sender)
+                    }
                 }
+            };
 
-                // If still getting 402, continue loop
-                if retry_response.status() == StatusCode::PAYMENT_REQUIRED {
-                    continue;
+            match in_flight {
+                Ok(sender) => {
+                    let share = PaymentShare {
+                        _guard: InFlightGuard {
+                            in_flight: &self.in_flight,
+                            key,
+                        },
+                        sender,
+                    };
+                    return match self.make_payment(request, amount).await {
+                        Ok(authorization) => Ok(Payment::Made(authorization, share)),
+                        Err(error) => {
+                            let _ = share.sender.send(Err(error.clone()));
+                            Err(error)
+                        }
+                    };
                 }
+                Err(payment) => match payment.await {
+                    Ok(result) => return result.map(Payment::Shared),
+                    // The paying call was dropped before sharing its payment
+                    Err(_) => continue,
+                },
+            }
+        }
+    }
+
+    /// Count `amount` against the session's limit and pay `request`
+    async fn make_payment(
+        &self,
+        request: &PaymentRequest,
+        amount: u64,
+    ) -> X402Result<PaymentAuthorization> {
+        self.reserve_spend(amount)?;
+
+        let result = self.client.create_payment(request).await;
+        // A broadcast that failed or timed out may still land, so only
+        // payments that certainly weren't sent are uncounted
+        if matches!(&result, Err(error) if !is_maybe_sent(error)) {
+            let _ = self
+                .spent
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |spent| {
+                    Some(spent.saturating_sub(amount))
+                });
+        }
+        result
+    }
+
+    /// Keep an accepted authorization for later requests
+    ///
+    /// With `reuse_authorizations` it is kept for `url`. A bundle payment is
+    /// also kept for each of the bundle's other resources, to be presented
+    /// there once (or until refused, with `reuse_authorizations`).
+    fn keep_authorization(
+        &self,
+        url: &str,
+        cache_key: String,
+        request: PaymentRequest,
+        authorization: PaymentAuthorization,
+    ) -> X402Result<()> {
+        let reusable = self.options.reuse_authorizations;
+        let url = parse_url(url)?;
+        let mut cache = self.cached_authorizations();
 
-                // Return other error responses
-                return Ok(retry_response);
+        for resource in &request.resources {
+            let Ok(resource) = url.join(resource) else {
+                continue;
+            };
+            let key = resource_key(&resource);
+            if key != cache_key {
+                let cached = CachedAuthorization {
+                    request: request.clone(),
+                    authorization: authorization.clone(),
+                    reusable,
+                };
+                cache.insert(key, cached);
             }
+        }
+        if reusable {
+            let cached = CachedAuthorization {
+                request,
+                authorization,
+                reusable,
+            };
+            cache.insert(cache_key, cached);
+        }
+        Ok(())
+    }
+
+    fn cached_authorizations(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<String, CachedAuthorization>> {
+        self.authorizations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
 
-            // Return successful or non-402 error responses
-            return Ok(response);
+    /// Unexpired authorization kept for `cache_key`
+    ///
+    /// One that may only be presented once is taken out of the cache.
+    fn cached_authorization(&self, cache_key: &str) -> Option<PaymentAuthorization> {
+        let mut cache = self.cached_authorizations();
+        cache.retain(|_, cached| !cached.request.is_expired());
+        let cached = cache.get(cache_key)?;
+        if cached.reusable {
+            return Some(cached.authorization.clone());
         }
+        cache.remove(cache_key).map(|cached| cached.authorization)
     }
 
-    /// Check if the payment amount is acceptable
-    fn check_payment_amount(&self, request: &PaymentRequest) -> X402Result<()> {
-        let max_amount: f64 = self.options.max_payment_amount.parse().map_err(|e| {
-            X402Error::Configuration(format!("Invalid max_payment_amount: {}", e))
-        })?;
+    /// Apply `resource_mismatch` if the challenge's resource isn't `url`'s path
+    ///
+    /// The resource may be a path or an absolute URL; only paths are compared.
+    fn check_resource(&self, url: &str, request: &PaymentRequest) -> X402Result<()> {
+        let requested = parse_url(url)?;
+        let resource = match Url::parse(&request.resource) {
+            Ok(resource) => resource.path().to_string(),
+            Err(_) => request
+                .resource
+                .split(['?', '#'])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        };
+        if resource == requested.path() {
+            return Ok(());
+        }
 
-        let required_amount: f64 = request.max_amount_required.parse().map_err(|e| {
-            X402Error::InvalidPaymentRequest(format!("Invalid payment amount: {}", e))
-        })?;
+        match self.options.resource_mismatch {
+            ResourceMismatch::Ignore => Ok(()),
+            ResourceMismatch::Warn => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    resource = %request.resource,
+                    requested = requested.path(),
+                    "payment request is for a different resource"
+                );
+                Ok(())
+            }
+            ResourceMismatch::Reject => Err(X402Error::InvalidPaymentRequest(format!(
+                "Payment request is for resource {} but {} was requested",
+                request.resource,
+                requested.path()
+            ))),
+        }
+    }
 
-        if required_amount > max_amount {
+    /// Add `amount` to the spend, unless it would exceed `session_spend_limit`
+    fn reserve_spend(&self, amount: u64) -> X402Result<()> {
+        let limit = self.options.session_spend_limit;
+        self.spent
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |spent| {
+                let total = spent.saturating_add(amount);
+                match limit {
+                    Some(limit) if total > limit => None,
+                    _ => Some(total),
+                }
+            })
+            .map(|_| ())
+            .map_err(|spent| {
+                X402Error::SpendLimitExceeded(format!(
+                    "Paying {} would bring the session's spend from {} past its limit of {}",
+                    amount,
+                    spent,
+                    limit.unwrap_or_default()
+                ))
+            })
+    }
+
+    /// Check if the payment amount is acceptable, returning it in base units
+    ///
+    /// The amount and `max_payment_amount` are both converted to base units
+    /// of the request's asset, so they compare exactly.
+    async fn check_payment_amount(&self, request: &PaymentRequest) -> X402Result<u64> {
+        let decimals = self.client.asset_decimals(request).await?;
+        let max_amount =
+            SolanaPaymentProcessor::parse_amount(&self.options.max_payment_amount, decimals)
+                .map_err(|e| {
+                    X402Error::Configuration(format!("Invalid max_payment_amount: {}", e))
+                })?;
+        let amount = SolanaPaymentProcessor::parse_amount(&request.max_amount_required, decimals)?;
+
+        if amount > max_amount {
             return Err(X402Error::PaymentRequired(format!(
                 "Payment amount {} exceeds maximum allowed amount {}",
-                required_amount, max_amount
+                request.max_amount_required, self.options.max_payment_amount
             )));
         }
 
-        Ok(())
+        Ok(amount)
     }
 
     /// Get the underlying client for manual operations
@@ -161,6 +921,27 @@ impl X402AutoClient {
     }
 }
 
+/// Whether a payment that failed with `error` may still have been sent
+fn is_maybe_sent(error: &X402Error) -> bool {
+    matches!(
+        error,
+        X402Error::TransactionBroadcast(_) | X402Error::Timeout { .. }
+    )
+}
+
+fn parse_url(url: &str) -> X402Result<Url> {
+    Url::parse(url).map_err(|e| X402Error::Configuration(format!("Invalid URL {}: {}", url, e)))
+}
+
+/// Key of the resource `url` points at: its origin and path
+fn resource_key(url: &Url) -> String {
+    format!("{}{}", url.origin().ascii_serialization(), url.path())
+}
+
+fn cache_key(url: &str) -> X402Result<String> {
+    Ok(resource_key(&parse_url(url)?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -180,9 +961,708 @@ mod tests {
             max_payment_amount: "5.0".to_string(),
             auto_retry: false,
             max_retries: 1,
+            ..Default::default()
         };
         let client = X402AutoClient::new(keypair, None, Some(options));
         assert_eq!(client.options().max_payment_amount, "5.0");
         assert!(!client.options().auto_retry);
     }
+
+    async fn always_payment_required() -> (wiremock::MockServer, crate::testing::Fixture, X402Client)
+    {
+        let (client, fixture) = crate::testing::funded_client();
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/data"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(402)
+                    .set_body_json(fixture.payment_request("0.10", "/data")),
+            )
+            .mount(&server)
+            .await;
+        (server, fixture, client)
+    }
+
+    #[tokio::test]
+    async fn test_default_policy_retries_payment_required() {
+        let (server, fixture, client) = always_payment_required().await;
+        let client = X402AutoClient::from_client(client, None);
+
+        let err = client
+            .get(&format!("{}/data", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_REQUIRED");
+        assert_eq!(fixture.payments(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_custom_retry_policy_stops_retrying() {
+        let (server, fixture, client) = always_payment_required().await;
+        let options = AutoClientOptions {
+            retry_policy: Arc::new(|error, _attempt| {
+                !matches!(error, X402Error::PaymentRequired(_))
+            }),
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+
+        let err = client
+            .get(&format!("{}/data", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_REQUIRED");
+        assert_eq!(fixture.payments(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_terminal_errors_are_not_retried() {
+        use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        let mut expired = fixture.payment_request("0.10", "/data");
+        expired.expires_at = chrono::Utc::now() - chrono::Duration::seconds(1);
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(402).set_body_json(expired))
+            .expect(1)
+            .mount(&server)
+            .await;
+        // Retry anything that may succeed next time
+        let options = AutoClientOptions {
+            retry_policy: Arc::new(|error, _attempt| error.is_retryable()),
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+
+        // Paying an expired challenge again won't succeed either
+        let err = client
+            .get(&format!("{}/data", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_EXPIRED");
+        assert_eq!(fixture.payments(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_challenges_about_to_expire_are_not_paid() {
+        use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        let mut expiring = fixture.payment_request("0.10", "/data");
+        expiring.expires_at = chrono::Utc::now() + chrono::Duration::seconds(10);
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(402).set_body_json(expiring))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let options = AutoClientOptions {
+            min_payment_ttl: Duration::from_secs(30),
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+
+        let err = client
+            .get(&format!("{}/data", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_EXPIRED");
+        assert_eq!(fixture.payments(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_resource_mismatch_policy() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        Mock::given(path("/expensive"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/expensive"))
+            .respond_with(
+                ResponseTemplate::new(402).set_body_json(fixture.payment_request("0.01", "/cheap")),
+            )
+            .mount(&server)
+            .await;
+        let url = format!("{}/expensive", server.uri());
+
+        let options = AutoClientOptions {
+            resource_mismatch: ResourceMismatch::Reject,
+            ..Default::default()
+        };
+        let rejecting = X402AutoClient::from_client(client, Some(options));
+        let err = rejecting.get(&url).await.unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_REQUEST");
+        assert_eq!(fixture.payments(), 0);
+
+        // The default only warns and goes on to pay
+        let warning = X402AutoClient::from_client(rejecting.client, None);
+        let response = warning.get(&url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(fixture.payments(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_detailed_response_reports_payment() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        Mock::given(path("/free"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/data"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/data"))
+            .respond_with(
+                ResponseTemplate::new(402).set_body_json(fixture.payment_request("0.10", "/data")),
+            )
+            .mount(&server)
+            .await;
+        let (events, mut progress) = mpsc::channel(16);
+        let options = AutoClientOptions {
+            payment_events: Some(events),
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+
+        let free = client
+            .get_detailed(&format!("{}/free", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(free.response.status(), StatusCode::OK);
+        assert!(free.payment.is_none());
+        assert!(free.request.is_none());
+        assert_eq!(free.attempts, 0);
+
+        let paid = client
+            .get_detailed(&format!("{}/data", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(paid.response.status(), StatusCode::OK);
+        assert_eq!(paid.attempts, 1);
+        let (payment, request) = (paid.payment.unwrap(), paid.request.unwrap());
+        assert_eq!(payment.payment_id, request.payment_id);
+        assert_eq!(request.max_amount_required, "0.10");
+        assert_eq!(fixture.payments(), 1);
+
+        let mut last = None;
+        while let Ok(event) = progress.try_recv() {
+            last = Some(event);
+        }
+        match last {
+            Some(PaymentEvent::Done {
+                payment_id,
+                signature,
+            }) => {
+                assert_eq!(payment_id, payment.payment_id);
+                assert_eq!(signature, payment.signature);
+            }
+            other => panic!("expected a Done event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_payment_amount_compares_base_units() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        Mock::given(header_exists("X-Payment-Authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        // The fixture's mint has 6 decimals, so 0.100001 is one unit over 0.1
+        for (resource, amount) in [("/at-limit", "0.10"), ("/over-limit", "0.100001")] {
+            Mock::given(path(resource))
+                .respond_with(
+                    ResponseTemplate::new(402)
+                        .set_body_json(fixture.payment_request(amount, resource)),
+                )
+                .mount(&server)
+                .await;
+        }
+        let options = AutoClientOptions {
+            max_payment_amount: "0.1".to_string(),
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+
+        let response = client
+            .get(&format!("{}/at-limit", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(client.total_spent(), 100_000);
+
+        let err = client
+            .get(&format!("{}/over-limit", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_REQUIRED");
+        assert!(err.to_string().contains("0.100001 exceeds"), "{}", err);
+        assert_eq!(fixture.payments(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_spend_limit_stops_payments() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        Mock::given(path("/data"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/data"))
+            .respond_with(
+                ResponseTemplate::new(402).set_body_json(fixture.payment_request("0.10", "/data")),
+            )
+            .mount(&server)
+            .await;
+        // Every payment is well under max_payment_amount, but only two fit the session
+        let options = AutoClientOptions {
+            session_spend_limit: Some(250_000),
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+        let url = format!("{}/data", server.uri());
+
+        for _ in 0..2 {
+            let response = client.get(&url).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert_eq!(client.total_spent(), 200_000);
+
+        let err = client.get(&url).await.unwrap_err();
+        assert_eq!(err.code(), "SPEND_LIMIT_EXCEEDED");
+        assert_eq!(fixture.payments(), 2);
+        assert_eq!(client.total_spent(), 200_000);
+
+        client.reset_spend();
+        client.get(&url).await.unwrap();
+        assert_eq!(fixture.payments(), 3);
+        assert_eq!(client.total_spent(), 100_000);
+    }
+
+    #[tokio::test]
+    async fn test_patch_is_retried_with_authorization() {
+        use wiremock::{
+            matchers::{body_string, header_exists, method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        let body = r#"{"status":"archived"}"#;
+        Mock::given(method("PATCH"))
+            .and(path("/items/7"))
+            .and(header_exists("X-Payment-Authorization"))
+            .and(body_string(body))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/items/7"))
+            .and(body_string(body))
+            .respond_with(
+                ResponseTemplate::new(402)
+                    .set_body_json(fixture.payment_request("0.10", "/items/7")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = X402AutoClient::from_client(client, None);
+
+        let response = client
+            .patch(&format!("{}/items/7", server.uri()), Some(body.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(fixture.payments(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_large_body_is_replayed_after_payment() {
+        use wiremock::{
+            matchers::{header_exists, method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/upload"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload"))
+            .respond_with(
+                ResponseTemplate::new(402)
+                    .set_body_json(fixture.payment_request("0.10", "/upload")),
+            )
+            .mount(&server)
+            .await;
+        let client = X402AutoClient::from_client(client, None);
+
+        let payload = bytes::Bytes::from((0..2 * 1024 * 1024).map(|i| i as u8).collect::<Vec<_>>());
+        let body = ReplayableBody::from(payload.clone());
+        let response = client
+            .post(&format!("{}/upload", server.uri()), body.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let received = server.received_requests().await.unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(received.iter().all(|request| request.body == payload));
+
+        // Each attempt's body shares the caller's buffer rather than copying it
+        let sent = body.to_body().unwrap();
+        assert_eq!(sent.as_bytes().unwrap().as_ptr(), payload.as_ptr());
+    }
+
+    #[tokio::test]
+    async fn test_stream_download_is_paid_once() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        let payload = (0..4 * 1024 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        Mock::given(path("/dataset"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(payload.clone()))
+            .mount(&server)
+            .await;
+        Mock::given(path("/dataset"))
+            .respond_with(
+                ResponseTemplate::new(402)
+                    .set_body_json(fixture.payment_request("0.10", "/dataset")),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(path("/refused"))
+            .respond_with(
+                ResponseTemplate::new(402)
+                    .set_body_json(fixture.payment_request("0.10", "/refused")),
+            )
+            .mount(&server)
+            .await;
+        let client = X402AutoClient::from_client(client, None);
+
+        let mut response = client
+            .get_stream(&format!("{}/dataset", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut received = Vec::new();
+        let mut chunks = 0;
+        while let Some(chunk) = response.chunk().await.unwrap() {
+            received.extend_from_slice(&chunk);
+            chunks += 1;
+        }
+        assert!(chunks > 1, "body arrived in {} chunk", chunks);
+        assert_eq!(received, payload);
+        assert_eq!(fixture.payments(), 1);
+
+        // A refused payment isn't followed by another, unlike with `get`
+        let err = client
+            .get_stream(&format!("{}/refused", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_REQUIRED");
+        assert_eq!(fixture.payments(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_share_one_payment() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        // Slow RPC calls keep the payment in flight while every request gets its 402
+        let (builder, fixture) = crate::testing::funded_client_builder_with(|backend| {
+            backend.with_latency(Duration::from_millis(50))
+        });
+        let server = MockServer::start().await;
+        Mock::given(path("/data"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        // Every challenge has its own payment ID, as servers issue them
+        let request = fixture.payment_request("0.10", "/data");
+        let issued = AtomicU64::new(0);
+        Mock::given(path("/data"))
+            .respond_with(move |_: &wiremock::Request| {
+                let mut request = request.clone();
+                request.payment_id = format!("pay_{}", issued.fetch_add(1, Ordering::SeqCst));
+                ResponseTemplate::new(402).set_body_json(request)
+            })
+            .mount(&server)
+            .await;
+        let client = X402AutoClient::from_client(builder.build().unwrap(), None);
+
+        let url = format!("{}/data", server.uri());
+        let responses = futures_util::future::join_all((0..10).map(|_| client.get(&url))).await;
+        for response in responses {
+            assert_eq!(response.unwrap().status(), StatusCode::OK);
+        }
+        assert_eq!(fixture.payments(), 1);
+        assert_eq!(client.total_spent(), 100_000);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_raised_price_on_reissued_challenge() {
+        let (client, fixture) = crate::testing::funded_client();
+        let server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path("/data"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(402)
+                    .set_body_json(fixture.payment_request("0.10", "/data")),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        wiremock::Mock::given(wiremock::matchers::path("/data"))
+            .respond_with(
+                wiremock::ResponseTemplate::new(402)
+                    .set_body_json(fixture.payment_request("0.50", "/data")),
+            )
+            .mount(&server)
+            .await;
+        let client = X402AutoClient::from_client(client, None);
+
+        let err = client
+            .get(&format!("{}/data", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_REQUEST");
+        assert_eq!(fixture.payments(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_attempts_count_against_max_retries() {
+        use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+        let (builder, fixture) = crate::testing::funded_client_builder();
+        let client = builder
+            .timeout(std::time::Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let server = MockServer::start().await;
+        Mock::given(path("/slow"))
+            .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(5)))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let options = AutoClientOptions {
+            max_retries: 2,
+            retry_policy: Arc::new(|error, _attempt| matches!(error, X402Error::Timeout { .. })),
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+
+        let err = client
+            .get(&format!("{}/slow", server.uri()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "TIMEOUT");
+        assert_eq!(fixture.payments(), 0);
+    }
+
+    #[test]
+    fn test_backoff_schedule() {
+        let options = AutoClientOptions {
+            initial_backoff: Duration::from_millis(100),
+            backoff_multiplier: 3.0,
+            max_backoff: Duration::from_millis(1_000),
+            ..Default::default()
+        };
+        assert_eq!(options.backoff(0), Duration::from_millis(100));
+        assert_eq!(options.backoff(1), Duration::from_millis(300));
+        assert_eq!(options.backoff(2), Duration::from_millis(900));
+        assert_eq!(options.backoff(3), Duration::from_millis(1_000));
+        assert_eq!(options.backoff(u32::MAX), Duration::from_millis(1_000));
+    }
+
+    #[tokio::test]
+    async fn test_retries_back_off_exponentially() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        let challenge =
+            ResponseTemplate::new(402).set_body_json(fixture.payment_request("0.10", "/data"));
+        // The first two payments are not accepted, the third is
+        Mock::given(path("/data"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(challenge.clone())
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(path("/data"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/data"))
+            .respond_with(challenge)
+            .mount(&server)
+            .await;
+        let options = AutoClientOptions {
+            initial_backoff: Duration::from_millis(200),
+            backoff_multiplier: 3.0,
+            max_backoff: Duration::from_millis(400),
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+
+        let started = std::time::Instant::now();
+        let response = client.get(&format!("{}/data", server.uri())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(fixture.payments(), 3);
+        // 200ms, then 600ms capped at 400ms
+        assert!(started.elapsed() >= Duration::from_millis(600));
+        assert!(started.elapsed() < Duration::from_millis(1_400));
+    }
+
+    #[tokio::test]
+    async fn test_default_headers_are_sent_with_every_attempt() {
+        use reqwest::header::HeaderValue;
+        use wiremock::{
+            matchers::{header, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        Mock::given(path("/data"))
+            .and(header("X-Payment-Authorization", "stale"))
+            .respond_with(
+                ResponseTemplate::new(402).set_body_json(fixture.payment_request("0.10", "/data")),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let mut options = AutoClientOptions::default();
+        options
+            .default_headers
+            .insert("X-Api-Key", HeaderValue::from_static("key-1"));
+        options
+            .default_headers
+            .insert("X-Payment-Authorization", HeaderValue::from_static("stale"));
+        assert!(!format!("{:?}", options).contains("key-1"));
+        let client = X402AutoClient::from_client(client, Some(options));
+
+        let response = client.get(&format!("{}/data", server.uri())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(fixture.payments(), 1);
+
+        let received = server.received_requests().await.unwrap();
+        assert_eq!(received.len(), 2);
+        for request in &received {
+            assert_eq!(request.headers.get("X-Api-Key").unwrap(), "key-1");
+        }
+        let paid: Vec<_> = received[1]
+            .headers
+            .get_all("X-Payment-Authorization")
+            .iter()
+            .collect();
+        assert_eq!(paid.len(), 1);
+        assert_ne!(paid[0], "stale");
+    }
+
+    #[tokio::test]
+    async fn test_reuses_authorization_until_refused() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let server = MockServer::start().await;
+        let challenge =
+            ResponseTemplate::new(402).set_body_json(fixture.payment_request("0.10", "/data"));
+        // Requests with an authorization are served, others challenged
+        let mount = || {
+            let (server, challenge) = (&server, challenge.clone());
+            async move {
+                Mock::given(path("/data"))
+                    .and(header_exists("X-Payment-Authorization"))
+                    .respond_with(ResponseTemplate::new(200))
+                    .mount(server)
+                    .await;
+                Mock::given(path("/data"))
+                    .respond_with(challenge)
+                    .mount(server)
+                    .await;
+            }
+        };
+        mount().await;
+        let options = AutoClientOptions {
+            reuse_authorizations: true,
+            ..Default::default()
+        };
+        let client = X402AutoClient::from_client(client, Some(options));
+        let url = format!("{}/data?page=1", server.uri());
+
+        for _ in 0..3 {
+            let response = client.get(&url).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert_eq!(fixture.payments(), 1);
+
+        // A refused authorization is dropped and paid again
+        server.reset().await;
+        Mock::given(path("/data"))
+            .and(header_exists("X-Payment-Authorization"))
+            .respond_with(challenge.clone())
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        mount().await;
+        for _ in 0..2 {
+            let response = client.get(&url).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert_eq!(fixture.payments(), 2);
+
+        client.clear_cache();
+        client.get(&url).await.unwrap();
+        assert_eq!(fixture.payments(), 3);
+    }
 }
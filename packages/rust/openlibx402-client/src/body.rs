@@ -0,0 +1,88 @@
+//! Request bodies that can be sent more than once
+//!
+//! A paid request is sent at least twice: once to receive the 402 challenge
+//! and again with the payment authorization. [`ReplayableBody`] hands out the
+//! body for each attempt without copying it.
+
+use bytes::Bytes;
+use reqwest::Body;
+use std::{fmt, sync::Arc};
+
+/// Request body that can be sent again on each retry
+///
+/// `Bytes` bodies are shared between attempts rather than copied. A
+/// `Factory` builds a fresh body for each attempt, e.g. a stream reopening a
+/// file, for payloads that can't be held in memory.
+#[derive(Clone, Default)]
+pub enum ReplayableBody {
+    /// No body
+    #[default]
+    Empty,
+
+    /// Body held in memory
+    Bytes(Bytes),
+
+    /// Builds the body for each attempt
+    Factory(Arc<dyn Fn() -> Body + Send + Sync>),
+}
+
+impl ReplayableBody {
+    /// Body built by `factory` for each attempt
+    pub fn factory(factory: impl Fn() -> Body + Send + Sync + 'static) -> Self {
+        Self::Factory(Arc::new(factory))
+    }
+
+    /// Whether there is no body to send
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Self::Empty)
+    }
+
+    /// Body for the next attempt, or `None` for an empty body
+    pub fn to_body(&self) -> Option<Body> {
+        match self {
+            Self::Empty => None,
+            Self::Bytes(bytes) => Some(Body::from(bytes.clone())),
+            Self::Factory(factory) => Some(factory()),
+        }
+    }
+}
+
+impl fmt::Debug for ReplayableBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("Empty"),
+            Self::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Self::Factory(_) => f.write_str("Factory"),
+        }
+    }
+}
+
+impl From<Bytes> for ReplayableBody {
+    fn from(bytes: Bytes) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl From<Vec<u8>> for ReplayableBody {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes.into())
+    }
+}
+
+impl From<String> for ReplayableBody {
+    fn from(body: String) -> Self {
+        Self::Bytes(body.into())
+    }
+}
+
+impl From<&'static str> for ReplayableBody {
+    fn from(body: &'static str) -> Self {
+        Self::Bytes(Bytes::from_static(body.as_bytes()))
+    }
+}
+
+impl From<Option<String>> for ReplayableBody {
+    fn from(body: Option<String>) -> Self {
+        body.map_or(Self::Empty, Self::from)
+    }
+}
@@ -1,8 +1,28 @@
 use openlibx402_core::{
-    PaymentAuthorization, PaymentRequest, SolanaPaymentProcessor, X402Error, X402Result,
+    CostEstimate, MetricsRecorder, PassAuthorization, PaymentAuthorization, PaymentEvent,
+    PaymentRequest, PaymentSignatureStore, PriorityFeeConfig, Settlement, SolanaPaymentProcessor,
+    TransactionSigner, TransactionVersion, X402Error, X402Result, AUTHORIZATION_HEADER,
+    PASS_HEADER,
 };
-use reqwest::{Client, Response, StatusCode};
-use solana_sdk::signature::Keypair;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Certificate, Client, Method, Proxy, Response, StatusCode,
+};
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount, commitment_config::CommitmentConfig,
+    signature::Keypair,
+};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+use crate::{body::ReplayableBody, redaction::LogRedaction};
+
+/// Networks whose public RPC endpoints identify the client's network
+const KNOWN_NETWORKS: [&str; 3] = ["solana-mainnet", "solana-devnet", "solana-testnet"];
 
 /// X402 HTTP client with explicit payment control
 ///
@@ -11,7 +31,247 @@ use solana_sdk::signature::Keypair;
 pub struct X402Client {
     http_client: Client,
     payment_processor: SolanaPaymentProcessor,
-    keypair: Keypair,
+    signer: Arc<dyn TransactionSigner>,
+    network: Option<String>,
+    redaction: LogRedaction,
+    authorization_header: HeaderName,
+}
+
+/// Builder for an [`X402Client`]
+///
+/// Defaults:
+/// - the devnet RPC endpoint at `confirmed` commitment
+/// - no request or connect timeout
+/// - no default headers
+/// - proxies taken from the `HTTP_PROXY`/`HTTPS_PROXY` environment variables
+///   and the platform's root certificates
+/// - authorizations sent in the `X-Payment-Authorization` header
+/// - response bodies quoted in errors under [`LogRedaction::default`]
+/// - payments broadcast (no dry run)
+/// - only payment requests for the RPC endpoint's network are paid, when it
+///   is a public Solana endpoint
+///
+/// A keypair or another signer is required.
+#[derive(Default)]
+pub struct X402ClientBuilder {
+    signer: Option<Arc<dyn TransactionSigner>>,
+    rpc_url: Option<String>,
+    commitment: Option<CommitmentConfig>,
+    payment_processor: Option<SolanaPaymentProcessor>,
+    request_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    default_headers: Vec<(String, String)>,
+    proxies: Vec<Proxy>,
+    root_certificates: Vec<Certificate>,
+    redaction: LogRedaction,
+    authorization_header: Option<String>,
+    network: Option<String>,
+    allow_network_mismatch: bool,
+    dry_run: bool,
+}
+
+impl X402ClientBuilder {
+    /// Create a builder with the defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Solana keypair for signing transactions
+    ///
+    /// Either this or [`signer`](Self::signer) is required.
+    pub fn keypair(mut self, keypair: Keypair) -> Self {
+        self.signer = Some(Arc::new(keypair));
+        self
+    }
+
+    /// Sign payments with `signer` instead of a local keypair
+    ///
+    /// E.g. a hardware wallet or a remote key management service; the
+    /// client never sees the private key.
+    pub fn signer(mut self, signer: Arc<dyn TransactionSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Solana RPC URL payments are made through
+    pub fn rpc_url(mut self, rpc_url: &str) -> Self {
+        self.rpc_url = Some(rpc_url.to_string());
+        self
+    }
+
+    /// Commitment level payments are confirmed at
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// Pay through an existing processor instead of one for `rpc_url`
+    pub fn payment_processor(mut self, payment_processor: SolanaPaymentProcessor) -> Self {
+        self.payment_processor = Some(payment_processor);
+        self
+    }
+
+    /// Give up on HTTP requests that don't complete within `timeout`
+    ///
+    /// Covers the whole request, from connecting until the response body is
+    /// read. Requests that run out fail with `X402Error::Timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on connections that aren't established within `timeout`
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Send header `name: value` with every HTTP request
+    pub fn default_header(mut self, name: &str, value: &str) -> Self {
+        self.default_headers
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Send HTTP requests through `proxy`, e.g. `Proxy::all("http://proxy:3128")`
+    ///
+    /// May be called more than once; the first proxy matching a request's
+    /// URL is used. Replaces the proxies read from the environment. Only the
+    /// client's HTTP requests are proxied, not its Solana RPC calls.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Trust `certificate` as a root when connecting over TLS
+    ///
+    /// For services behind an internal certificate authority; the
+    /// platform's roots are still trusted.
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Send payment authorizations in header `name`
+    ///
+    /// Must match the header the server reads them from; a server looking
+    /// elsewhere answers paid requests with another 402.
+    pub fn authorization_header(mut self, name: &str) -> Self {
+        self.authorization_header = Some(name.to_string());
+        self
+    }
+
+    /// Only pay payment requests for `network`, e.g. `solana-mainnet`
+    ///
+    /// Needed for private RPC endpoints and existing processors; the network
+    /// of the public Solana endpoints is known.
+    pub fn network(mut self, network: &str) -> Self {
+        self.network = Some(network.to_string());
+        self
+    }
+
+    /// Pay payment requests for any network
+    ///
+    /// Payments are still made through the configured RPC endpoint, so a
+    /// request for another network only succeeds if that endpoint serves it.
+    pub fn allow_network_mismatch(mut self, allow: bool) -> Self {
+        self.allow_network_mismatch = allow;
+        self
+    }
+
+    /// Build and sign payments without broadcasting them
+    ///
+    /// See [`SolanaPaymentProcessor::with_dry_run`]; the authorizations
+    /// returned are marked `settled: false` and servers refuse them.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Redact response bodies quoted in errors according to `redaction`
+    pub fn log_redaction(mut self, redaction: LogRedaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    /// Build the client
+    ///
+    /// Fails with `X402Error::Configuration` if no keypair or signer was
+    /// given, a header name or value is invalid, or the HTTP client can't be
+    /// set up with the proxies and certificates given.
+    pub fn build(self) -> X402Result<X402Client> {
+        let signer = self.signer.ok_or_else(|| {
+            X402Error::Configuration("X402Client requires a keypair or signer".to_string())
+        })?;
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.default_headers {
+            let invalid = |e: &dyn std::fmt::Display| {
+                X402Error::Configuration(format!("Invalid default header {}: {}", name, e))
+            };
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+            let value = HeaderValue::from_str(value).map_err(|e| invalid(&e))?;
+            headers.insert(name, value);
+        }
+        let authorization_header = self
+            .authorization_header
+            .as_deref()
+            .unwrap_or(AUTHORIZATION_HEADER);
+        let authorization_header = HeaderName::from_bytes(authorization_header.as_bytes())
+            .map_err(|e| {
+                X402Error::Configuration(format!(
+                    "Invalid authorization header {}: {}",
+                    authorization_header, e
+                ))
+            })?;
+        let mut http_client = Client::builder().default_headers(headers);
+        if let Some(timeout) = self.request_timeout {
+            http_client = http_client.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            http_client = http_client.connect_timeout(timeout);
+        }
+        for proxy in self.proxies {
+            http_client = http_client.proxy(proxy);
+        }
+        for certificate in self.root_certificates {
+            http_client = http_client.add_root_certificate(certificate);
+        }
+        let http_client = http_client
+            .build()
+            .map_err(|e| X402Error::Configuration(format!("Failed to build HTTP client: {}", e)))?;
+
+        let rpc_url = self
+            .rpc_url
+            .as_deref()
+            .unwrap_or("https://api.devnet.solana.com");
+        let network = match (self.allow_network_mismatch, self.network) {
+            (true, _) => None,
+            (false, Some(network)) => Some(network),
+            // An existing processor's endpoint is unknown
+            (false, None) if self.payment_processor.is_some() => None,
+            (false, None) => KNOWN_NETWORKS
+                .iter()
+                .find(|network| SolanaPaymentProcessor::default_rpc_url(network) == rpc_url)
+                .map(|network| network.to_string()),
+        };
+        let payment_processor = self
+            .payment_processor
+            .unwrap_or_else(|| SolanaPaymentProcessor::new(rpc_url, self.commitment));
+        let payment_processor = if self.dry_run {
+            payment_processor.with_dry_run(true)
+        } else {
+            payment_processor
+        };
+        Ok(X402Client {
+            http_client,
+            payment_processor,
+            signer,
+            network,
+            redaction: self.redaction,
+            authorization_header,
+        })
+    }
 }
 
 impl X402Client {
@@ -21,12 +281,129 @@ impl X402Client {
     /// * `keypair` - Solana keypair for signing transactions
     /// * `rpc_url` - Optional Solana RPC URL (defaults to devnet)
     pub fn new(keypair: Keypair, rpc_url: Option<&str>) -> Self {
-        let rpc_url = rpc_url.unwrap_or("https://api.devnet.solana.com");
-        Self {
-            http_client: Client::new(),
-            payment_processor: SolanaPaymentProcessor::new(rpc_url, None),
-            keypair,
+        let mut builder = Self::builder().keypair(keypair);
+        if let Some(rpc_url) = rpc_url {
+            builder = builder.rpc_url(rpc_url);
         }
+        builder
+            .build()
+            .expect("client with a keypair and no headers")
+    }
+
+    /// Start building a client with the defaults listed on [`X402ClientBuilder`]
+    pub fn builder() -> X402ClientBuilder {
+        X402ClientBuilder::default()
+    }
+
+    /// Create a new X402 client that pays through an existing payment processor
+    ///
+    /// # Arguments
+    /// * `keypair` - Solana keypair for signing transactions
+    /// * `payment_processor` - Processor used to create and verify payments
+    pub fn with_payment_processor(
+        keypair: Keypair,
+        payment_processor: SolanaPaymentProcessor,
+    ) -> Self {
+        Self::builder()
+            .keypair(keypair)
+            .payment_processor(payment_processor)
+            .build()
+            .expect("client with a keypair and no headers")
+    }
+
+    /// Reuse a fetched blockhash across payments made within `max_age`
+    ///
+    /// Cuts one RPC round trip per payment for agents paying in quick
+    /// succession. See [`SolanaPaymentProcessor::with_blockhash_cache`].
+    pub fn with_blockhash_cache(mut self, max_age: Duration) -> Self {
+        self.payment_processor = self.payment_processor.with_blockhash_cache(max_age);
+        self
+    }
+
+    /// Resend a payment up to `retries` times when its blockhash expires
+    ///
+    /// See [`SolanaPaymentProcessor::with_max_broadcast_retries`].
+    pub fn with_max_broadcast_retries(mut self, retries: u32) -> Self {
+        self.payment_processor = self.payment_processor.with_max_broadcast_retries(retries);
+        self
+    }
+
+    /// Choose whether payments are simulated before they are sent
+    ///
+    /// See [`SolanaPaymentProcessor::with_simulate_before_send`].
+    pub fn with_simulate_before_send(mut self, simulate: bool) -> Self {
+        self.payment_processor = self.payment_processor.with_simulate_before_send(simulate);
+        self
+    }
+
+    /// Attach each payment's `payment_id` as an SPL Memo
+    ///
+    /// See [`SolanaPaymentProcessor::with_payment_id_memo`].
+    pub fn with_payment_id_memo(mut self) -> Self {
+        self.payment_processor = self.payment_processor.with_payment_id_memo();
+        self
+    }
+
+    /// Build payments in the given transaction format
+    ///
+    /// See [`SolanaPaymentProcessor::with_transaction_version`].
+    pub fn with_transaction_version(mut self, version: TransactionVersion) -> Self {
+        self.payment_processor = self.payment_processor.with_transaction_version(version);
+        self
+    }
+
+    /// Load payment accounts through these address lookup tables
+    ///
+    /// See [`SolanaPaymentProcessor::with_address_lookup_tables`].
+    pub fn with_address_lookup_tables(mut self, tables: Vec<AddressLookupTableAccount>) -> Self {
+        self.payment_processor = self.payment_processor.with_address_lookup_tables(tables);
+        self
+    }
+
+    /// Attach a compute budget and priority fee to payments
+    ///
+    /// See [`SolanaPaymentProcessor::with_priority_fee`].
+    pub fn with_priority_fee(mut self, config: PriorityFeeConfig) -> Self {
+        self.payment_processor = self.payment_processor.with_priority_fee(config);
+        self
+    }
+
+    /// Price payments from the priority fees recently paid on the cluster
+    ///
+    /// See [`SolanaPaymentProcessor::with_estimated_priority_fee`].
+    pub fn with_estimated_priority_fee(
+        mut self,
+        compute_unit_limit: u32,
+        max_micro_lamports_per_cu: u64,
+    ) -> Self {
+        self.payment_processor = self
+            .payment_processor
+            .with_estimated_priority_fee(compute_unit_limit, max_micro_lamports_per_cu);
+        self
+    }
+
+    /// Report payment latencies and failures to `recorder`
+    ///
+    /// See [`SolanaPaymentProcessor::with_metrics`].
+    pub fn with_metrics(mut self, recorder: Arc<dyn MetricsRecorder>) -> Self {
+        self.payment_processor = self.payment_processor.with_metrics(recorder);
+        self
+    }
+
+    /// Report the progress of each payment on `events`
+    ///
+    /// See [`SolanaPaymentProcessor::with_payment_events`].
+    pub fn with_payment_events(mut self, events: mpsc::Sender<PaymentEvent>) -> Self {
+        self.payment_processor = self.payment_processor.with_payment_events(events);
+        self
+    }
+
+    /// Record each payment's signature in `store` so a retry doesn't pay twice
+    ///
+    /// See [`SolanaPaymentProcessor::with_payment_signature_store`].
+    pub fn with_payment_signature_store(mut self, store: Arc<dyn PaymentSignatureStore>) -> Self {
+        self.payment_processor = self.payment_processor.with_payment_signature_store(store);
+        self
     }
 
     /// Make a GET request
@@ -43,6 +420,16 @@ impl X402Client {
         self.request("GET", url, None, Some(authorization)).await
     }
 
+    /// Make a GET request presenting a pass bought earlier
+    pub async fn get_with_pass(&self, url: &str, pass: &PassAuthorization) -> X402Result<Response> {
+        self.http_client
+            .get(url)
+            .header(PASS_HEADER, pass.to_header_value()?)
+            .send()
+            .await
+            .map_err(|e| http_error("HTTP request failed", e))
+    }
+
     /// Make a POST request
     pub async fn post(&self, url: &str, body: Option<String>) -> X402Result<Response> {
         self.request("POST", url, body, None).await
@@ -58,23 +445,90 @@ impl X402Client {
         self.request("POST", url, body, Some(authorization)).await
     }
 
+    /// Make a PUT request
+    pub async fn put(&self, url: &str, body: Option<String>) -> X402Result<Response> {
+        self.request("PUT", url, body, None).await
+    }
+
+    /// Make a PUT request with payment authorization
+    pub async fn put_with_auth(
+        &self,
+        url: &str,
+        body: Option<String>,
+        authorization: &PaymentAuthorization,
+    ) -> X402Result<Response> {
+        self.request("PUT", url, body, Some(authorization)).await
+    }
+
+    /// Make a PATCH request
+    pub async fn patch(&self, url: &str, body: Option<String>) -> X402Result<Response> {
+        self.request("PATCH", url, body, None).await
+    }
+
+    /// Make a PATCH request with payment authorization
+    pub async fn patch_with_auth(
+        &self,
+        url: &str,
+        body: Option<String>,
+        authorization: &PaymentAuthorization,
+    ) -> X402Result<Response> {
+        self.request("PATCH", url, body, Some(authorization)).await
+    }
+
+    /// Make a DELETE request
+    pub async fn delete(&self, url: &str) -> X402Result<Response> {
+        self.request("DELETE", url, None, None).await
+    }
+
+    /// Make a DELETE request with payment authorization
+    pub async fn delete_with_auth(
+        &self,
+        url: &str,
+        authorization: &PaymentAuthorization,
+    ) -> X402Result<Response> {
+        self.request("DELETE", url, None, Some(authorization)).await
+    }
+
     /// Make an HTTP request
-    async fn request(
+    ///
+    /// `method` is one of GET, POST, PUT, PATCH or DELETE. A body is sent as
+    /// JSON with any method.
+    pub(crate) async fn request(
         &self,
         method: &str,
         url: &str,
-        body: Option<String>,
+        body: impl Into<ReplayableBody>,
         authorization: Option<&PaymentAuthorization>,
     ) -> X402Result<Response> {
-        let mut request = match method {
-            "GET" => self.http_client.get(url),
-            "POST" => {
-                let mut req = self.http_client.post(url);
-                if let Some(b) = body {
-                    req = req.body(b).header("Content-Type", "application/json");
-                }
-                req
-            }
+        self.request_with_headers(method, url, body, authorization, &HeaderMap::new())
+            .await
+    }
+
+    /// Make an HTTP request with extra headers, e.g. an API key
+    ///
+    /// `method` is one of GET, POST, PUT, PATCH or DELETE. A body is sent as
+    /// JSON with any method; a `String`, `Bytes` or [`ReplayableBody`] all
+    /// convert, and `None` sends none.
+    ///
+    /// `headers` take precedence over the client's default headers and the
+    /// JSON `Content-Type`. The payment authorization header is the
+    /// exception: with an `authorization`, the client sets it and any value
+    /// for it in `headers` is dropped; without one, a value in `headers` is
+    /// sent unchanged.
+    pub async fn request_with_headers(
+        &self,
+        method: &str,
+        url: &str,
+        body: impl Into<ReplayableBody>,
+        authorization: Option<&PaymentAuthorization>,
+        headers: &HeaderMap,
+    ) -> X402Result<Response> {
+        let method = match method {
+            "GET" => Method::GET,
+            "POST" => Method::POST,
+            "PUT" => Method::PUT,
+            "PATCH" => Method::PATCH,
+            "DELETE" => Method::DELETE,
             _ => {
                 return Err(X402Error::Configuration(format!(
                     "Unsupported HTTP method: {}",
@@ -82,17 +536,26 @@ impl X402Client {
                 )))
             }
         };
+        let mut request = self.http_client.request(method, url);
+        if let Some(b) = body.into().to_body() {
+            request = request.body(b).header("Content-Type", "application/json");
+        }
 
-        // Add payment authorization header if provided
+        // Add payment authorization header if provided, replacing the caller's
+        let mut headers = headers.clone();
         if let Some(auth) = authorization {
-            let header_value = auth.to_header_value()?;
-            request = request.header("X-Payment-Authorization", header_value);
+            let header_value = HeaderValue::from_str(&auth.to_header_value()?).map_err(|e| {
+                X402Error::Serialization(format!("Invalid authorization header value: {}", e))
+            })?;
+            headers.insert(self.authorization_header.clone(), header_value);
         }
+        request = request.headers(headers);
 
         // Send request
-        let response = request.send().await.map_err(|e| {
-            X402Error::Network(format!("HTTP request failed: {}", e))
-        })?;
+        let response = request
+            .send()
+            .await
+            .map_err(|e| http_error("HTTP request failed", e))?;
 
         Ok(response)
     }
@@ -112,25 +575,143 @@ impl X402Client {
         }
 
         // Get payment request from response body
-        let body = response.text().await.map_err(|e| {
-            X402Error::Network(format!("Failed to read response body: {}", e))
-        })?;
+        let body = response
+            .text()
+            .await
+            .map_err(|e| http_error("Failed to read response body", e))?;
 
         PaymentRequest::from_json(&body)
     }
 
+    /// Poll a settlement endpoint until it is no longer pending
+    ///
+    /// For servers that accept a payment and deliver the result later, `url`
+    /// is the settlement's `/settle/{id}` endpoint. The endpoint is polled every
+    /// `interval` and the final settlement (complete or failed) is returned.
+    /// Returns an error if it is still pending after `timeout`.
+    pub async fn poll_settlement(
+        &self,
+        url: &str,
+        interval: Duration,
+        timeout: Duration,
+    ) -> X402Result<Settlement> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let response = self.get(url).await?;
+            let status = response.status();
+            let body = response
+                .text()
+                .await
+                .map_err(|e| http_error("Failed to read response body", e))?;
+
+            if !status.is_success() {
+                return Err(X402Error::Network(format!(
+                    "Settlement poll failed with status {}: {}",
+                    status,
+                    self.redaction.redact(&body)
+                )));
+            }
+
+            let settlement = Settlement::from_json(&body)?;
+            if !settlement.is_pending() {
+                return Ok(settlement);
+            }
+
+            if Instant::now() + interval > deadline {
+                return Err(X402Error::Network(format!(
+                    "Settlement {} still pending after {:?}",
+                    settlement.settlement_id, timeout
+                )));
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
     /// Create a payment from a payment request
     ///
     /// This creates, signs, and broadcasts the payment transaction
+    ///
+    /// Requests for a network other than the client's fail with
+    /// `X402Error::Configuration` before a transaction is built.
     pub async fn create_payment(
         &self,
         request: &PaymentRequest,
     ) -> X402Result<PaymentAuthorization> {
+        self.check_network(request)?;
+        self.payment_processor
+            .create_payment(request, self.signer.as_ref())
+            .await
+    }
+
+    /// Estimate the all-in cost of paying a payment request, without paying
+    ///
+    /// The token amount plus the SOL the client's payer would spend on the
+    /// transaction fee and, if the recipient's token account is missing, its
+    /// rent. See [`SolanaPaymentProcessor::estimate_cost`].
+    pub async fn estimate_cost(&self, request: &PaymentRequest) -> X402Result<CostEstimate> {
+        self.check_network(request)?;
         self.payment_processor
-            .create_payment(request, &self.keypair)
+            .estimate_cost(request, &self.signer.pubkey())
             .await
     }
 
+    /// Network payments are made on, if known
+    ///
+    /// See [`X402ClientBuilder::network`].
+    pub fn network(&self) -> Option<&str> {
+        self.network.as_deref()
+    }
+
+    /// Refuse `request` if it is for another network than the client's
+    fn check_network(&self, request: &PaymentRequest) -> X402Result<()> {
+        match &self.network {
+            Some(network) if *network != request.network => Err(X402Error::Configuration(format!(
+                "Payment request {} is for {} but the client pays on {}",
+                request.payment_id, request.network, network
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Amount a payment request asks for, in the smallest unit of its asset
+    pub async fn amount_in_base_units(&self, request: &PaymentRequest) -> X402Result<u64> {
+        self.payment_processor.amount_in_base_units(request).await
+    }
+
+    /// Decimals of the asset a payment request is paid in
+    pub async fn asset_decimals(&self, request: &PaymentRequest) -> X402Result<u8> {
+        self.payment_processor.asset_decimals(request).await
+    }
+
+    /// Wait for a payment left pending by a confirmation timeout to confirm
+    ///
+    /// See [`SolanaPaymentProcessor::poll_confirmation`].
+    pub async fn poll_confirmation(
+        &self,
+        request: &PaymentRequest,
+        signature: &str,
+        timeout: Duration,
+    ) -> X402Result<PaymentAuthorization> {
+        self.payment_processor
+            .poll_confirmation(request, signature, timeout)
+            .await
+    }
+
+    /// Create a payment in a specific asset offered by the payment request
+    ///
+    /// `asset_address` must be the request's primary asset or one of its
+    /// `accepts` entries; the payment is made for the amount advertised for
+    /// that asset.
+    pub async fn create_payment_with_asset(
+        &self,
+        request: &PaymentRequest,
+        asset_address: &str,
+    ) -> X402Result<PaymentAuthorization> {
+        let request = request.for_asset(asset_address)?;
+        self.create_payment(&request).await
+    }
+
     /// Verify a payment authorization
     pub async fn verify_payment(
         &self,
@@ -143,6 +724,45 @@ impl X402Client {
     }
 }
 
+// Keypairs print their secret key in `Debug`, so only the signer's public key is shown
+
+impl fmt::Debug for X402ClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X402ClientBuilder")
+            .field("payer", &self.signer.as_ref().map(|s| s.pubkey()))
+            .field("rpc_url", &self.rpc_url)
+            .field("commitment", &self.commitment)
+            .field("request_timeout", &self.request_timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("proxies", &self.proxies)
+            .field("root_certificates", &self.root_certificates.len())
+            .field("redaction", &self.redaction)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for X402Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X402Client")
+            .field("payer", &self.signer.pubkey())
+            .field("network", &self.network)
+            .field("redaction", &self.redaction)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Map a failed HTTP request to `X402Error::Timeout` or `X402Error::Network`
+fn http_error(context: &str, error: reqwest::Error) -> X402Error {
+    if error.is_timeout() {
+        X402Error::Timeout {
+            message: format!("{}: {}", context, error),
+            signature: None,
+        }
+    } else {
+        X402Error::Network(format!("{}: {}", context, error))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,7 +770,564 @@ mod tests {
     #[test]
     fn test_client_creation() {
         let keypair = Keypair::new();
-        let client = X402Client::new(keypair, None);
-        assert!(true); // Just verify it compiles
+        let _client = X402Client::new(keypair, None); // Just verify it compiles
+    }
+
+    #[tokio::test]
+    async fn test_client_builder() {
+        use wiremock::{
+            matchers::{header, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let err = X402Client::builder().build().err().unwrap();
+        assert_eq!(err.code(), "CONFIGURATION_ERROR");
+        let err = X402Client::builder()
+            .keypair(Keypair::new())
+            .default_header("bad header", "x")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "CONFIGURATION_ERROR");
+
+        let server = MockServer::start().await;
+        Mock::given(path("/data"))
+            .and(header("X-Agent-Id", "agent-7"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/slow"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let client = X402Client::builder()
+            .keypair(Keypair::new())
+            .rpc_url("http://localhost:8899")
+            .commitment(CommitmentConfig::finalized())
+            .timeout(Duration::from_millis(100))
+            .default_header("X-Agent-Id", "agent-7")
+            .build()
+            .unwrap();
+        let response = client.get(&format!("{}/data", server.uri())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let err = client
+            .get(&format!("{}/slow", server.uri()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, X402Error::Timeout { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_proxy() {
+        use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+        // The mock server stands in for a forward proxy to a host that
+        // doesn't resolve
+        let proxy = MockServer::start().await;
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&proxy)
+            .await;
+
+        let client = X402Client::builder()
+            .keypair(Keypair::new())
+            .proxy(Proxy::http(proxy.uri()).unwrap())
+            .build()
+            .unwrap();
+        let response = client.get("http://paid.invalid/data").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_custom_authorization_header() {
+        use wiremock::{
+            matchers::{header_exists, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let err = X402Client::builder()
+            .keypair(Keypair::new())
+            .authorization_header("bad header")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "CONFIGURATION_ERROR");
+
+        let server = MockServer::start().await;
+        Mock::given(path("/data"))
+            .and(header_exists("X-PAYMENT"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(402))
+            .mount(&server)
+            .await;
+
+        let authorization = PaymentAuthorization::new(
+            "pay_1".to_string(),
+            "0.10".to_string(),
+            Keypair::new().pubkey().to_string(),
+            Keypair::new().pubkey().to_string(),
+            "solana-devnet".to_string(),
+            "sig".to_string(),
+            Keypair::new().pubkey().to_string(),
+        );
+        let url = format!("{}/data", server.uri());
+        let client = X402Client::new(Keypair::new(), None);
+        let response = client.get_with_auth(&url, &authorization).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+
+        let client = X402Client::builder()
+            .keypair(Keypair::new())
+            .authorization_header("X-PAYMENT")
+            .build()
+            .unwrap();
+        let response = client.get_with_auth(&url, &authorization).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_request_with_extra_headers() {
+        use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let authorization = PaymentAuthorization::new(
+            "pay_1".to_string(),
+            "0.10".to_string(),
+            Keypair::new().pubkey().to_string(),
+            Keypair::new().pubkey().to_string(),
+            "solana-devnet".to_string(),
+            "sig".to_string(),
+            Keypair::new().pubkey().to_string(),
+        );
+        let client = X402Client::builder()
+            .keypair(Keypair::new())
+            .default_header("X-Tenant", "default")
+            .build()
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Api-Key", HeaderValue::from_static("key-1"));
+        headers.insert("X-Tenant", HeaderValue::from_static("acme"));
+        headers.insert(AUTHORIZATION_HEADER, HeaderValue::from_static("stale"));
+        let url = format!("{}/data", server.uri());
+
+        client
+            .request_with_headers("GET", &url, None, Some(&authorization), &headers)
+            .await
+            .unwrap();
+        client
+            .request_with_headers("GET", &url, None, None, &headers)
+            .await
+            .unwrap();
+
+        let received = server.received_requests().await.unwrap();
+        let values = |index: usize, name: &str| -> Vec<String> {
+            received[index]
+                .headers
+                .get_all(name)
+                .iter()
+                .map(|value| value.to_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(values(0, "X-Api-Key"), vec!["key-1"]);
+        assert_eq!(values(0, "X-Tenant"), vec!["acme"]);
+        // The client's authorization replaces the caller's
+        assert_eq!(
+            values(0, AUTHORIZATION_HEADER),
+            vec![authorization.to_header_value().unwrap()]
+        );
+        assert_eq!(values(1, AUTHORIZATION_HEADER), vec!["stale"]);
+    }
+
+    #[test]
+    fn test_network_is_known_for_public_endpoints() {
+        let network = |client: X402Client| client.network().map(str::to_string);
+        assert_eq!(
+            network(X402Client::new(Keypair::new(), None)).as_deref(),
+            Some("solana-devnet")
+        );
+        assert_eq!(
+            network(X402Client::new(
+                Keypair::new(),
+                Some("https://api.mainnet-beta.solana.com")
+            ))
+            .as_deref(),
+            Some("solana-mainnet")
+        );
+        assert_eq!(
+            network(X402Client::new(
+                Keypair::new(),
+                Some("https://rpc.example.com")
+            )),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_payment_request_for_other_network_is_refused() {
+        let (builder, fixture) = crate::testing::funded_client_builder();
+        let client = builder.network("solana-devnet").build().unwrap();
+        let request = fixture.payment_request("0.10", "/data");
+        client.create_payment(&request).await.unwrap();
+        assert_eq!(fixture.payments(), 1);
+
+        let mut mainnet = fixture.payment_request("0.10", "/data");
+        mainnet.network = "solana-mainnet".to_string();
+        let err = client.create_payment(&mainnet).await.unwrap_err();
+        assert!(matches!(err, X402Error::Configuration(_)));
+        assert_eq!(
+            err.to_string(),
+            "Configuration error: Payment request pay_data is for solana-mainnet but the client pays on solana-devnet"
+        );
+        assert_eq!(fixture.payments(), 1);
+
+        // Advanced users can pay through the configured endpoint regardless
+        let (builder, fixture) = crate::testing::funded_client_builder();
+        let client = builder
+            .network("solana-devnet")
+            .allow_network_mismatch(true)
+            .build()
+            .unwrap();
+        assert_eq!(client.network(), None);
+        let mut mainnet = fixture.payment_request("0.10", "/data");
+        mainnet.network = "solana-mainnet".to_string();
+        client.create_payment(&mainnet).await.unwrap();
+        assert_eq!(fixture.payments(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_client_does_not_broadcast() {
+        let (builder, fixture) = crate::testing::funded_client_builder();
+        let client = builder.dry_run(true).build().unwrap();
+
+        let authorization = client
+            .create_payment(&fixture.payment_request("0.10", "/data"))
+            .await
+            .unwrap();
+        assert!(!authorization.settled);
+        assert!(authorization.transaction_hash.is_none());
+        assert_eq!(fixture.payments(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_debug_and_errors_never_show_secrets() {
+        use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+        let keypair = Keypair::new();
+        let secret = [
+            keypair.to_base58_string(),
+            format!("{:?}", keypair.to_bytes()),
+            format!("{:?}", keypair.secret().to_bytes()),
+        ];
+        let pubkey = keypair.pubkey().to_string();
+        let client = X402Client::new(keypair.insecure_clone(), None);
+        let auto_client = crate::X402AutoClient::from_client(
+            X402Client::new(keypair.insecure_clone(), None),
+            None,
+        );
+        let builder = X402Client::builder().keypair(keypair);
+        for output in [
+            format!("{:?}", builder),
+            format!("{:?}", client),
+            format!("{:#?}", auto_client),
+        ] {
+            assert!(output.contains(&pubkey), "{}", output);
+            for secret in &secret {
+                assert!(!output.contains(secret.as_str()), "{}", output);
+            }
+        }
+
+        // Bodies quoted in errors are redacted and truncated
+        let server = MockServer::start().await;
+        let body = serde_json::json!({
+            "error": "x".repeat(1_000),
+            "secret_key": secret[0],
+        });
+        Mock::given(path("/settle/1"))
+            .respond_with(ResponseTemplate::new(500).set_body_json(body))
+            .mount(&server)
+            .await;
+        let err = client
+            .poll_settlement(
+                &format!("{}/settle/1", server.uri()),
+                Duration::from_millis(10),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(!err.contains(secret[0].as_str()), "{}", err);
+        assert!(err.contains("bytes truncated"), "{}", err);
+        assert!(err.len() < 400, "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_rapid_payments_reuse_cached_blockhash() {
+        let (client, fixture) = crate::testing::funded_client();
+        let client = client.with_blockhash_cache(Duration::from_secs(30));
+
+        for resource in ["/a", "/b", "/c"] {
+            let request = fixture.payment_request("0.10", resource);
+            client.create_payment(&request).await.unwrap();
+        }
+
+        assert_eq!(fixture.payments(), 3);
+        assert_eq!(fixture.backend.call_count("get_latest_blockhash"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_payment_settles_through_advertised_facilitator() {
+        use openlibx402_core::{FacilitatorRequest, FacilitatorResponse};
+        use solana_sdk::signature::Signature;
+        use wiremock::{
+            matchers::{method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let settled = Signature::new_unique().to_string();
+        let facilitator = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/settle"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(FacilitatorResponse {
+                    signature: settled.clone(),
+                }),
+            )
+            .expect(1)
+            .mount(&facilitator)
+            .await;
+
+        let request = fixture
+            .payment_request("0.10", "/data")
+            .with_facilitator_url(format!("{}/settle", facilitator.uri()));
+        let authorization = client.create_payment(&request).await.unwrap();
+        assert_eq!(authorization.signature, settled);
+        assert_eq!(fixture.payments(), 0, "the payer must not broadcast");
+
+        // The facilitator received the payer-signed transfer
+        let received = facilitator.received_requests().await.unwrap();
+        let submitted: FacilitatorRequest = received[0].body_json().unwrap();
+        assert_eq!(submitted.payment_request, request);
+        let transaction = submitted.transaction().unwrap();
+        assert!(transaction
+            .verify_with_results()
+            .into_iter()
+            .all(|valid| valid));
+        assert_eq!(
+            transaction.message.static_account_keys()[0].to_string(),
+            authorization.public_key
+        );
+
+        // Without a facilitator the payment is broadcast directly
+        let request = fixture.payment_request("0.10", "/data");
+        client.create_payment(&request).await.unwrap();
+        assert_eq!(fixture.payments(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_estimate_cost_does_not_pay() {
+        let (client, fixture) = crate::testing::funded_client();
+        let request = fixture.payment_request("0.10", "/data");
+
+        let estimate = client.estimate_cost(&request).await.unwrap();
+        assert_eq!(estimate.token_amount, 100_000);
+        assert_eq!(estimate.asset, fixture.mint.to_string());
+        assert!(estimate.fee_lamports > 0);
+        // The fixture's recipient already has a token account
+        assert_eq!(estimate.rent_lamports, 0);
+        assert_eq!(fixture.payments(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_create_payment_with_asset_pays_in_chosen_mint() {
+        let (client, fixture) = crate::testing::funded_client();
+        let alt_mint = fixture.alt_mint.to_string();
+        let request = fixture
+            .payment_request("0.10", "/data")
+            .with_accepted_asset(alt_mint.clone(), "0.12".to_string());
+
+        let authorization = client
+            .create_payment_with_asset(&request, &alt_mint)
+            .await
+            .unwrap();
+        assert_eq!(authorization.asset_address, alt_mint);
+        assert_eq!(authorization.actual_amount, "0.12");
+
+        let sent = fixture.backend.sent_transactions();
+        let keys = &sent[0].message.account_keys;
+        assert!(keys.contains(&fixture.alt_mint));
+        assert!(!keys.contains(&fixture.mint));
+
+        // Assets the server did not advertise are refused before paying
+        let unknown = solana_sdk::pubkey::Pubkey::new_unique().to_string();
+        let result = client.create_payment_with_asset(&request, &unknown).await;
+        assert!(matches!(result, Err(X402Error::InvalidPaymentRequest(_))));
+        assert_eq!(fixture.payments(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_payment_records_otel_latency() {
+        use openlibx402_core::OtelMetrics;
+        use opentelemetry::metrics::MeterProvider;
+        use opentelemetry_sdk::metrics::{
+            data::{AggregatedMetrics, MetricData},
+            InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+        };
+
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let metrics = Arc::new(OtelMetrics::new(&provider.meter("test")));
+
+        let (client, fixture) = crate::testing::funded_client();
+        let client = client.with_metrics(metrics);
+        let request = fixture.payment_request("0.10", "/data");
+        client.create_payment(&request).await.unwrap();
+
+        provider.force_flush().unwrap();
+        let samples: u64 = exporter
+            .get_finished_metrics()
+            .unwrap()
+            .iter()
+            .flat_map(|rm| rm.scope_metrics())
+            .flat_map(|sm| sm.metrics())
+            .filter(|m| m.name() == "x402.payment.confirmation_latency")
+            .map(|m| match m.data() {
+                AggregatedMetrics::F64(MetricData::Histogram(h)) => {
+                    h.data_points().map(|dp| dp.count()).sum()
+                }
+                _ => 0,
+            })
+            .sum();
+        assert_eq!(samples, 1);
+    }
+
+    #[tokio::test]
+    async fn test_payment_is_traced_in_a_span() {
+        use std::sync::Mutex;
+        use tracing::{field::Field, span, subscriber::Subscriber, Id};
+        use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+        /// Records the payment ID of each span entered
+        #[derive(Clone, Default)]
+        struct SpanRecorder(Arc<Mutex<Vec<(String, String)>>>);
+
+        struct PaymentId(String);
+
+        impl tracing::field::Visit for PaymentId {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                if field.name() == "payment_id" {
+                    self.0 = format!("{:?}", value);
+                }
+            }
+        }
+
+        impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+            fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+                let mut payment_id = PaymentId(String::new());
+                attrs.record(&mut payment_id);
+                ctx.span(id).unwrap().extensions_mut().insert(payment_id);
+            }
+
+            fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+                let span = ctx.span(id).unwrap();
+                let payment_id = span.extensions().get::<PaymentId>().unwrap().0.clone();
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((span.name().to_string(), payment_id));
+            }
+        }
+
+        let recorder = SpanRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+        let (client, fixture) = crate::testing::funded_client();
+        let request = fixture.payment_request("0.10", "/data");
+        client.create_payment(&request).await.unwrap();
+
+        let entered = recorder.0.lock().unwrap().clone();
+        assert!(!entered.is_empty());
+        assert!(entered
+            .iter()
+            .all(|span| span == &("create_payment".to_string(), "pay_data".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_poll_settlement_until_complete() {
+        use openlibx402_core::{SettlementStatus, SettlementTracker};
+        use wiremock::{
+            matchers::{method, path},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        let (client, fixture) = crate::testing::funded_client();
+        let request = fixture.payment_request("0.10", "/report");
+        let authorization = client.create_payment(&request).await.unwrap();
+
+        // Server issues a settlement for the payment and polls twice as pending
+        let tracker = SettlementTracker::new();
+        let issued = tracker.issue(&authorization);
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(issued.poll_path()))
+            .respond_with(ResponseTemplate::new(202).set_body_json(&issued))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        tracker.confirm_payment(&issued.settlement_id).unwrap();
+        let completed = tracker
+            .complete(&issued.settlement_id, serde_json::json!({ "pages": 12 }))
+            .unwrap();
+        Mock::given(method("GET"))
+            .and(path(issued.poll_path()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&completed))
+            .mount(&server)
+            .await;
+
+        let url = format!("{}{}", server.uri(), issued.poll_path());
+        let settlement = client
+            .poll_settlement(&url, Duration::from_millis(10), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(settlement.status, SettlementStatus::Complete);
+        assert_eq!(settlement.payment_id, "pay_report");
+        assert_eq!(settlement.result, Some(serde_json::json!({ "pages": 12 })));
+    }
+
+    #[tokio::test]
+    async fn test_poll_settlement_times_out() {
+        use openlibx402_core::SettlementTracker;
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let (client, fixture) = crate::testing::funded_client();
+        let request = fixture.payment_request("0.10", "/report");
+        let authorization = client.create_payment(&request).await.unwrap();
+        let issued = SettlementTracker::new().issue(&authorization);
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(202).set_body_json(&issued))
+            .mount(&server)
+            .await;
+
+        let url = format!("{}{}", server.uri(), issued.poll_path());
+        let result = client
+            .poll_settlement(&url, Duration::from_millis(10), Duration::from_millis(50))
+            .await;
+        assert!(matches!(result, Err(X402Error::Network(_))));
     }
 }
@@ -0,0 +1,136 @@
+//! Loading the payer's keypair
+//!
+//! Reads keys in the formats the Solana CLI and wallets hand them out: the
+//! JSON byte array of `solana-keygen` files such as `~/.config/solana/id.json`,
+//! or a base58 secret key as exported by wallets.
+
+use openlibx402_core::{X402Error, X402Result};
+use solana_sdk::{bs58, signature::Keypair};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Length of a keypair: the 32-byte secret key followed by the public key
+const KEYPAIR_LENGTH: usize = 64;
+
+/// Load a keypair from a `solana-keygen` file
+///
+/// The file holds the keypair's 64 bytes as a JSON array. A leading `~/` is
+/// expanded to the home directory. Fails with `X402Error::Configuration` if
+/// the file can't be read or doesn't hold a valid keypair.
+pub fn load_keypair_from_file(path: impl AsRef<Path>) -> X402Result<Keypair> {
+    let path = expand_home(path.as_ref());
+    let invalid = |reason: String| {
+        X402Error::Configuration(format!(
+            "Invalid keypair file {}: {}",
+            path.display(),
+            reason
+        ))
+    };
+    let contents = std::fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+    let bytes: Vec<u8> = serde_json::from_str(&contents)
+        .map_err(|e| invalid(format!("expected a JSON array of bytes ({})", e)))?;
+    keypair_from_bytes(&bytes).map_err(invalid)
+}
+
+/// Load a keypair from its base58-encoded 64 bytes
+///
+/// Fails with `X402Error::Configuration` if `s` isn't valid base58 or
+/// doesn't decode to a valid keypair. The key itself never appears in the
+/// error.
+pub fn load_keypair_from_base58(s: &str) -> X402Result<Keypair> {
+    let invalid =
+        |reason: String| X402Error::Configuration(format!("Invalid base58 keypair: {}", reason));
+    let bytes = bs58::decode(s.trim())
+        .into_vec()
+        .map_err(|e| invalid(e.to_string()))?;
+    keypair_from_bytes(&bytes).map_err(invalid)
+}
+
+fn keypair_from_bytes(bytes: &[u8]) -> Result<Keypair, String> {
+    if bytes.len() != KEYPAIR_LENGTH {
+        return Err(format!(
+            "expected {} bytes, got {}",
+            KEYPAIR_LENGTH,
+            bytes.len()
+        ));
+    }
+    Keypair::from_bytes(bytes).map_err(|e| e.to_string())
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Signer;
+
+    fn keypair_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_keypair_from_file() {
+        let keypair = Keypair::new();
+        let path = keypair_file(
+            "x402-keypair",
+            &serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap(),
+        );
+
+        let loaded = load_keypair_from_file(&path).unwrap();
+        assert_eq!(loaded.pubkey(), keypair.pubkey());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_keypair_file_is_refused() {
+        let bytes = Keypair::new().to_bytes();
+        let json = serde_json::to_string(&bytes.to_vec()).unwrap();
+
+        // Cut off mid-array, and an array that's complete but short
+        for (name, contents) in [
+            ("x402-cut", &json[..json.len() / 2]),
+            ("x402-short", &serde_json::to_string(&bytes[..32]).unwrap()),
+        ] {
+            let path = keypair_file(name, contents);
+            let err = load_keypair_from_file(&path).unwrap_err();
+            assert_eq!(err.code(), "CONFIGURATION_ERROR");
+            assert!(err.to_string().contains("Invalid keypair file"), "{}", err);
+            std::fs::remove_file(path).unwrap();
+        }
+        assert!(err_contains(
+            load_keypair_from_file("/nonexistent/id.json"),
+            "/nonexistent/id.json"
+        ));
+    }
+
+    #[test]
+    fn test_load_keypair_from_base58() {
+        let keypair = Keypair::new();
+        let loaded = load_keypair_from_base58(&keypair.to_base58_string()).unwrap();
+        assert_eq!(loaded.pubkey(), keypair.pubkey());
+
+        // '0' and 'l' aren't in the base58 alphabet
+        assert!(err_contains(
+            load_keypair_from_base58("0l0l0l"),
+            "Invalid base58 keypair"
+        ));
+        // A public key is valid base58 but not a keypair
+        assert!(err_contains(
+            load_keypair_from_base58(&keypair.pubkey().to_string()),
+            "expected 64 bytes, got 32"
+        ));
+    }
+
+    fn err_contains(result: X402Result<Keypair>, text: &str) -> bool {
+        matches!(result, Err(X402Error::Configuration(message)) if message.contains(text))
+    }
+}
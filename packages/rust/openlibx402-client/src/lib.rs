@@ -11,17 +11,18 @@
 //! - Automatic detection of 402 Payment Required responses
 //! - Seamless payment creation and transaction broadcasting
 //! - Configurable payment limits and retry behavior
-//! - Support for GET and POST requests
+//! - Support for GET, POST, PUT, PATCH and DELETE requests
+//! - Request bodies replayed after a 402 without copying, via `ReplayableBody`
+//! - Keypairs loaded from `solana-keygen` files or base58 secrets
 //!
 //! ## Example: Explicit Client
 //!
 //! ```rust,no_run
-//! use openlibx402_client::X402Client;
-//! use solana_sdk::signature::Keypair;
+//! use openlibx402_client::{load_keypair_from_file, X402Client};
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!     let keypair = Keypair::new(); // Load your keypair
+//!     let keypair = load_keypair_from_file("~/.config/solana/id.json")?;
 //!     let client = X402Client::new(keypair, None);
 //!
 //!     // Make request
@@ -51,18 +52,18 @@
 //! ## Example: Auto Client
 //!
 //! ```rust,no_run
-//! use openlibx402_client::{X402AutoClient, AutoClientOptions};
-//! use solana_sdk::signature::Keypair;
+//! use openlibx402_client::{load_keypair_from_file, X402AutoClient, AutoClientOptions};
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!     let keypair = Keypair::new(); // Load your keypair
+//!     let keypair = load_keypair_from_file("~/.config/solana/id.json")?;
 //!
 //!     // Configure options
 //!     let options = AutoClientOptions {
 //!         max_payment_amount: "5.0".to_string(),
 //!         auto_retry: true,
 //!         max_retries: 3,
+//!         ..Default::default()
 //!     };
 //!
 //!     let client = X402AutoClient::new(keypair, None, Some(options));
@@ -76,15 +77,27 @@
 //! ```
 
 pub mod auto_client;
+pub mod body;
 pub mod client;
+pub mod keypair;
+pub mod redaction;
+
+#[cfg(test)]
+pub(crate) mod testing;
 
 // Re-export commonly used types
-pub use auto_client::{AutoClientOptions, X402AutoClient};
-pub use client::X402Client;
+pub use auto_client::{
+    AutoClientOptions, DetailedResponse, ResourceMismatch, RetryPolicy, X402AutoClient,
+};
+pub use body::ReplayableBody;
+pub use client::{X402Client, X402ClientBuilder};
+pub use keypair::{load_keypair_from_base58, load_keypair_from_file};
+pub use redaction::LogRedaction;
 
 // Re-export core types for convenience
 pub use openlibx402_core::{
-    PaymentAuthorization, PaymentRequest, SolanaPaymentProcessor, X402Error, X402Result,
+    PassAuthorization, PassRequest, PaymentAuthorization, PaymentRequest, Settlement,
+    SettlementStatus, SolanaPaymentProcessor, X402Error, X402Result,
 };
 
 /// Library version
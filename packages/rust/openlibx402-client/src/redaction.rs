@@ -0,0 +1,97 @@
+//! Redaction of response bodies quoted in errors and warnings
+
+use serde_json::Value;
+
+/// Placeholder written over the values of redacted fields
+pub const REDACTED: &str = "[REDACTED]";
+
+/// How much of a response body the client may quote in errors
+///
+/// Defaults:
+/// - bodies are cut to 256 characters
+/// - JSON fields named like key material (`secret_key`, `private_key`,
+///   `keypair`, `seed`, `mnemonic`) are replaced with `[REDACTED]`
+///
+/// Field names are matched case-insensitively at any depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRedaction {
+    /// Longest body quoted, in characters; longer bodies are truncated
+    pub max_body_len: usize,
+
+    /// JSON fields whose values are never quoted
+    pub redacted_fields: Vec<String>,
+}
+
+impl Default for LogRedaction {
+    fn default() -> Self {
+        Self {
+            max_body_len: 256,
+            redacted_fields: ["secret_key", "private_key", "keypair", "seed", "mnemonic"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+impl LogRedaction {
+    /// Prepare `body` for quoting
+    ///
+    /// Redacts the configured fields if the body is JSON, then truncates it
+    /// to `max_body_len` characters.
+    pub fn redact(&self, body: &str) -> String {
+        let body = match serde_json::from_str::<Value>(body) {
+            Ok(mut json) if !self.redacted_fields.is_empty() => {
+                self.redact_value(&mut json);
+                json.to_string()
+            }
+            _ => body.to_string(),
+        };
+
+        match body.char_indices().nth(self.max_body_len) {
+            Some((cut, _)) => format!("{}... ({} bytes truncated)", &body[..cut], body.len() - cut),
+            None => body,
+        }
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (name, value) in fields {
+                    if self
+                        .redacted_fields
+                        .iter()
+                        .any(|field| field.eq_ignore_ascii_case(name))
+                    {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_value(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|v| self.redact_value(v)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_and_truncates() {
+        let redaction = LogRedaction::default();
+        let body = r#"{"status":"failed","debug":{"Secret_Key":[1,2,3]}}"#;
+        assert_eq!(
+            redaction.redact(body),
+            r#"{"debug":{"Secret_Key":"[REDACTED]"},"status":"failed"}"#
+        );
+
+        let short = LogRedaction {
+            max_body_len: 4,
+            ..Default::default()
+        };
+        assert_eq!(short.redact("événement"), "évén... (5 bytes truncated)");
+        assert_eq!(short.redact("ok"), "ok");
+    }
+}
@@ -0,0 +1,85 @@
+//! Shared fixtures for client tests
+
+use openlibx402_core::{rpc::mock::MockRpcBackend, PaymentRequest, SolanaPaymentProcessor};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use std::sync::Arc;
+
+use crate::client::{X402Client, X402ClientBuilder};
+
+/// Chain state shared by a funded test client
+pub(crate) struct Fixture {
+    pub backend: Arc<MockRpcBackend>,
+    pub mint: Pubkey,
+    /// Second token the payer also holds
+    pub alt_mint: Pubkey,
+    pub recipient: Pubkey,
+}
+
+impl Fixture {
+    /// Build a payment request for `amount` against this fixture's mint and recipient
+    pub fn payment_request(&self, amount: &str, resource: &str) -> PaymentRequest {
+        PaymentRequest::builder()
+            .amount(amount)
+            .asset_address(self.mint.to_string())
+            .payment_address(self.recipient.to_string())
+            .network("solana-devnet")
+            .nonce("nonce")
+            .payment_id(format!("pay_{}", resource.trim_start_matches('/')))
+            .resource(resource)
+            .build()
+            .expect("request with all required fields")
+    }
+
+    /// Number of payment transactions broadcast so far
+    pub fn payments(&self) -> usize {
+        self.backend.sent_transactions().len()
+    }
+}
+
+/// Create a client whose payer holds plenty of tokens on a mock chain
+pub(crate) fn funded_client() -> (X402Client, Fixture) {
+    let (builder, fixture) = funded_client_builder();
+    (builder.build().unwrap(), fixture)
+}
+
+/// Like [`funded_client`], leaving the rest of the client to configure
+pub(crate) fn funded_client_builder() -> (X402ClientBuilder, Fixture) {
+    funded_client_builder_with(|backend| backend)
+}
+
+/// Like [`funded_client_builder`], with extra `configure`d mock chain behaviour
+pub(crate) fn funded_client_builder_with(
+    configure: impl FnOnce(MockRpcBackend) -> MockRpcBackend,
+) -> (X402ClientBuilder, Fixture) {
+    let keypair = Keypair::new();
+    let mint = Pubkey::new_unique();
+    let alt_mint = Pubkey::new_unique();
+    let recipient = Pubkey::new_unique();
+    let backend = Arc::new(configure(
+        MockRpcBackend::new()
+            .with_mint(mint, 6)
+            .with_mint(alt_mint, 6)
+            .with_balance(keypair.pubkey(), 1_000_000_000)
+            .with_associated_token_account(keypair.pubkey(), mint, 1_000_000_000)
+            .with_associated_token_account(keypair.pubkey(), alt_mint, 1_000_000_000)
+            .with_associated_token_account(recipient, mint, 0)
+            .with_associated_token_account(recipient, alt_mint, 0),
+    ));
+    let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+    let builder = X402Client::builder()
+        .keypair(keypair)
+        .payment_processor(processor);
+
+    (
+        builder,
+        Fixture {
+            backend,
+            mint,
+            alt_mint,
+            recipient,
+        },
+    )
+}
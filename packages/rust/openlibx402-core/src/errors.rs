@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
 use thiserror::Error;
 
+use crate::{models::NATIVE_SOL, verification::SLOT_DURATION};
+
 /// Base error type for all X402 operations
 #[derive(Debug, Error, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "details")]
@@ -12,11 +15,17 @@ pub enum X402Error {
     PaymentExpired(String),
 
     #[error("Insufficient funds: {0}")]
-    InsufficientFunds(String),
+    InsufficientFunds(InsufficientFundsDetails),
+
+    #[error("Spend limit exceeded: {0}")]
+    SpendLimitExceeded(String),
 
     #[error("Payment verification failed: {0}")]
     PaymentVerification(String),
 
+    #[error("Payment already used: {0}")]
+    PaymentReplayed(String),
+
     #[error("Transaction broadcast failed: {0}")]
     TransactionBroadcast(String),
 
@@ -32,6 +41,15 @@ pub enum X402Error {
     #[error("Network error: {0}")]
     Network(String),
 
+    #[error("Request timed out: {message}")]
+    Timeout {
+        message: String,
+
+        /// Signature of a payment that was sent but not confirmed in time
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
+
     #[error("Blockchain error: {0}")]
     Blockchain(String),
 
@@ -46,12 +64,15 @@ impl X402Error {
             X402Error::PaymentRequired(_) => "PAYMENT_REQUIRED",
             X402Error::PaymentExpired(_) => "PAYMENT_EXPIRED",
             X402Error::InsufficientFunds(_) => "INSUFFICIENT_FUNDS",
+            X402Error::SpendLimitExceeded(_) => "SPEND_LIMIT_EXCEEDED",
             X402Error::PaymentVerification(_) => "PAYMENT_VERIFICATION_FAILED",
+            X402Error::PaymentReplayed(_) => "PAYMENT_REPLAYED",
             X402Error::TransactionBroadcast(_) => "TRANSACTION_BROADCAST_FAILED",
             X402Error::InvalidPaymentRequest(_) => "INVALID_PAYMENT_REQUEST",
             X402Error::InvalidPaymentAuthorization(_) => "INVALID_PAYMENT_AUTHORIZATION",
             X402Error::Configuration(_) => "CONFIGURATION_ERROR",
             X402Error::Network(_) => "NETWORK_ERROR",
+            X402Error::Timeout { .. } => "TIMEOUT",
             X402Error::Blockchain(_) => "BLOCKCHAIN_ERROR",
             X402Error::Serialization(_) => "SERIALIZATION_ERROR",
         }
@@ -61,6 +82,136 @@ impl X402Error {
     pub fn message(&self) -> String {
         self.to_string()
     }
+
+    /// Whether the failed operation may succeed if tried again
+    ///
+    /// Network failures, timeouts, failed broadcasts and 402s answered to a
+    /// payment are transient; every other error is terminal and repeating
+    /// the operation unchanged will fail the same way.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            X402Error::PaymentRequired(_)
+            | X402Error::TransactionBroadcast(_)
+            | X402Error::Network(_)
+            | X402Error::Timeout { .. } => true,
+            X402Error::PaymentExpired(_)
+            | X402Error::InsufficientFunds(_)
+            | X402Error::SpendLimitExceeded(_)
+            | X402Error::PaymentVerification(_)
+            | X402Error::PaymentReplayed(_)
+            | X402Error::InvalidPaymentRequest(_)
+            | X402Error::InvalidPaymentAuthorization(_)
+            | X402Error::Configuration(_)
+            | X402Error::Blockchain(_)
+            | X402Error::Serialization(_) => false,
+        }
+    }
+
+    /// Suggested wait before retrying, for retryable errors
+    ///
+    /// Failed broadcasts wait out a couple of slots so the retry gets a fresh
+    /// blockhash; network failures and timeouts wait a second.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            X402Error::TransactionBroadcast(_) => Some(SLOT_DURATION * 2),
+            X402Error::Network(_) | X402Error::Timeout { .. } => Some(Duration::from_secs(1)),
+            _ => None,
+        }
+    }
+
+    /// HTTP status a server should answer with when this error rejects a request
+    ///
+    /// Payments that are missing, stale or fail to verify answer `402` so the
+    /// client can pay again; a replayed payment is a `409`. Malformed requests
+    /// are `400`, failures talking to the chain or RPC are `502`/`504`, and
+    /// misconfiguration is the server's own `500`.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            X402Error::PaymentRequired(_)
+            | X402Error::PaymentExpired(_)
+            | X402Error::InsufficientFunds(_)
+            | X402Error::SpendLimitExceeded(_)
+            | X402Error::PaymentVerification(_) => 402,
+            X402Error::PaymentReplayed(_) => 409,
+            X402Error::InvalidPaymentRequest(_)
+            | X402Error::InvalidPaymentAuthorization(_)
+            | X402Error::Serialization(_) => 400,
+            X402Error::TransactionBroadcast(_)
+            | X402Error::Network(_)
+            | X402Error::Blockchain(_) => 502,
+            X402Error::Timeout { .. } => 504,
+            X402Error::Configuration(_) => 500,
+        }
+    }
+
+    /// Signature of a payment that was sent but not confirmed in time
+    ///
+    /// Set on the [`X402Error::Timeout`] returned by
+    /// `SolanaPaymentProcessor::create_payment_with_confirmation`; pass it to
+    /// `poll_confirmation` to find out whether the payment landed.
+    pub fn pending_signature(&self) -> Option<&str> {
+        match self {
+            X402Error::Timeout { signature, .. } => signature.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Amounts involved, if this is an [`X402Error::InsufficientFunds`]
+    pub fn insufficient_funds(&self) -> Option<&InsufficientFundsDetails> {
+        match self {
+            X402Error::InsufficientFunds(details) => Some(details),
+            _ => None,
+        }
+    }
+}
+
+/// How far a balance fell short of a payment
+///
+/// Amounts are in the smallest unit of `mint` (lamports for native SOL).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InsufficientFundsDetails {
+    /// Amount the payment needs
+    pub required: u64,
+
+    /// Amount the payer holds
+    pub available: u64,
+
+    /// Token mint, or `SOL` for native SOL
+    pub mint: String,
+}
+
+impl InsufficientFundsDetails {
+    /// Shortfall of `required` over `available` in `mint`
+    pub fn new(required: u64, available: u64, mint: impl Into<String>) -> Self {
+        Self {
+            required,
+            available,
+            mint: mint.into(),
+        }
+    }
+
+    /// Amount missing to make the payment
+    pub fn shortfall(&self) -> u64 {
+        self.required.saturating_sub(self.available)
+    }
+}
+
+impl fmt::Display for InsufficientFundsDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.mint == NATIVE_SOL {
+            write!(
+                f,
+                "{} lamports required, {} available",
+                self.required, self.available
+            )
+        } else {
+            write!(
+                f,
+                "{} required, {} available in {}",
+                self.required, self.available, self.mint
+            )
+        }
+    }
 }
 
 /// Result type alias for X402 operations
@@ -106,11 +257,117 @@ mod tests {
             "PAYMENT_EXPIRED"
         );
         assert_eq!(
-            X402Error::InsufficientFunds("test".to_string()).code(),
+            X402Error::InsufficientFunds(InsufficientFundsDetails::new(2, 1, NATIVE_SOL)).code(),
             "INSUFFICIENT_FUNDS"
         );
     }
 
+    #[test]
+    fn test_insufficient_funds_details_round_trip() {
+        let mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        let error = X402Error::InsufficientFunds(InsufficientFundsDetails::new(100_000, 250, mint));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Insufficient funds: 100000 required, 250 available in {}",
+                mint
+            )
+        );
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["type"], "InsufficientFunds");
+        assert_eq!(json["details"]["required"], 100_000);
+        assert_eq!(json["details"]["mint"], mint);
+
+        let deserialized: X402Error = serde_json::from_value(json).unwrap();
+        let details = deserialized.insufficient_funds().unwrap();
+        assert_eq!(details, error.insufficient_funds().unwrap());
+        assert_eq!(details.shortfall(), 99_750);
+        assert!(X402Error::Timeout {
+            message: "slow".to_string(),
+            signature: None,
+        }
+        .insufficient_funds()
+        .is_none());
+    }
+
+    #[test]
+    fn test_retry_classification() {
+        let text = || "test".to_string();
+        let errors = [
+            (X402Error::PaymentRequired(text()), true, None),
+            (X402Error::PaymentExpired(text()), false, None),
+            (
+                X402Error::InsufficientFunds(InsufficientFundsDetails::new(2, 1, NATIVE_SOL)),
+                false,
+                None,
+            ),
+            (X402Error::SpendLimitExceeded(text()), false, None),
+            (X402Error::PaymentVerification(text()), false, None),
+            (X402Error::PaymentReplayed(text()), false, None),
+            (
+                X402Error::TransactionBroadcast(text()),
+                true,
+                Some(Duration::from_millis(800)),
+            ),
+            (X402Error::InvalidPaymentRequest(text()), false, None),
+            (X402Error::InvalidPaymentAuthorization(text()), false, None),
+            (X402Error::Configuration(text()), false, None),
+            (
+                X402Error::Network(text()),
+                true,
+                Some(Duration::from_secs(1)),
+            ),
+            (
+                X402Error::Timeout {
+                    message: text(),
+                    signature: None,
+                },
+                true,
+                Some(Duration::from_secs(1)),
+            ),
+            (X402Error::Blockchain(text()), false, None),
+            (X402Error::Serialization(text()), false, None),
+        ];
+        for (error, retryable, retry_after) in errors {
+            assert_eq!(error.is_retryable(), retryable, "{}", error.code());
+            assert_eq!(error.retry_after(), retry_after, "{}", error.code());
+        }
+    }
+
+    #[test]
+    fn test_http_status() {
+        let text = || "test".to_string();
+        let errors = [
+            (X402Error::PaymentRequired(text()), 402),
+            (X402Error::PaymentExpired(text()), 402),
+            (
+                X402Error::InsufficientFunds(InsufficientFundsDetails::new(2, 1, NATIVE_SOL)),
+                402,
+            ),
+            (X402Error::SpendLimitExceeded(text()), 402),
+            (X402Error::PaymentVerification(text()), 402),
+            (X402Error::PaymentReplayed(text()), 409),
+            (X402Error::TransactionBroadcast(text()), 502),
+            (X402Error::InvalidPaymentRequest(text()), 400),
+            (X402Error::InvalidPaymentAuthorization(text()), 400),
+            (X402Error::Configuration(text()), 500),
+            (X402Error::Network(text()), 502),
+            (
+                X402Error::Timeout {
+                    message: text(),
+                    signature: None,
+                },
+                504,
+            ),
+            (X402Error::Blockchain(text()), 502),
+            (X402Error::Serialization(text()), 400),
+        ];
+        for (error, status) in errors {
+            assert_eq!(error.http_status(), status, "{}", error.code());
+        }
+    }
+
     #[test]
     fn test_error_serialization() {
         let error = X402Error::PaymentRequired("Test payment required".to_string());
@@ -118,4 +375,30 @@ mod tests {
         let deserialized: X402Error = serde_json::from_str(&json).unwrap();
         assert_eq!(error.code(), deserialized.code());
     }
+
+    #[test]
+    fn test_pending_signature_round_trip() {
+        let error = X402Error::Timeout {
+            message: "Payment not confirmed in time".to_string(),
+            signature: Some("sig".to_string()),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Request timed out: Payment not confirmed in time"
+        );
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["details"]["signature"], "sig");
+        let deserialized: X402Error = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized.pending_signature(), Some("sig"));
+
+        let plain = X402Error::Timeout {
+            message: "slow".to_string(),
+            signature: None,
+        };
+        assert!(plain.pending_signature().is_none());
+        assert!(X402Error::Network("pending signature sig".to_string())
+            .pending_signature()
+            .is_none());
+    }
 }
@@ -0,0 +1,71 @@
+//! Payments held in escrow by a payment program
+//!
+//! Instead of paying the recipient's token account directly, a payer can
+//! deposit into a token account owned by an escrow PDA of a payment program.
+//! The program releases the funds to the recipient on delivery. Servers that
+//! accept such deposits configure the program with
+//! [`VerificationOptions::with_escrow`](crate::VerificationOptions::with_escrow)
+//! and later collect them with
+//! [`SolanaPaymentProcessor::claim_escrow`](crate::SolanaPaymentProcessor::claim_escrow).
+//!
+//! The program is expected to derive one escrow per recipient from the seeds
+//! `["escrow", recipient]`, and to expose an Anchor-style `claim` instruction
+//! (see [`EscrowProgram::claim_instruction`]).
+
+use solana_sdk::{
+    hash::hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+/// Seed prefix of escrow PDAs
+pub const ESCROW_SEED: &[u8] = b"escrow";
+
+/// Payment program holding funds in per-recipient escrow PDAs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscrowProgram {
+    /// Address of the payment program
+    pub program_id: Pubkey,
+}
+
+impl EscrowProgram {
+    /// Use the payment program at `program_id`
+    pub fn new(program_id: Pubkey) -> Self {
+        Self { program_id }
+    }
+
+    /// Escrow PDA holding deposits for `recipient`
+    pub fn escrow_address(&self, recipient: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[ESCROW_SEED, recipient.as_ref()], &self.program_id).0
+    }
+
+    /// Token account of `recipient`'s escrow for `mint`
+    pub fn escrow_token_account(&self, recipient: &Pubkey, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address(&self.escrow_address(recipient), mint)
+    }
+
+    /// Instruction releasing `amount` of `mint` from the escrow to `recipient`
+    ///
+    /// Data is the Anchor discriminator of `claim` followed by the amount as
+    /// a little-endian `u64`. Accounts, in order: the escrow PDA, its token
+    /// account (writable), the recipient's token account (writable), the
+    /// mint, the recipient (signer) and the SPL Token program.
+    pub fn claim_instruction(&self, recipient: &Pubkey, mint: &Pubkey, amount: u64) -> Instruction {
+        let mut data = hash(b"global:claim").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&amount.to_le_bytes());
+
+        Instruction::new_with_bytes(
+            self.program_id,
+            &data,
+            vec![
+                AccountMeta::new_readonly(self.escrow_address(recipient), false),
+                AccountMeta::new(self.escrow_token_account(recipient, mint), false),
+                AccountMeta::new(get_associated_token_address(recipient, mint), false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(*recipient, true),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+        )
+    }
+}
@@ -0,0 +1,117 @@
+//! Progress events for payments being made
+//!
+//! Pass a channel to
+//! [`SolanaPaymentProcessor::with_payment_events`](crate::SolanaPaymentProcessor::with_payment_events)
+//! to follow each `create_payment` through its stages, e.g. to show it in a
+//! UI or stream it to a WebSocket or SSE endpoint.
+
+use tokio::sync::mpsc;
+
+use crate::errors::X402Error;
+
+/// Stage reached by a payment
+///
+/// A successful payment goes through `Building`, `Broadcasting` (again for
+/// each rebroadcast with a fresh blockhash), `Confirming` when it waits for
+/// a stronger commitment than the processor's, and `Done`. A payment that
+/// fails ends with `Failed` instead.
+#[derive(Debug, Clone)]
+pub enum PaymentEvent {
+    /// Checking balances and building the payment transaction
+    Building { payment_id: String },
+
+    /// Sending the signed transaction, or handing it to a facilitator, and
+    /// waiting for the processor's commitment
+    Broadcasting {
+        payment_id: String,
+        signature: String,
+    },
+
+    /// Sent; waiting for the commitment requested in `ConfirmationConfig`
+    Confirming {
+        payment_id: String,
+        signature: String,
+    },
+
+    /// Paid; a dry run reports the signature it didn't send
+    Done {
+        payment_id: String,
+        signature: String,
+    },
+
+    /// The payment failed with `error`
+    Failed {
+        payment_id: String,
+        error: X402Error,
+    },
+}
+
+impl PaymentEvent {
+    /// Payment ID of the request being paid
+    pub fn payment_id(&self) -> &str {
+        match self {
+            PaymentEvent::Building { payment_id }
+            | PaymentEvent::Broadcasting { payment_id, .. }
+            | PaymentEvent::Confirming { payment_id, .. }
+            | PaymentEvent::Done { payment_id, .. }
+            | PaymentEvent::Failed { payment_id, .. } => payment_id,
+        }
+    }
+
+    /// Signature of the payment transaction, once signed
+    pub fn signature(&self) -> Option<&str> {
+        match self {
+            PaymentEvent::Broadcasting { signature, .. }
+            | PaymentEvent::Confirming { signature, .. }
+            | PaymentEvent::Done { signature, .. } => Some(signature),
+            PaymentEvent::Building { .. } | PaymentEvent::Failed { .. } => None,
+        }
+    }
+}
+
+/// Send `event` without waiting; payments never stall on a slow listener
+///
+/// Events that don't fit in the channel, or whose receiver is gone, are
+/// dropped. With the `tracing` feature, every event is also logged.
+pub(crate) fn emit(
+    sender: Option<&mpsc::Sender<PaymentEvent>>,
+    event: impl FnOnce() -> PaymentEvent,
+) {
+    if sender.is_none() && !cfg!(feature = "tracing") {
+        return;
+    }
+    let event = event();
+    #[cfg(feature = "tracing")]
+    trace(&event);
+    if let Some(sender) = sender {
+        let _ = sender.try_send(event);
+    }
+}
+
+/// Log `event` in the current span, which carries the payment's amount and network
+#[cfg(feature = "tracing")]
+fn trace(event: &PaymentEvent) {
+    match event {
+        PaymentEvent::Building { payment_id } => {
+            tracing::debug!(payment_id, "building payment")
+        }
+        PaymentEvent::Broadcasting {
+            payment_id,
+            signature,
+        } => tracing::debug!(payment_id, signature, "broadcasting payment"),
+        PaymentEvent::Confirming {
+            payment_id,
+            signature,
+        } => tracing::debug!(payment_id, signature, "waiting for confirmation"),
+        PaymentEvent::Done {
+            payment_id,
+            signature,
+        } => tracing::info!(payment_id, signature, "payment sent"),
+        PaymentEvent::Failed { payment_id, error } => tracing::warn!(
+            payment_id,
+            code = error.code(),
+            error = %error,
+            "payment failed"
+        ),
+    }
+}
@@ -0,0 +1,92 @@
+//! Settlement of signed payments through a facilitator service
+//!
+//! Instead of broadcasting a payment itself, a payer can hand the signed
+//! transaction to a facilitator, which submits it and reports the resulting
+//! signature. The transaction is POSTed as a [`FacilitatorRequest`] and the
+//! facilitator answers with a [`FacilitatorResponse`].
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use solana_sdk::transaction::VersionedTransaction;
+#[cfg(feature = "payments")]
+use {solana_sdk::signature::Signature, std::str::FromStr};
+
+use crate::{
+    errors::{X402Error, X402Result},
+    models::PaymentRequest,
+};
+
+/// Signed payment submitted to a facilitator
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FacilitatorRequest {
+    /// Payment request the transaction pays
+    pub payment_request: PaymentRequest,
+
+    /// Signed legacy or v0 transaction in wire format, base64-encoded
+    pub transaction: String,
+}
+
+/// Facilitator's answer to a settled payment
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FacilitatorResponse {
+    /// Signature of the settled transaction
+    pub signature: String,
+}
+
+impl FacilitatorRequest {
+    /// Request settling `transaction` as payment of `payment_request`
+    pub fn new(
+        payment_request: &PaymentRequest,
+        transaction: &VersionedTransaction,
+    ) -> X402Result<Self> {
+        let wire = bincode::serialize(transaction).map_err(|e| {
+            X402Error::Blockchain(format!("Failed to serialize transaction: {}", e))
+        })?;
+        Ok(Self {
+            payment_request: payment_request.clone(),
+            transaction: general_purpose::STANDARD.encode(wire),
+        })
+    }
+
+    /// Decode the submitted transaction
+    pub fn transaction(&self) -> X402Result<VersionedTransaction> {
+        let wire = general_purpose::STANDARD
+            .decode(&self.transaction)
+            .map_err(|e| X402Error::Blockchain(format!("Invalid transaction encoding: {}", e)))?;
+        bincode::deserialize(&wire)
+            .map_err(|e| X402Error::Blockchain(format!("Invalid transaction: {}", e)))
+    }
+}
+
+/// POST `transaction` to the facilitator at `url` and return the settled signature
+#[cfg(feature = "payments")]
+pub(crate) async fn settle(
+    http: &reqwest::Client,
+    url: &str,
+    payment_request: &PaymentRequest,
+    transaction: &VersionedTransaction,
+) -> X402Result<Signature> {
+    let request = FacilitatorRequest::new(payment_request, transaction)?;
+    let response =
+        http.post(url).json(&request).send().await.map_err(|e| {
+            X402Error::Network(format!("Failed to reach facilitator {}: {}", url, e))
+        })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(X402Error::TransactionBroadcast(format!(
+            "Facilitator {} refused the payment with status {}",
+            url, status
+        )));
+    }
+
+    let settled: FacilitatorResponse = response.json().await.map_err(|e| {
+        X402Error::TransactionBroadcast(format!("Invalid facilitator response: {}", e))
+    })?;
+    Signature::from_str(&settled.signature).map_err(|e| {
+        X402Error::TransactionBroadcast(format!(
+            "Facilitator returned invalid signature {:?}: {}",
+            settled.signature, e
+        ))
+    })
+}
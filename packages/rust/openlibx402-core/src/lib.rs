@@ -12,6 +12,25 @@
 //! - **Error Handling**: Comprehensive error types for all X402 operations
 //! - **Solana Integration**: `SolanaPaymentProcessor` for blockchain transactions
 //! - **Serialization**: Base64-encoded JSON for HTTP headers
+//! - **Content Negotiation**: JSON, problem+json, or header-only 402 challenges
+//! - **Metrics**: `MetricsRecorder` hook, with OpenTelemetry export behind the `otel` feature
+//! - **External Signers**: `TransactionSigner` for paying with hardware, browser or remote wallets instead of a local `Keypair`
+//! - **Payment Events**: `PaymentEvent` progress reports on a channel, for UIs and logs
+//! - **Settlements**: `SettlementTracker` for pay-then-poll flows with long-running work
+//! - **Facilitators**: payments signed by the payer and settled by a facilitator service instead of broadcast directly
+//! - **Escrow Payments**: `EscrowProgram` deposits accepted on verification and claimed by the recipient
+//! - **Access Passes**: `PassIssuer` for server-signed, time-boxed passes bought with one payment
+//! - **USD Pricing**: `UsdPricing` over a `PriceOracle` for USD prices paid in volatile tokens, behind the `oracle` feature
+//! - **Shared State**: `ChallengeStore`/`NonceStore`/`GrantStore` traits, in memory or in Redis behind the `redis` feature
+//!
+//! ## Verify-Only Builds
+//!
+//! Creating payments needs the `payments` feature, which is on by default.
+//! Servers that only verify payments can depend on this crate with
+//! `default-features = false` to leave out payment creation, `TransactionSigner`,
+//! `PaymentEvent` and the HTTP client used to reach facilitators;
+//! `SolanaPaymentProcessor` then verifies payments as before. The Actix and
+//! Rocket integrations depend on it this way.
 //!
 //! ## Example
 //!
@@ -40,6 +59,8 @@
 //!         None
 //!     );
 //!
+//!     # #[cfg(feature = "payments")]
+//!     # {
 //!     // Create and send payment
 //!     let keypair = Keypair::new(); // Use your actual keypair
 //!     let authorization = processor.create_payment(&payment_request, &keypair).await?;
@@ -47,19 +68,70 @@
 //!     // Use authorization in retry request
 //!     let header_value = authorization.to_header_value()?;
 //!     println!("X-Payment-Authorization: {}", header_value);
+//!     # }
 //!
 //!     Ok(())
 //! }
 //! ```
 
 pub mod errors;
+pub mod escrow;
+#[cfg(feature = "payments")]
+pub mod events;
+pub mod facilitator;
+pub mod metrics;
 pub mod models;
+pub mod negotiation;
+#[cfg(feature = "oracle")]
+pub mod oracle;
+pub mod pass;
+pub mod payment_id;
 pub mod payment_processor;
+pub mod receipt;
+pub mod rpc;
+pub mod settlement;
+#[cfg(feature = "payments")]
+pub mod signer;
+pub mod store;
+pub mod transfer;
+pub mod verification;
 
 // Re-export commonly used types
-pub use errors::{X402Error, X402Result};
-pub use models::{PaymentAuthorization, PaymentRequest};
-pub use payment_processor::SolanaPaymentProcessor;
+pub use errors::{InsufficientFundsDetails, X402Error, X402Result};
+pub use escrow::EscrowProgram;
+#[cfg(feature = "payments")]
+pub use events::PaymentEvent;
+pub use facilitator::{FacilitatorRequest, FacilitatorResponse};
+pub use metrics::MetricsRecorder;
+#[cfg(feature = "otel")]
+pub use metrics::OtelMetrics;
+pub use models::{
+    AcceptedAsset, PaymentAuthorization, PaymentRequest, PaymentRequestBuilder,
+    AUTHORIZATION_HEADER, DEFAULT_EXPIRES_IN, NATIVE_SOL, SCHEME, SOL_DECIMALS, SUPPORTED_SCHEMES,
+};
+pub use negotiation::{ChallengeFormat, PaymentProblem, PAYMENT_REQUEST_HEADER, PRICE_HEADER};
+#[cfg(feature = "oracle")]
+pub use oracle::{PriceOracle, UsdPricing};
+pub use pass::{PassAuthorization, PassIssuer, PassRequest, PASS_HEADER};
+pub use payment_id::{MonotonicGenerator, PaymentIdGenerator, UuidGenerator};
+#[cfg(feature = "payments")]
+pub use payment_processor::{
+    ConfirmationConfig, CostEstimate, PartialPayment, PriorityFeeConfig, TransactionVersion,
+};
+pub use payment_processor::{RentExemptionReport, SolanaPaymentProcessor};
+pub use receipt::{PaymentReceipt, RECEIPT_HEADER};
+pub use rpc::RpcBackend;
+pub use settlement::{Settlement, SettlementStatus, SettlementTracker, SETTLE_PATH};
+#[cfg(feature = "payments")]
+pub use signer::TransactionSigner;
+#[cfg(feature = "redis")]
+pub use store::RedisStore;
+pub use store::{
+    ChallengeStore, Grant, GrantStore, MemoryStore, NonceStore, PaymentSignatureStore,
+    StoreSnapshot,
+};
+pub use transfer::{SolTransfer, TokenTransfer};
+pub use verification::VerificationOptions;
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
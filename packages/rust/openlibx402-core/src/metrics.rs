@@ -0,0 +1,165 @@
+//! Hooks for observing payment activity
+//!
+//! Implement [`MetricsRecorder`] and install it with
+//! [`SolanaPaymentProcessor::with_metrics`](crate::SolanaPaymentProcessor::with_metrics)
+//! to feed payment outcomes into your own monitoring. With the `otel` feature,
+//! `OtelMetrics` records them as OpenTelemetry instruments. The Actix
+//! middleware and Rocket guard report the payments they verify to the same
+//! hook.
+
+use std::time::Duration;
+
+/// Operation label for failures of `create_payment`
+pub const CREATE_PAYMENT: &str = "create_payment";
+
+/// Operation label for failures of `verify_payment`
+pub const VERIFY_PAYMENT: &str = "verify_payment";
+
+/// Receiver for payment metrics
+///
+/// All methods default to doing nothing, so implementations only override
+/// what they record.
+///
+/// # Example
+///
+/// Counters in the style of Prometheus, for a server verifying payments:
+///
+/// ```rust
+/// use openlibx402_core::MetricsRecorder;
+/// use std::{
+///     collections::HashMap,
+///     sync::{
+///         atomic::{AtomicU64, Ordering},
+///         Mutex,
+///     },
+///     time::Duration,
+/// };
+///
+/// #[derive(Default)]
+/// struct PaymentCounters {
+///     verified: AtomicU64,
+///     collected: Mutex<HashMap<String, u64>>,
+///     failures: Mutex<HashMap<&'static str, u64>>,
+/// }
+///
+/// impl MetricsRecorder for PaymentCounters {
+///     fn record_verification(&self, _latency: Duration) {
+///         self.verified.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn record_payment_collected(&self, mint: &str, amount: u64) {
+///         *self.collected.lock().unwrap().entry(mint.to_string()).or_default() += amount;
+///     }
+///
+///     fn record_failure(&self, _operation: &'static str, reason: &'static str) {
+///         *self.failures.lock().unwrap().entry(reason).or_default() += 1;
+///     }
+/// }
+/// ```
+pub trait MetricsRecorder: Send + Sync {
+    /// A payment transaction was sent and confirmed after `latency`
+    fn record_confirmation_latency(&self, _latency: Duration) {}
+
+    /// A payment was verified on-chain, taking `latency`
+    fn record_verification(&self, _latency: Duration) {}
+
+    /// A verified payment delivered `amount` of `mint`, in its smallest unit
+    ///
+    /// `mint` is the authorization's asset address, `SOL` for native SOL.
+    /// Recorded on every successful verification: a payment presented again
+    /// is counted again before a server refuses it as a replay, which is
+    /// reported as a `PAYMENT_REPLAYED` failure.
+    fn record_payment_collected(&self, _mint: &str, _amount: u64) {}
+
+    /// `operation` failed with the error code `reason` (see [`X402Error::code`](crate::X402Error::code))
+    fn record_failure(&self, _operation: &'static str, _reason: &'static str) {}
+}
+
+#[cfg(feature = "otel")]
+pub use otel::OtelMetrics;
+
+#[cfg(feature = "otel")]
+mod otel {
+    use opentelemetry::{
+        metrics::{Counter, Histogram, Meter},
+        KeyValue,
+    };
+    use std::time::Duration;
+
+    use super::MetricsRecorder;
+
+    /// [`MetricsRecorder`] that emits OpenTelemetry instruments
+    ///
+    /// Records:
+    /// - `x402.payment.confirmation_latency`: histogram of seconds from
+    ///   broadcast to confirmation
+    /// - `x402.payment.verification_latency`: histogram of seconds spent
+    ///   verifying accepted payments
+    /// - `x402.payment.amount_collected`: counter of base units received by
+    ///   verified payments, with a `mint` attribute
+    /// - `x402.payment.failures`: counter with `operation` and `reason`
+    ///   attributes
+    #[derive(Debug, Clone)]
+    pub struct OtelMetrics {
+        confirmation_latency: Histogram<f64>,
+        verification_latency: Histogram<f64>,
+        amount_collected: Counter<u64>,
+        failures: Counter<u64>,
+    }
+
+    impl OtelMetrics {
+        /// Create the instruments on `meter`
+        pub fn new(meter: &Meter) -> Self {
+            Self {
+                confirmation_latency: meter
+                    .f64_histogram("x402.payment.confirmation_latency")
+                    .with_unit("s")
+                    .with_description("Time from broadcast to on-chain confirmation")
+                    .build(),
+                verification_latency: meter
+                    .f64_histogram("x402.payment.verification_latency")
+                    .with_unit("s")
+                    .with_description("Time spent verifying accepted payments")
+                    .build(),
+                amount_collected: meter
+                    .u64_counter("x402.payment.amount_collected")
+                    .with_description("Base units received by verified payments, by mint")
+                    .build(),
+                failures: meter
+                    .u64_counter("x402.payment.failures")
+                    .with_description("Failed payment operations by reason")
+                    .build(),
+            }
+        }
+
+        /// Create the instruments on the globally installed meter provider
+        pub fn global() -> Self {
+            Self::new(&opentelemetry::global::meter("openlibx402"))
+        }
+    }
+
+    impl MetricsRecorder for OtelMetrics {
+        fn record_confirmation_latency(&self, latency: Duration) {
+            self.confirmation_latency.record(latency.as_secs_f64(), &[]);
+        }
+
+        fn record_verification(&self, latency: Duration) {
+            self.verification_latency.record(latency.as_secs_f64(), &[]);
+        }
+
+        fn record_payment_collected(&self, mint: &str, amount: u64) {
+            self.amount_collected
+                .add(amount, &[KeyValue::new("mint", mint.to_string())]);
+        }
+
+        fn record_failure(&self, operation: &'static str, reason: &'static str) {
+            self.failures.add(
+                1,
+                &[
+                    KeyValue::new("operation", operation),
+                    KeyValue::new("reason", reason),
+                ],
+            );
+        }
+    }
+}
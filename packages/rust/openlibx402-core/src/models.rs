@@ -1,34 +1,74 @@
 use base64::{engine::general_purpose, Engine as _};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::{str::FromStr, time::Duration};
+use uuid::Uuid;
 
 use crate::errors::{X402Error, X402Result};
 
+/// `asset_type` of payments in native SOL rather than an SPL token
+pub const NATIVE_SOL: &str = "SOL";
+
+/// Decimals of native SOL amounts (lamports)
+pub const SOL_DECIMALS: u8 = 9;
+
+/// Default header carrying a [`PaymentAuthorization`] on a paid request
+pub const AUTHORIZATION_HEADER: &str = "X-Payment-Authorization";
+
+/// Payment scheme implemented by this library, the default
+/// [`PaymentRequest::scheme`]
+pub const SCHEME: &str = "solana-spl-v1";
+
+/// Schemes this library can pay, see [`PaymentRequest::check_scheme`]
+pub const SUPPORTED_SCHEMES: &[&str] = &[SCHEME];
+
+/// How long a [`PaymentRequestBuilder`]'s request stays payable by default
+pub const DEFAULT_EXPIRES_IN: Duration = Duration::from_secs(300);
+
 /// Payment request received from server in 402 response
+///
+/// Fields are named in snake_case, and their camelCase names used by
+/// JavaScript SDKs (e.g. `maxAmountRequired`) are accepted too. With the
+/// `camel-case` feature, they are serialized in camelCase.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "camel-case", serde(rename_all(serialize = "camelCase")))]
 pub struct PaymentRequest {
     /// Maximum amount required in USDC (e.g., "0.10")
+    #[serde(alias = "maxAmountRequired")]
     pub max_amount_required: String,
 
-    /// Type of asset (e.g., "SPL" for Solana Program Library tokens)
+    /// Type of asset ("SPL" for Solana Program Library tokens, "SOL" for native SOL)
+    #[serde(alias = "assetType")]
     pub asset_type: String,
 
-    /// Token mint address (USDC address on Solana)
+    /// Token mint address (USDC address on Solana), empty for native SOL
+    #[serde(alias = "assetAddress")]
     pub asset_address: String,
 
     /// Recipient wallet address for payment
+    #[serde(alias = "paymentAddress")]
     pub payment_address: String,
 
     /// Network identifier (e.g., "solana-devnet", "solana-mainnet")
     pub network: String,
 
+    /// Payment scheme the request is expressed in, [`SCHEME`] by default
+    ///
+    /// Requests from servers that don't send one are taken to use
+    /// [`SCHEME`].
+    #[serde(default = "default_scheme")]
+    pub scheme: String,
+
     /// Expiration timestamp (ISO 8601 format)
+    #[serde(alias = "expiresAt")]
     pub expires_at: DateTime<Utc>,
 
     /// Nonce for replay protection
     pub nonce: String,
 
     /// Unique payment identifier
+    #[serde(alias = "paymentId")]
     pub payment_id: String,
 
     /// API endpoint/resource being accessed
@@ -37,10 +77,77 @@ pub struct PaymentRequest {
     /// Human-readable description (optional)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+
+    /// Decimals of `asset_address` (optional)
+    ///
+    /// Payers read the decimals from the token's mint account; a value here
+    /// overrides that lookup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<u8>,
+
+    /// Token program that owns `asset_address` (optional)
+    ///
+    /// The SPL Token or Token-2022 program. Payers read it from the owner of
+    /// the token's mint account; a value here overrides that lookup.
+    #[serde(
+        alias = "tokenProgram",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub token_program: Option<String>,
+
+    /// Other assets the server accepts instead of `asset_address` (optional)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accepts: Vec<AcceptedAsset>,
+
+    /// Every resource this payment unlocks, for a bundle (optional)
+    ///
+    /// `max_amount_required` is then the total for the whole bundle, paid
+    /// once. Empty for a payment covering only `resource`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<String>,
+
+    /// Facilitator that settles payments for the server (optional)
+    ///
+    /// Payers POST the signed transaction here instead of broadcasting it.
+    #[serde(
+        alias = "facilitatorUrl",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub facilitator_url: Option<String>,
+}
+
+fn default_scheme() -> String {
+    SCHEME.to_string()
+}
+
+/// Alternative asset a [`PaymentRequest`] can be paid in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "camel-case", serde(rename_all(serialize = "camelCase")))]
+pub struct AcceptedAsset {
+    /// Type of asset (e.g., "SPL")
+    #[serde(alias = "assetType")]
+    pub asset_type: String,
+
+    /// Token mint address
+    #[serde(alias = "assetAddress")]
+    pub asset_address: String,
+
+    /// Maximum amount required in this asset
+    #[serde(alias = "maxAmountRequired")]
+    pub max_amount_required: String,
 }
 
 impl PaymentRequest {
+    /// Start building a payment request with [`PaymentRequestBuilder`]
+    pub fn builder() -> PaymentRequestBuilder {
+        PaymentRequestBuilder::default()
+    }
+
     /// Create a new payment request
+    #[deprecated(note = "use `PaymentRequest::builder()`, which names each field")]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         max_amount_required: String,
         asset_address: String,
@@ -57,11 +164,48 @@ impl PaymentRequest {
             asset_address,
             payment_address,
             network,
+            scheme: default_scheme(),
+            expires_at,
+            nonce,
+            payment_id,
+            resource,
+            description: None,
+            decimals: None,
+            token_program: None,
+            accepts: Vec::new(),
+            resources: Vec::new(),
+            facilitator_url: None,
+        }
+    }
+
+    /// Create a new payment request for native SOL
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_sol(
+        max_amount_required: String,
+        payment_address: String,
+        network: String,
+        expires_at: DateTime<Utc>,
+        nonce: String,
+        payment_id: String,
+        resource: String,
+    ) -> Self {
+        Self {
+            max_amount_required,
+            asset_type: NATIVE_SOL.to_string(),
+            asset_address: String::new(),
+            payment_address,
+            network,
+            scheme: default_scheme(),
             expires_at,
             nonce,
             payment_id,
             resource,
             description: None,
+            decimals: Some(SOL_DECIMALS),
+            token_program: None,
+            accepts: Vec::new(),
+            resources: Vec::new(),
+            facilitator_url: None,
         }
     }
 
@@ -71,11 +215,156 @@ impl PaymentRequest {
         self
     }
 
+    /// Set the token decimals, overriding the on-chain mint lookup
+    pub fn with_decimals(mut self, decimals: u8) -> Self {
+        self.decimals = Some(decimals);
+        self
+    }
+
+    /// Set the token program, overriding the on-chain mint lookup
+    pub fn with_token_program(mut self, token_program: String) -> Self {
+        self.token_program = Some(token_program);
+        self
+    }
+
+    /// Also accept payment of `amount` in the SPL token `asset_address`
+    pub fn with_accepted_asset(mut self, asset_address: String, amount: String) -> Self {
+        self.accepts.push(AcceptedAsset {
+            asset_type: "SPL".to_string(),
+            asset_address,
+            max_amount_required: amount,
+        });
+        self
+    }
+
+    /// Make this a bundle payment unlocking all of `resources`
+    pub fn with_resources(mut self, resources: Vec<String>) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    /// Ask payers to settle through the facilitator at `url`
+    pub fn with_facilitator_url(mut self, url: String) -> Self {
+        self.facilitator_url = Some(url);
+        self
+    }
+
+    /// Whether the request pays for several resources at once
+    pub fn is_bundle(&self) -> bool {
+        !self.resources.is_empty()
+    }
+
+    /// Whether paying this request unlocks `resource`
+    pub fn covers(&self, resource: &str) -> bool {
+        self.resource == resource || self.resources.iter().any(|r| r == resource)
+    }
+
+    /// Copy of this request asking for payment in `asset_address`
+    ///
+    /// `asset_address` must be the primary asset or one listed in `accepts`;
+    /// the amount is the one advertised for that asset.
+    pub fn for_asset(&self, asset_address: &str) -> X402Result<Self> {
+        if asset_address == self.asset_address {
+            return Ok(self.clone());
+        }
+
+        let accepted = self
+            .accepts
+            .iter()
+            .find(|asset| asset.asset_address == asset_address)
+            .ok_or_else(|| {
+                X402Error::InvalidPaymentRequest(format!(
+                    "Asset {} is not accepted for payment {}",
+                    asset_address, self.payment_id
+                ))
+            })?;
+
+        Ok(Self {
+            max_amount_required: accepted.max_amount_required.clone(),
+            asset_type: accepted.asset_type.clone(),
+            asset_address: accepted.asset_address.clone(),
+            // Any decimals and token program given describe the primary asset
+            decimals: None,
+            token_program: None,
+            ..self.clone()
+        })
+    }
+
+    /// Whether the request asks for native SOL rather than an SPL token
+    pub fn is_native_sol(&self) -> bool {
+        self.asset_type == NATIVE_SOL
+    }
+
+    /// Check that the asset fields are consistent
+    ///
+    /// Native SOL requests carry no token mint; SPL requests need one.
+    pub fn validate(&self) -> X402Result<()> {
+        match (self.is_native_sol(), self.asset_address.is_empty()) {
+            (true, false) => Err(X402Error::InvalidPaymentRequest(format!(
+                "SOL payment {} must not name a token mint, got {}",
+                self.payment_id, self.asset_address
+            ))),
+            (false, true) => Err(X402Error::InvalidPaymentRequest(format!(
+                "{} payment {} is missing a token mint",
+                self.asset_type, self.payment_id
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the request's scheme is one this library can pay
+    ///
+    /// A request in a scheme from a newer protocol version may need a
+    /// different transaction than the one this library would build, so it
+    /// fails with [`X402Error::Configuration`] before any payment is made.
+    pub fn check_scheme(&self) -> X402Result<()> {
+        if SUPPORTED_SCHEMES.contains(&self.scheme.as_str()) {
+            return Ok(());
+        }
+        Err(X402Error::Configuration(format!(
+            "Payment request {} uses unsupported scheme {}; supported schemes: {}",
+            self.payment_id,
+            self.scheme,
+            SUPPORTED_SCHEMES.join(", ")
+        )))
+    }
+
     /// Check if the payment request has expired
     pub fn is_expired(&self) -> bool {
         Utc::now() > self.expires_at
     }
 
+    /// Time left to pay before the request expires, zero once it has
+    pub fn remaining_ttl(&self) -> Duration {
+        (self.expires_at - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Check that the request is still payable and expires within `max`
+    ///
+    /// A request with no time left fails with [`X402Error::PaymentExpired`];
+    /// one that stays payable longer than `max` is an
+    /// [`X402Error::InvalidPaymentRequest`].
+    pub fn validate_ttl(&self, max: Duration) -> X402Result<()> {
+        let remaining = self.remaining_ttl();
+        if remaining.is_zero() {
+            return Err(X402Error::PaymentExpired(format!(
+                "Payment request {} expired at {}",
+                self.payment_id, self.expires_at
+            )));
+        }
+        if remaining > max {
+            return Err(X402Error::InvalidPaymentRequest(format!(
+                "Payment request {} stays payable for {}s, more than the {}s allowed",
+                self.payment_id,
+                remaining.as_secs(),
+                max.as_secs()
+            )));
+        }
+        Ok(())
+    }
+
     /// Parse payment request from JSON string
     pub fn from_json(json: &str) -> X402Result<Self> {
         serde_json::from_str(json).map_err(|e| {
@@ -106,19 +395,164 @@ impl PaymentRequest {
     }
 }
 
+/// Builder for a [`PaymentRequest`]
+///
+/// The amount, recipient, token mint and network are required. Defaults:
+/// - a random UUIDv4 `nonce` and `payment_id`
+/// - expiry [`DEFAULT_EXPIRES_IN`] from when the request is built
+/// - an empty `resource` and no description
+#[derive(Debug, Clone, Default)]
+pub struct PaymentRequestBuilder {
+    max_amount_required: Option<String>,
+    asset_address: Option<String>,
+    payment_address: Option<String>,
+    network: Option<String>,
+    native_sol: bool,
+    expires_at: Option<DateTime<Utc>>,
+    nonce: Option<String>,
+    payment_id: Option<String>,
+    resource: String,
+    description: Option<String>,
+}
+
+impl PaymentRequestBuilder {
+    /// Maximum amount required, e.g. `"0.10"` (required)
+    pub fn amount(mut self, amount: impl Into<String>) -> Self {
+        self.max_amount_required = Some(amount.into());
+        self
+    }
+
+    /// SPL token mint to pay in (required unless [`native_sol`](Self::native_sol))
+    pub fn asset_address(mut self, asset_address: impl Into<String>) -> Self {
+        self.asset_address = Some(asset_address.into());
+        self
+    }
+
+    /// Recipient wallet address (required)
+    pub fn payment_address(mut self, payment_address: impl Into<String>) -> Self {
+        self.payment_address = Some(payment_address.into());
+        self
+    }
+
+    /// Network identifier, e.g. `"solana-devnet"` (required)
+    pub fn network(mut self, network: impl Into<String>) -> Self {
+        self.network = Some(network.into());
+        self
+    }
+
+    /// Ask for native SOL instead of an SPL token
+    pub fn native_sol(mut self) -> Self {
+        self.native_sol = true;
+        self
+    }
+
+    /// Expire the request at `expires_at`
+    pub fn expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Expire the request `expires_in` from now
+    pub fn expires_in(self, expires_in: Duration) -> Self {
+        let expires_in = chrono::Duration::from_std(expires_in).unwrap_or(chrono::Duration::MAX);
+        self.expires_at(Utc::now() + expires_in)
+    }
+
+    /// Nonce for replay protection
+    pub fn nonce(mut self, nonce: impl Into<String>) -> Self {
+        self.nonce = Some(nonce.into());
+        self
+    }
+
+    /// Unique payment identifier
+    pub fn payment_id(mut self, payment_id: impl Into<String>) -> Self {
+        self.payment_id = Some(payment_id.into());
+        self
+    }
+
+    /// API endpoint or resource being paid for
+    pub fn resource(mut self, resource: impl Into<String>) -> Self {
+        self.resource = resource.into();
+        self
+    }
+
+    /// Human-readable description
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Build the request
+    ///
+    /// Fails with [`X402Error::InvalidPaymentRequest`] naming the first
+    /// required field that was not set.
+    pub fn build(self) -> X402Result<PaymentRequest> {
+        let missing = |field: &str| {
+            X402Error::InvalidPaymentRequest(format!("Payment request needs {}", field))
+        };
+        let max_amount_required = self
+            .max_amount_required
+            .ok_or_else(|| missing("an amount"))?;
+        let payment_address = self
+            .payment_address
+            .ok_or_else(|| missing("a payment address"))?;
+        let network = self.network.ok_or_else(|| missing("a network"))?;
+        let (asset_type, asset_address, decimals) = if self.native_sol {
+            (NATIVE_SOL.to_string(), String::new(), Some(SOL_DECIMALS))
+        } else {
+            let asset_address = self
+                .asset_address
+                .ok_or_else(|| missing("an asset address"))?;
+            ("SPL".to_string(), asset_address, None)
+        };
+        let expires_at = self.expires_at.unwrap_or_else(|| {
+            Utc::now() + chrono::Duration::from_std(DEFAULT_EXPIRES_IN).expect("in range")
+        });
+
+        Ok(PaymentRequest {
+            max_amount_required,
+            asset_type,
+            asset_address,
+            payment_address,
+            network,
+            scheme: default_scheme(),
+            expires_at,
+            nonce: self.nonce.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            payment_id: self
+                .payment_id
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            resource: self.resource,
+            description: self.description,
+            decimals,
+            token_program: None,
+            accepts: Vec::new(),
+            resources: Vec::new(),
+            facilitator_url: None,
+        })
+    }
+}
+
 /// Payment authorization sent with retry request
+///
+/// Like [`PaymentRequest`], accepts camelCase field names and serializes
+/// them with the `camel-case` feature.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "camel-case", serde(rename_all(serialize = "camelCase")))]
 pub struct PaymentAuthorization {
     /// Payment ID from the original request
+    #[serde(alias = "paymentId")]
     pub payment_id: String,
 
     /// Actual amount paid in USDC (e.g., "0.10")
+    #[serde(alias = "actualAmount")]
     pub actual_amount: String,
 
     /// Recipient wallet address
+    #[serde(alias = "paymentAddress")]
     pub payment_address: String,
 
-    /// Token mint address
+    /// Token mint address, empty for native SOL
+    #[serde(alias = "assetAddress")]
     pub asset_address: String,
 
     /// Network identifier
@@ -131,11 +565,35 @@ pub struct PaymentAuthorization {
     pub signature: String,
 
     /// Payer's public key
+    #[serde(alias = "publicKey")]
     pub public_key: String,
 
     /// On-chain transaction hash (optional, may be same as signature)
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "transactionHash", skip_serializing_if = "Option::is_none")]
     pub transaction_hash: Option<String>,
+
+    /// Additional candidate signatures for the same logical payment
+    ///
+    /// A client whose broadcast was retried may hold several signatures and
+    /// not know which one landed. Verification accepts the payment if any
+    /// single candidate confirms the transfer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<String>,
+
+    /// Whether the payment transaction was broadcast
+    ///
+    /// `false` for dry-run authorizations, whose transaction was signed but
+    /// never sent; they carry no `transaction_hash` and fail verification.
+    #[serde(default = "settled_default", skip_serializing_if = "is_settled")]
+    pub settled: bool,
+}
+
+fn settled_default() -> bool {
+    true
+}
+
+fn is_settled(settled: &bool) -> bool {
+    *settled
 }
 
 impl PaymentAuthorization {
@@ -159,9 +617,114 @@ impl PaymentAuthorization {
             signature: signature.clone(),
             public_key,
             transaction_hash: Some(signature),
+            signatures: Vec::new(),
+            settled: true,
         }
     }
 
+    /// Authorization for a payment of `request` settled outside the processor
+    ///
+    /// For payments broadcast by another app, e.g. a wallet, that left only
+    /// the transaction `signature`. The payment ID, amount, recipient, asset
+    /// and network are copied from `request` and the timestamp is now;
+    /// `public_key` is the payer's.
+    pub fn from_settled(request: &PaymentRequest, signature: String, public_key: String) -> Self {
+        Self::new(
+            request.payment_id.clone(),
+            request.max_amount_required.clone(),
+            request.payment_address.clone(),
+            request.asset_address.clone(),
+            request.network.clone(),
+            signature,
+            public_key,
+        )
+    }
+
+    /// Mark the authorization as built in dry-run mode, without a broadcast
+    pub fn unsettled(mut self) -> Self {
+        self.transaction_hash = None;
+        self.settled = false;
+        self
+    }
+
+    /// Time since the authorization was made, zero if its timestamp is ahead
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.timestamp)
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Check that the authorization was made at most `max_age` ago
+    ///
+    /// Servers call this before accepting a payment, so a settled payment
+    /// can't be presented long after the request it answered has expired.
+    /// A stale authorization fails with [`X402Error::PaymentExpired`].
+    pub fn check_age(&self, max_age: Duration) -> X402Result<()> {
+        let age = self.age();
+        if age > max_age {
+            return Err(X402Error::PaymentExpired(format!(
+                "Payment authorization {} is {}s old; authorizations are accepted for {}s",
+                self.payment_id,
+                age.as_secs(),
+                max_age.as_secs()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the payment was made in native SOL rather than an SPL token
+    pub fn is_native_sol(&self) -> bool {
+        self.asset_address.is_empty()
+    }
+
+    /// Attach additional candidate signatures
+    pub fn with_signatures(mut self, signatures: Vec<String>) -> Self {
+        self.signatures = signatures;
+        self
+    }
+
+    /// All signatures to try during verification, primary first, without duplicates
+    pub fn candidate_signatures(&self) -> Vec<&str> {
+        let mut candidates = vec![self.signature.as_str()];
+        for signature in &self.signatures {
+            if !candidates.contains(&signature.as_str()) {
+                candidates.push(signature);
+            }
+        }
+        candidates
+    }
+
+    /// Check that `public_key` and every signature are well-formed
+    ///
+    /// Parses `public_key` as a Solana public key and `signature` and
+    /// `signatures` as transaction signatures, and rejects dry-run
+    /// authorizations that were never settled. Whether the payer actually
+    /// signed is only known on-chain; see
+    /// [`VerificationOptions::require_payer_signature`](crate::VerificationOptions::require_payer_signature).
+    pub fn verify_self_consistency(&self) -> X402Result<()> {
+        if !self.settled {
+            return Err(X402Error::InvalidPaymentAuthorization(format!(
+                "Payment {} is a dry run and was never broadcast",
+                self.payment_id
+            )));
+        }
+        Pubkey::from_str(&self.public_key).map_err(|e| {
+            X402Error::InvalidPaymentAuthorization(format!(
+                "Invalid public key {:?}: {}",
+                self.public_key, e
+            ))
+        })?;
+        for signature in self.candidate_signatures() {
+            Signature::from_str(signature).map_err(|e| {
+                X402Error::InvalidPaymentAuthorization(format!(
+                    "Invalid signature {:?}: {}",
+                    signature, e
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
     /// Parse payment authorization from JSON string
     pub fn from_json(json: &str) -> X402Result<Self> {
         serde_json::from_str(json).map_err(|e| {
@@ -188,6 +751,33 @@ impl PaymentAuthorization {
     /// Decode payment authorization from X-Payment-Authorization header value
     pub fn from_header_value(encoded: &str) -> X402Result<Self> {
         let decoded = general_purpose::STANDARD.decode(encoded)?;
+        Self::from_decoded(decoded)
+    }
+
+    /// Encode payment authorization as unpadded URL-safe base64 JSON
+    ///
+    /// Unlike [`to_header_value`](Self::to_header_value) the result contains
+    /// no `+`, `/` or `=`, so it survives proxies that mangle them and can be
+    /// passed as a query parameter.
+    pub fn to_header_value_urlsafe(&self) -> X402Result<String> {
+        let json = self.to_json()?;
+        Ok(general_purpose::URL_SAFE_NO_PAD.encode(json.as_bytes()))
+    }
+
+    /// Decode payment authorization from base64 JSON in either alphabet
+    ///
+    /// Accepts both [`to_header_value_urlsafe`](Self::to_header_value_urlsafe)
+    /// and [`to_header_value`](Self::to_header_value) output, padded or not.
+    pub fn from_header_value_urlsafe(encoded: &str) -> X402Result<Self> {
+        let urlsafe = encoded
+            .trim_end_matches('=')
+            .replace('+', "-")
+            .replace('/', "_");
+        let decoded = general_purpose::URL_SAFE_NO_PAD.decode(urlsafe)?;
+        Self::from_decoded(decoded)
+    }
+
+    fn from_decoded(decoded: Vec<u8>) -> X402Result<Self> {
         let json = String::from_utf8(decoded).map_err(|e| {
             X402Error::InvalidPaymentAuthorization(format!("Invalid UTF-8 in header: {}", e))
         })?;
@@ -203,36 +793,122 @@ mod tests {
     #[test]
     fn test_payment_request_serialization() {
         let expires_at = Utc::now() + Duration::seconds(300);
-        let request = PaymentRequest::new(
-            "0.10".to_string(),
-            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
-            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
-            "solana-devnet".to_string(),
-            expires_at,
-            "nonce123".to_string(),
-            "payment123".to_string(),
-            "/api/premium-data".to_string(),
-        )
-        .with_description("Access premium data".to_string());
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .expires_at(expires_at)
+            .nonce("nonce123")
+            .payment_id("payment123")
+            .resource("/api/premium-data")
+            .build()
+            .unwrap()
+            .with_description("Access premium data".to_string());
 
         let json = request.to_json().unwrap();
         let deserialized = PaymentRequest::from_json(&json).unwrap();
         assert_eq!(request, deserialized);
     }
 
+    #[test]
+    fn test_payment_request_builder_defaults() {
+        let builder = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet");
+        let (first, second) = (
+            builder.clone().build().unwrap(),
+            builder.clone().build().unwrap(),
+        );
+        assert_eq!(first.asset_type, "SPL");
+        assert!(Uuid::parse_str(&first.nonce).is_ok());
+        assert!(Uuid::parse_str(&first.payment_id).is_ok());
+        assert_ne!(first.nonce, second.nonce);
+        assert_ne!(first.payment_id, second.payment_id);
+        let expires_in = first.expires_at - Utc::now();
+        assert!(expires_in > Duration::seconds(290) && expires_in <= Duration::seconds(300));
+
+        let sol = PaymentRequest::builder()
+            .amount("0.5")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .native_sol()
+            .build()
+            .unwrap();
+        assert!(sol.is_native_sol());
+        assert_eq!(sol.decimals, Some(SOL_DECIMALS));
+        sol.validate().unwrap();
+
+        // Each required field is checked
+        let missing = [
+            PaymentRequest::builder()
+                .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+                .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+                .network("solana-devnet"),
+            PaymentRequest::builder()
+                .amount("0.10")
+                .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+                .network("solana-devnet"),
+            PaymentRequest::builder()
+                .amount("0.10")
+                .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+                .network("solana-devnet"),
+            PaymentRequest::builder()
+                .amount("0.10")
+                .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+                .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU"),
+        ];
+        for builder in missing {
+            let err = builder.build().unwrap_err();
+            assert_eq!(err.code(), "INVALID_PAYMENT_REQUEST");
+        }
+    }
+
+    #[test]
+    fn test_payment_request_for_accepted_asset() {
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .nonce("nonce123")
+            .payment_id("payment123")
+            .resource("/api/premium-data")
+            .build()
+            .unwrap()
+            .with_accepted_asset(
+                "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB".to_string(),
+                "0.11".to_string(),
+            );
+
+        let decoded = PaymentRequest::from_json(&request.to_json().unwrap()).unwrap();
+        assert_eq!(decoded.accepts, request.accepts);
+
+        let alt = request
+            .for_asset("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB")
+            .unwrap();
+        assert_eq!(alt.max_amount_required, "0.11");
+        assert_eq!(alt.payment_id, request.payment_id);
+        assert_eq!(request.for_asset(&request.asset_address).unwrap(), request);
+        assert!(request.for_asset("unknown").is_err());
+    }
+
     #[test]
     fn test_payment_request_base64() {
         let expires_at = Utc::now() + Duration::seconds(300);
-        let request = PaymentRequest::new(
-            "0.10".to_string(),
-            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
-            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
-            "solana-devnet".to_string(),
-            expires_at,
-            "nonce123".to_string(),
-            "payment123".to_string(),
-            "/api/premium-data".to_string(),
-        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .expires_at(expires_at)
+            .nonce("nonce123")
+            .payment_id("payment123")
+            .resource("/api/premium-data")
+            .build()
+            .unwrap();
 
         let encoded = request.to_base64().unwrap();
         let decoded = PaymentRequest::from_base64(&encoded).unwrap();
@@ -242,32 +918,48 @@ mod tests {
     #[test]
     fn test_payment_request_expiration() {
         let past = Utc::now() - Duration::seconds(10);
-        let request = PaymentRequest::new(
-            "0.10".to_string(),
-            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
-            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
-            "solana-devnet".to_string(),
-            past,
-            "nonce123".to_string(),
-            "payment123".to_string(),
-            "/api/premium-data".to_string(),
-        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .expires_at(past)
+            .nonce("nonce123")
+            .payment_id("payment123")
+            .resource("/api/premium-data")
+            .build()
+            .unwrap();
 
         assert!(request.is_expired());
 
         let future = Utc::now() + Duration::seconds(300);
-        let request2 = PaymentRequest::new(
-            "0.10".to_string(),
-            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
-            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
-            "solana-devnet".to_string(),
-            future,
-            "nonce123".to_string(),
-            "payment123".to_string(),
-            "/api/premium-data".to_string(),
-        );
+        let request2 = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .expires_at(future)
+            .nonce("nonce123")
+            .payment_id("payment123")
+            .resource("/api/premium-data")
+            .build()
+            .unwrap();
 
         assert!(!request2.is_expired());
+
+        assert_eq!(request.remaining_ttl(), std::time::Duration::ZERO);
+        let remaining = request2.remaining_ttl();
+        assert!(remaining > std::time::Duration::from_secs(290));
+        assert!(remaining <= std::time::Duration::from_secs(300));
+
+        let max = std::time::Duration::from_secs(600);
+        let err = request.validate_ttl(max).unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_EXPIRED");
+        request2.validate_ttl(max).unwrap();
+        let err = request2
+            .validate_ttl(std::time::Duration::from_secs(60))
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_REQUEST");
     }
 
     #[test]
@@ -288,5 +980,231 @@ mod tests {
         assert_eq!(auth.payment_id, decoded.payment_id);
         assert_eq!(auth.signature, decoded.signature);
         assert_eq!(auth.public_key, decoded.public_key);
+
+        // Settled authorizations keep the wire format; dry runs are marked
+        assert!(!auth.to_json().unwrap().contains("settled"));
+        assert!(decoded.settled);
+        let dry_run = auth.unsettled();
+        let decoded = PaymentAuthorization::from_json(&dry_run.to_json().unwrap()).unwrap();
+        assert!(!decoded.settled);
+        assert!(decoded.transaction_hash.is_none());
+    }
+
+    #[test]
+    fn test_authorization_age() {
+        let mut auth = PaymentAuthorization::new(
+            "payment123".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "signature".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        );
+        let max_age = std::time::Duration::from_secs(300);
+        auth.check_age(max_age).unwrap();
+
+        auth.timestamp = Utc::now() - Duration::seconds(301);
+        let err = auth.check_age(max_age).unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_EXPIRED");
+        assert!(err.to_string().contains("accepted for 300s"));
+
+        // Clock skew doesn't make an authorization from the future stale
+        auth.timestamp = Utc::now() + Duration::seconds(30);
+        assert_eq!(auth.age(), std::time::Duration::ZERO);
+        auth.check_age(max_age).unwrap();
+    }
+
+    #[test]
+    fn test_authorization_from_settled_signature() {
+        let request = PaymentRequest::builder()
+            .amount("0.25")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-mainnet")
+            .resource("/data")
+            .build()
+            .unwrap();
+        let signature = "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+        let payer = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        let auth =
+            PaymentAuthorization::from_settled(&request, signature.to_string(), payer.to_string());
+        assert_eq!(auth.payment_id, request.payment_id);
+        assert_eq!(auth.actual_amount, "0.25");
+        assert_eq!(auth.payment_address, request.payment_address);
+        assert_eq!(auth.asset_address, request.asset_address);
+        assert_eq!(auth.network, "solana-mainnet");
+        assert_eq!(auth.transaction_hash.as_deref(), Some(signature));
+        assert!(auth.verify_self_consistency().is_ok());
+
+        let decoded =
+            PaymentAuthorization::from_header_value(&auth.to_header_value().unwrap()).unwrap();
+        assert_eq!(decoded, auth);
+    }
+
+    #[test]
+    fn test_payment_authorization_urlsafe_header() {
+        // A payment ID whose JSON encodes to `+` and `/` in standard base64
+        let auth = PaymentAuthorization::new(
+            "pay>>>???".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        );
+        let standard = auth.to_header_value().unwrap();
+        assert!(standard.contains('+') || standard.contains('/'));
+
+        let urlsafe = auth.to_header_value_urlsafe().unwrap();
+        assert!(!urlsafe.contains(['+', '/', '=']));
+        assert_eq!(
+            PaymentAuthorization::from_header_value_urlsafe(&urlsafe).unwrap(),
+            auth
+        );
+
+        // Standard-encoded values still decode through the lenient reader
+        assert_eq!(
+            PaymentAuthorization::from_header_value_urlsafe(&standard).unwrap(),
+            auth
+        );
+        assert!(PaymentAuthorization::from_header_value_urlsafe("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_camel_case_payloads_round_trip() {
+        // As produced by a JavaScript SDK's camelCase models
+        let request_json = r#"{
+            "maxAmountRequired": "0.10",
+            "assetType": "SPL",
+            "assetAddress": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+            "paymentAddress": "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU",
+            "network": "solana-devnet",
+            "expiresAt": "2030-01-01T00:00:00.000Z",
+            "nonce": "nonce123",
+            "paymentId": "payment123",
+            "resource": "/api/premium-data",
+            "description": "Access premium data",
+            "accepts": [{
+                "assetType": "SOL",
+                "assetAddress": "",
+                "maxAmountRequired": "0.001"
+            }]
+        }"#;
+        let authorization_json = r#"{
+            "paymentId": "payment123",
+            "actualAmount": "0.10",
+            "paymentAddress": "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU",
+            "assetAddress": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+            "network": "solana-devnet",
+            "timestamp": "2029-12-31T23:59:00.000Z",
+            "signature": "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW",
+            "publicKey": "9aE476sH92Vz7DMPyq5WLPkrKWivxeuTKEFKd2sZZcde",
+            "transactionHash": "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW"
+        }"#;
+
+        let request = PaymentRequest::from_json(request_json).unwrap();
+        assert_eq!(request.max_amount_required, "0.10");
+        assert_eq!(request.payment_id, "payment123");
+        assert_eq!(request.accepts[0].max_amount_required, "0.001");
+        let authorization = PaymentAuthorization::from_json(authorization_json).unwrap();
+        assert_eq!(
+            authorization.public_key,
+            "9aE476sH92Vz7DMPyq5WLPkrKWivxeuTKEFKd2sZZcde"
+        );
+        assert!(authorization.transaction_hash.is_some());
+
+        // Serialized in the configured case, and read back either way
+        let (amount_field, key_field) = if cfg!(feature = "camel-case") {
+            ("\"maxAmountRequired\"", "\"publicKey\"")
+        } else {
+            ("\"max_amount_required\"", "\"public_key\"")
+        };
+        let json = request.to_json().unwrap();
+        assert!(json.contains(amount_field));
+        assert_eq!(PaymentRequest::from_json(&json).unwrap(), request);
+        let json = authorization.to_json().unwrap();
+        assert!(json.contains(key_field));
+        assert_eq!(
+            PaymentAuthorization::from_json(&json).unwrap(),
+            authorization
+        );
+    }
+
+    #[test]
+    fn test_payment_request_scheme() {
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        assert_eq!(request.scheme, SCHEME);
+        assert!(request.check_scheme().is_ok());
+
+        // Requests from servers predating schemes use the default
+        let mut json: serde_json::Value =
+            serde_json::from_str(&request.to_json().unwrap()).unwrap();
+        assert_eq!(json["scheme"], "solana-spl-v1");
+        json.as_object_mut().unwrap().remove("scheme");
+        let legacy = PaymentRequest::from_json(&json.to_string()).unwrap();
+        assert_eq!(legacy.scheme, SCHEME);
+
+        json["scheme"] = "solana-spl-v2".into();
+        let newer = PaymentRequest::from_json(&json.to_string()).unwrap();
+        let err = newer.check_scheme().unwrap_err();
+        assert_eq!(err.code(), "CONFIGURATION_ERROR");
+        assert!(err.to_string().contains("solana-spl-v2"));
+    }
+
+    #[test]
+    fn test_candidate_signatures() {
+        let auth = PaymentAuthorization::new(
+            "payment123".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "sig1".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        )
+        .with_signatures(vec!["sig2".to_string(), "sig1".to_string()]);
+
+        assert_eq!(auth.candidate_signatures(), vec!["sig1", "sig2"]);
+
+        let decoded =
+            PaymentAuthorization::from_header_value(&auth.to_header_value().unwrap()).unwrap();
+        assert_eq!(decoded.signatures, auth.signatures);
+    }
+
+    #[test]
+    fn test_verify_self_consistency() {
+        let auth = PaymentAuthorization::new(
+            "payment123".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            Signature::new_unique().to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        );
+        assert!(auth.verify_self_consistency().is_ok());
+
+        let mut bad_key = auth.clone();
+        bad_key.public_key = "not-a-key".to_string();
+        let err = bad_key.verify_self_consistency().unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_AUTHORIZATION");
+        assert!(err.to_string().contains("Invalid public key"));
+
+        let mut bad_signature = auth.clone();
+        bad_signature.signature = "sig1".to_string();
+        assert!(bad_signature.verify_self_consistency().is_err());
+
+        let bad_candidate = auth.with_signatures(vec!["0OIl".to_string()]);
+        let err = bad_candidate.verify_self_consistency().unwrap_err();
+        assert!(err.to_string().contains("Invalid signature \"0OIl\""));
     }
 }
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{X402Error, X402Result},
+    models::PaymentRequest,
+};
+
+/// Header carrying the base64-encoded `PaymentRequest` in header-only challenges
+pub const PAYMENT_REQUEST_HEADER: &str = "X-Payment-Request";
+
+/// Header advertising a route's price, e.g. `X-Payment-Price: 0.10 USDC`
+pub const PRICE_HEADER: &str = "X-Payment-Price";
+
+/// Content type for RFC 7807 problem details responses
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Problem type URI used in problem+json challenges
+pub const PAYMENT_REQUIRED_PROBLEM_TYPE: &str = "https://openlib.xyz/problems/payment-required";
+
+/// Form in which a 402 Payment Required challenge is delivered to the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChallengeFormat {
+    /// `PaymentRequest` serialized as the plain JSON body (default)
+    #[default]
+    Json,
+
+    /// RFC 7807 problem details body embedding the `PaymentRequest`
+    ProblemJson,
+
+    /// Empty body with the `PaymentRequest` in the `X-Payment-Request` header
+    HeaderOnly,
+}
+
+impl ChallengeFormat {
+    /// Pick a challenge format from the value of an `Accept` header
+    ///
+    /// Media ranges are considered in order of their `q` weight. A missing or
+    /// empty header yields plain JSON; a header that accepts neither JSON nor
+    /// problem+json yields a header-only challenge.
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        let accept = match accept.map(str::trim) {
+            Some(a) if !a.is_empty() => a,
+            _ => return ChallengeFormat::Json,
+        };
+
+        let mut ranges: Vec<(&str, f32)> = accept
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let media = parts.next()?.trim();
+                let q = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (!media.is_empty() && q > 0.0).then_some((media, q))
+            })
+            .collect();
+        ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        for (media, _) in ranges {
+            match media.to_ascii_lowercase().as_str() {
+                PROBLEM_JSON_CONTENT_TYPE => return ChallengeFormat::ProblemJson,
+                "application/json" | "application/*" | "*/*" => return ChallengeFormat::Json,
+                _ => {}
+            }
+        }
+
+        ChallengeFormat::HeaderOnly
+    }
+
+    /// Content type of the response body, if the format has one
+    pub fn content_type(&self) -> Option<&'static str> {
+        match self {
+            ChallengeFormat::Json => Some("application/json"),
+            ChallengeFormat::ProblemJson => Some(PROBLEM_JSON_CONTENT_TYPE),
+            ChallengeFormat::HeaderOnly => None,
+        }
+    }
+}
+
+/// RFC 7807 problem details body for a 402 challenge
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PaymentProblem {
+    /// Problem type URI
+    #[serde(rename = "type")]
+    pub problem_type: String,
+
+    /// Short, human-readable summary
+    pub title: String,
+
+    /// HTTP status code (always 402)
+    pub status: u16,
+
+    /// Human-readable explanation
+    pub detail: String,
+
+    /// The payment request the client must satisfy
+    pub payment_request: PaymentRequest,
+}
+
+impl PaymentProblem {
+    /// Create a problem details body for a payment request
+    pub fn new(payment_request: PaymentRequest) -> Self {
+        let detail = payment_request
+            .description
+            .clone()
+            .unwrap_or_else(|| format!("Payment required to access {}", payment_request.resource));
+
+        Self {
+            problem_type: PAYMENT_REQUIRED_PROBLEM_TYPE.to_string(),
+            title: "Payment Required".to_string(),
+            status: 402,
+            detail,
+            payment_request,
+        }
+    }
+
+    /// Convert problem details to JSON string
+    pub fn to_json(&self) -> X402Result<String> {
+        serde_json::to_string(self).map_err(|e| {
+            X402Error::Serialization(format!("Failed to serialize payment problem: {}", e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_accept() {
+        assert_eq!(ChallengeFormat::from_accept(None), ChallengeFormat::Json);
+        assert_eq!(
+            ChallengeFormat::from_accept(Some("")),
+            ChallengeFormat::Json
+        );
+        assert_eq!(
+            ChallengeFormat::from_accept(Some("application/json")),
+            ChallengeFormat::Json
+        );
+        assert_eq!(
+            ChallengeFormat::from_accept(Some("text/html, */*;q=0.8")),
+            ChallengeFormat::Json
+        );
+        assert_eq!(
+            ChallengeFormat::from_accept(Some("application/problem+json")),
+            ChallengeFormat::ProblemJson
+        );
+        assert_eq!(
+            ChallengeFormat::from_accept(Some("application/json;q=0.5, application/problem+json")),
+            ChallengeFormat::ProblemJson
+        );
+        assert_eq!(
+            ChallengeFormat::from_accept(Some("text/plain")),
+            ChallengeFormat::HeaderOnly
+        );
+        assert_eq!(
+            ChallengeFormat::from_accept(Some("text/plain, application/json;q=0")),
+            ChallengeFormat::HeaderOnly
+        );
+    }
+}
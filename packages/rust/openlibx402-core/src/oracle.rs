@@ -0,0 +1,206 @@
+//! USD pricing for payments in volatile tokens
+//!
+//! Servers that price in USD but accept a token whose value moves implement
+//! [`PriceOracle`] over their feed of choice (Pyth, Switchboard, an exchange
+//! API) and wrap it in [`UsdPricing`]. The token amount is computed from the
+//! price at challenge time, and verification re-prices the payment so it
+//! still has to be worth the USD target, within a tolerance, when it lands.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::{
+    errors::{X402Error, X402Result},
+    models::PaymentAuthorization,
+    payment_processor::SolanaPaymentProcessor,
+    verification::VerificationOptions,
+};
+
+/// Source of USD prices for tokens
+#[async_trait]
+pub trait PriceOracle: Send + Sync {
+    /// Current USD price of one whole token of `mint`
+    async fn usd_price(&self, mint: &str) -> X402Result<f64>;
+}
+
+/// Converts USD prices into token amounts using a [`PriceOracle`]
+///
+/// Defaults to a tolerance of 100 basis points, so a payment still verifies
+/// if the token lost up to 1% of its value since the challenge.
+#[derive(Clone)]
+pub struct UsdPricing {
+    oracle: Arc<dyn PriceOracle>,
+    tolerance_bps: u16,
+}
+
+impl UsdPricing {
+    /// Price through `oracle` with the default tolerance
+    pub fn new(oracle: Arc<dyn PriceOracle>) -> Self {
+        Self {
+            oracle,
+            tolerance_bps: 100,
+        }
+    }
+
+    /// Accept payments worth up to `bps` basis points less than the USD target
+    pub fn with_tolerance_bps(mut self, bps: u16) -> Self {
+        self.tolerance_bps = bps;
+        self
+    }
+
+    /// Amount of `mint` worth `usd_amount` at the current price
+    ///
+    /// Formatted with `decimals` fractional digits and rounded up, ready to
+    /// use as a challenge's `max_amount_required`.
+    pub async fn token_amount(
+        &self,
+        usd_amount: &str,
+        mint: &str,
+        decimals: u8,
+    ) -> X402Result<String> {
+        let usd = usd_amount
+            .parse::<f64>()
+            .ok()
+            .filter(|usd| usd.is_finite() && *usd >= 0.0)
+            .ok_or_else(|| {
+                X402Error::InvalidPaymentRequest(format!("Invalid USD amount {:?}", usd_amount))
+            })?;
+        let price = self.oracle.usd_price(mint).await?;
+        if !price.is_finite() || price <= 0.0 {
+            return Err(X402Error::Configuration(format!(
+                "Oracle returned invalid USD price {} for {}",
+                price, mint
+            )));
+        }
+
+        let scale = 10f64.powi(i32::from(decimals));
+        let units = (usd / price * scale).ceil();
+        if units > u64::MAX as f64 {
+            return Err(X402Error::InvalidPaymentRequest(format!(
+                "{} USD of {} is too large an amount",
+                usd_amount, mint
+            )));
+        }
+        let units = units as u64;
+        if decimals == 0 {
+            return Ok(units.to_string());
+        }
+
+        let scale = 10u64.pow(u32::from(decimals));
+        Ok(format!(
+            "{}.{:0width$}",
+            units / scale,
+            units % scale,
+            width = usize::from(decimals)
+        ))
+    }
+
+    /// Verify that `authorization` paid at least `usd_amount` at the current price
+    ///
+    /// Re-prices the USD target in the paid token and verifies against that
+    /// amount with `options`, whose `amount_tolerance_bps` is replaced by this
+    /// pricing's tolerance.
+    pub async fn verify_payment(
+        &self,
+        processor: &SolanaPaymentProcessor,
+        authorization: &PaymentAuthorization,
+        usd_amount: &str,
+        options: &VerificationOptions,
+    ) -> X402Result<bool> {
+        let mint = authorization.asset_address.parse().map_err(|e| {
+            X402Error::InvalidPaymentAuthorization(format!(
+                "Invalid token mint {}: {}",
+                authorization.asset_address, e
+            ))
+        })?;
+        let decimals = processor.mint_decimals(&mint).await?;
+        let required = self
+            .token_amount(usd_amount, &authorization.asset_address, decimals)
+            .await?;
+        let options = options
+            .clone()
+            .with_amount_tolerance_bps(self.tolerance_bps);
+
+        processor
+            .verify_payment_with_options(authorization, &required, &options)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::{MockRpcBackend, TransactionFixture};
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
+    use std::sync::Mutex;
+
+    /// Oracle quoting a price set by the test
+    struct MockOracle(Mutex<f64>);
+
+    #[async_trait]
+    impl PriceOracle for MockOracle {
+        async fn usd_price(&self, _mint: &str) -> X402Result<f64> {
+            Ok(*self.0.lock().unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_usd_pricing_with_mock_oracle() {
+        let oracle = Arc::new(MockOracle(Mutex::new(150.0)));
+        let pricing = UsdPricing::new(oracle.clone());
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        // 1.50 USD at 150 USD per token
+        let amount = pricing
+            .token_amount("1.50", &mint.to_string(), 9)
+            .await
+            .unwrap();
+        assert_eq!(amount, "0.010000000");
+        assert_eq!(
+            pricing
+                .token_amount("1", &mint.to_string(), 0)
+                .await
+                .unwrap(),
+            "1"
+        );
+
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new().with_mint(mint, 9).with_fixture(
+            TransactionFixture::new(signature)
+                .transfer_with_decimals(mint, recipient, 10_000_000, 9),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+        let authorization = PaymentAuthorization::new(
+            "payment123".to_string(),
+            amount,
+            recipient.to_string(),
+            mint.to_string(),
+            "solana-devnet".to_string(),
+            signature.to_string(),
+            Pubkey::new_unique().to_string(),
+        );
+        let options = VerificationOptions::default();
+
+        // A 0.5% drop since the challenge is within the 1% tolerance
+        *oracle.0.lock().unwrap() = 149.25;
+        assert!(pricing
+            .verify_payment(&processor, &authorization, "1.50", &options)
+            .await
+            .unwrap());
+
+        // A 5% drop is not
+        *oracle.0.lock().unwrap() = 142.5;
+        let err = pricing
+            .verify_payment(&processor, &authorization, "1.50", &options)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+
+        *oracle.0.lock().unwrap() = 0.0;
+        let err = pricing
+            .token_amount("1.50", &mint.to_string(), 9)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "CONFIGURATION_ERROR");
+    }
+}
@@ -0,0 +1,337 @@
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+};
+use std::{str::FromStr, sync::Arc};
+use uuid::Uuid;
+
+use crate::{
+    errors::{X402Error, X402Result},
+    models::{PaymentAuthorization, PaymentRequest},
+};
+
+/// Header carrying the base64-encoded `PassAuthorization` on requests made with a pass
+pub const PASS_HEADER: &str = "X-Payment-Pass";
+
+/// Offer of a time-boxed pass to a group of resources
+///
+/// The client pays `payment_request` like any other challenge and sends the
+/// resulting authorization back to the server, which issues a
+/// [`PassAuthorization`] for `scope` lasting `duration_secs`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PassRequest {
+    /// Resources the pass grants access to: an exact path (`/report`) or a
+    /// prefix wildcard (`/api/*`, covering `/api` and everything below it)
+    pub scope: String,
+
+    /// How long the pass lasts once issued, in seconds
+    pub duration_secs: i64,
+
+    /// Payment buying the pass
+    pub payment_request: PaymentRequest,
+}
+
+impl PassRequest {
+    /// Offer a pass to `scope` for `duration`, paid through `payment_request`
+    pub fn new(payment_request: PaymentRequest, scope: &str, duration: Duration) -> Self {
+        Self {
+            scope: scope.to_string(),
+            duration_secs: duration.num_seconds(),
+            payment_request,
+        }
+    }
+
+    /// How long the pass lasts once issued
+    pub fn duration(&self) -> Duration {
+        Duration::seconds(self.duration_secs)
+    }
+
+    /// Convert to JSON string
+    pub fn to_json(&self) -> X402Result<String> {
+        serde_json::to_string(self).map_err(|e| e.into())
+    }
+
+    /// Parse pass request from JSON string
+    pub fn from_json(json: &str) -> X402Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| X402Error::InvalidPaymentRequest(format!("Failed to parse pass: {}", e)))
+    }
+}
+
+/// Pass issued and signed by a server in exchange for a payment
+///
+/// The client presents it in the `X-Payment-Pass` header instead of paying
+/// again. The server checks its own signature and the expiry, without going
+/// back to the chain.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PassAuthorization {
+    /// Unique pass identifier
+    pub pass_id: String,
+
+    /// Resources the pass grants access to, as in [`PassRequest::scope`]
+    pub scope: String,
+
+    /// Payment ID of the payment that bought the pass
+    pub payment_id: String,
+
+    /// When the pass was issued
+    pub issued_at: DateTime<Utc>,
+
+    /// When the pass stops being accepted
+    pub expires_at: DateTime<Utc>,
+
+    /// Public key of the issuing server
+    pub issuer: String,
+
+    /// Issuer's signature over the fields above
+    pub signature: String,
+}
+
+impl PassAuthorization {
+    /// Check if the pass has expired
+    pub fn is_expired(&self) -> bool {
+        Utc::now() > self.expires_at
+    }
+
+    /// Whether the pass grants access to `resource`
+    pub fn covers(&self, resource: &str) -> bool {
+        match self.scope.strip_suffix("/*") {
+            Some(prefix) => {
+                resource == prefix
+                    || resource
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            }
+            None => resource == self.scope,
+        }
+    }
+
+    /// Bytes covered by the issuer's signature
+    fn signed_message(&self) -> Vec<u8> {
+        format!(
+            "x402-pass\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.pass_id,
+            self.scope,
+            self.payment_id,
+            self.issued_at.to_rfc3339(),
+            self.expires_at.to_rfc3339(),
+            self.issuer
+        )
+        .into_bytes()
+    }
+
+    /// Convert to JSON string
+    pub fn to_json(&self) -> X402Result<String> {
+        serde_json::to_string(self).map_err(|e| e.into())
+    }
+
+    /// Parse pass from JSON string
+    pub fn from_json(json: &str) -> X402Result<Self> {
+        serde_json::from_str(json).map_err(|e| {
+            X402Error::InvalidPaymentAuthorization(format!("Failed to parse pass: {}", e))
+        })
+    }
+
+    /// Encode pass for the X-Payment-Pass header
+    pub fn to_header_value(&self) -> X402Result<String> {
+        let json = self.to_json()?;
+        Ok(general_purpose::STANDARD.encode(json.as_bytes()))
+    }
+
+    /// Decode pass from an X-Payment-Pass header value
+    pub fn from_header_value(encoded: &str) -> X402Result<Self> {
+        let decoded = general_purpose::STANDARD.decode(encoded)?;
+        let json = String::from_utf8(decoded).map_err(|e| {
+            X402Error::InvalidPaymentAuthorization(format!("Invalid UTF-8 in header: {}", e))
+        })?;
+        Self::from_json(&json)
+    }
+}
+
+/// Server-side issuer and verifier of passes
+///
+/// Passes are signed with the issuer's keypair, so any server instance
+/// holding the same key accepts them. Cloning the issuer shares the key.
+#[derive(Clone)]
+pub struct PassIssuer {
+    keypair: Arc<Keypair>,
+}
+
+impl PassIssuer {
+    /// Create an issuer signing with `keypair`
+    pub fn new(keypair: Keypair) -> Self {
+        Self {
+            keypair: Arc::new(keypair),
+        }
+    }
+
+    /// Public key passes are verified against
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    /// Issue a pass for `request`, bought by `authorization`
+    ///
+    /// Only the authorization's payment ID is checked here; verify the
+    /// payment on-chain before issuing.
+    pub fn issue(
+        &self,
+        request: &PassRequest,
+        authorization: &PaymentAuthorization,
+    ) -> X402Result<PassAuthorization> {
+        if authorization.payment_id != request.payment_request.payment_id {
+            return Err(X402Error::InvalidPaymentAuthorization(format!(
+                "Payment {} does not pay for pass request {}",
+                authorization.payment_id, request.payment_request.payment_id
+            )));
+        }
+
+        let issued_at = Utc::now();
+        let mut pass = PassAuthorization {
+            pass_id: Uuid::new_v4().to_string(),
+            scope: request.scope.clone(),
+            payment_id: authorization.payment_id.clone(),
+            issued_at,
+            expires_at: issued_at + request.duration(),
+            issuer: self.pubkey().to_string(),
+            signature: String::new(),
+        };
+        pass.signature = self
+            .keypair
+            .try_sign_message(&pass.signed_message())?
+            .to_string();
+        Ok(pass)
+    }
+
+    /// Check that `pass` was issued here, is still valid and covers `resource`
+    pub fn verify(&self, pass: &PassAuthorization, resource: &str) -> X402Result<()> {
+        let signature = Signature::from_str(&pass.signature).map_err(|e| {
+            X402Error::InvalidPaymentAuthorization(format!("Invalid pass signature: {}", e))
+        })?;
+        let issuer = self.pubkey();
+        if pass.issuer != issuer.to_string()
+            || !signature.verify(issuer.as_ref(), &pass.signed_message())
+        {
+            return Err(X402Error::PaymentVerification(format!(
+                "Pass {} was not issued by {}",
+                pass.pass_id, issuer
+            )));
+        }
+        if pass.is_expired() {
+            return Err(X402Error::PaymentExpired(format!(
+                "Pass {} expired at {}",
+                pass.pass_id, pass.expires_at
+            )));
+        }
+        if !pass.covers(resource) {
+            return Err(X402Error::PaymentVerification(format!(
+                "Pass {} for {} does not cover {}",
+                pass.pass_id, pass.scope, resource
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for PassIssuer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PassIssuer")
+            .field("pubkey", &self.pubkey())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pass_request(scope: &str, duration: Duration) -> PassRequest {
+        let payment_request = PaymentRequest::builder()
+            .amount("5.00")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .nonce("nonce123")
+            .payment_id("pass_payment")
+            .resource("/passes")
+            .build()
+            .unwrap();
+        PassRequest::new(payment_request, scope, duration)
+    }
+
+    fn payment_for(request: &PassRequest) -> PaymentAuthorization {
+        PaymentAuthorization::new(
+            request.payment_request.payment_id.clone(),
+            request.payment_request.max_amount_required.clone(),
+            request.payment_request.payment_address.clone(),
+            request.payment_request.asset_address.clone(),
+            request.payment_request.network.clone(),
+            Signature::new_unique().to_string(),
+            Pubkey::new_unique().to_string(),
+        )
+    }
+
+    #[test]
+    fn test_issue_pass() {
+        let issuer = PassIssuer::new(Keypair::new());
+        let request = pass_request("/api/*", Duration::hours(1));
+
+        let pass = issuer.issue(&request, &payment_for(&request)).unwrap();
+        assert_eq!(pass.scope, "/api/*");
+        assert_eq!(pass.payment_id, "pass_payment");
+        assert_eq!(pass.issuer, issuer.pubkey().to_string());
+        assert_eq!(pass.expires_at - pass.issued_at, Duration::hours(1));
+
+        // A payment for another challenge does not buy the pass
+        let mut other = payment_for(&request);
+        other.payment_id = "other_payment".to_string();
+        let err = issuer.issue(&request, &other).unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_AUTHORIZATION");
+    }
+
+    #[test]
+    fn test_present_pass() {
+        let issuer = PassIssuer::new(Keypair::new());
+        let request = pass_request("/api/*", Duration::hours(1));
+        let issued = issuer.issue(&request, &payment_for(&request)).unwrap();
+
+        let header = issued.to_header_value().unwrap();
+        let pass = PassAuthorization::from_header_value(&header).unwrap();
+        assert_eq!(pass, issued);
+        issuer.verify(&pass, "/api/data").unwrap();
+        issuer.verify(&pass, "/api").unwrap();
+
+        let err = issuer.verify(&pass, "/apiary").unwrap_err();
+        assert!(err.to_string().contains("does not cover /apiary"));
+
+        // Widening the scope breaks the signature
+        let mut widened = pass.clone();
+        widened.scope = "/*".to_string();
+        let err = issuer.verify(&widened, "/admin").unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+
+        // Another server's key does not accept the pass
+        let stranger = PassIssuer::new(Keypair::new());
+        assert!(stranger.verify(&pass, "/api/data").is_err());
+    }
+
+    #[test]
+    fn test_pass_expiry() {
+        let issuer = PassIssuer::new(Keypair::new());
+        let request = pass_request("/api/*", Duration::seconds(-1));
+        let pass = issuer.issue(&request, &payment_for(&request)).unwrap();
+        assert!(pass.is_expired());
+
+        let err = issuer.verify(&pass, "/api/data").unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_EXPIRED");
+
+        // Extending the expiry breaks the signature
+        let mut extended = pass.clone();
+        extended.expires_at = Utc::now() + Duration::hours(1);
+        let err = issuer.verify(&extended, "/api/data").unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::Uuid;
+
+/// Source of the `payment_id`s servers put in their payment requests
+///
+/// Every ID a server issues must be unique across all of its instances,
+/// since stores and replay protection key on it.
+pub trait PaymentIdGenerator: Send + Sync + std::fmt::Debug {
+    /// Produce a new payment ID
+    fn generate(&self) -> String;
+}
+
+/// Random UUIDv4 payment IDs (default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidGenerator;
+
+impl PaymentIdGenerator for UuidGenerator {
+    fn generate(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+}
+
+/// Monotonic payment IDs tagged with the issuing node
+///
+/// IDs look like `{node_id}-{millis}-{sequence}`: the node that issued the
+/// payment, the issue time in Unix milliseconds and a per-node sequence
+/// number. Within a node they sort by issue order, and operators can route or
+/// shard payments by their prefix. Node IDs must be unique per instance.
+#[derive(Debug)]
+pub struct MonotonicGenerator {
+    node_id: String,
+    sequence: AtomicU64,
+}
+
+impl MonotonicGenerator {
+    /// Create a generator for the instance `node_id`
+    pub fn new(node_id: &str) -> Self {
+        Self {
+            node_id: node_id.to_string(),
+            sequence: AtomicU64::new(0),
+        }
+    }
+}
+
+impl PaymentIdGenerator for MonotonicGenerator {
+    fn generate(&self) -> String {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        format!(
+            "{}-{:013}-{:010}",
+            self.node_id,
+            chrono::Utc::now().timestamp_millis(),
+            sequence
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{store::ChallengeStore, MemoryStore, PaymentRequest};
+
+    #[tokio::test]
+    async fn test_monotonic_ids_are_traceable_and_stored() {
+        let generator = MonotonicGenerator::new("node-7");
+        let first = generator.generate();
+        let second = generator.generate();
+
+        let parts: Vec<_> = first.rsplitn(3, '-').collect();
+        assert_eq!(parts[2], "node-7");
+        assert_eq!(parts[1].len(), 13);
+        assert_eq!(parts[0], "0000000000");
+        assert!(second.ends_with("-0000000001"));
+        assert!(first < second);
+
+        let store = MemoryStore::new();
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .nonce("nonce123")
+            .payment_id(first.clone())
+            .resource("/premium")
+            .build()
+            .unwrap();
+        store.save(&request).await.unwrap();
+        assert_eq!(store.get(&first).await.unwrap(), Some(request));
+        assert_eq!(store.get(&second).await.unwrap(), None);
+    }
+}
@@ -1,46 +1,208 @@
-use solana_client::rpc_client::RpcClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
-    instruction::Instruction,
-    message::Message,
-    pubkey::Pubkey,
-    signature::{Keypair, Signature, Signer},
-    transaction::Transaction,
+    commitment_config::CommitmentConfig, program_pack::Pack, pubkey::Pubkey, signature::Signature,
 };
-use spl_token::instruction as token_instruction;
-use spl_associated_token_account::{
-    get_associated_token_address, instruction::create_associated_token_account,
+use spl_associated_token_account::get_associated_token_address;
+use spl_token_2022::{
+    extension::transfer_fee::TransferFeeConfig,
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::{Account as Token2022Account, Mint as Token2022Mint},
+};
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
 };
-use std::str::FromStr;
 
 use crate::{
     errors::{X402Error, X402Result},
-    models::{PaymentAuthorization, PaymentRequest},
+    metrics::{self, MetricsRecorder},
+    models::{PaymentAuthorization, PaymentRequest, SOL_DECIMALS},
+    rpc::{failover::FailoverBackend, RpcBackend},
+    transfer::{
+        fee_payer, lamport_balance_change, memos, signers, sol_transfers, token_balance_change,
+        token_transfers,
+    },
+    verification::{VerificationOptions, SLOT_DURATION},
+};
+
+#[cfg(feature = "payments")]
+mod payer;
+
+#[cfg(feature = "payments")]
+pub use payer::{
+    ConfirmationConfig, CostEstimate, PartialPayment, PriorityFeeConfig, TransactionVersion,
+    BLOCKHASH_VALIDITY, DEFAULT_MAX_BROADCAST_RETRIES,
 };
 
+/// Rent-exemption status of a payment recipient's wallet and token account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RentExemptionReport {
+    /// Recipient wallet address
+    pub wallet: Pubkey,
+
+    /// Recipient wallet balance in lamports
+    pub wallet_balance: u64,
+
+    /// Minimum lamports for the wallet to be rent-exempt
+    pub wallet_minimum: u64,
+
+    /// Recipient associated token account for the payment mint
+    pub token_account: Pubkey,
+
+    /// Token account balance in lamports (`None` if the account doesn't exist)
+    pub token_account_balance: Option<u64>,
+
+    /// Minimum lamports for the token account to be rent-exempt
+    pub token_account_minimum: u64,
+}
+
+impl RentExemptionReport {
+    /// Check if the recipient wallet holds at least the rent-exempt minimum
+    pub fn wallet_is_rent_exempt(&self) -> bool {
+        self.wallet_balance >= self.wallet_minimum
+    }
+
+    /// Check if the recipient token account exists and is rent-exempt
+    pub fn token_account_is_rent_exempt(&self) -> bool {
+        self.token_account_balance
+            .is_some_and(|balance| balance >= self.token_account_minimum)
+    }
+
+    /// Check if both the wallet and the token account are rent-exempt
+    pub fn is_rent_exempt(&self) -> bool {
+        self.wallet_is_rent_exempt() && self.token_account_is_rent_exempt()
+    }
+
+    /// Human-readable warnings for each account that isn't rent-exempt
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.wallet_is_rent_exempt() {
+            warnings.push(format!(
+                "Recipient wallet {} holds {} lamports, below the rent-exempt minimum of {}",
+                self.wallet, self.wallet_balance, self.wallet_minimum
+            ));
+        }
+        match self.token_account_balance {
+            None => warnings.push(format!(
+                "Recipient token account {} does not exist; the first payer will pay its rent",
+                self.token_account
+            )),
+            Some(balance) if balance < self.token_account_minimum => warnings.push(format!(
+                "Recipient token account {} holds {} lamports, below the rent-exempt minimum of {}",
+                self.token_account, balance, self.token_account_minimum
+            )),
+            Some(_) => {}
+        }
+        warnings
+    }
+}
+
+/// Token mint details read from its on-chain account
+struct MintInfo {
+    /// Program owning the mint
+    token_program: Pubkey,
+    decimals: u8,
+    /// Size of the mint's associated token accounts
+    #[cfg_attr(not(feature = "payments"), allow(dead_code))]
+    account_len: usize,
+    /// Token-2022 transfer-fee extension, if the mint has one
+    transfer_fee: Option<TransferFeeConfig>,
+}
+
+impl MintInfo {
+    /// Most the mint's transfer fee can withhold from a transfer of `amount`
+    ///
+    /// The mint may be switching between two fee schedules, the newer
+    /// taking effect at an epoch; whichever withholds more is assumed.
+    fn max_transfer_fee(&self, amount: u64) -> u64 {
+        let Some(config) = &self.transfer_fee else {
+            return 0;
+        };
+        [&config.older_transfer_fee, &config.newer_transfer_fee]
+            .into_iter()
+            .filter_map(|fee| fee.calculate_fee(amount))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Whether `program` is the SPL Token or the Token-2022 program
+fn is_token_program(program: &Pubkey) -> bool {
+    *program == spl_token::id() || *program == spl_token_2022::id()
+}
+
 /// Solana payment processor for handling blockchain operations
+///
+/// Verifying payments is always available. Creating them, and the builders
+/// configuring how they are made, need the `payments` feature (on by default).
 pub struct SolanaPaymentProcessor {
-    rpc_client: RpcClient,
-    #[allow(dead_code)]
+    rpc_client: Arc<dyn RpcBackend>,
     commitment: CommitmentConfig,
+    metrics: Option<Arc<dyn MetricsRecorder>>,
+    #[cfg(feature = "payments")]
+    payer: payer::PayerSettings,
 }
 
 impl SolanaPaymentProcessor {
     /// Create a new Solana payment processor
     ///
+    /// RPC calls go through Solana's nonblocking client and yield the tokio
+    /// worker thread while waiting on the endpoint.
+    ///
     /// # Arguments
     /// * `rpc_url` - Solana RPC endpoint URL
     /// * `commitment` - Transaction commitment level (default: confirmed)
     pub fn new(rpc_url: &str, commitment: Option<CommitmentConfig>) -> Self {
-        Self {
-            rpc_client: RpcClient::new_with_commitment(
+        let commitment = commitment.unwrap_or(CommitmentConfig::confirmed());
+        Self::with_backend(
+            Arc::new(RpcClient::new_with_commitment(
                 rpc_url.to_string(),
-                commitment.unwrap_or(CommitmentConfig::confirmed()),
-            ),
+                commitment,
+            )),
+            Some(commitment),
+        )
+    }
+
+    /// Create a processor that fails over between several RPC endpoints
+    ///
+    /// `rpc_urls` are tried in order; see [`FailoverBackend`] for when a call
+    /// moves on to the next endpoint.
+    ///
+    /// # Arguments
+    /// * `rpc_urls` - Solana RPC endpoint URLs, most preferred first
+    /// * `commitment` - Transaction commitment level (default: confirmed)
+    pub fn with_rpc_urls(rpc_urls: &[&str], commitment: Option<CommitmentConfig>) -> Self {
+        Self::with_backend(
+            Arc::new(FailoverBackend::from_urls(rpc_urls, commitment)),
+            commitment,
+        )
+    }
+
+    /// Create a processor that talks to the chain through a custom RPC backend
+    ///
+    /// # Arguments
+    /// * `backend` - RPC backend implementation
+    /// * `commitment` - Transaction commitment level (default: confirmed)
+    pub fn with_backend(
+        backend: Arc<dyn RpcBackend>,
+        commitment: Option<CommitmentConfig>,
+    ) -> Self {
+        Self {
+            rpc_client: backend,
             commitment: commitment.unwrap_or(CommitmentConfig::confirmed()),
+            metrics: None,
+            #[cfg(feature = "payments")]
+            payer: payer::PayerSettings::default(),
         }
     }
 
+    /// Report payment latencies and failures to `recorder`
+    pub fn with_metrics(mut self, recorder: Arc<dyn MetricsRecorder>) -> Self {
+        self.metrics = Some(recorder);
+        self
+    }
+
     /// Get the default RPC URL for a network
     pub fn default_rpc_url(network: &str) -> &'static str {
         match network {
@@ -51,211 +213,676 @@ impl SolanaPaymentProcessor {
         }
     }
 
-    /// Create a payment from a payment request
-    ///
-    /// This creates, signs, and broadcasts a Solana SPL token transfer transaction
-    pub async fn create_payment(
-        &self,
-        request: &PaymentRequest,
-        payer: &Keypair,
-    ) -> X402Result<PaymentAuthorization> {
-        // Check if payment has expired
-        if request.is_expired() {
-            return Err(X402Error::PaymentExpired(format!(
-                "Payment request expired at {}",
-                request.expires_at
-            )));
-        }
-
-        // Parse addresses
-        let token_mint = Pubkey::from_str(&request.asset_address).map_err(|e| {
-            X402Error::InvalidPaymentRequest(format!("Invalid token mint address: {}", e))
-        })?;
-
-        let recipient = Pubkey::from_str(&request.payment_address).map_err(|e| {
-            X402Error::InvalidPaymentRequest(format!("Invalid payment address: {}", e))
-        })?;
-
-        let amount = Self::parse_amount(&request.max_amount_required)?;
-
-        // Get or create associated token accounts
-        let sender_ata = get_associated_token_address(&payer.pubkey(), &token_mint);
-        let recipient_ata = get_associated_token_address(&recipient, &token_mint);
+    /// Parse a commitment level: `processed`, `confirmed` or `finalized`
+    pub fn parse_commitment(commitment: &str) -> X402Result<CommitmentConfig> {
+        commitment.parse().map_err(|_| {
+            X402Error::Configuration(format!(
+                "Unknown commitment level '{}'; expected processed, confirmed or finalized",
+                commitment
+            ))
+        })
+    }
 
-        // Check sender balance
-        self.check_balance(&sender_ata, amount).await?;
+    fn record_result<T>(&self, operation: &'static str, result: &X402Result<T>) {
+        if let (Some(metrics), Err(e)) = (&self.metrics, result) {
+            metrics.record_failure(operation, e.code());
+        }
+    }
 
-        // Build transaction
-        let mut instructions: Vec<Instruction> = Vec::new();
+    /// Verify a payment transaction
+    ///
+    /// This checks that the transaction exists on-chain and that it transferred at least
+    /// the expected amount of `authorization.asset_address` to `authorization.payment_address`.
+    /// Transfers are read from the RPC's parsed instruction data, so payments made through
+    /// another program (CPI) or by Token-2022 are recognized. When the authorization carries
+    /// several candidate signatures (e.g. from retried broadcasts), the payment is accepted
+    /// if any one of them confirms on its own; amounts are never summed across candidates.
+    pub async fn verify_payment(
+        &self,
+        authorization: &PaymentAuthorization,
+        expected_amount: &str,
+    ) -> X402Result<bool> {
+        self.verify_payment_with_options(
+            authorization,
+            expected_amount,
+            &VerificationOptions::default(),
+        )
+        .await
+    }
 
-        // Check if recipient ATA exists, if not create it
-        if !self.account_exists(&recipient_ata).await? {
-            instructions.push(create_associated_token_account(
-                &payer.pubkey(),
-                &recipient,
-                &token_mint,
-                &spl_token::id(),
-            ));
-        }
+    /// Verify a payment transaction under a server's verification policy
+    ///
+    /// Same as [`verify_payment`](Self::verify_payment), but applies `options`,
+    /// e.g. accepting amounts within `amount_tolerance_bps` of the expected amount.
+    pub async fn verify_payment_with_options(
+        &self,
+        authorization: &PaymentAuthorization,
+        expected_amount: &str,
+        options: &VerificationOptions,
+    ) -> X402Result<bool> {
+        self.verify_payment_signature(authorization, expected_amount, options)
+            .await
+            .map(|_| true)
+    }
 
-        // Add transfer instruction
-        instructions.push(
-            token_instruction::transfer_checked(
-                &spl_token::id(),
-                &sender_ata,
-                &token_mint,
-                &recipient_ata,
-                &payer.pubkey(),
-                &[],
-                amount,
-                6, // USDC uses 6 decimals
+    /// Verify a payment transaction, returning the candidate signature that paid
+    ///
+    /// Same as [`verify_payment_with_options`](Self::verify_payment_with_options).
+    /// Servers guarding against replays should record only the returned
+    /// signature, since the other candidates may be anyone's.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "verify_payment",
+            skip_all,
+            fields(
+                payment_id = %authorization.payment_id,
+                amount = %expected_amount,
+                network = %authorization.network,
+                signature = %authorization.signature,
             )
-            .map_err(|e| {
-                X402Error::Blockchain(format!("Failed to create transfer instruction: {}", e))
-            })?,
-        );
-
-        // Get recent blockhash
-        let recent_blockhash = self
-            .rpc_client
-            .get_latest_blockhash()
-            .map_err(|e| X402Error::Network(format!("Failed to get recent blockhash: {}", e)))?;
+        )
+    )]
+    pub async fn verify_payment_signature(
+        &self,
+        authorization: &PaymentAuthorization,
+        expected_amount: &str,
+        options: &VerificationOptions,
+    ) -> X402Result<String> {
+        let started = Instant::now();
+        let result = self
+            .verify_candidates(authorization, expected_amount, options)
+            .await
+            .map(|(signature, received)| {
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_verification(started.elapsed());
+                    metrics.record_payment_collected(&authorization.asset_address, received);
+                }
+                signature.to_string()
+            });
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(_) => tracing::info!("payment verified"),
+            Err(e) => tracing::warn!(code = e.code(), error = %e, "payment not verified"),
+        }
+        self.record_result(metrics::VERIFY_PAYMENT, &result);
+        result
+    }
 
-        // Create and sign transaction
-        let message = Message::new(&instructions, Some(&payer.pubkey()));
-        let mut transaction = Transaction::new_unsigned(message);
-        transaction.sign(&[payer], recent_blockhash);
+    /// First candidate signature that verifies, and the amount it paid
+    async fn verify_candidates<'a>(
+        &self,
+        authorization: &'a PaymentAuthorization,
+        expected_amount: &str,
+        options: &VerificationOptions,
+    ) -> X402Result<(&'a str, u64)> {
+        authorization.verify_self_consistency()?;
+        let mut last_error = None;
 
-        // Send transaction
-        let signature = self
-            .rpc_client
-            .send_and_confirm_transaction(&transaction)
-            .map_err(|e| {
-                X402Error::TransactionBroadcast(format!("Failed to broadcast transaction: {}", e))
-            })?;
+        for signature in authorization.candidate_signatures() {
+            match self
+                .verify_candidate(authorization, signature, expected_amount, options)
+                .await
+            {
+                Ok(received) => return Ok((signature, received)),
+                Err(e) => last_error = Some(e),
+            }
+        }
 
-        // Create payment authorization
-        Ok(PaymentAuthorization::new(
-            request.payment_id.clone(),
-            request.max_amount_required.clone(),
-            request.payment_address.clone(),
-            request.asset_address.clone(),
-            request.network.clone(),
-            signature.to_string(),
-            payer.pubkey().to_string(),
-        ))
+        Err(last_error.unwrap_or_else(|| {
+            X402Error::InvalidPaymentAuthorization("No signature to verify".to_string())
+        }))
     }
 
-    /// Verify a payment transaction
+    /// Verify a single candidate signature of an authorization
     ///
-    /// This checks that the transaction exists on-chain and matches the expected parameters
-    pub async fn verify_payment(
+    /// Returns the amount the payees received, in the asset's smallest unit.
+    async fn verify_candidate(
         &self,
         authorization: &PaymentAuthorization,
+        signature: &str,
         expected_amount: &str,
-    ) -> X402Result<bool> {
-        let signature = Signature::from_str(&authorization.signature).map_err(|e| {
+        options: &VerificationOptions,
+    ) -> X402Result<u64> {
+        let signature = Signature::from_str(signature).map_err(|e| {
             X402Error::InvalidPaymentAuthorization(format!("Invalid signature: {}", e))
         })?;
 
+        // Confirm the signature landed, searching history for ones that have
+        // aged out of the node's recent status cache
+        let status = self
+            .rpc_client
+            .get_signature_status(&signature, true)
+            .await
+            .map_err(|e| {
+                X402Error::PaymentVerification(format!("Failed to fetch signature status: {}", e))
+            })?
+            .ok_or_else(|| {
+                X402Error::PaymentVerification(format!("Transaction {} not found", signature))
+            })?;
+        if status.err.is_some() {
+            return Err(X402Error::PaymentVerification(format!(
+                "Transaction {} failed on-chain",
+                signature
+            )));
+        }
+        if !status.satisfies_commitment(self.commitment) {
+            return Err(X402Error::PaymentVerification(format!(
+                "Transaction {} is not confirmed yet",
+                signature
+            )));
+        }
+
         // Get transaction details
         let transaction = self
             .rpc_client
-            .get_transaction(&signature, solana_transaction_status::UiTransactionEncoding::Json)
+            .get_transaction(
+                &signature,
+                solana_transaction_status::UiTransactionEncoding::JsonParsed,
+            )
+            .await
             .map_err(|e| {
                 X402Error::PaymentVerification(format!("Failed to fetch transaction: {}", e))
             })?;
 
         // Verify transaction succeeded
-        if transaction.transaction.meta.as_ref().and_then(|m| m.err.as_ref()).is_some() {
-            return Err(X402Error::PaymentVerification(
-                "Transaction failed on-chain".to_string(),
-            ));
+        if transaction
+            .transaction
+            .meta
+            .as_ref()
+            .and_then(|m| m.err.as_ref())
+            .is_some()
+        {
+            return Err(X402Error::PaymentVerification(format!(
+                "Transaction {} failed on-chain",
+                signature
+            )));
         }
 
+        // The payer covers fees; a recipient paying them points to a spoofed transfer
+        if fee_payer(&transaction).as_deref() == Some(authorization.payment_address.as_str()) {
+            return Err(X402Error::PaymentVerification(format!(
+                "Transaction {} fee payer is the recipient {}",
+                signature, authorization.payment_address
+            )));
+        }
+
+        if options.require_payer_signature
+            && !signers(&transaction).contains(&authorization.public_key)
+        {
+            return Err(X402Error::InvalidPaymentAuthorization(format!(
+                "Transaction {} was not signed by the payer {}",
+                signature, authorization.public_key
+            )));
+        }
+
+        if options.require_payment_id_memo
+            && !memos(&transaction).contains(&authorization.payment_id)
+        {
+            return Err(X402Error::PaymentVerification(format!(
+                "Transaction {} has no memo for payment {}",
+                signature, authorization.payment_id
+            )));
+        }
+
+        // Verify the transfer on-chain rather than trusting the reported amount
+        let (recipient, mint) = (&authorization.payment_address, &authorization.asset_address);
+        let payees = options.payees(recipient);
+        let (decimals, sent, fees) = if authorization.is_native_sol() {
+            let received: u64 = sol_transfers(&transaction)
+                .iter()
+                .filter(|t| &t.destination == recipient)
+                .map(|t| t.lamports)
+                .sum();
+            if received == 0 {
+                return Err(X402Error::PaymentVerification(format!(
+                    "Transaction {} transferred no SOL to {}",
+                    signature, recipient
+                )));
+            }
+            (SOL_DECIMALS, received, 0)
+        } else {
+            self.token_payment(&transaction, &signature, authorization, &payees)
+                .await?
+        };
+
         // Parse and verify amount
-        let expected = Self::parse_amount(expected_amount)?;
-        let actual = Self::parse_amount(&authorization.actual_amount)?;
+        let expected = Self::parse_amount(expected_amount, decimals)?;
+        let minimum = options.minimum_accepted(expected)?;
+        let actual = Self::parse_amount(&authorization.actual_amount, decimals)?;
 
-        if actual < expected {
+        if actual < minimum {
             return Err(X402Error::PaymentVerification(format!(
                 "Payment amount {} is less than required {}",
                 authorization.actual_amount, expected_amount
             )));
         }
 
-        Ok(true)
+        // Transfer-fee mints withhold part of each transfer from the
+        // recipient. Unless the payer must cover the fees, what was sent counts
+        let received = sent.saturating_sub(fees);
+        let (counted, withheld) = if options.require_amount_after_transfer_fees {
+            (received, 0)
+        } else {
+            (sent, fees)
+        };
+        if counted < minimum {
+            let after_fees = match fees {
+                0 => String::new(),
+                fees => format!(" after {} in transfer fees", fees),
+            };
+            return Err(X402Error::PaymentVerification(format!(
+                "Transaction {} delivered {} to {}{}, less than required {}",
+                signature, received, authorization.payment_address, after_fees, minimum
+            )));
+        }
+
+        // The recipient's balance must actually have grown, whatever the
+        // instructions claim, by at least what wasn't withheld in fees
+        let minimum_change = i128::from(minimum.saturating_sub(withheld));
+        let (asset, change) = if authorization.is_native_sol() {
+            ("SOL", lamport_balance_change(&transaction, recipient))
+        } else {
+            (
+                mint.as_str(),
+                payees
+                    .iter()
+                    .filter_map(|payee| token_balance_change(&transaction, payee, mint))
+                    .reduce(|total, change| total + change),
+            )
+        };
+        match change {
+            Some(change) if change >= minimum_change => {}
+            Some(change) => {
+                return Err(X402Error::PaymentVerification(format!(
+                    "Transaction {} changed {}'s {} balance by {}, less than required {}",
+                    signature, recipient, asset, change, minimum_change
+                )))
+            }
+            None => {
+                return Err(X402Error::PaymentVerification(format!(
+                    "Transaction {} reports no {} balance for {}",
+                    signature, asset, recipient
+                )))
+            }
+        }
+
+        if options.checks_confirmation_age() {
+            let age = match transaction.block_time {
+                Some(block_time) => {
+                    let elapsed = chrono::Utc::now().timestamp().saturating_sub(block_time);
+                    Duration::from_secs(elapsed.max(0) as u64)
+                }
+                None => {
+                    let current = self.rpc_client.get_slot().await.map_err(|e| {
+                        X402Error::Network(format!("Failed to get current slot: {}", e))
+                    })?;
+                    SLOT_DURATION.saturating_mul(
+                        u32::try_from(current.saturating_sub(transaction.slot)).unwrap_or(u32::MAX),
+                    )
+                }
+            };
+            options.check_confirmation_age(&signature.to_string(), age)?;
+        }
+
+        Ok(received)
+    }
+
+    /// Decimals, amount sent and transfer fees withheld for the SPL token
+    /// payment in `transaction`
+    ///
+    /// Transfers to any of `payees` count towards the payment.
+    async fn token_payment(
+        &self,
+        transaction: &solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta,
+        signature: &Signature,
+        authorization: &PaymentAuthorization,
+        payees: &[String],
+    ) -> X402Result<(u8, u64, u64)> {
+        let (recipient, mint) = (&authorization.payment_address, &authorization.asset_address);
+        let transfers = token_transfers(transaction);
+        let paid: Vec<_> = transfers
+            .iter()
+            .filter(|t| payees.iter().any(|payee| t.pays(payee, mint)))
+            .collect();
+        if paid.is_empty() {
+            let other_mint = transfers
+                .iter()
+                .find(|t| {
+                    t.destination_owner
+                        .as_ref()
+                        .is_some_and(|owner| payees.contains(owner))
+                })
+                .and_then(|t| t.mint.as_deref());
+            let reason = match other_mint {
+                Some(other) => format!("paid {} in {} instead of {}", recipient, other, mint),
+                None if transfers.iter().any(|t| t.mint.as_ref() == Some(mint)) => {
+                    format!(
+                        "transferred {} to another recipient than {}",
+                        mint, recipient
+                    )
+                }
+                None => format!("transferred nothing to {}", recipient),
+            };
+            return Err(X402Error::PaymentVerification(format!(
+                "Transaction {} {}",
+                signature, reason
+            )));
+        }
+
+        // `transfer_checked` asserts the mint's decimals, so a transfer
+        // claiming others is malformed whatever its amount
+        let mint_key = Pubkey::from_str(mint).map_err(|e| {
+            X402Error::InvalidPaymentAuthorization(format!("Invalid token mint address: {}", e))
+        })?;
+        let mint_info = self.read_mint(&mint_key).await?;
+        let decimals = mint_info.decimals;
+        if let Some(reported) = paid
+            .iter()
+            .filter_map(|t| t.decimals)
+            .find(|reported| *reported != decimals)
+        {
+            return Err(X402Error::PaymentVerification(format!(
+                "Transaction {} transferred {} with {} decimals, but the mint has {}",
+                signature, mint, reported, decimals
+            )));
+        }
+
+        // Only `transferCheckedWithFee` reports the fee withheld; a plain
+        // transfer on a transfer-fee mint is charged it all the same
+        let sent: u64 = paid.iter().map(|t| t.amount).sum();
+        let fees: u64 = paid
+            .iter()
+            .map(|t| match t.fee {
+                0 => mint_info.max_transfer_fee(t.amount),
+                fee => fee,
+            })
+            .sum();
+
+        Ok((decimals, sent, fees))
     }
 
     /// Get token balance for an account
     pub async fn get_token_balance(&self, token_account: &Pubkey) -> X402Result<u64> {
-        let balance = self
-            .rpc_client
+        self.rpc_client
             .get_token_account_balance(token_account)
-            .map_err(|e| X402Error::Network(format!("Failed to get token balance: {}", e)))?;
+            .await
+            .map_err(|e| X402Error::Network(format!("Failed to get token balance: {}", e)))
+    }
 
-        balance
-            .amount
-            .parse::<u64>()
-            .map_err(|e| X402Error::Blockchain(format!("Failed to parse balance: {}", e)))
-    }
-
-    /// Check if an account exists
-    async fn account_exists(&self, account: &Pubkey) -> X402Result<bool> {
-        match self.rpc_client.get_account(account) {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                // Account not found is not an error
-                if e.to_string().contains("AccountNotFound") {
-                    Ok(false)
-                } else {
-                    Err(X402Error::Network(format!(
-                        "Failed to check account existence: {}",
-                        e
-                    )))
-                }
-            }
-        }
+    /// Check whether a payment recipient's wallet and token account are rent-exempt
+    ///
+    /// Accounts below the rent-exempt minimum risk being reclaimed, which makes
+    /// payments to them fail. Servers should run this at startup.
+    ///
+    /// # Arguments
+    /// * `payment_address` - Recipient wallet address
+    /// * `token_mint` - Token mint address payments are made in
+    pub async fn check_rent_exemption(
+        &self,
+        payment_address: &str,
+        token_mint: &str,
+    ) -> X402Result<RentExemptionReport> {
+        let recipient = Pubkey::from_str(payment_address)
+            .map_err(|e| X402Error::Configuration(format!("Invalid payment address: {}", e)))?;
+        let token_mint = Pubkey::from_str(token_mint)
+            .map_err(|e| X402Error::Configuration(format!("Invalid token mint address: {}", e)))?;
+        let token_account = get_associated_token_address(&recipient, &token_mint);
+
+        let wallet_balance =
+            self.rpc_client.get_balance(&recipient).await.map_err(|e| {
+                X402Error::Network(format!("Failed to get recipient balance: {}", e))
+            })?;
+        let wallet_minimum = self.minimum_balance_for_rent_exemption(0).await?;
+
+        let token_account_balance = self
+            .rpc_client
+            .get_account(&token_account)
+            .await
+            .map_err(|e| X402Error::Network(format!("Failed to check account existence: {}", e)))?
+            .map(|account| account.lamports);
+        let token_account_minimum = self
+            .minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            .await?;
+
+        Ok(RentExemptionReport {
+            wallet: recipient,
+            wallet_balance,
+            wallet_minimum,
+            token_account,
+            token_account_balance,
+            token_account_minimum,
+        })
     }
 
-    /// Check if the sender has sufficient balance
-    async fn check_balance(&self, token_account: &Pubkey, required_amount: u64) -> X402Result<()> {
-        let balance = self.get_token_balance(token_account).await?;
+    /// Run the rent-exemption check, warning or erroring when it fails
+    ///
+    /// With `strict` set, a recipient that isn't rent-exempt produces an
+    /// `X402Error::Configuration`; otherwise the report is returned with each
+    /// problem in its [`warnings`](RentExemptionReport::warnings), which are
+    /// also logged with the `tracing` feature.
+    pub async fn ensure_rent_exemption(
+        &self,
+        payment_address: &str,
+        token_mint: &str,
+        strict: bool,
+    ) -> X402Result<RentExemptionReport> {
+        let report = self
+            .check_rent_exemption(payment_address, token_mint)
+            .await?;
+        let warnings = report.warnings();
 
-        if balance < required_amount {
-            return Err(X402Error::InsufficientFunds(format!(
-                "Insufficient balance: {} required, {} available",
-                required_amount, balance
-            )));
+        if strict && !warnings.is_empty() {
+            return Err(X402Error::Configuration(warnings.join("; ")));
+        }
+        #[cfg(feature = "tracing")]
+        for warning in &warnings {
+            tracing::warn!(recipient = payment_address, "{}", warning);
         }
 
-        Ok(())
+        Ok(report)
     }
 
-    /// Parse amount string to lamports (assumes 6 decimals for USDC)
-    fn parse_amount(amount_str: &str) -> X402Result<u64> {
-        let amount: f64 = amount_str.parse().map_err(|e| {
-            X402Error::InvalidPaymentRequest(format!("Invalid amount format: {}", e))
+    /// Amount `request` asks for, in the smallest unit of its asset
+    ///
+    /// Native SOL amounts are in lamports. Token amounts use the request's
+    /// `decimals` when given and the mint's on-chain decimals otherwise.
+    pub async fn amount_in_base_units(&self, request: &PaymentRequest) -> X402Result<u64> {
+        let decimals = self.asset_decimals(request).await?;
+        Self::parse_amount(&request.max_amount_required, decimals)
+    }
+
+    /// Decimals of the asset `request` is paid in
+    ///
+    /// 9 for native SOL; for tokens, the request's `decimals` when given and
+    /// the mint's on-chain decimals otherwise.
+    pub async fn asset_decimals(&self, request: &PaymentRequest) -> X402Result<u8> {
+        if request.is_native_sol() {
+            return Ok(SOL_DECIMALS);
+        }
+        if let Some(decimals) = request.decimals {
+            return Ok(decimals);
+        }
+        let token_mint = Pubkey::from_str(&request.asset_address).map_err(|e| {
+            X402Error::InvalidPaymentRequest(format!("Invalid token mint address: {}", e))
         })?;
+        self.mint_decimals(&token_mint).await
+    }
+
+    /// Read the decimals of an SPL token mint from its on-chain account
+    pub async fn mint_decimals(&self, mint: &Pubkey) -> X402Result<u8> {
+        self.read_mint(mint).await.map(|mint| mint.decimals)
+    }
+
+    /// Token program that owns `mint`: SPL Token or Token-2022
+    pub async fn mint_token_program(&self, mint: &Pubkey) -> X402Result<Pubkey> {
+        self.read_mint(mint).await.map(|mint| mint.token_program)
+    }
+
+    /// Read what payments need to know about `mint` from its on-chain account
+    async fn read_mint(&self, mint: &Pubkey) -> X402Result<MintInfo> {
+        let account = self
+            .rpc_client
+            .get_account(mint)
+            .await
+            .map_err(|e| X402Error::Network(format!("Failed to get token mint: {}", e)))?
+            .ok_or_else(|| {
+                X402Error::InvalidPaymentRequest(format!("Token mint {} not found", mint))
+            })?;
+        let not_a_mint = |reason: String| {
+            X402Error::InvalidPaymentRequest(format!(
+                "Account {} is not a token mint: {}",
+                mint, reason
+            ))
+        };
+        if !is_token_program(&account.owner) {
+            return Err(not_a_mint(format!("owned by {}", account.owner)));
+        }
+
+        // Token-2022 mints are SPL Token mints followed by their extensions
+        let state = StateWithExtensions::<Token2022Mint>::unpack(&account.data)
+            .map_err(|e| not_a_mint(e.to_string()))?;
+        let account_len = if account.owner == spl_token::id() {
+            spl_token::state::Account::LEN
+        } else {
+            // The associated token account program sizes new accounts for
+            // the extensions the mint requires, plus an immutable owner
+            let mint_extensions = state
+                .get_extension_types()
+                .map_err(|e| not_a_mint(e.to_string()))?;
+            let mut extensions =
+                ExtensionType::get_required_init_account_extensions(&mint_extensions);
+            extensions.push(ExtensionType::ImmutableOwner);
+            ExtensionType::try_calculate_account_len::<Token2022Account>(&extensions)
+                .map_err(|e| not_a_mint(e.to_string()))?
+        };
+
+        Ok(MintInfo {
+            token_program: account.owner,
+            decimals: state.base.decimals,
+            account_len,
+            transfer_fee: state.get_extension::<TransferFeeConfig>().ok().copied(),
+        })
+    }
 
-        // Convert to smallest unit (6 decimals for USDC)
-        let lamports = (amount * 1_000_000.0) as u64;
-        Ok(lamports)
+    async fn minimum_balance_for_rent_exemption(&self, data_len: usize) -> X402Result<u64> {
+        self.rpc_client
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await
+            .map_err(|e| X402Error::Network(format!("Failed to get rent-exempt minimum: {}", e)))
+    }
+
+    /// Parse a decimal amount string into the smallest unit of a token with `decimals`
+    ///
+    /// Works on the digits directly, so every amount the token can represent
+    /// converts exactly. Amounts with more significant fractional digits than
+    /// `decimals` are rejected rather than truncated; trailing zeros are fine.
+    pub fn parse_amount(amount_str: &str, decimals: u8) -> X402Result<u64> {
+        let invalid = |reason: String| {
+            X402Error::InvalidPaymentRequest(format!(
+                "Invalid amount format {:?}: {}",
+                amount_str, reason
+            ))
+        };
+
+        let (whole, fraction) = match amount_str.split_once('.') {
+            Some((whole, fraction)) if !fraction.is_empty() => (whole, fraction),
+            Some(_) => {
+                return Err(invalid(
+                    "missing digits after the decimal point".to_string(),
+                ))
+            }
+            None => (amount_str, ""),
+        };
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || !digits(whole) || !digits(fraction) {
+            return Err(invalid(
+                "expected digits with an optional fraction".to_string(),
+            ));
+        }
+
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > usize::from(decimals) {
+            return Err(invalid(format!(
+                "token supports at most {} decimal places",
+                decimals
+            )));
+        }
+
+        let scale = 10u64
+            .checked_pow(u32::from(decimals))
+            .ok_or_else(|| invalid(format!("unsupported token decimals {}", decimals)))?;
+        let fraction_units = if fraction.is_empty() {
+            0
+        } else {
+            // Fewer digits than `decimals`, so neither step can overflow
+            fraction
+                .parse::<u64>()
+                .map_err(|e| invalid(e.to_string()))?
+                * 10u64.pow(u32::from(decimals) - fraction.len() as u32)
+        };
+
+        whole
+            .parse::<u64>()
+            .ok()
+            .and_then(|whole| whole.checked_mul(scale))
+            .and_then(|units| units.checked_add(fraction_units))
+            .ok_or_else(|| invalid("amount is too large".to_string()))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        escrow::EscrowProgram,
+        rpc::mock::{MockRpcBackend, TransactionFixture},
+    };
 
     #[test]
     fn test_parse_amount() {
-        assert_eq!(SolanaPaymentProcessor::parse_amount("0.10").unwrap(), 100_000);
-        assert_eq!(SolanaPaymentProcessor::parse_amount("1.0").unwrap(), 1_000_000);
         assert_eq!(
-            SolanaPaymentProcessor::parse_amount("0.000001").unwrap(),
+            SolanaPaymentProcessor::parse_amount("0.10", 6).unwrap(),
+            100_000
+        );
+        assert_eq!(
+            SolanaPaymentProcessor::parse_amount("1.0", 6).unwrap(),
+            1_000_000
+        );
+        assert_eq!(
+            SolanaPaymentProcessor::parse_amount("0.000001", 6).unwrap(),
             1
         );
+        assert_eq!(
+            SolanaPaymentProcessor::parse_amount("0.5", 9).unwrap(),
+            500_000_000
+        );
+        assert_eq!(SolanaPaymentProcessor::parse_amount("3", 0).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_parse_amount_is_exact() {
+        let parse = |amount| SolanaPaymentProcessor::parse_amount(amount, 6);
+
+        // None of these are exactly representable as f64
+        assert_eq!(parse("0.1").unwrap(), 100_000);
+        assert_eq!(parse("0.30").unwrap(), 300_000);
+        assert_eq!(parse("123.456789").unwrap(), 123_456_789);
+        assert_eq!(parse("0.1000000").unwrap(), 100_000);
+
+        // Precision the token can't hold is an error, not a rounded amount
+        assert!(parse("0.0000001").is_err());
+        assert!(parse("1.2345678").is_err());
+
+        for malformed in ["", ".5", "1.", "-1", "+1", "1e3", "0.1.2", " 1", "NaN"] {
+            assert!(
+                parse(malformed).is_err(),
+                "{:?} should be rejected",
+                malformed
+            );
+        }
+        assert!(parse("18446744073709.551616").is_err());
+        assert!(SolanaPaymentProcessor::parse_amount("1", 20).is_err());
     }
 
     #[test]
@@ -269,4 +896,643 @@ mod tests {
             "https://api.devnet.solana.com"
         );
     }
+
+    #[test]
+    fn test_parse_commitment() {
+        assert_eq!(
+            SolanaPaymentProcessor::parse_commitment("finalized").unwrap(),
+            CommitmentConfig::finalized()
+        );
+        assert_eq!(
+            SolanaPaymentProcessor::parse_commitment("processed").unwrap(),
+            CommitmentConfig::processed()
+        );
+
+        let error = SolanaPaymentProcessor::parse_commitment("final").unwrap_err();
+        assert!(matches!(error, X402Error::Configuration(_)));
+        assert!(error.to_string().contains("'final'"));
+    }
+
+    #[tokio::test]
+    async fn test_rent_exemption_warns_below_minimum() {
+        let recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let token_account = get_associated_token_address(&recipient, &mint);
+        let backend = MockRpcBackend::new()
+            .with_balance(recipient, 1_000)
+            .with_token_account(token_account, mint, recipient, 0);
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        let report = processor
+            .ensure_rent_exemption(&recipient.to_string(), &mint.to_string(), false)
+            .await
+            .unwrap();
+        assert!(!report.wallet_is_rent_exempt());
+        assert!(report.token_account_is_rent_exempt());
+        assert_eq!(report.warnings().len(), 1);
+
+        let err = processor
+            .ensure_rent_exemption(&recipient.to_string(), &mint.to_string(), true)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "CONFIGURATION_ERROR");
+    }
+
+    #[tokio::test]
+    async fn test_rent_exemption_ok() {
+        let recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let token_account = get_associated_token_address(&recipient, &mint);
+        let backend = MockRpcBackend::new()
+            .with_balance(recipient, 1_000_000_000)
+            .with_token_account(token_account, mint, recipient, 0);
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        let report = processor
+            .ensure_rent_exemption(&recipient.to_string(), &mint.to_string(), true)
+            .await
+            .unwrap();
+        assert!(report.is_rent_exempt());
+        assert!(report.warnings().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rent_exemption_missing_token_account() {
+        let recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let backend = MockRpcBackend::new().with_balance(recipient, 1_000_000_000);
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        let report = processor
+            .check_rent_exemption(&recipient.to_string(), &mint.to_string())
+            .await
+            .unwrap();
+        assert_eq!(report.token_account_balance, None);
+        assert!(!report.is_rent_exempt());
+    }
+
+    fn test_authorization(
+        signature: &Signature,
+        recipient: &Pubkey,
+        mint: &Pubkey,
+    ) -> PaymentAuthorization {
+        PaymentAuthorization::new(
+            "payment123".to_string(),
+            "0.10".to_string(),
+            recipient.to_string(),
+            mint.to_string(),
+            "solana-devnet".to_string(),
+            signature.to_string(),
+            Pubkey::new_unique().to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_accepts_any_candidate() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let failed = Signature::new_unique();
+        let succeeded = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(
+                TransactionFixture::new(failed)
+                    .transfer(mint, recipient, 100_000)
+                    .failed(),
+            )
+            .with_fixture(TransactionFixture::new(succeeded).transfer(mint, recipient, 100_000));
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        let authorization = test_authorization(&failed, &recipient, &mint)
+            .with_signatures(vec![succeeded.to_string()]);
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+        assert_eq!(
+            processor
+                .verify_payment_signature(&authorization, "0.10", &VerificationOptions::default())
+                .await
+                .unwrap(),
+            succeeded.to_string()
+        );
+
+        let err = processor
+            .verify_payment(&test_authorization(&failed, &recipient, &mint), "0.10")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_searches_status_history() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_fixture(TransactionFixture::new(signature).transfer(mint, recipient, 100_000))
+                .with_pruned_status(signature),
+        );
+
+        // The recent status cache no longer knows the signature
+        assert!(backend
+            .get_signature_status(&signature, false)
+            .await
+            .unwrap()
+            .is_none());
+
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let authorization = test_authorization(&signature, &recipient, &mint);
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+        assert_eq!(backend.call_count("get_signature_status"), 2);
+
+        // Signatures unknown even to the history search are rejected up front
+        let unknown = test_authorization(&Signature::new_unique(), &recipient, &mint);
+        let err = processor
+            .verify_payment(&unknown, "0.10")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        assert_eq!(backend.call_count("get_transaction"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_reads_cpi_transfer() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let fixture = TransactionFixture::new(signature).cpi_transfer(
+            Pubkey::new_unique(),
+            mint,
+            recipient,
+            100_000,
+        );
+
+        // The only top-level instruction is the router program; a raw matcher
+        // looking for a token transfer there finds nothing
+        let json = fixture.to_json();
+        let top_level = &json["transaction"]["message"]["instructions"];
+        assert_eq!(top_level.as_array().unwrap().len(), 1);
+        assert!(top_level[0].get("parsed").is_none());
+
+        let processor = SolanaPaymentProcessor::with_backend(
+            Arc::new(MockRpcBackend::new().with_fixture(fixture)),
+            None,
+        );
+        let authorization = test_authorization(&signature, &recipient, &mint);
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+
+        // Same transaction does not satisfy a different recipient
+        let other = test_authorization(&signature, &Pubkey::new_unique(), &mint);
+        let err = processor.verify_payment(&other, "0.10").await.unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_rejects_short_transfer() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(signature).transfer(mint, recipient, 50_000));
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        let err = processor
+            .verify_payment(&test_authorization(&signature, &recipient, &mint), "0.10")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+    }
+
+    /// USDC `transferChecked` in the shape mainnet RPC nodes return it,
+    /// including compute budget instructions and the payer's own balances
+    const MAINNET_USDC_TRANSFER: &str =
+        include_str!("../tests/fixtures/mainnet_usdc_transfer.json");
+
+    #[tokio::test]
+    async fn test_verify_payment_checks_balance_changes() {
+        let json: serde_json::Value = serde_json::from_str(MAINNET_USDC_TRANSFER).unwrap();
+        let signature =
+            Signature::from_str(json["transaction"]["signatures"][0].as_str().unwrap()).unwrap();
+        let recipient = Pubkey::from_str("CKec2g1C914oUtnopW29DMQuLnEE3iLUZbAcD7iC8a9e").unwrap();
+        let usdc = Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap();
+        let processor = |json: serde_json::Value| {
+            SolanaPaymentProcessor::with_backend(
+                Arc::new(
+                    MockRpcBackend::new()
+                        .with_mint(usdc, 6)
+                        .with_transaction_json(signature, json),
+                ),
+                None,
+            )
+        };
+        let verify = |json: serde_json::Value, authorization: PaymentAuthorization, amount| async move {
+            processor(json).verify_payment(&authorization, amount).await
+        };
+        let authorization = test_authorization(&signature, &recipient, &usdc);
+
+        assert!(verify(json.clone(), authorization.clone(), "0.10")
+            .await
+            .unwrap());
+
+        let err = verify(json.clone(), authorization.clone(), "0.25")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("less than required"));
+
+        let wrong_recipient = test_authorization(&signature, &Pubkey::new_unique(), &usdc);
+        let err = verify(json.clone(), wrong_recipient, "0.10")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("to another recipient"));
+
+        let wrong_mint = test_authorization(&signature, &recipient, &Pubkey::new_unique());
+        let err = verify(json.clone(), wrong_mint, "0.10").await.unwrap_err();
+        assert!(err.to_string().contains(&format!("in {} instead of", usdc)));
+
+        // An instruction claiming the transfer is not enough if the
+        // recipient's balance did not move
+        let mut spoofed = json.clone();
+        spoofed["meta"]["postTokenBalances"][1] = json["meta"]["preTokenBalances"][1].clone();
+        let err = verify(spoofed, authorization, "0.10").await.unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+        assert!(err.to_string().contains("balance by 0"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_counts_amount_after_transfer_fee_if_required() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let short = Signature::new_unique();
+        let covered = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(
+                TransactionFixture::new(short).transfer_with_fee(mint, recipient, 100_000, 1_000),
+            )
+            .with_fixture(
+                TransactionFixture::new(covered).transfer_with_fee(mint, recipient, 101_000, 1_000),
+            );
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+        let payer_pays_fees =
+            VerificationOptions::default().with_amount_after_transfer_fees_required();
+
+        // The sender sent the full price, which is enough by default
+        let authorization = test_authorization(&short, &recipient, &mint);
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+
+        // But the recipient got less
+        let err = processor
+            .verify_payment_with_options(&authorization, "0.10", &payer_pays_fees)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+        assert!(err.to_string().contains("delivered 99000"));
+        assert!(err.to_string().contains("after 1000 in transfer fees"));
+
+        // Sending the price plus the fee covers it
+        assert!(processor
+            .verify_payment_with_options(
+                &test_authorization(&covered, &recipient, &mint),
+                "0.10",
+                &payer_pays_fees
+            )
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_allows_fee_withheld_by_mint() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let paid = Signature::new_unique();
+        let overcharged = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            // 1% fee, at most 0.05
+            .with_transfer_fee_mint(mint, 6, 100, 50_000)
+            .with_fixture(
+                TransactionFixture::new(paid)
+                    .transfer_withholding_fee(mint, recipient, 100_000, 1_000),
+            )
+            .with_fixture(
+                TransactionFixture::new(overcharged)
+                    .transfer_withholding_fee(mint, recipient, 100_000, 2_000),
+            );
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        // The instruction doesn't report the fee, but the mint's config
+        // accounts for the recipient's balance growing by only 99000
+        let authorization = test_authorization(&paid, &recipient, &mint);
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+
+        // More went missing than the mint withholds
+        let err = processor
+            .verify_payment(&test_authorization(&overcharged, &recipient, &mint), "0.10")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("balance by 98000"));
+
+        // A recipient who must get the full price after fees wasn't paid it
+        let err = processor
+            .verify_payment_with_options(
+                &authorization,
+                "0.10",
+                &VerificationOptions::default().with_amount_after_transfer_fees_required(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("after 1000 in transfer fees"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_rejects_recipient_as_fee_payer() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new().with_fixture(
+            TransactionFixture::new(signature)
+                .fee_payer(recipient)
+                .transfer(mint, recipient, 100_000),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        let err = processor
+            .verify_payment(&test_authorization(&signature, &recipient, &mint), "0.10")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+        assert!(err.to_string().contains("fee payer is the recipient"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_requires_payer_signature() {
+        let (recipient, mint, payer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new().with_fixture(
+            TransactionFixture::new(signature)
+                .fee_payer(payer)
+                .transfer(mint, recipient, 100_000),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+        let options = VerificationOptions::default().with_payer_signature_required();
+
+        let mut authorization = test_authorization(&signature, &recipient, &mint);
+        authorization.public_key = payer.to_string();
+        assert!(processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap());
+
+        // Claiming someone else's transfer
+        authorization.public_key = Pubkey::new_unique().to_string();
+        let err = processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_AUTHORIZATION");
+        assert!(err.to_string().contains("was not signed by the payer"));
+
+        // Malformed authorizations fail before any lookup
+        authorization.public_key = "not-a-key".to_string();
+        let err = processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_AUTHORIZATION");
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_amount_tolerance() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let within = Signature::new_unique();
+        let outside = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(within).transfer(mint, recipient, 99_600))
+            .with_fixture(TransactionFixture::new(outside).transfer(mint, recipient, 99_000));
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+        let options = VerificationOptions::default().with_amount_tolerance_bps(50);
+
+        let mut authorization = test_authorization(&within, &recipient, &mint);
+        authorization.actual_amount = "0.0996".to_string();
+        assert!(processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap());
+
+        // Exact matching still applies without a tolerance
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .is_err());
+
+        let mut authorization = test_authorization(&outside, &recipient, &mint);
+        authorization.actual_amount = "0.099".to_string();
+        let err = processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_min_confirmation_age() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let options =
+            VerificationOptions::default().with_min_confirmation_age(Duration::from_secs(20));
+
+        // Aged by block time
+        let fresh = Signature::new_unique();
+        let aged = Signature::new_unique();
+        let now = chrono::Utc::now().timestamp();
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_slot(100)
+                .with_fixture(
+                    TransactionFixture::new(fresh)
+                        .confirmed_at(95, Some(now - 2))
+                        .transfer(mint, recipient, 100_000),
+                )
+                .with_fixture(
+                    TransactionFixture::new(aged)
+                        .confirmed_at(10, Some(now - 60))
+                        .transfer(mint, recipient, 100_000),
+                ),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        let err = processor
+            .verify_payment_with_options(
+                &test_authorization(&fresh, &recipient, &mint),
+                "0.10",
+                &options,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+        assert!(err.to_string().contains("retry in"));
+        assert!(processor
+            .verify_payment_with_options(
+                &test_authorization(&aged, &recipient, &mint),
+                "0.10",
+                &options
+            )
+            .await
+            .unwrap());
+
+        // Aged by slot distance when the block time is unknown
+        let signature = Signature::new_unique();
+        let backend = Arc::new(
+            MockRpcBackend::new().with_slot(100).with_fixture(
+                TransactionFixture::new(signature)
+                    .confirmed_at(90, None)
+                    .transfer(mint, recipient, 100_000),
+            ),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let authorization = test_authorization(&signature, &recipient, &mint);
+
+        assert!(processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .is_err());
+        backend.set_slot(200);
+        assert!(processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_max_confirmation_age_with_clock_skew() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let now = chrono::Utc::now().timestamp();
+        let backend = Arc::new(
+            MockRpcBackend::new().with_slot(300).with_fixture(
+                TransactionFixture::new(signature)
+                    .confirmed_at(100, Some(now - 70))
+                    .transfer(mint, recipient, 100_000),
+            ),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend, None);
+        let authorization = test_authorization(&signature, &recipient, &mint);
+        let options =
+            VerificationOptions::default().with_max_confirmation_age(Duration::from_secs(60));
+
+        let err = processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("more than the allowed"));
+
+        // The node's clock may run ahead of the cluster's block times
+        let options = options.with_clock_skew(Duration::from_secs(15));
+        assert!(processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_scales_by_transfer_decimals() {
+        let recipient = Pubkey::new_unique();
+        let (nine_mint, zero_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (nine, zero) = (Signature::new_unique(), Signature::new_unique());
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(nine).transfer_with_decimals(
+                nine_mint,
+                recipient,
+                100_000_000,
+                9,
+            ))
+            .with_fixture(
+                TransactionFixture::new(zero).transfer_with_decimals(zero_mint, recipient, 2, 0),
+            );
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        let authorization = test_authorization(&nine, &recipient, &nine_mint);
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+
+        let mut authorization = test_authorization(&zero, &recipient, &zero_mint);
+        authorization.actual_amount = "2".to_string();
+        assert!(processor.verify_payment(&authorization, "2").await.unwrap());
+        assert!(processor.verify_payment(&authorization, "3").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_payment_flags_wrong_decimals() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (wrong, short) = (Signature::new_unique(), Signature::new_unique());
+        let backend = MockRpcBackend::new()
+            .with_mint(mint, 6)
+            // The right amount of base units, checked against 9 decimals
+            .with_fixture(
+                TransactionFixture::new(wrong).transfer_with_decimals(mint, recipient, 100_000, 9),
+            )
+            .with_fixture(TransactionFixture::new(short).transfer(mint, recipient, 90_000));
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+
+        let err = processor
+            .verify_payment(&test_authorization(&wrong, &recipient, &mint), "0.10")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+        assert!(err
+            .to_string()
+            .contains("with 9 decimals, but the mint has 6"));
+
+        let err = processor
+            .verify_payment(&test_authorization(&short, &recipient, &mint), "0.10")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("less than required"));
+        assert!(!err.to_string().contains("decimals"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_escrow_deposit() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let escrow = EscrowProgram::new(Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend =
+            MockRpcBackend::new().with_fixture(TransactionFixture::new(signature).transfer(
+                mint,
+                escrow.escrow_address(&recipient),
+                100_000,
+            ));
+        let processor = SolanaPaymentProcessor::with_backend(Arc::new(backend), None);
+        let authorization = test_authorization(&signature, &recipient, &mint);
+
+        let options = VerificationOptions::default().with_escrow(escrow);
+        assert!(processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap());
+
+        // Without the escrow program configured the deposit pays someone else
+        let err = processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("to another recipient"));
+
+        // Nor does another program's escrow count
+        let other =
+            VerificationOptions::default().with_escrow(EscrowProgram::new(Pubkey::new_unique()));
+        assert!(processor
+            .verify_payment_with_options(&authorization, "0.10", &other)
+            .await
+            .is_err());
+    }
 }
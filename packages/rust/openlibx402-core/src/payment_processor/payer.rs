@@ -0,0 +1,3186 @@
+//! Making payments: building, signing and broadcasting transfers
+//!
+//! Compiled with the `payments` feature, on by default. Servers that only
+//! verify payments can leave it off; see the crate docs.
+
+use serde::{Deserialize, Serialize};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_request::RpcError,
+};
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, Message, VersionedMessage},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::instruction as token_instruction;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    errors::{InsufficientFundsDetails, X402Error, X402Result},
+    escrow::EscrowProgram,
+    events::{self, PaymentEvent},
+    facilitator, metrics,
+    models::{PaymentAuthorization, PaymentRequest, NATIVE_SOL, SOL_DECIMALS},
+    signer,
+    store::PaymentSignatureStore,
+    transfer::{fee_payer, MEMO_PROGRAM_ID},
+    verification::SLOT_DURATION,
+};
+
+use super::{is_token_program, SolanaPaymentProcessor};
+
+/// How long a blockhash stays usable for new transactions (~150 slots)
+pub const BLOCKHASH_VALIDITY: Duration = Duration::from_secs(60);
+
+/// Times a payment is re-signed and resent after its blockhash expires
+pub const DEFAULT_MAX_BROADCAST_RETRIES: u32 = 3;
+
+/// Base fee charged for each signature on a transaction
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Recently fetched blockhash reused across payments
+struct BlockhashCache {
+    max_age: Duration,
+    entry: Mutex<Option<(Hash, Instant)>>,
+}
+
+impl BlockhashCache {
+    fn get(&self) -> Option<Hash> {
+        let entry = self.entry.lock().unwrap_or_else(|e| e.into_inner());
+        entry
+            .filter(|(_, fetched_at)| fetched_at.elapsed() < self.max_age)
+            .map(|(hash, _)| hash)
+    }
+
+    fn set(&self, hash: Hash) {
+        *self.entry.lock().unwrap_or_else(|e| e.into_inner()) = Some((hash, Instant::now()));
+    }
+}
+
+/// Transaction format used for payments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransactionVersion {
+    /// Legacy transaction listing every account in the message
+    #[default]
+    Legacy,
+
+    /// Version 0 transaction, which can load accounts from address lookup tables
+    V0,
+}
+
+/// Compute budget and priority fee attached to payment transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityFeeConfig {
+    /// Price paid per compute unit, in micro-lamports
+    pub micro_lamports_per_cu: u64,
+
+    /// Most compute units the transaction may consume
+    pub compute_unit_limit: u32,
+}
+
+impl PriorityFeeConfig {
+    /// Most lamports the priority fee can cost, if every compute unit is used
+    pub fn max_fee_lamports(&self) -> u64 {
+        let micro_lamports =
+            u128::from(self.micro_lamports_per_cu) * u128::from(self.compute_unit_limit);
+        u64::try_from(micro_lamports.div_ceil(1_000_000)).unwrap_or(u64::MAX)
+    }
+}
+
+/// How far and how long a payment's broadcast waits for confirmation
+///
+/// The default waits for the processor's own commitment with no time limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConfirmationConfig {
+    /// Commitment the payment must reach, e.g. `finalized` for high-value
+    /// payments; the processor's commitment if `None`
+    pub commitment: Option<CommitmentConfig>,
+
+    /// Longest to wait for the payment to be sent and reach `commitment`
+    ///
+    /// A payment still unconfirmed when it runs out fails with
+    /// [`X402Error::Timeout`] carrying the pending signature; the payment
+    /// may still land.
+    pub timeout: Option<Duration>,
+}
+
+/// Memo attached to payments
+#[derive(Debug, Clone)]
+enum PaymentMemo {
+    PaymentId,
+    Text(String),
+}
+
+/// How the priority fee of a payment is chosen
+#[derive(Debug, Clone, Copy)]
+enum PriorityFee {
+    Fixed(PriorityFeeConfig),
+    Estimated {
+        compute_unit_limit: u32,
+        max_micro_lamports_per_cu: u64,
+    },
+}
+
+/// All-in cost of paying a payment request
+///
+/// Returned by [`SolanaPaymentProcessor::estimate_cost`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Amount paid, in the smallest unit of `asset`
+    pub token_amount: u64,
+
+    /// Mint of the token paid, or [`NATIVE_SOL`]
+    pub asset: String,
+
+    /// Transaction fee in lamports, including any priority fee
+    pub fee_lamports: u64,
+
+    /// Rent in lamports for the recipient token account the payment
+    /// creates; 0 if it already exists
+    pub rent_lamports: u64,
+}
+
+impl CostEstimate {
+    /// Lamports the payer spends: the fee, the rent and a native SOL amount
+    pub fn total_lamports(&self) -> u64 {
+        let amount = if self.asset == NATIVE_SOL {
+            self.token_amount
+        } else {
+            0
+        };
+        self.fee_lamports
+            .saturating_add(self.rent_lamports)
+            .saturating_add(amount)
+    }
+}
+
+/// Payment transaction awaiting signatures, e.g. from a multisig
+///
+/// Created by [`SolanaPaymentProcessor::create_partial_payment`]. Serializes
+/// with serde so it can be handed to other signers and back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialPayment {
+    /// Payment request being paid
+    pub request: PaymentRequest,
+
+    /// Owner of the token account the payment is made from
+    pub owner: Pubkey,
+
+    /// Unsigned or partially signed transaction
+    pub transaction: Transaction,
+}
+
+impl PartialPayment {
+    /// Required signers that haven't signed yet
+    pub fn missing_signers(&self) -> Vec<Pubkey> {
+        let required = usize::from(self.transaction.message.header.num_required_signatures);
+        self.transaction.message.account_keys[..required]
+            .iter()
+            .zip(&self.transaction.signatures)
+            .filter(|(_, signature)| **signature == Signature::default())
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Whether every required signature is present
+    pub fn is_fully_signed(&self) -> bool {
+        self.missing_signers().is_empty()
+    }
+
+    /// Add `signer`'s signature
+    pub fn sign(&mut self, signer: &Keypair) -> X402Result<()> {
+        let blockhash = self.transaction.message.recent_blockhash;
+        self.transaction.try_partial_sign(&[signer], blockhash)?;
+        Ok(())
+    }
+}
+
+/// How a processor makes payments, set through its payment builders
+pub(super) struct PayerSettings {
+    blockhash_cache: Option<BlockhashCache>,
+    max_broadcast_retries: u32,
+    simulate_before_send: Option<bool>,
+    transaction_version: TransactionVersion,
+    memo: Option<PaymentMemo>,
+    lookup_tables: Vec<AddressLookupTableAccount>,
+    priority_fee: Option<PriorityFee>,
+    facilitator_url: Option<String>,
+    http: reqwest::Client,
+    dry_run: bool,
+    events: Option<mpsc::Sender<PaymentEvent>>,
+    payment_signatures: Option<Arc<dyn PaymentSignatureStore>>,
+}
+
+impl Default for PayerSettings {
+    fn default() -> Self {
+        Self {
+            blockhash_cache: None,
+            max_broadcast_retries: DEFAULT_MAX_BROADCAST_RETRIES,
+            simulate_before_send: None,
+            transaction_version: TransactionVersion::Legacy,
+            memo: None,
+            lookup_tables: Vec::new(),
+            priority_fee: None,
+            facilitator_url: None,
+            http: reqwest::Client::new(),
+            dry_run: false,
+            events: None,
+            payment_signatures: None,
+        }
+    }
+}
+
+impl SolanaPaymentProcessor {
+    /// Reuse a fetched blockhash across payments made within `max_age`
+    ///
+    /// Saves one RPC round trip per payment for agents paying in quick
+    /// succession. `max_age` is capped at [`BLOCKHASH_VALIDITY`]. If the
+    /// cluster rejects a cached blockhash as expired, the payment is rebuilt
+    /// with a fresh blockhash like any other expired broadcast (see
+    /// [`with_max_broadcast_retries`](Self::with_max_broadcast_retries)).
+    pub fn with_blockhash_cache(mut self, max_age: Duration) -> Self {
+        self.payer.blockhash_cache = Some(BlockhashCache {
+            max_age: max_age.min(BLOCKHASH_VALIDITY),
+            entry: Mutex::new(None),
+        });
+        self
+    }
+
+    /// Resend a payment up to `retries` times when its blockhash expires
+    ///
+    /// Each retry fetches a fresh blockhash and re-signs the payment.
+    /// Defaults to [`DEFAULT_MAX_BROADCAST_RETRIES`]; `0` gives up on the
+    /// first expiry.
+    pub fn with_max_broadcast_retries(mut self, retries: u32) -> Self {
+        self.payer.max_broadcast_retries = retries;
+        self
+    }
+
+    /// Choose whether payments are simulated before they are sent
+    ///
+    /// A payment that fails simulation is not broadcast, and `create_payment`
+    /// returns the reason read from the simulation, e.g. a frozen token
+    /// account, as [`X402Error::TransactionBroadcast`]. Defaults to simulating payments to
+    /// `solana-mainnet`, where a failed transaction still costs fees, and
+    /// sending others directly.
+    pub fn with_simulate_before_send(mut self, simulate: bool) -> Self {
+        self.payer.simulate_before_send = Some(simulate);
+        self
+    }
+
+    /// Build and sign payments without sending them
+    ///
+    /// `create_payment` and `create_batch_payment` still read the chain to
+    /// assemble the transaction, but skip balance checks, simulation,
+    /// facilitators and the broadcast. The authorizations they return carry
+    /// the unsent transaction's signature, no `transaction_hash`, and
+    /// `settled: false`; servers reject them. Meant for exercising payment
+    /// flows in tests and estimating costs without a funded wallet.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.payer.dry_run = dry_run;
+        self
+    }
+
+    /// Attach each payment's `payment_id` as an SPL Memo
+    ///
+    /// Lets the recipient match on-chain transfers to the payment requests
+    /// it issued; servers can insist on it with
+    /// [`VerificationOptions::with_payment_id_memo_required`]. Payments carry
+    /// no memo by default.
+    pub fn with_payment_id_memo(mut self) -> Self {
+        self.payer.memo = Some(PaymentMemo::PaymentId);
+        self
+    }
+
+    /// Attach `memo` to every payment as an SPL Memo
+    pub fn with_memo(mut self, memo: &str) -> Self {
+        self.payer.memo = Some(PaymentMemo::Text(memo.to_string()));
+        self
+    }
+
+    /// Build payments in the given transaction format
+    ///
+    /// Payments are legacy transactions by default. Partial payments for
+    /// multisig signing stay legacy regardless.
+    pub fn with_transaction_version(mut self, version: TransactionVersion) -> Self {
+        self.payer.transaction_version = version;
+        self
+    }
+
+    /// Load payment accounts through these address lookup tables
+    ///
+    /// Only used by [`TransactionVersion::V0`] payments; accounts found in
+    /// a table are referenced by index instead of listed in the message.
+    pub fn with_address_lookup_tables(mut self, tables: Vec<AddressLookupTableAccount>) -> Self {
+        self.payer.lookup_tables = tables;
+        self
+    }
+
+    /// Attach a compute budget and priority fee to payment transactions
+    ///
+    /// Payments carry no priority fee by default, which is fine on an idle
+    /// cluster but lets them be dropped under mainnet congestion.
+    pub fn with_priority_fee(mut self, config: PriorityFeeConfig) -> Self {
+        self.payer.priority_fee = Some(PriorityFee::Fixed(config));
+        self
+    }
+
+    /// Price payments from the priority fees recently paid on the cluster
+    ///
+    /// Before each payment, the median of the fees recently paid by
+    /// transactions writing to the payment's accounts is looked up over RPC
+    /// and used as the price per compute unit, up to
+    /// `max_micro_lamports_per_cu`.
+    pub fn with_estimated_priority_fee(
+        mut self,
+        compute_unit_limit: u32,
+        max_micro_lamports_per_cu: u64,
+    ) -> Self {
+        self.payer.priority_fee = Some(PriorityFee::Estimated {
+            compute_unit_limit,
+            max_micro_lamports_per_cu,
+        });
+        self
+    }
+
+    /// Report the progress of each `create_payment` on `events`
+    ///
+    /// Events are sent without waiting and dropped when the channel is full,
+    /// so size it for the payments in flight. See [`PaymentEvent`] for the
+    /// stages reported.
+    pub fn with_payment_events(mut self, events: mpsc::Sender<PaymentEvent>) -> Self {
+        self.payer.events = Some(events);
+        self
+    }
+
+    /// Record the signature of each payment sent in `store`, by `payment_id`
+    ///
+    /// Makes `create_payment` idempotent: a retry for a `payment_id` already
+    /// sent, e.g. after a confirmation timeout, waits for the recorded
+    /// transaction and returns its authorization instead of paying again.
+    /// Only transactions the RPC accepted are recorded, and one still unseen
+    /// once its blockhash has expired is forgotten and paid afresh.
+    /// Signatures are kept until their payment request expires.
+    pub fn with_payment_signature_store(mut self, store: Arc<dyn PaymentSignatureStore>) -> Self {
+        self.payer.payment_signatures = Some(store);
+        self
+    }
+
+    /// Settle every payment through the facilitator at `url`
+    ///
+    /// Takes precedence over a `facilitator_url` advertised in the payment
+    /// request. Without either, payments are broadcast directly.
+    pub fn with_facilitator_url(mut self, url: &str) -> Self {
+        self.payer.facilitator_url = Some(url.to_string());
+        self
+    }
+
+    /// Estimate the all-in cost of paying `request` from `payer`, without paying
+    ///
+    /// Builds the transaction [`create_payment`](Self::create_payment) would
+    /// send and asks the RPC for its fee with `get_fee_for_message`. Rent is
+    /// included only when the recipient's token account has to be created.
+    /// Balances aren't checked, so the estimate can be compared against them
+    /// before committing to the payment.
+    pub async fn estimate_cost(
+        &self,
+        request: &PaymentRequest,
+        payer: &Pubkey,
+    ) -> X402Result<CostEstimate> {
+        // Resolve the mint once, for the amount and the instructions
+        let mut request = request.clone();
+        let (token_amount, asset) = if request.is_native_sol() {
+            let lamports = Self::parse_amount(&request.max_amount_required, SOL_DECIMALS)?;
+            (lamports, NATIVE_SOL.to_string())
+        } else {
+            let token_mint = Pubkey::from_str(&request.asset_address).map_err(|e| {
+                X402Error::InvalidPaymentRequest(format!("Invalid token mint address: {}", e))
+            })?;
+            let (token_program, decimals) = self.mint_details(&request, &token_mint).await?;
+            request.decimals = Some(decimals);
+            request.token_program = Some(token_program.to_string());
+            let amount = Self::parse_amount(&request.max_amount_required, decimals)?;
+            (amount, request.asset_address.clone())
+        };
+
+        let mut instructions = self
+            .payment_instructions(&request, payer, &[], payer, false)
+            .await?;
+        instructions.extend(self.memo_instruction(&request));
+        let budget = self.compute_budget(&instructions).await?;
+        let (rent_lamports, recent_blockhash) =
+            tokio::join!(self.account_rent(&instructions), self.latest_blockhash());
+        let rent_lamports = rent_lamports?;
+        let instructions = Self::with_compute_budget(budget, instructions);
+        let message = self.payment_message(&instructions, payer, recent_blockhash?)?;
+        let fee_lamports = self
+            .rpc_client
+            .get_fee_for_message(&message)
+            .await
+            .map_err(|e| X402Error::Network(format!("Failed to get transaction fee: {}", e)))?;
+
+        Ok(CostEstimate {
+            token_amount,
+            asset,
+            fee_lamports,
+            rent_lamports,
+        })
+    }
+
+    /// Create a payment from a payment request
+    ///
+    /// This creates, signs, and broadcasts a Solana SPL token transfer transaction.
+    /// When a facilitator is configured or advertised by the request, the signed
+    /// transaction is POSTed to it for settlement instead of being broadcast, and
+    /// the authorization carries the signature it reports.
+    ///
+    /// The sender's balance, the recipient's token account and the latest
+    /// blockhash are looked up concurrently. When several lookups fail, the
+    /// error reported is the one a sequential lookup would have hit first.
+    pub async fn create_payment(
+        &self,
+        request: &PaymentRequest,
+        payer: &dyn signer::TransactionSigner,
+    ) -> X402Result<PaymentAuthorization> {
+        self.create_payment_with_confirmation(request, payer, ConfirmationConfig::default())
+            .await
+    }
+
+    /// Create a payment, waiting for confirmation as `confirmation` says
+    ///
+    /// Same as [`create_payment`](Self::create_payment), but the broadcast
+    /// waits until the payment reaches `confirmation.commitment` and gives
+    /// up after `confirmation.timeout`. Facilitators settle payments on
+    /// their own terms, so `confirmation` doesn't apply to them.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "create_payment",
+            skip_all,
+            fields(
+                payment_id = %request.payment_id,
+                amount = %request.max_amount_required,
+                asset = %request.asset_address,
+                network = %request.network,
+                payer = %payer.pubkey(),
+            )
+        )
+    )]
+    pub async fn create_payment_with_confirmation(
+        &self,
+        request: &PaymentRequest,
+        payer: &dyn signer::TransactionSigner,
+        confirmation: ConfirmationConfig,
+    ) -> X402Result<PaymentAuthorization> {
+        let payment_id = &request.payment_id;
+        events::emit(self.payer.events.as_ref(), || PaymentEvent::Building {
+            payment_id: payment_id.clone(),
+        });
+        let result = self.send_payment(request, payer, &confirmation).await;
+        self.record_result(metrics::CREATE_PAYMENT, &result);
+        events::emit(self.payer.events.as_ref(), || match &result {
+            Ok(authorization) => PaymentEvent::Done {
+                payment_id: payment_id.clone(),
+                signature: authorization.signature.clone(),
+            },
+            Err(error) => PaymentEvent::Failed {
+                payment_id: payment_id.clone(),
+                error: error.clone(),
+            },
+        });
+        result
+    }
+
+    async fn send_payment(
+        &self,
+        request: &PaymentRequest,
+        payer: &dyn signer::TransactionSigner,
+        confirmation: &ConfirmationConfig,
+    ) -> X402Result<PaymentAuthorization> {
+        if let Some(authorization) = self.recover_payment(request, payer, confirmation).await? {
+            return Ok(authorization);
+        }
+
+        let facilitator_url = self
+            .payer
+            .facilitator_url
+            .as_deref()
+            .or(request.facilitator_url.as_deref());
+        // The facilitator adds a hop before the transaction lands, so it skips
+        // the blockhash cache and gets the full validity window
+        let cached_blockhash = match facilitator_url {
+            Some(_) => None,
+            None => self
+                .payer
+                .blockhash_cache
+                .as_ref()
+                .and_then(BlockhashCache::get),
+        };
+
+        // Fetch the blockhash while the payment is assembled; failures to
+        // assemble it are reported before a failed blockhash lookup
+        let (instructions, recent_blockhash) =
+            tokio::join!(self.prepare_payment(request, payer), async {
+                match cached_blockhash {
+                    Some(hash) => Ok(hash),
+                    None => self.latest_blockhash().await,
+                }
+            });
+        let instructions = instructions?;
+        let recent_blockhash = recent_blockhash?;
+
+        if self.payer.dry_run {
+            let transaction = self
+                .sign_payment(&instructions, payer, recent_blockhash)
+                .await?;
+            return Ok(
+                Self::authorization(request, &transaction.signatures[0], &payer.pubkey())
+                    .unsettled(),
+            );
+        }
+
+        // Create, sign, and send transaction
+        let simulate = self
+            .payer
+            .simulate_before_send
+            .unwrap_or(request.network == "solana-mainnet");
+        if let Some(url) = facilitator_url {
+            return self
+                .settle_payment(
+                    url,
+                    request,
+                    &instructions,
+                    payer,
+                    recent_blockhash,
+                    simulate,
+                )
+                .await;
+        }
+
+        let sent_at = Instant::now();
+        let signature = self
+            .broadcast_with_retries(
+                &instructions,
+                payer,
+                recent_blockhash,
+                simulate,
+                confirmation,
+                Some(request),
+            )
+            .await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_confirmation_latency(sent_at.elapsed());
+        }
+
+        Ok(Self::authorization(request, &signature, &payer.pubkey()))
+    }
+
+    /// Re-confirm the transaction an earlier attempt sent for `request`, if any
+    ///
+    /// Waits up to `confirmation.timeout`, or [`BLOCKHASH_VALIDITY`] if unset,
+    /// since a transaction not seen by then can no longer land. Such a
+    /// transaction's record is dropped and `None` returned, so the payment
+    /// is made afresh.
+    async fn recover_payment(
+        &self,
+        request: &PaymentRequest,
+        payer: &dyn signer::TransactionSigner,
+        confirmation: &ConfirmationConfig,
+    ) -> X402Result<Option<PaymentAuthorization>> {
+        let Some(store) = self
+            .payer
+            .payment_signatures
+            .as_ref()
+            .filter(|_| !self.payer.dry_run)
+        else {
+            return Ok(None);
+        };
+        let Some(signature) = store.get_payment_signature(&request.payment_id).await? else {
+            return Ok(None);
+        };
+        let signature = Signature::from_str(&signature).map_err(|e| {
+            X402Error::Serialization(format!("Invalid recorded signature {}: {}", signature, e))
+        })?;
+
+        events::emit(self.payer.events.as_ref(), || PaymentEvent::Confirming {
+            payment_id: request.payment_id.clone(),
+            signature: signature.to_string(),
+        });
+        let timeout = confirmation.timeout.unwrap_or(BLOCKHASH_VALIDITY);
+        let confirmed = self
+            .await_commitment(
+                &signature,
+                confirmation.commitment.unwrap_or(self.commitment),
+                Some(tokio::time::Instant::now() + timeout),
+            )
+            .await;
+        if let Err(X402Error::Timeout { .. }) = &confirmed {
+            let status = self
+                .rpc_client
+                .get_signature_status(&signature, false)
+                .await;
+            if timeout >= BLOCKHASH_VALIDITY && matches!(status, Ok(None)) {
+                store.remove_payment_signature(&request.payment_id).await?;
+                return Ok(None);
+            }
+        }
+        confirmed?;
+        Ok(Some(Self::authorization(
+            request,
+            &signature,
+            &payer.pubkey(),
+        )))
+    }
+
+    /// Record `signature` as the payment sent for `request`
+    async fn record_signature(
+        &self,
+        request: &PaymentRequest,
+        signature: &Signature,
+    ) -> X402Result<()> {
+        match &self.payer.payment_signatures {
+            Some(store) => {
+                store
+                    .save_payment_signature(
+                        &request.payment_id,
+                        &signature.to_string(),
+                        request.expires_at,
+                    )
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Build the instructions of a payment from `payer`, checking it can afford them
+    async fn prepare_payment(
+        &self,
+        request: &PaymentRequest,
+        payer: &dyn signer::TransactionSigner,
+    ) -> X402Result<Vec<Instruction>> {
+        let mut instructions = self
+            .payment_instructions(
+                request,
+                &payer.pubkey(),
+                &[],
+                &payer.pubkey(),
+                !self.payer.dry_run,
+            )
+            .await?;
+        instructions.extend(self.memo_instruction(request));
+        let budget = self.compute_budget(&instructions).await?;
+        if !self.payer.dry_run {
+            self.check_fee_balance(
+                std::slice::from_ref(request),
+                &payer.pubkey(),
+                &instructions,
+                budget,
+            )
+            .await?;
+        }
+        Ok(Self::with_compute_budget(budget, instructions))
+    }
+
+    /// Sign `instructions` and have the facilitator at `url` settle them
+    async fn settle_payment(
+        &self,
+        url: &str,
+        request: &PaymentRequest,
+        instructions: &[Instruction],
+        payer: &dyn signer::TransactionSigner,
+        recent_blockhash: Hash,
+        simulate: bool,
+    ) -> X402Result<PaymentAuthorization> {
+        let transaction = self
+            .sign_payment(instructions, payer, recent_blockhash)
+            .await?;
+        if simulate {
+            self.simulate(&transaction).await?;
+        }
+
+        events::emit(self.payer.events.as_ref(), || PaymentEvent::Broadcasting {
+            payment_id: request.payment_id.clone(),
+            signature: transaction.signatures[0].to_string(),
+        });
+        let sent_at = Instant::now();
+        let signature = facilitator::settle(&self.payer.http, url, request, &transaction).await?;
+        self.record_signature(request, &signature).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_confirmation_latency(sent_at.elapsed());
+        }
+
+        Ok(Self::authorization(request, &signature, &payer.pubkey()))
+    }
+
+    /// Wait for a payment sent for `request` to confirm
+    ///
+    /// Resolves a payment left pending by a confirmation timeout, whose
+    /// signature is [`X402Error::pending_signature`]. Polls `signature` until
+    /// it is confirmed (finalized, if that is the processor's commitment) and
+    /// returns the authorization `create_payment` would have, paid by the
+    /// transaction's fee payer. Fails with [`X402Error::Timeout`] if it is
+    /// still unconfirmed after `timeout`, and `TransactionBroadcast` if it
+    /// failed on-chain.
+    pub async fn poll_confirmation(
+        &self,
+        request: &PaymentRequest,
+        signature: &str,
+        timeout: Duration,
+    ) -> X402Result<PaymentAuthorization> {
+        let signature = Signature::from_str(signature).map_err(|e| {
+            X402Error::InvalidPaymentAuthorization(format!("Invalid signature: {}", e))
+        })?;
+        let commitment = if self.commitment.is_finalized() {
+            self.commitment
+        } else {
+            CommitmentConfig::confirmed()
+        };
+        let deadline = tokio::time::Instant::now() + timeout;
+        self.await_commitment(&signature, commitment, Some(deadline))
+            .await?;
+
+        let transaction = self
+            .rpc_client
+            .get_transaction(
+                &signature,
+                solana_transaction_status::UiTransactionEncoding::JsonParsed,
+            )
+            .await
+            .map_err(|e| X402Error::Network(format!("Failed to fetch transaction: {}", e)))?;
+        let payer = fee_payer(&transaction)
+            .and_then(|payer| Pubkey::from_str(&payer).ok())
+            .ok_or_else(|| {
+                X402Error::Blockchain(format!("Transaction {} has no fee payer", signature))
+            })?;
+
+        Ok(Self::authorization(request, &signature, &payer))
+    }
+
+    /// Pay several payment requests in a single transaction
+    ///
+    /// Combines the transfers of all `requests`, to the same or different
+    /// recipients and mints, into one transaction, so it pays one fee and
+    /// either every payment lands or none does. Each request gets its own
+    /// [`PaymentAuthorization`], all carrying the shared signature.
+    ///
+    /// All requests must be for the same network and unexpired, and the payer
+    /// must hold the combined amount of each mint. Batches are always
+    /// broadcast directly, without a facilitator.
+    pub async fn create_batch_payment(
+        &self,
+        requests: &[PaymentRequest],
+        payer: &dyn signer::TransactionSigner,
+    ) -> X402Result<Vec<PaymentAuthorization>> {
+        let result = self.send_batch_payment(requests, payer).await;
+        self.record_result(metrics::CREATE_PAYMENT, &result);
+        result
+    }
+
+    async fn send_batch_payment(
+        &self,
+        requests: &[PaymentRequest],
+        payer: &dyn signer::TransactionSigner,
+    ) -> X402Result<Vec<PaymentAuthorization>> {
+        let Some(first) = requests.first() else {
+            return Err(X402Error::InvalidPaymentRequest(
+                "Batch payment needs at least one payment request".to_string(),
+            ));
+        };
+        if let Some(request) = requests.iter().find(|r| r.network != first.network) {
+            return Err(X402Error::InvalidPaymentRequest(format!(
+                "Batch payment mixes networks {} and {}",
+                first.network, request.network
+            )));
+        }
+
+        let mut instructions: Vec<Instruction> = Vec::new();
+        for request in requests {
+            let payment = self
+                .payment_instructions(
+                    request,
+                    &payer.pubkey(),
+                    &[],
+                    &payer.pubkey(),
+                    !self.payer.dry_run,
+                )
+                .await?;
+            for instruction in payment {
+                // Requests paying the same recipient in the same mint create
+                // its token account only once
+                let creates_account = instruction.program_id == spl_associated_token_account::id();
+                if !(creates_account && instructions.contains(&instruction)) {
+                    instructions.push(instruction);
+                }
+            }
+            instructions.extend(self.memo_instruction(request));
+        }
+        let budget = self.compute_budget(&instructions).await?;
+        if !self.payer.dry_run {
+            self.check_batch_token_balances(&payer.pubkey(), &instructions)
+                .await?;
+            self.check_fee_balance(requests, &payer.pubkey(), &instructions, budget)
+                .await?;
+        }
+        let instructions = Self::with_compute_budget(budget, instructions);
+
+        let simulate = self
+            .payer
+            .simulate_before_send
+            .unwrap_or(first.network == "solana-mainnet");
+        let cached_blockhash = self
+            .payer
+            .blockhash_cache
+            .as_ref()
+            .and_then(BlockhashCache::get);
+        let recent_blockhash = match cached_blockhash {
+            Some(hash) => hash,
+            None => self.latest_blockhash().await?,
+        };
+
+        if self.payer.dry_run {
+            let transaction = self
+                .sign_payment(&instructions, payer, recent_blockhash)
+                .await?;
+            return Ok(requests
+                .iter()
+                .map(|request| {
+                    Self::authorization(request, &transaction.signatures[0], &payer.pubkey())
+                        .unsettled()
+                })
+                .collect());
+        }
+
+        let sent_at = Instant::now();
+        let signature = self
+            .broadcast_with_retries(
+                &instructions,
+                payer,
+                recent_blockhash,
+                simulate,
+                &ConfirmationConfig::default(),
+                None,
+            )
+            .await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_confirmation_latency(sent_at.elapsed());
+        }
+
+        Ok(requests
+            .iter()
+            .map(|request| Self::authorization(request, &signature, &payer.pubkey()))
+            .collect())
+    }
+
+    /// Check the payer's token accounts cover every transfer out of them combined
+    ///
+    /// Each transfer was checked on its own while building it; this catches
+    /// batches whose transfers only fit the balance individually.
+    async fn check_batch_token_balances(
+        &self,
+        owner: &Pubkey,
+        instructions: &[Instruction],
+    ) -> X402Result<()> {
+        let mut totals: HashMap<(Pubkey, Pubkey), (u64, usize)> = HashMap::new();
+        for instruction in instructions {
+            if !is_token_program(&instruction.program_id) {
+                continue;
+            }
+            if let Ok(token_instruction::TokenInstruction::TransferChecked { amount, .. }) =
+                token_instruction::TokenInstruction::unpack(&instruction.data)
+            {
+                let key = (
+                    instruction.accounts[0].pubkey,
+                    instruction.accounts[1].pubkey,
+                );
+                let (total, transfers) = totals.entry(key).or_default();
+                *total = total.saturating_add(amount);
+                *transfers += 1;
+            }
+        }
+
+        for ((source, mint), (total, transfers)) in totals {
+            if transfers > 1 {
+                self.check_balance(owner, &source, &mint, total).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a payment transaction for offline or multisig signing
+    ///
+    /// Builds the same transfer as [`create_payment`](Self::create_payment),
+    /// but from `owner`'s token account and without signing or sending it.
+    /// When `owner` is an SPL Token multisig account, pass its `signers`;
+    /// otherwise leave `signers` empty and `owner` itself must sign.
+    /// `fee_payer` pays transaction fees and creates the recipient's token
+    /// account if needed.
+    ///
+    /// Collect the signatures with [`PartialPayment::sign`] or external
+    /// multisig tooling, then broadcast with
+    /// [`finalize_payment`](Self::finalize_payment). All signatures must be
+    /// collected before the transaction's blockhash expires (about
+    /// [`BLOCKHASH_VALIDITY`]).
+    pub async fn create_partial_payment(
+        &self,
+        request: &PaymentRequest,
+        owner: &Pubkey,
+        signers: &[Pubkey],
+        fee_payer: &Pubkey,
+    ) -> X402Result<PartialPayment> {
+        let mut instructions = self
+            .payment_instructions(request, owner, signers, fee_payer, !self.payer.dry_run)
+            .await?;
+        instructions.extend(self.memo_instruction(request));
+        let budget = self.compute_budget(&instructions).await?;
+        let instructions = Self::with_compute_budget(budget, instructions);
+        let mut message = Message::new(&instructions, Some(fee_payer));
+        message.recent_blockhash = self.latest_blockhash().await?;
+
+        Ok(PartialPayment {
+            request: request.clone(),
+            owner: *owner,
+            transaction: Transaction::new_unsigned(message),
+        })
+    }
+
+    /// Broadcast a fully signed [`PartialPayment`]
+    ///
+    /// Fails without sending if any required signature is missing or invalid.
+    pub async fn finalize_payment(
+        &self,
+        payment: &PartialPayment,
+    ) -> X402Result<PaymentAuthorization> {
+        let result = self.send_partial_payment(payment).await;
+        self.record_result(metrics::CREATE_PAYMENT, &result);
+        result
+    }
+
+    async fn send_partial_payment(
+        &self,
+        payment: &PartialPayment,
+    ) -> X402Result<PaymentAuthorization> {
+        let missing = payment.missing_signers();
+        if !missing.is_empty() {
+            let missing: Vec<String> = missing.iter().map(Pubkey::to_string).collect();
+            return Err(X402Error::Blockchain(format!(
+                "Transaction is missing signatures from {}",
+                missing.join(", ")
+            )));
+        }
+        payment
+            .transaction
+            .verify()
+            .map_err(|e| X402Error::Blockchain(format!("Invalid transaction signature: {}", e)))?;
+
+        let sent_at = Instant::now();
+        let signature = self
+            .rpc_client
+            .send_and_confirm_transaction(&payment.transaction)
+            .await
+            .map_err(|e| {
+                X402Error::TransactionBroadcast(format!("Failed to broadcast transaction: {}", e))
+            })?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_confirmation_latency(sent_at.elapsed());
+        }
+
+        Ok(Self::authorization(
+            &payment.request,
+            &signature,
+            &payment.owner,
+        ))
+    }
+
+    /// Build the instructions paying `request` from `owner`'s token account
+    ///
+    /// `signers` are the signers of a multisig `owner` and empty otherwise;
+    /// `fee_payer` funds a missing recipient token account. With
+    /// `check_balances`, fails if `owner` can't afford the amount.
+    async fn payment_instructions(
+        &self,
+        request: &PaymentRequest,
+        owner: &Pubkey,
+        signers: &[Pubkey],
+        fee_payer: &Pubkey,
+        check_balances: bool,
+    ) -> X402Result<Vec<Instruction>> {
+        request.check_scheme()?;
+
+        // Check if payment has expired
+        if request.is_expired() {
+            return Err(X402Error::PaymentExpired(format!(
+                "Payment request expired at {}",
+                request.expires_at
+            )));
+        }
+
+        request.validate()?;
+
+        let recipient = Pubkey::from_str(&request.payment_address).map_err(|e| {
+            X402Error::InvalidPaymentRequest(format!("Invalid payment address: {}", e))
+        })?;
+
+        // Native SOL moves lamports straight between wallets; there are no
+        // token accounts to look up or create
+        if request.is_native_sol() {
+            if !signers.is_empty() {
+                return Err(X402Error::InvalidPaymentRequest(
+                    "Native SOL payments cannot be made from a multisig".to_string(),
+                ));
+            }
+            let lamports = Self::parse_amount(&request.max_amount_required, SOL_DECIMALS)?;
+            if check_balances {
+                let balance =
+                    self.rpc_client.get_balance(owner).await.map_err(|e| {
+                        X402Error::Network(format!("Failed to get SOL balance: {}", e))
+                    })?;
+                if balance < lamports {
+                    return Err(X402Error::InsufficientFunds(InsufficientFundsDetails::new(
+                        lamports, balance, NATIVE_SOL,
+                    )));
+                }
+            }
+            return Ok(vec![system_instruction::transfer(
+                owner, &recipient, lamports,
+            )]);
+        }
+
+        let token_mint = Pubkey::from_str(&request.asset_address).map_err(|e| {
+            X402Error::InvalidPaymentRequest(format!("Invalid token mint address: {}", e))
+        })?;
+
+        let (token_program, decimals) = self.mint_details(request, &token_mint).await?;
+        let amount = Self::parse_amount(&request.max_amount_required, decimals)?;
+
+        // Get or create associated token accounts, which are derived from
+        // the token program as well as the wallet and mint
+        let sender_ata =
+            get_associated_token_address_with_program_id(owner, &token_mint, &token_program);
+        let recipient_ata =
+            get_associated_token_address_with_program_id(&recipient, &token_mint, &token_program);
+
+        // Check sender balance (if asked to) and whether the recipient ATA
+        // exists concurrently, reporting a short balance first
+        let (balance, recipient_ata_exists) = tokio::join!(
+            async {
+                if !check_balances {
+                    return Ok(());
+                }
+                self.check_balance(owner, &sender_ata, &token_mint, amount)
+                    .await
+            },
+            self.account_exists(&recipient_ata)
+        );
+        balance?;
+
+        // Build transaction
+        let mut instructions: Vec<Instruction> = Vec::new();
+
+        // Create the recipient ATA if it doesn't exist. Idempotently, as
+        // another transaction may create it before this one lands
+        if !recipient_ata_exists? {
+            instructions.push(create_associated_token_account_idempotent(
+                fee_payer,
+                &recipient,
+                &token_mint,
+                &token_program,
+            ));
+        }
+
+        // Add transfer instruction
+        instructions.push(
+            token_instruction::transfer_checked(
+                &token_program,
+                &sender_ata,
+                &token_mint,
+                &recipient_ata,
+                owner,
+                &signers.iter().collect::<Vec<_>>(),
+                amount,
+                decimals,
+            )
+            .map_err(|e| {
+                X402Error::Blockchain(format!("Failed to create transfer instruction: {}", e))
+            })?,
+        );
+
+        Ok(instructions)
+    }
+
+    /// Token program and decimals to pay `request` in `mint` with
+    ///
+    /// The request's `token_program` and `decimals` are used when given; the
+    /// mint account is only read for those that aren't.
+    async fn mint_details(
+        &self,
+        request: &PaymentRequest,
+        mint: &Pubkey,
+    ) -> X402Result<(Pubkey, u8)> {
+        let token_program = request
+            .token_program
+            .as_deref()
+            .map(|program| {
+                Pubkey::from_str(program)
+                    .ok()
+                    .filter(is_token_program)
+                    .ok_or_else(|| {
+                        X402Error::InvalidPaymentRequest(format!(
+                            "Token program {} is neither SPL Token nor Token-2022",
+                            program
+                        ))
+                    })
+            })
+            .transpose()?;
+
+        match (token_program, request.decimals) {
+            (Some(token_program), Some(decimals)) => Ok((token_program, decimals)),
+            (token_program, decimals) => {
+                let info = self.read_mint(mint).await?;
+                Ok((
+                    token_program.unwrap_or(info.token_program),
+                    decimals.unwrap_or(info.decimals),
+                ))
+            }
+        }
+    }
+
+    /// SPL Memo instruction for `request`, if payments carry a memo
+    fn memo_instruction(&self, request: &PaymentRequest) -> Option<Instruction> {
+        let memo = match self.payer.memo.as_ref()? {
+            PaymentMemo::PaymentId => &request.payment_id,
+            PaymentMemo::Text(text) => text,
+        };
+        Some(Instruction::new_with_bytes(
+            MEMO_PROGRAM_ID,
+            memo.as_bytes(),
+            Vec::new(),
+        ))
+    }
+
+    /// Compute budget to attach to `instructions`, if payments carry one
+    async fn compute_budget(
+        &self,
+        instructions: &[Instruction],
+    ) -> X402Result<Option<PriorityFeeConfig>> {
+        let config = match self.payer.priority_fee {
+            None => return Ok(None),
+            Some(PriorityFee::Fixed(config)) => config,
+            Some(PriorityFee::Estimated {
+                compute_unit_limit,
+                max_micro_lamports_per_cu,
+            }) => {
+                let mut accounts: Vec<Pubkey> = instructions
+                    .iter()
+                    .flat_map(|instruction| &instruction.accounts)
+                    .filter(|account| account.is_writable)
+                    .map(|account| account.pubkey)
+                    .collect();
+                accounts.sort();
+                accounts.dedup();
+
+                let mut fees = self
+                    .rpc_client
+                    .get_recent_prioritization_fees(&accounts)
+                    .await
+                    .map_err(|e| {
+                        X402Error::Network(format!("Failed to get recent priority fees: {}", e))
+                    })?;
+                fees.sort_unstable();
+                let median = fees.get(fees.len() / 2).copied().unwrap_or(0);
+                PriorityFeeConfig {
+                    micro_lamports_per_cu: median.min(max_micro_lamports_per_cu),
+                    compute_unit_limit,
+                }
+            }
+        };
+        Ok(Some(config))
+    }
+
+    /// Prepend `budget`'s compute budget instructions to `instructions`
+    fn with_compute_budget(
+        budget: Option<PriorityFeeConfig>,
+        instructions: Vec<Instruction>,
+    ) -> Vec<Instruction> {
+        let Some(config) = budget else {
+            return instructions;
+        };
+        let mut budgeted = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(config.compute_unit_limit),
+            ComputeBudgetInstruction::set_compute_unit_price(config.micro_lamports_per_cu),
+        ];
+        budgeted.extend(instructions);
+        budgeted
+    }
+
+    fn authorization(
+        request: &PaymentRequest,
+        signature: &Signature,
+        payer: &Pubkey,
+    ) -> PaymentAuthorization {
+        PaymentAuthorization::from_settled(request, signature.to_string(), payer.to_string())
+    }
+
+    /// Fetch the latest blockhash, refreshing the cache if enabled
+    async fn latest_blockhash(&self) -> X402Result<Hash> {
+        let hash =
+            self.rpc_client.get_latest_blockhash().await.map_err(|e| {
+                X402Error::Network(format!("Failed to get recent blockhash: {}", e))
+            })?;
+
+        if let Some(cache) = &self.payer.blockhash_cache {
+            cache.set(hash);
+        }
+        Ok(hash)
+    }
+
+    /// Sign and send `instructions`, re-signing with a fresh blockhash when it expires
+    ///
+    /// Up to `max_broadcast_retries` retries are made, and only for expired
+    /// blockhashes; other failures, such as insufficient funds, are returned
+    /// straight away. Before each retry the expired attempt is looked up once
+    /// more, so a payment that landed at the last moment isn't sent twice.
+    /// With `simulate`, the first attempt is simulated before it is sent.
+    /// Sending and confirming is bounded by `confirmation`. Progress and
+    /// the signature of the attempt that was sent are recorded for
+    /// `request`, if given.
+    async fn broadcast_with_retries(
+        &self,
+        instructions: &[Instruction],
+        payer: &dyn signer::TransactionSigner,
+        mut recent_blockhash: Hash,
+        simulate: bool,
+        confirmation: &ConfirmationConfig,
+        request: Option<&PaymentRequest>,
+    ) -> X402Result<Signature> {
+        let events = self.payer.events.as_ref().filter(|_| request.is_some());
+        let payment_id = request.map_or("", |request| request.payment_id.as_str());
+        let deadline = confirmation
+            .timeout
+            .map(|timeout| tokio::time::Instant::now() + timeout);
+        let mut retries = 0;
+        loop {
+            let transaction = self
+                .sign_payment(instructions, payer, recent_blockhash)
+                .await?;
+            if simulate && retries == 0 {
+                self.simulate(&transaction).await?;
+            }
+            let signature = transaction.signatures[0];
+            events::emit(events, || PaymentEvent::Broadcasting {
+                payment_id: payment_id.to_string(),
+                signature: signature.to_string(),
+            });
+            let sent = match deadline {
+                Some(deadline) => {
+                    tokio::time::timeout_at(deadline, self.send_transaction(&transaction))
+                        .await
+                        .map_err(|_| Self::confirmation_timeout(&signature))?
+                }
+                None => self.send_transaction(&transaction).await,
+            };
+            let error = match sent {
+                Ok(signature) => {
+                    if let Some(request) = request {
+                        self.record_signature(request, &signature).await?;
+                    }
+                    if let Some(commitment) = confirmation.commitment {
+                        events::emit(events, || PaymentEvent::Confirming {
+                            payment_id: payment_id.to_string(),
+                            signature: signature.to_string(),
+                        });
+                        self.await_commitment(&signature, commitment, deadline)
+                            .await?;
+                    }
+                    return Ok(signature);
+                }
+                Err(e) => e,
+            };
+
+            if retries >= self.payer.max_broadcast_retries || !Self::is_blockhash_expired(&error) {
+                return Err(X402Error::TransactionBroadcast(format!(
+                    "Failed to broadcast transaction: {}",
+                    error
+                )));
+            }
+            if let Ok(Some(status)) = self
+                .rpc_client
+                .get_signature_status(&signature, false)
+                .await
+            {
+                if status.err.is_none() {
+                    return Ok(signature);
+                }
+            }
+
+            retries += 1;
+            recent_blockhash = self.latest_blockhash().await?;
+        }
+    }
+
+    /// Poll `signature` until it reaches `commitment` or `deadline` passes
+    async fn await_commitment(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentConfig,
+        deadline: Option<tokio::time::Instant>,
+    ) -> X402Result<()> {
+        loop {
+            let status = self
+                .rpc_client
+                .get_signature_status(signature, false)
+                .await
+                .map_err(|e| {
+                    X402Error::Network(format!("Failed to get signature status: {}", e))
+                })?;
+            if let Some(status) = status {
+                if let Some(err) = status.err {
+                    return Err(X402Error::TransactionBroadcast(format!(
+                        "Payment transaction {} failed: {}",
+                        signature, err
+                    )));
+                }
+                if status.satisfies_commitment(commitment) {
+                    return Ok(());
+                }
+            }
+
+            let next_poll = tokio::time::Instant::now() + SLOT_DURATION;
+            if deadline.is_some_and(|deadline| next_poll > deadline) {
+                return Err(Self::confirmation_timeout(signature));
+            }
+            tokio::time::sleep_until(next_poll).await;
+        }
+    }
+
+    fn confirmation_timeout(signature: &Signature) -> X402Error {
+        X402Error::Timeout {
+            message: format!(
+                "Payment not confirmed in time, it may still land; pending signature {}",
+                signature
+            ),
+            signature: Some(signature.to_string()),
+        }
+    }
+
+    /// Message paying `instructions` from `payer` in the configured [`TransactionVersion`]
+    fn payment_message(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        recent_blockhash: Hash,
+    ) -> X402Result<VersionedMessage> {
+        Ok(match self.payer.transaction_version {
+            TransactionVersion::Legacy => VersionedMessage::Legacy(Message::new_with_blockhash(
+                instructions,
+                Some(payer),
+                &recent_blockhash,
+            )),
+            TransactionVersion::V0 => VersionedMessage::V0(
+                v0::Message::try_compile(
+                    payer,
+                    instructions,
+                    &self.payer.lookup_tables,
+                    recent_blockhash,
+                )
+                .map_err(|e| {
+                    X402Error::Blockchain(format!("Failed to compile v0 message: {}", e))
+                })?,
+            ),
+        })
+    }
+
+    /// Build and sign a payment in the configured [`TransactionVersion`]
+    async fn sign_payment(
+        &self,
+        instructions: &[Instruction],
+        payer: &dyn signer::TransactionSigner,
+        recent_blockhash: Hash,
+    ) -> X402Result<VersionedTransaction> {
+        let message = self.payment_message(instructions, &payer.pubkey(), recent_blockhash)?;
+        let signature = payer.sign(&message).await?;
+        if !signature.verify(payer.pubkey().as_ref(), &message.serialize()) {
+            return Err(X402Error::Blockchain(format!(
+                "Signer returned an invalid signature for {}",
+                payer.pubkey()
+            )));
+        }
+        Ok(VersionedTransaction {
+            signatures: vec![signature],
+            message,
+        })
+    }
+
+    /// Broadcast `transaction`, keeping legacy transactions in the legacy format
+    async fn send_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<Signature, ClientError> {
+        match transaction.clone().into_legacy_transaction() {
+            Some(legacy) => self.rpc_client.send_and_confirm_transaction(&legacy).await,
+            None => {
+                self.rpc_client
+                    .send_and_confirm_versioned_transaction(transaction)
+                    .await
+            }
+        }
+    }
+
+    /// Simulate `transaction`, failing with the reason it would be rejected
+    async fn simulate(&self, transaction: &VersionedTransaction) -> X402Result<()> {
+        let result = self
+            .rpc_client
+            .simulate_transaction(transaction)
+            .await
+            .map_err(|e| X402Error::Network(format!("Failed to simulate transaction: {}", e)))?;
+        let Some(err) = result.err else {
+            return Ok(());
+        };
+
+        let logs = result.logs.unwrap_or_default();
+        let logged = |text: &str| logs.iter().any(|line| line.contains(text));
+        let reason = if logged("Error: Account is frozen") {
+            "the token account is frozen".to_string()
+        } else if logged("Error: insufficient funds") {
+            "the token account holds too little".to_string()
+        } else {
+            err.to_string()
+        };
+        let message = match logs.len() {
+            0 => format!("Payment would fail, not sent: {}", reason),
+            n => format!(
+                "Payment would fail, not sent: {} (logs: {})",
+                reason,
+                logs[n.saturating_sub(3)..].join("; ")
+            ),
+        };
+
+        // Simulation doesn't report balances, so shortfalls it finds can't be
+        // described as `InsufficientFunds`; the reason is in the message
+        Err(X402Error::TransactionBroadcast(message))
+    }
+
+    async fn sign_and_send(
+        &self,
+        message: &Message,
+        payer: &Keypair,
+        recent_blockhash: Hash,
+    ) -> Result<Signature, ClientError> {
+        let mut transaction = Transaction::new_unsigned(message.clone());
+        transaction.sign(&[payer], recent_blockhash);
+        self.rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .await
+    }
+
+    fn is_blockhash_expired(error: &ClientError) -> bool {
+        match error.kind() {
+            // The client gave up waiting because the blockhash stopped being valid
+            ClientErrorKind::RpcError(RpcError::ForUser(message)) => {
+                message.starts_with("unable to confirm transaction")
+            }
+            _ => matches!(
+                error.get_transaction_error(),
+                Some(TransactionError::BlockhashNotFound)
+            ),
+        }
+    }
+
+    /// Claim everything held for `recipient` in its `mint` escrow with `escrow`
+    ///
+    /// Creates the recipient's token account if needed and sends the
+    /// program's `claim` instruction, signed and paid for by `recipient`.
+    /// Fails with `InsufficientFunds` if the escrow is empty.
+    pub async fn claim_escrow(
+        &self,
+        escrow: &EscrowProgram,
+        mint: &Pubkey,
+        recipient: &Keypair,
+    ) -> X402Result<Signature> {
+        let owner = recipient.pubkey();
+        let escrow_account = escrow.escrow_token_account(&owner, mint);
+        let amount = match self.account_exists(&escrow_account).await? {
+            true => self.get_token_balance(&escrow_account).await?,
+            false => 0,
+        };
+        // Claiming needs at least one unit in the escrow
+        if amount == 0 {
+            return Err(X402Error::InsufficientFunds(InsufficientFundsDetails::new(
+                1,
+                0,
+                mint.to_string(),
+            )));
+        }
+
+        let mut instructions = Vec::new();
+        let recipient_ata = get_associated_token_address(&owner, mint);
+        if !self.account_exists(&recipient_ata).await? {
+            instructions.push(create_associated_token_account_idempotent(
+                &owner,
+                &owner,
+                mint,
+                &spl_token::id(),
+            ));
+        }
+        instructions.push(escrow.claim_instruction(&owner, mint, amount));
+
+        let message = Message::new(&instructions, Some(&owner));
+        let recent_blockhash = self.latest_blockhash().await?;
+        self.sign_and_send(&message, recipient, recent_blockhash)
+            .await
+            .map_err(|e| {
+                X402Error::TransactionBroadcast(format!("Failed to broadcast claim: {}", e))
+            })
+    }
+
+    /// Check if an account exists
+    async fn account_exists(&self, account: &Pubkey) -> X402Result<bool> {
+        self.rpc_client
+            .get_account(account)
+            .await
+            .map(|account| account.is_some())
+            .map_err(|e| X402Error::Network(format!("Failed to check account existence: {}", e)))
+    }
+
+    /// Check that `payer` holds the SOL `instructions` will cost it
+    ///
+    /// Covers the transaction fee with its priority fee, rent for any token
+    /// account the instructions create and the amounts of native SOL
+    /// `requests`.
+    async fn check_fee_balance(
+        &self,
+        requests: &[PaymentRequest],
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        budget: Option<PriorityFeeConfig>,
+    ) -> X402Result<()> {
+        let mut required =
+            LAMPORTS_PER_SIGNATURE + budget.map_or(0, |config| config.max_fee_lamports());
+        required = required.saturating_add(self.account_rent(instructions).await?);
+        for request in requests.iter().filter(|request| request.is_native_sol()) {
+            let lamports = Self::parse_amount(&request.max_amount_required, SOL_DECIMALS)?;
+            required = required.saturating_add(lamports);
+        }
+
+        let balance = self
+            .rpc_client
+            .get_balance(payer)
+            .await
+            .map_err(|e| X402Error::Network(format!("Failed to get SOL balance: {}", e)))?;
+        if balance < required {
+            return Err(X402Error::InsufficientFunds(InsufficientFundsDetails::new(
+                required, balance, NATIVE_SOL,
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rent for the token accounts `instructions` create
+    ///
+    /// Token-2022 accounts are sized for the extensions their mint requires,
+    /// so their mints are read to price them.
+    async fn account_rent(&self, instructions: &[Instruction]) -> X402Result<u64> {
+        let mut accounts: HashMap<usize, u64> = HashMap::new();
+        for instruction in instructions {
+            if instruction.program_id != spl_associated_token_account::id() {
+                continue;
+            }
+            // Funder, account, wallet, mint, system program, token program
+            let (mint, token_program) = (&instruction.accounts[3], &instruction.accounts[5]);
+            let len = if token_program.pubkey == spl_token::id() {
+                spl_token::state::Account::LEN
+            } else {
+                self.read_mint(&mint.pubkey).await?.account_len
+            };
+            *accounts.entry(len).or_default() += 1;
+        }
+
+        let mut rent = 0u64;
+        for (len, count) in accounts {
+            let minimum = self.minimum_balance_for_rent_exemption(len).await?;
+            rent = rent.saturating_add(minimum.saturating_mul(count));
+        }
+        Ok(rent)
+    }
+
+    /// Check if the sender has sufficient balance
+    ///
+    /// A sender without a token account for `mint` is told so rather than
+    /// getting the RPC's error for the balance lookup.
+    async fn check_balance(
+        &self,
+        owner: &Pubkey,
+        token_account: &Pubkey,
+        mint: &Pubkey,
+        required_amount: u64,
+    ) -> X402Result<()> {
+        let balance = match self.get_token_balance(token_account).await {
+            Ok(balance) => balance,
+            Err(err) => {
+                if self.account_exists(token_account).await? {
+                    return Err(err);
+                }
+                return Err(X402Error::InvalidPaymentRequest(format!(
+                    "Payer {} has no token account for mint {}; its associated token account {} must be created and funded before paying",
+                    owner, mint, token_account
+                )));
+            }
+        };
+
+        if balance < required_amount {
+            return Err(X402Error::InsufficientFunds(InsufficientFundsDetails::new(
+                required_amount,
+                balance,
+                mint.to_string(),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rpc::mock::{MockRpcBackend, TransactionFixture, MOCK_LAMPORTS_PER_SIGNATURE},
+        verification::VerificationOptions,
+    };
+
+    #[tokio::test]
+    async fn test_multisig_partial_payment_is_finalized_once_signed() {
+        let (multisig, recipient, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (first, second) = (Keypair::new(), Keypair::new());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_associated_token_account(multisig, mint, 1_000_000)
+                .with_associated_token_account(recipient, mint, 0),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .nonce("nonce")
+            .payment_id("pay_1")
+            .resource("/data")
+            .build()
+            .unwrap();
+
+        let mut payment = processor
+            .create_partial_payment(
+                &request,
+                &multisig,
+                &[first.pubkey(), second.pubkey()],
+                &first.pubkey(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            payment.missing_signers(),
+            vec![first.pubkey(), second.pubkey()]
+        );
+
+        // One co-signer signs, then the transaction travels to the other
+        payment.sign(&first).unwrap();
+        let mut payment: PartialPayment =
+            serde_json::from_str(&serde_json::to_string(&payment).unwrap()).unwrap();
+        assert_eq!(payment.missing_signers(), vec![second.pubkey()]);
+        assert!(processor.finalize_payment(&payment).await.is_err());
+        assert!(backend.sent_transactions().is_empty());
+
+        payment.sign(&second).unwrap();
+        assert!(payment.is_fully_signed());
+        let authorization = processor.finalize_payment(&payment).await.unwrap();
+        assert_eq!(authorization.public_key, multisig.to_string());
+        assert_eq!(
+            backend.sent_transactions(),
+            vec![payment.transaction.clone()]
+        );
+    }
+
+    /// Amount and decimals of the `transferChecked` in a sent transaction
+    fn sent_transfer(transaction: &Transaction) -> (u64, u8) {
+        use spl_token::instruction::TokenInstruction;
+
+        transaction
+            .message
+            .instructions
+            .iter()
+            .find_map(|ix| match TokenInstruction::unpack(&ix.data) {
+                Ok(TokenInstruction::TransferChecked { amount, decimals }) => {
+                    Some((amount, decimals))
+                }
+                _ => None,
+            })
+            .expect("transferChecked instruction")
+    }
+
+    #[tokio::test]
+    async fn test_create_payment_uses_mint_decimals() {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let (nine, zero) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(nine, 9)
+                .with_mint(zero, 0)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), nine, 10_000_000_000)
+                .with_associated_token_account(payer.pubkey(), zero, 10)
+                .with_associated_token_account(recipient, nine, 0)
+                .with_associated_token_account(recipient, zero, 0),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let request = |amount: &str, mint: Pubkey| {
+            PaymentRequest::builder()
+                .amount(amount.to_string())
+                .asset_address(mint.to_string())
+                .payment_address(recipient.to_string())
+                .network("solana-devnet")
+                .nonce("nonce")
+                .payment_id("pay_1")
+                .resource("/data")
+                .build()
+                .unwrap()
+        };
+
+        processor
+            .create_payment(&request("0.5", nine), &payer)
+            .await
+            .unwrap();
+        processor
+            .create_payment(&request("3", zero), &payer)
+            .await
+            .unwrap();
+        // Decimals in the request take precedence over the mint account
+        processor
+            .create_payment(&request("0.5", nine).with_decimals(6), &payer)
+            .await
+            .unwrap();
+
+        let sent: Vec<_> = backend
+            .sent_transactions()
+            .iter()
+            .map(sent_transfer)
+            .collect();
+        assert_eq!(sent, vec![(500_000_000, 9), (3, 0), (500_000, 6)]);
+    }
+
+    #[tokio::test]
+    async fn test_create_payment_creates_missing_recipient_account() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000)
+                .with_confirmed_sends(),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .resource("/data")
+            .build()
+            .unwrap();
+
+        let authorization = processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(authorization.payment_id, request.payment_id);
+        assert_eq!(authorization.public_key, payer.pubkey().to_string());
+
+        // The recipient's token account is created ahead of the transfer
+        let sent = backend.sent_transactions();
+        let message = &sent[0].message;
+        let programs: Vec<_> = message
+            .instructions
+            .iter()
+            .map(|ix| message.account_keys[usize::from(ix.program_id_index)])
+            .collect();
+        assert_eq!(
+            programs,
+            vec![spl_associated_token_account::id(), spl_token::id()]
+        );
+        // An account created concurrently by someone else doesn't fail the transfer
+        assert_eq!(
+            message.instructions[0].data,
+            create_associated_token_account_idempotent(
+                &payer.pubkey(),
+                &recipient,
+                &mint,
+                &spl_token::id()
+            )
+            .data
+        );
+        assert_eq!(sent_transfer(&sent[0]), (100_000, 6));
+        assert_eq!(authorization.signature, sent[0].signatures[0].to_string());
+
+        // An existing account is paid into directly
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_confirmed_sends(),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(backend.sent_transactions()[0].message.instructions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_payment_uses_mint_token_program() {
+        /// Programs of `transaction`'s instructions and its transfer's destination
+        fn programs_and_destination(transaction: &Transaction) -> (Vec<Pubkey>, Pubkey) {
+            let message = &transaction.message;
+            let programs = message
+                .instructions
+                .iter()
+                .map(|ix| message.account_keys[usize::from(ix.program_id_index)])
+                .collect();
+            let transfer = message.instructions.last().unwrap();
+            (
+                programs,
+                message.account_keys[usize::from(transfer.accounts[2])],
+            )
+        }
+
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let request = |mint: Pubkey| {
+            PaymentRequest::builder()
+                .amount("0.10")
+                .asset_address(mint.to_string())
+                .payment_address(recipient.to_string())
+                .network("solana-devnet")
+                .build()
+                .unwrap()
+        };
+
+        // Classic SPL Token mint
+        let mint = Pubkey::new_unique();
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        processor
+            .create_payment(&request(mint), &payer)
+            .await
+            .unwrap();
+        assert_eq!(
+            programs_and_destination(&backend.sent_transactions()[0]),
+            (
+                vec![spl_associated_token_account::id(), spl_token::id()],
+                get_associated_token_address(&recipient, &mint)
+            )
+        );
+
+        // Token-2022 mint: its accounts are derived from and created by Token-2022
+        let mint = Pubkey::new_unique();
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_token_2022_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_2022_account(payer.pubkey(), mint, 1_000_000),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let request = request(mint);
+        let estimate = processor
+            .estimate_cost(&request, &payer.pubkey())
+            .await
+            .unwrap();
+        // Token-2022 accounts carry an immutable owner extension
+        assert_eq!(
+            estimate.rent_lamports,
+            solana_sdk::rent::Rent::default().minimum_balance(170)
+        );
+        processor.create_payment(&request, &payer).await.unwrap();
+        let sent = backend.sent_transactions();
+        let recipient_ata =
+            get_associated_token_address_with_program_id(&recipient, &mint, &spl_token_2022::id());
+        assert_eq!(
+            programs_and_destination(&sent[0]),
+            (
+                vec![spl_associated_token_account::id(), spl_token_2022::id()],
+                recipient_ata
+            )
+        );
+        assert_eq!(
+            sent[0].message.instructions[0].data,
+            create_associated_token_account_idempotent(
+                &payer.pubkey(),
+                &recipient,
+                &mint,
+                &spl_token_2022::id()
+            )
+            .data
+        );
+        assert_eq!(sent_transfer(&sent[0]), (100_000, 6));
+    }
+
+    #[tokio::test]
+    async fn test_token_program_override_skips_mint_lookup() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // The mint account isn't known to the backend
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_2022_account(payer.pubkey(), mint, 1_000_000)
+                .with_associated_token_2022_account(recipient, mint, 0),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap()
+            .with_decimals(6)
+            .with_token_program(spl_token_2022::id().to_string());
+
+        processor.create_payment(&request, &payer).await.unwrap();
+        let message = &backend.sent_transactions()[0].message;
+        let transfer = &message.instructions[0];
+        assert_eq!(
+            message.account_keys[usize::from(transfer.program_id_index)],
+            spl_token_2022::id()
+        );
+        assert_eq!(backend.call_count("get_account"), 1);
+
+        // Only the two token programs are accepted
+        let request = request.with_token_program(solana_sdk::system_program::id().to_string());
+        let err = processor
+            .create_payment(&request, &payer)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, X402Error::InvalidPaymentRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn test_create_payment_adds_compute_budget() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 10_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_prioritization_fees(vec![0, 5_000, 1_000, 2_000, 90_000]),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .nonce("nonce")
+            .payment_id("pay_1")
+            .resource("/data")
+            .build()
+            .unwrap();
+        let budget = |transaction: &Transaction| -> Vec<Vec<u8>> {
+            let message = &transaction.message;
+            message
+                .instructions
+                .iter()
+                .filter(|ix| {
+                    message.account_keys[usize::from(ix.program_id_index)]
+                        == solana_sdk::compute_budget::id()
+                })
+                .map(|ix| ix.data.clone())
+                .collect()
+        };
+
+        // No priority fee by default
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        processor.create_payment(&request, &payer).await.unwrap();
+
+        let fixed = SolanaPaymentProcessor::with_backend(backend.clone(), None).with_priority_fee(
+            PriorityFeeConfig {
+                micro_lamports_per_cu: 10_000,
+                compute_unit_limit: 50_000,
+            },
+        );
+        fixed.create_payment(&request, &payer).await.unwrap();
+
+        let estimated = SolanaPaymentProcessor::with_backend(backend.clone(), None)
+            .with_estimated_priority_fee(50_000, 1_500);
+        estimated.create_payment(&request, &payer).await.unwrap();
+
+        let sent = backend.sent_transactions();
+        assert!(budget(&sent[0]).is_empty());
+        assert_eq!(
+            budget(&sent[1]),
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(50_000).data,
+                ComputeBudgetInstruction::set_compute_unit_price(10_000).data,
+            ]
+        );
+        // The median recent fee of 2000 is capped at 1500
+        assert_eq!(
+            budget(&sent[2]),
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(50_000).data,
+                ComputeBudgetInstruction::set_compute_unit_price(1_500).data,
+            ]
+        );
+        // The transfer itself still follows the budget instructions
+        assert_eq!(sent_transfer(&sent[1]), (100_000, 6));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_cost_includes_rent_only_for_missing_account() {
+        let payer = Pubkey::new_unique();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .resource("/data")
+            .build()
+            .unwrap();
+        let rent =
+            solana_sdk::rent::Rent::default().minimum_balance(spl_token::state::Account::LEN);
+
+        // A payer without SOL or tokens still gets an estimate
+        let backend = Arc::new(MockRpcBackend::new().with_mint(mint, 6));
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let estimate = processor.estimate_cost(&request, &payer).await.unwrap();
+        assert_eq!(
+            estimate,
+            CostEstimate {
+                token_amount: 100_000,
+                asset: mint.to_string(),
+                fee_lamports: MOCK_LAMPORTS_PER_SIGNATURE,
+                rent_lamports: rent,
+            }
+        );
+        assert_eq!(
+            estimate.total_lamports(),
+            MOCK_LAMPORTS_PER_SIGNATURE + rent
+        );
+        assert_eq!(backend.call_count("get_fee_for_message"), 1);
+        assert!(backend.sent_transactions().is_empty());
+
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_associated_token_account(recipient, mint, 0),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend, None);
+        let estimate = processor.estimate_cost(&request, &payer).await.unwrap();
+        assert_eq!(estimate.rent_lamports, 0);
+        assert_eq!(estimate.total_lamports(), MOCK_LAMPORTS_PER_SIGNATURE);
+
+        // Native SOL amounts are spent in lamports too
+        let sol = PaymentRequest::builder()
+            .amount("0.10")
+            .native_sol()
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .resource("/data")
+            .build()
+            .unwrap();
+        let estimate = processor.estimate_cost(&sol, &payer).await.unwrap();
+        assert_eq!(estimate.token_amount, 100_000_000);
+        assert_eq!(estimate.rent_lamports, 0);
+        assert_eq!(
+            estimate.total_lamports(),
+            100_000_000 + MOCK_LAMPORTS_PER_SIGNATURE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_payment_reads_chain_state_concurrently() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 10_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_latency(Duration::from_millis(20)),
+        );
+        let mut request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        // Nothing left to read from the mint account
+        request.decimals = Some(6);
+        request.token_program = Some(spl_token::id().to_string());
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        processor.create_payment(&request, &payer).await.unwrap();
+        // Sender balance, recipient token account and blockhash at once
+        assert_eq!(backend.max_concurrent_calls(), 3);
+        assert_eq!(backend.sent_transactions().len(), 1);
+
+        // A failed lookup still surfaces as the same error
+        backend.fail_next(
+            "get_latest_blockhash",
+            ClientErrorKind::Custom("connection refused".to_string()).into(),
+        );
+        let err = processor
+            .create_payment(&request, &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "NETWORK_ERROR");
+        assert!(err.to_string().contains("recent blockhash"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_payment_to_two_recipients() {
+        let payer = Keypair::new();
+        let (alice, bob, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000)
+                .with_associated_token_account(alice, mint, 0)
+                .with_confirmed_sends(),
+        );
+        let request = |recipient: Pubkey, amount: &str| {
+            PaymentRequest::builder()
+                .amount(amount)
+                .asset_address(mint.to_string())
+                .payment_address(recipient.to_string())
+                .network("solana-devnet")
+                .build()
+                .unwrap()
+        };
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        let requests = [request(alice, "0.25"), request(bob, "0.50")];
+        let authorizations = processor
+            .create_batch_payment(&requests, &payer)
+            .await
+            .unwrap();
+        assert_eq!(authorizations.len(), 2);
+        assert_eq!(authorizations[0].signature, authorizations[1].signature);
+        assert_eq!(authorizations[0].payment_address, alice.to_string());
+        assert_eq!(authorizations[1].payment_id, requests[1].payment_id);
+        assert_eq!(backend.sent_transactions().len(), 1);
+        for (authorization, request) in authorizations.iter().zip(&requests) {
+            assert!(processor
+                .verify_payment(authorization, &request.max_amount_required)
+                .await
+                .unwrap());
+        }
+
+        // Each transfer fits the balance, but not both together
+        let err = processor
+            .create_batch_payment(&[request(alice, "0.60"), request(bob, "0.60")], &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INSUFFICIENT_FUNDS");
+
+        let mut devnet_and_mainnet = [request(alice, "0.10"), request(bob, "0.10")];
+        devnet_and_mainnet[1].network = "solana-mainnet".to_string();
+        let err = processor
+            .create_batch_payment(&devnet_and_mainnet, &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_REQUEST");
+        assert_eq!(backend.sent_transactions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_payment_is_signed_but_not_sent() {
+        // Neither SOL nor tokens are needed for a dry run
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(MockRpcBackend::new().with_mint(mint, 6));
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-mainnet")
+            .build()
+            .unwrap();
+        let processor =
+            SolanaPaymentProcessor::with_backend(backend.clone(), None).with_dry_run(true);
+
+        let authorization = processor.create_payment(&request, &payer).await.unwrap();
+        assert!(!authorization.settled);
+        assert!(authorization.transaction_hash.is_none());
+        assert!(Signature::from_str(&authorization.signature).is_ok());
+        assert!(backend.sent_transactions().is_empty());
+        assert_eq!(backend.call_count("simulate_transaction"), 0);
+
+        let err = processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_AUTHORIZATION");
+        assert_eq!(backend.call_count("get_transaction"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_payment_waits_for_requested_commitment() {
+        use solana_transaction_status::TransactionConfirmationStatus::{Confirmed, Finalized};
+
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = |statuses| {
+            Arc::new(
+                MockRpcBackend::new()
+                    .with_mint(mint, 6)
+                    .with_balance(payer.pubkey(), 1_000_000_000)
+                    .with_associated_token_account(payer.pubkey(), mint, 10_000_000)
+                    .with_associated_token_account(recipient, mint, 0)
+                    .with_confirmed_sends()
+                    .with_status_sequence(statuses),
+            )
+        };
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-mainnet")
+            .build()
+            .unwrap();
+        let finalized = ConfirmationConfig {
+            commitment: Some(CommitmentConfig::finalized()),
+            timeout: Some(Duration::from_secs(5)),
+        };
+
+        let finalizing = backend(vec![Confirmed, Finalized]);
+        let processor = SolanaPaymentProcessor::with_backend(finalizing.clone(), None);
+        let authorization = processor
+            .create_payment_with_confirmation(&request, &payer, finalized)
+            .await
+            .unwrap();
+        assert_eq!(finalizing.call_count("get_signature_status"), 2);
+        assert!(authorization.transaction_hash.is_some());
+
+        // Never finalized: the timeout reports the signature still pending
+        let stuck = backend(vec![Confirmed]);
+        let processor = SolanaPaymentProcessor::with_backend(stuck.clone(), None);
+        let err = processor
+            .create_payment_with_confirmation(
+                &request,
+                &payer,
+                ConfirmationConfig {
+                    timeout: Some(Duration::from_millis(100)),
+                    ..finalized
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "TIMEOUT");
+        let sent = stuck.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert!(err.to_string().contains(&sent[0].signatures[0].to_string()));
+
+        // The default waits for nothing beyond the send itself
+        let processor = SolanaPaymentProcessor::with_backend(stuck.clone(), None);
+        processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(stuck.call_count("get_signature_status"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_poll_confirmation_resolves_pending_payment() {
+        use solana_transaction_status::TransactionConfirmationStatus::{Confirmed, Processed};
+
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 10_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_confirmed_sends()
+                .with_status_sequence(vec![Processed, Processed, Confirmed]),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        let err = processor
+            .create_payment_with_confirmation(
+                &request,
+                &payer,
+                ConfirmationConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    timeout: Some(Duration::from_millis(100)),
+                },
+            )
+            .await
+            .unwrap_err();
+        let pending = err.pending_signature().unwrap();
+        assert_eq!(
+            pending,
+            backend.sent_transactions()[0].signatures[0].to_string()
+        );
+
+        // Still processed on the first poll, confirmed on the next
+        let authorization = processor
+            .poll_confirmation(&request, pending, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(backend.call_count("get_signature_status"), 3);
+        assert_eq!(authorization.signature, pending);
+        assert_eq!(authorization.public_key, payer.pubkey().to_string());
+        assert_eq!(authorization.payment_id, request.payment_id);
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+        assert!(X402Error::Network("down".to_string())
+            .pending_signature()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_payment_events_follow_each_stage() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 10_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_confirmed_sends(),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        let (sender, mut receiver) = mpsc::channel(16);
+        let processor =
+            SolanaPaymentProcessor::with_backend(backend, None).with_payment_events(sender);
+
+        let authorization = processor
+            .create_payment_with_confirmation(
+                &request,
+                &payer,
+                ConfirmationConfig {
+                    commitment: Some(CommitmentConfig::finalized()),
+                    timeout: None,
+                },
+            )
+            .await
+            .unwrap();
+        drop(processor);
+
+        let mut stages = Vec::new();
+        while let Some(event) = receiver.recv().await {
+            assert_eq!(event.payment_id(), request.payment_id);
+            let stage = match &event {
+                PaymentEvent::Building { .. } => "building",
+                PaymentEvent::Broadcasting { .. } => "broadcasting",
+                PaymentEvent::Confirming { .. } => "confirming",
+                PaymentEvent::Done { .. } => "done",
+                PaymentEvent::Failed { error, .. } => panic!("payment failed: {}", error),
+            };
+            stages.push((stage, event.signature().map(str::to_string)));
+        }
+        let signature = Some(authorization.signature);
+        assert_eq!(
+            stages,
+            vec![
+                ("building", None),
+                ("broadcasting", signature.clone()),
+                ("confirming", signature.clone()),
+                ("done", signature),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retried_payment_recovers_recorded_signature() {
+        use solana_transaction_status::TransactionConfirmationStatus::{Confirmed, Processed};
+
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 10_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_confirmed_sends()
+                .with_status_sequence(vec![Processed, Confirmed]),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        let store = Arc::new(crate::store::MemoryStore::new());
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None)
+            .with_payment_signature_store(store.clone());
+
+        let err = processor
+            .create_payment_with_confirmation(
+                &request,
+                &payer,
+                ConfirmationConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    timeout: Some(Duration::from_millis(100)),
+                },
+            )
+            .await
+            .unwrap_err();
+        let pending = err.pending_signature().unwrap().to_string();
+        assert_eq!(
+            store
+                .get_payment_signature(&request.payment_id)
+                .await
+                .unwrap(),
+            Some(pending.clone())
+        );
+
+        // The retry confirms the recorded transaction instead of paying again
+        let authorization = processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(authorization.signature, pending);
+        assert_eq!(backend.sent_transactions().len(), 1);
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+
+        // Other payment IDs are paid as usual
+        let other = PaymentRequest {
+            payment_id: "other".to_string(),
+            ..request.clone()
+        };
+        processor.create_payment(&other, &payer).await.unwrap();
+        assert_eq!(backend.sent_transactions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_broadcast_is_not_recorded() {
+        use solana_client::client_error::ClientErrorKind;
+
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 10_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_confirmed_sends(),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        let store = Arc::new(crate::store::MemoryStore::new());
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None)
+            .with_payment_signature_store(store.clone());
+
+        backend.fail_next(
+            "send_and_confirm_transaction",
+            ClientErrorKind::Custom("connection reset".to_string()).into(),
+        );
+        let err = processor
+            .create_payment(&request, &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "TRANSACTION_BROADCAST_FAILED");
+        assert!(store
+            .get_payment_signature(&request.payment_id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Nothing was sent, so the retry pays straight away
+        let authorization = processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(backend.sent_transactions().len(), 1);
+        assert_eq!(
+            store
+                .get_payment_signature(&request.payment_id)
+                .await
+                .unwrap(),
+            Some(authorization.signature.clone())
+        );
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_scheme_is_not_paid() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 10_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_confirmed_sends(),
+        );
+        let mut request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        request.scheme = "solana-spl-v2".to_string();
+        let err = processor
+            .create_payment(&request, &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "CONFIGURATION_ERROR");
+        assert!(backend.sent_transactions().is_empty());
+
+        request.scheme = crate::models::SCHEME.to_string();
+        processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(backend.sent_transactions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_payment_without_sol_for_fees_is_refused() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_associated_token_account(payer.pubkey(), mint, 10_000_000),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        // The recipient has no token account, so the payer also owes its rent
+        let err = processor
+            .create_payment(&request, &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INSUFFICIENT_FUNDS");
+        let details = err.insufficient_funds().unwrap();
+        let rent =
+            solana_sdk::rent::Rent::default().minimum_balance(spl_token::state::Account::LEN);
+        assert_eq!(details.mint, NATIVE_SOL);
+        assert_eq!(details.required, rent + LAMPORTS_PER_SIGNATURE);
+        assert_eq!(details.available, 0);
+        assert!(backend.sent_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_payment_from_missing_token_account() {
+        let (missing, empty) = (Keypair::new(), Keypair::new());
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(missing.pubkey(), 1_000_000_000)
+                .with_balance(empty.pubkey(), 1_000_000_000)
+                .with_associated_token_account(empty.pubkey(), mint, 0)
+                .with_associated_token_account(recipient, mint, 0),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .build()
+            .unwrap();
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        let err = processor
+            .create_payment(&request, &missing)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_REQUEST");
+        assert!(err.to_string().contains("has no token account for mint"));
+        assert!(err.insufficient_funds().is_none());
+
+        let err = processor
+            .create_payment(&request, &empty)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INSUFFICIENT_FUNDS");
+        assert_eq!(err.insufficient_funds().unwrap().available, 0);
+        assert!(backend.sent_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_native_sol_payment() {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_balance(payer.pubkey(), 2_000_000_000)
+                .with_confirmed_sends(),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let request = PaymentRequest::new_sol(
+            "0.25".to_string(),
+            recipient.to_string(),
+            "solana-devnet".to_string(),
+            chrono::Utc::now() + chrono::Duration::seconds(300),
+            "nonce".to_string(),
+            "pay_1".to_string(),
+            "/data".to_string(),
+        );
+        assert_eq!(request.asset_type, "SOL");
+        assert_eq!(request.asset_address, "");
+
+        let authorization = processor.create_payment(&request, &payer).await.unwrap();
+        assert!(authorization.is_native_sol());
+
+        // A single System transfer and no token account setup
+        let sent = backend.sent_transactions();
+        let instructions = &sent[0].message.instructions;
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(
+            sent[0].message.account_keys[usize::from(instructions[0].program_id_index)],
+            solana_sdk::system_program::id()
+        );
+        assert_eq!(backend.call_count("get_token_account_balance"), 0);
+
+        assert!(processor
+            .verify_payment(&authorization, "0.25")
+            .await
+            .unwrap());
+        let err = processor
+            .verify_payment(&authorization, "0.30")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("less than required"));
+
+        // A token transfer to the recipient doesn't pay a SOL request
+        let signature = Signature::new_unique();
+        let tokens = SolanaPaymentProcessor::with_backend(
+            Arc::new(MockRpcBackend::new().with_fixture(
+                TransactionFixture::new(signature).transfer(
+                    Pubkey::new_unique(),
+                    recipient,
+                    250_000_000,
+                ),
+            )),
+            None,
+        );
+        let mut spoofed = authorization.clone();
+        spoofed.signature = signature.to_string();
+        let err = tokens.verify_payment(&spoofed, "0.25").await.unwrap_err();
+        assert!(err.to_string().contains("transferred no SOL"));
+
+        // Payers without enough SOL are stopped before sending
+        let err = processor
+            .create_payment(&request, &Keypair::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INSUFFICIENT_FUNDS");
+        let details = err.insufficient_funds().unwrap();
+        assert_eq!((details.available, details.mint.as_str()), (0, NATIVE_SOL));
+    }
+
+    #[tokio::test]
+    async fn test_sol_request_with_token_mint_is_rejected() {
+        let payer = Keypair::new();
+        let backend = Arc::new(MockRpcBackend::new().with_balance(payer.pubkey(), 2_000_000_000));
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let mut request = PaymentRequest::new_sol(
+            "0.25".to_string(),
+            Pubkey::new_unique().to_string(),
+            "solana-devnet".to_string(),
+            chrono::Utc::now() + chrono::Duration::seconds(300),
+            "nonce".to_string(),
+            "pay_1".to_string(),
+            "/data".to_string(),
+        );
+        request.asset_address = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string();
+
+        let err = processor
+            .create_payment(&request, &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INVALID_PAYMENT_REQUEST");
+        assert!(err.to_string().contains("must not name a token mint"));
+        assert!(backend.sent_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_claim_escrow() {
+        let (recipient, mint) = (Keypair::new(), Pubkey::new_unique());
+        let escrow = EscrowProgram::new(Pubkey::new_unique());
+        let pda = escrow.escrow_address(&recipient.pubkey());
+        let backend = Arc::new(MockRpcBackend::new().with_mint(mint, 6).with_token_account(
+            escrow.escrow_token_account(&recipient.pubkey(), &mint),
+            mint,
+            pda,
+            250_000,
+        ));
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        processor
+            .claim_escrow(&escrow, &mint, &recipient)
+            .await
+            .unwrap();
+
+        let sent = backend.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        let message = &sent[0].message;
+        assert_eq!(message.account_keys[0], recipient.pubkey());
+        let instructions = &message.instructions;
+        // The recipient's token account is created before claiming into it
+        assert_eq!(instructions.len(), 2);
+        let claim = &instructions[1];
+        assert_eq!(
+            message.account_keys[usize::from(claim.program_id_index)],
+            escrow.program_id
+        );
+        assert_eq!(claim.data[8..], 250_000u64.to_le_bytes());
+        assert_eq!(message.account_keys[usize::from(claim.accounts[0])], pda);
+
+        // An empty escrow has nothing to claim
+        let empty = EscrowProgram::new(Pubkey::new_unique());
+        let err = processor
+            .claim_escrow(&empty, &mint, &recipient)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "INSUFFICIENT_FUNDS");
+    }
+
+    #[tokio::test]
+    async fn test_stale_cached_blockhash_is_refreshed() {
+        use solana_client::client_error::ClientErrorKind;
+
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000)
+                .with_associated_token_account(recipient, mint, 0),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None)
+            .with_blockhash_cache(Duration::from_secs(30));
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .nonce("nonce")
+            .payment_id("pay_1")
+            .resource("/data")
+            .build()
+            .unwrap();
+
+        processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(backend.call_count("get_latest_blockhash"), 1);
+
+        // The cluster no longer knows the cached blockhash
+        backend.fail_next(
+            "send_and_confirm_transaction",
+            ClientErrorKind::TransactionError(TransactionError::BlockhashNotFound).into(),
+        );
+        processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(backend.call_count("get_latest_blockhash"), 2);
+        assert_eq!(backend.call_count("send_and_confirm_transaction"), 3);
+        assert_eq!(backend.sent_transactions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_expired_broadcasts_are_retried() {
+        use solana_client::client_error::ClientErrorKind;
+
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000)
+                .with_associated_token_account(recipient, mint, 0),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .nonce("nonce")
+            .payment_id("pay_1")
+            .resource("/data")
+            .build()
+            .unwrap();
+        let expire = |times: usize| {
+            for _ in 0..times {
+                backend.fail_next(
+                    "send_and_confirm_transaction",
+                    ClientErrorKind::RpcError(RpcError::ForUser(
+                        "unable to confirm transaction. This can happen in situations such as \
+                         transaction expiration and insufficient fee-payer funds"
+                            .to_string(),
+                    ))
+                    .into(),
+                );
+            }
+        };
+
+        // Two expiries are within the default limit of three retries
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        expire(2);
+        processor.create_payment(&request, &payer).await.unwrap();
+        assert_eq!(backend.call_count("send_and_confirm_transaction"), 3);
+        assert_eq!(backend.call_count("get_latest_blockhash"), 3);
+
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None)
+            .with_max_broadcast_retries(1);
+        expire(2);
+        let err = processor
+            .create_payment(&request, &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "TRANSACTION_BROADCAST_FAILED");
+        assert_eq!(backend.call_count("send_and_confirm_transaction"), 5);
+
+        // Fatal errors are not retried
+        backend.fail_next(
+            "send_and_confirm_transaction",
+            ClientErrorKind::TransactionError(TransactionError::InsufficientFundsForFee).into(),
+        );
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        assert!(processor.create_payment(&request, &payer).await.is_err());
+        assert_eq!(backend.call_count("send_and_confirm_transaction"), 6);
+    }
+
+    #[tokio::test]
+    async fn test_failed_simulation_is_not_sent() {
+        use solana_sdk::instruction::InstructionError;
+
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_simulation_error(
+                    TransactionError::InstructionError(0, InstructionError::Custom(1)),
+                    vec![
+                        "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [1]"
+                            .to_string(),
+                        "Program log: Error: insufficient funds".to_string(),
+                    ],
+                ),
+        );
+        let request = |network: &str| {
+            PaymentRequest::builder()
+                .amount("0.10")
+                .asset_address(mint.to_string())
+                .payment_address(recipient.to_string())
+                .network(network.to_string())
+                .nonce("nonce")
+                .payment_id("pay_1")
+                .resource("/data")
+                .build()
+                .unwrap()
+        };
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        // Mainnet payments are simulated by default
+        let err = processor
+            .create_payment(&request("solana-mainnet"), &payer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "TRANSACTION_BROADCAST_FAILED");
+        assert!(err.to_string().contains("holds too little"));
+        assert!(err.to_string().contains("Error: insufficient funds"));
+        assert_eq!(backend.call_count("simulate_transaction"), 1);
+        assert_eq!(backend.call_count("send_and_confirm_transaction"), 0);
+
+        // Devnet payments are sent directly unless simulation is enabled
+        processor
+            .create_payment(&request("solana-devnet"), &payer)
+            .await
+            .unwrap();
+        assert_eq!(backend.call_count("simulate_transaction"), 1);
+
+        let processor = processor.with_simulate_before_send(true);
+        assert!(processor
+            .create_payment(&request("solana-devnet"), &payer)
+            .await
+            .is_err());
+        assert_eq!(backend.call_count("simulate_transaction"), 2);
+        assert_eq!(backend.call_count("send_and_confirm_transaction"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_v0_payment_with_lookup_table() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let recipient_ata = get_associated_token_address(&recipient, &mint);
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000)
+                .with_associated_token_account(recipient, mint, 0),
+        );
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![mint, recipient_ata],
+        };
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None)
+            .with_transaction_version(TransactionVersion::V0)
+            .with_address_lookup_tables(vec![table.clone()]);
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .nonce("nonce")
+            .payment_id("pay_1")
+            .resource("/data")
+            .build()
+            .unwrap();
+
+        let authorization = processor.create_payment(&request, &payer).await.unwrap();
+        assert!(backend.sent_transactions().is_empty());
+        let sent = backend.sent_versioned_transactions();
+        let transaction = &sent[0];
+        assert_eq!(
+            authorization.signature,
+            transaction.signatures[0].to_string()
+        );
+
+        // The mint and recipient account are loaded from the table
+        let VersionedMessage::V0(message) = &transaction.message else {
+            panic!("expected a v0 message");
+        };
+        assert_eq!(message.address_table_lookups.len(), 1);
+        assert_eq!(message.address_table_lookups[0].account_key, table.key);
+        assert!(!message.account_keys.contains(&recipient_ata));
+        assert_eq!(message.account_keys[0], payer.pubkey());
+
+        // Signed by the payer and stable over the wire
+        assert!(transaction
+            .verify_with_results()
+            .into_iter()
+            .all(|valid| valid));
+        let wire = bincode::serialize(transaction).unwrap();
+        let decoded: VersionedTransaction = bincode::deserialize(&wire).unwrap();
+        assert_eq!(&decoded, transaction);
+    }
+
+    #[tokio::test]
+    async fn test_payment_id_memo_is_attached_and_verified() {
+        let payer = Keypair::new();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_confirmed_sends(),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .nonce("nonce")
+            .payment_id("pay_memo")
+            .resource("/data")
+            .build()
+            .unwrap();
+        let options = VerificationOptions::default().with_payment_id_memo_required();
+
+        let processor =
+            SolanaPaymentProcessor::with_backend(backend.clone(), None).with_payment_id_memo();
+        let authorization = processor.create_payment(&request, &payer).await.unwrap();
+        let memo = backend.sent_transactions()[0]
+            .message
+            .instructions
+            .last()
+            .unwrap()
+            .data
+            .clone();
+        assert_eq!(memo, b"pay_memo");
+        assert!(processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap());
+
+        // A transfer without the memo can't be reconciled
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+        let authorization = processor.create_payment(&request, &payer).await.unwrap();
+        let err = processor
+            .verify_payment_with_options(&authorization, "0.10", &options)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "PAYMENT_VERIFICATION_FAILED");
+        assert!(err.to_string().contains("has no memo for payment pay_memo"));
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+    }
+
+    /// Signer whose key is held by a separate wallet task, like a hardware
+    /// or browser wallet; the processor only ever sees the public key
+    struct WalletSigner {
+        pubkey: Pubkey,
+        wallet: mpsc::Sender<(Vec<u8>, tokio::sync::oneshot::Sender<Signature>)>,
+    }
+
+    impl WalletSigner {
+        fn spawn(keypair: Keypair) -> Self {
+            let (wallet, mut requests) =
+                mpsc::channel::<(Vec<u8>, tokio::sync::oneshot::Sender<Signature>)>(1);
+            let pubkey = Signer::pubkey(&keypair);
+            tokio::spawn(async move {
+                while let Some((message, reply)) = requests.recv().await {
+                    let _ = reply.send(keypair.sign_message(&message));
+                }
+            });
+            Self { pubkey, wallet }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl signer::TransactionSigner for WalletSigner {
+        fn pubkey(&self) -> Pubkey {
+            self.pubkey
+        }
+
+        async fn sign(&self, message: &VersionedMessage) -> X402Result<Signature> {
+            let (reply, signature) = tokio::sync::oneshot::channel();
+            self.wallet
+                .send((message.serialize(), reply))
+                .await
+                .map_err(|_| X402Error::Blockchain("Wallet disconnected".to_string()))?;
+            signature
+                .await
+                .map_err(|_| X402Error::Blockchain("Wallet declined to sign".to_string()))
+        }
+    }
+
+    /// Signer that answers with a signature that isn't its own
+    struct ForgingSigner(Pubkey);
+
+    #[async_trait::async_trait]
+    impl signer::TransactionSigner for ForgingSigner {
+        fn pubkey(&self) -> Pubkey {
+            self.0
+        }
+
+        async fn sign(&self, message: &VersionedMessage) -> X402Result<Signature> {
+            Ok(Keypair::new().sign_message(&message.serialize()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_payment_signed_by_external_signer() {
+        let keypair = Keypair::new();
+        let payer = keypair.pubkey();
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_mint(mint, 6)
+                .with_balance(payer, 1_000_000_000)
+                .with_associated_token_account(payer, mint, 1_000_000)
+                .with_associated_token_account(recipient, mint, 0)
+                .with_confirmed_sends(),
+        );
+        let request = PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address(mint.to_string())
+            .payment_address(recipient.to_string())
+            .network("solana-devnet")
+            .nonce("nonce")
+            .payment_id("pay_wallet")
+            .resource("/data")
+            .build()
+            .unwrap();
+        let processor = SolanaPaymentProcessor::with_backend(backend.clone(), None);
+
+        // The key moves into the wallet; only its public key stays here
+        let signer = WalletSigner::spawn(keypair);
+        let authorization = processor.create_payment(&request, &signer).await.unwrap();
+        assert_eq!(authorization.public_key, payer.to_string());
+        let sent = backend.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].message.account_keys[0], payer);
+        sent[0].verify().unwrap();
+        assert!(processor
+            .verify_payment(&authorization, "0.10")
+            .await
+            .unwrap());
+
+        // A signature by any other key is refused before broadcasting
+        let err = processor
+            .create_payment(&request, &ForgingSigner(payer))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "BLOCKCHAIN_ERROR");
+        assert!(err.to_string().contains("invalid signature"));
+        assert_eq!(backend.sent_transactions().len(), 1);
+    }
+}
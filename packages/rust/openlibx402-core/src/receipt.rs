@@ -0,0 +1,108 @@
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{X402Error, X402Result},
+    models::PaymentAuthorization,
+};
+
+/// Header servers may attach the base64-encoded `PaymentReceipt` to
+pub const RECEIPT_HEADER: &str = "X-Payment-Receipt";
+
+/// Server's record of a payment it verified and accepted
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PaymentReceipt {
+    /// Payment ID of the accepted payment
+    pub payment_id: String,
+
+    /// Transaction signature of the payment
+    pub signature: String,
+
+    /// Amount the payment was verified against
+    pub amount: String,
+
+    /// Token mint address, empty for native SOL
+    pub asset_address: String,
+
+    /// Recipient wallet address
+    pub payment_address: String,
+
+    /// Network identifier
+    pub network: String,
+
+    /// Payer's public key
+    pub payer: String,
+
+    /// Resource the payment was accepted for
+    pub resource: String,
+
+    /// When the payment was verified
+    pub verified_at: DateTime<Utc>,
+}
+
+impl PaymentReceipt {
+    /// Receipt for `authorization`, verified now as paying `amount` for `resource`
+    pub fn new(authorization: &PaymentAuthorization, amount: &str, resource: &str) -> Self {
+        Self {
+            payment_id: authorization.payment_id.clone(),
+            signature: authorization.signature.clone(),
+            amount: amount.to_string(),
+            asset_address: authorization.asset_address.clone(),
+            payment_address: authorization.payment_address.clone(),
+            network: authorization.network.clone(),
+            payer: authorization.public_key.clone(),
+            resource: resource.to_string(),
+            verified_at: Utc::now(),
+        }
+    }
+
+    /// Convert to JSON string
+    pub fn to_json(&self) -> X402Result<String> {
+        serde_json::to_string(self).map_err(|e| e.into())
+    }
+
+    /// Parse receipt from JSON string
+    pub fn from_json(json: &str) -> X402Result<Self> {
+        serde_json::from_str(json).map_err(|e| {
+            X402Error::Serialization(format!("Failed to parse payment receipt: {}", e))
+        })
+    }
+
+    /// Encode receipt for the X-Payment-Receipt header
+    pub fn to_header_value(&self) -> X402Result<String> {
+        let json = self.to_json()?;
+        Ok(general_purpose::STANDARD.encode(json.as_bytes()))
+    }
+
+    /// Decode receipt from an X-Payment-Receipt header value
+    pub fn from_header_value(encoded: &str) -> X402Result<Self> {
+        let decoded = general_purpose::STANDARD.decode(encoded)?;
+        let json = String::from_utf8(decoded)
+            .map_err(|e| X402Error::Serialization(format!("Invalid UTF-8 in header: {}", e)))?;
+        Self::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt_header_roundtrip() {
+        let authorization = PaymentAuthorization::new(
+            "pay_123".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "sig123".to_string(),
+            "payer123".to_string(),
+        );
+        let receipt = PaymentReceipt::new(&authorization, "0.10", "/premium");
+        assert_eq!(receipt.payer, "payer123");
+
+        let header = receipt.to_header_value().unwrap();
+        assert_eq!(PaymentReceipt::from_header_value(&header).unwrap(), receipt);
+    }
+}
@@ -0,0 +1,289 @@
+//! Solana RPC access used by the payment processor
+//!
+//! [`SolanaPaymentProcessor`](crate::SolanaPaymentProcessor) talks to the chain
+//! through the [`RpcBackend`] trait. The production implementation is the
+//! Solana nonblocking `RpcClient`; [`failover::FailoverBackend`] fails over
+//! between several endpoints, and tests can substitute [`mock::MockRpcBackend`]
+//! (enabled with the `test-util` feature) to script chain state
+//! deterministically.
+//!
+//! # Threading
+//!
+//! The nonblocking client awaits its HTTP requests, so RPC calls yield the
+//! tokio worker thread instead of holding it for the round trip; a server
+//! verifying payments keeps serving other requests meanwhile. The blocking
+//! `solana_client::rpc_client::RpcClient` also implements [`RpcBackend`] for
+//! existing callers of
+//! [`with_backend`](crate::SolanaPaymentProcessor::with_backend), but each of
+//! its calls blocks the thread it runs on and should not be used inside an
+//! async server.
+
+use async_trait::async_trait;
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    nonblocking::rpc_client::RpcClient as NonblockingRpcClient,
+    rpc_client::RpcClient,
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, VersionedTransaction},
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiTransactionEncoding,
+};
+
+pub mod failover;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+
+/// Solana RPC operations required by the payment processor
+#[async_trait]
+pub trait RpcBackend: Send + Sync {
+    /// Fetch an account, returning `None` if it doesn't exist
+    async fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Option<Account>>;
+
+    /// Get the native SOL balance of an account in lamports
+    async fn get_balance(&self, pubkey: &Pubkey) -> ClientResult<u64>;
+
+    /// Get the raw balance of a token account in the token's smallest unit
+    async fn get_token_account_balance(&self, token_account: &Pubkey) -> ClientResult<u64>;
+
+    /// Get the minimum lamports for an account of `data_len` bytes to be rent-exempt
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64>;
+
+    /// Get the latest blockhash
+    async fn get_latest_blockhash(&self) -> ClientResult<Hash>;
+
+    /// Get the minimum priority fees, in micro-lamports per compute unit,
+    /// that landed transactions writing to `accounts` paid in recent slots
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> ClientResult<Vec<u64>>;
+
+    /// Get the fee in lamports the cluster would charge for `message`
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> ClientResult<u64>;
+
+    /// Broadcast a signed transaction and wait for confirmation
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature>;
+
+    /// Broadcast a signed versioned transaction and wait for confirmation
+    async fn send_and_confirm_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<Signature>;
+
+    /// Simulate a signed transaction without broadcasting it
+    ///
+    /// Legacy transactions convert with `VersionedTransaction::from`.
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<RpcSimulateTransactionResult>;
+
+    /// Get the slot the cluster has reached
+    async fn get_slot(&self) -> ClientResult<u64>;
+
+    /// Look up the status of a signature, returning `None` if it is unknown
+    ///
+    /// Nodes only keep statuses for recent slots; with
+    /// `search_transaction_history` older, finalized signatures are looked up
+    /// in the ledger history as well.
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+        search_transaction_history: bool,
+    ) -> ClientResult<Option<TransactionStatus>>;
+
+    /// Fetch a confirmed transaction
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+        encoding: UiTransactionEncoding,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta>;
+}
+
+#[async_trait]
+impl RpcBackend for RpcClient {
+    async fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Option<Account>> {
+        self.get_account_with_commitment(pubkey, self.commitment())
+            .map(|response| response.value)
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> ClientResult<u64> {
+        RpcClient::get_balance(self, pubkey)
+    }
+
+    async fn get_token_account_balance(&self, token_account: &Pubkey) -> ClientResult<u64> {
+        let balance = RpcClient::get_token_account_balance(self, token_account)?;
+        balance.amount.parse::<u64>().map_err(|e| {
+            ClientErrorKind::Custom(format!("Failed to parse token balance: {}", e)).into()
+        })
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64> {
+        RpcClient::get_minimum_balance_for_rent_exemption(self, data_len)
+    }
+
+    async fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+        RpcClient::get_latest_blockhash(self)
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> ClientResult<Vec<u64>> {
+        let fees = RpcClient::get_recent_prioritization_fees(self, accounts)?;
+        Ok(fees.into_iter().map(|fee| fee.prioritization_fee).collect())
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> ClientResult<u64> {
+        match message {
+            VersionedMessage::Legacy(message) => RpcClient::get_fee_for_message(self, message),
+            VersionedMessage::V0(message) => RpcClient::get_fee_for_message(self, message),
+        }
+    }
+
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature> {
+        RpcClient::send_and_confirm_transaction(self, transaction)
+    }
+
+    async fn send_and_confirm_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<Signature> {
+        RpcClient::send_and_confirm_transaction(self, transaction)
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<RpcSimulateTransactionResult> {
+        RpcClient::simulate_transaction(self, transaction).map(|response| response.value)
+    }
+
+    async fn get_slot(&self) -> ClientResult<u64> {
+        RpcClient::get_slot(self)
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+        search_transaction_history: bool,
+    ) -> ClientResult<Option<TransactionStatus>> {
+        let signatures = [*signature];
+        let response = if search_transaction_history {
+            self.get_signature_statuses_with_history(&signatures)?
+        } else {
+            self.get_signature_statuses(&signatures)?
+        };
+        Ok(response.value.into_iter().next().flatten())
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+        encoding: UiTransactionEncoding,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        RpcClient::get_transaction(self, signature, encoding)
+    }
+}
+
+#[async_trait]
+impl RpcBackend for NonblockingRpcClient {
+    async fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Option<Account>> {
+        self.get_account_with_commitment(pubkey, self.commitment())
+            .await
+            .map(|response| response.value)
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> ClientResult<u64> {
+        NonblockingRpcClient::get_balance(self, pubkey).await
+    }
+
+    async fn get_token_account_balance(&self, token_account: &Pubkey) -> ClientResult<u64> {
+        let balance = NonblockingRpcClient::get_token_account_balance(self, token_account).await?;
+        balance.amount.parse::<u64>().map_err(|e| {
+            ClientErrorKind::Custom(format!("Failed to parse token balance: {}", e)).into()
+        })
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64> {
+        NonblockingRpcClient::get_minimum_balance_for_rent_exemption(self, data_len).await
+    }
+
+    async fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+        NonblockingRpcClient::get_latest_blockhash(self).await
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> ClientResult<Vec<u64>> {
+        let fees = NonblockingRpcClient::get_recent_prioritization_fees(self, accounts).await?;
+        Ok(fees.into_iter().map(|fee| fee.prioritization_fee).collect())
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> ClientResult<u64> {
+        match message {
+            VersionedMessage::Legacy(message) => {
+                NonblockingRpcClient::get_fee_for_message(self, message).await
+            }
+            VersionedMessage::V0(message) => {
+                NonblockingRpcClient::get_fee_for_message(self, message).await
+            }
+        }
+    }
+
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature> {
+        NonblockingRpcClient::send_and_confirm_transaction(self, transaction).await
+    }
+
+    async fn send_and_confirm_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<Signature> {
+        NonblockingRpcClient::send_and_confirm_transaction(self, transaction).await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<RpcSimulateTransactionResult> {
+        NonblockingRpcClient::simulate_transaction(self, transaction)
+            .await
+            .map(|response| response.value)
+    }
+
+    async fn get_slot(&self) -> ClientResult<u64> {
+        NonblockingRpcClient::get_slot(self).await
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+        search_transaction_history: bool,
+    ) -> ClientResult<Option<TransactionStatus>> {
+        let signatures = [*signature];
+        let response = if search_transaction_history {
+            self.get_signature_statuses_with_history(&signatures)
+                .await?
+        } else {
+            self.get_signature_statuses(&signatures).await?
+        };
+        Ok(response.value.into_iter().next().flatten())
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+        encoding: UiTransactionEncoding,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        NonblockingRpcClient::get_transaction(self, signature, encoding).await
+    }
+}
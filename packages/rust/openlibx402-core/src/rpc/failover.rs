@@ -0,0 +1,466 @@
+//! RPC backend that fails over between several endpoints
+//!
+//! Public RPC endpoints rate-limit and go down. [`FailoverBackend`] holds a
+//! prioritized list of backends and sends each call to the first healthy one,
+//! moving on to the next when an endpoint is unreachable or answers with a
+//! server error or `429 Too Many Requests`. An endpoint that fails is skipped
+//! for a cooldown period and only tried again once the healthy ones are
+//! exhausted or the cooldown has passed.
+
+use async_trait::async_trait;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    nonblocking::rpc_client::RpcClient,
+    rpc_request::RpcError,
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, VersionedTransaction},
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiTransactionEncoding,
+};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::RpcBackend;
+
+/// How long a failed endpoint is skipped by default
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// JSON-RPC error code of a node that is behind or otherwise unhealthy
+const NODE_UNHEALTHY: i64 = -32005;
+
+/// Health of one endpoint of a [`FailoverBackend`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointHealth {
+    /// Endpoint label, the RPC URL for endpoints created from URLs
+    pub label: String,
+
+    /// Failures since the endpoint last answered
+    pub consecutive_failures: u32,
+
+    /// Whether the endpoint is tried in priority order (`false` while cooling down)
+    pub healthy: bool,
+}
+
+struct Endpoint {
+    label: String,
+    backend: Arc<dyn RpcBackend>,
+    state: Mutex<EndpointState>,
+}
+
+#[derive(Default)]
+struct EndpointState {
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+impl Endpoint {
+    fn state(&self) -> std::sync::MutexGuard<'_, EndpointState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.state()
+            .unhealthy_until
+            .is_none_or(|until| until <= now)
+    }
+
+    fn record_success(&self) {
+        *self.state() = EndpointState::default();
+    }
+
+    fn record_failure(&self, cooldown: Duration) {
+        let mut state = self.state();
+        state.consecutive_failures += 1;
+        state.unhealthy_until = Some(Instant::now() + cooldown);
+    }
+}
+
+/// [`RpcBackend`] that fails over between prioritized endpoints
+///
+/// Reads and broadcasts go to the first healthy endpoint. Errors that say
+/// nothing about the request itself (connection failures, timeouts, 5xx and
+/// 429 responses, unhealthy nodes) move on to the next endpoint; any other
+/// error is returned as is.
+///
+/// Broadcasts are idempotent across failover: before resending a transaction
+/// to the next endpoint, its signature is looked up there, and a transaction
+/// that already landed is not sent again.
+///
+/// ```rust,no_run
+/// use openlibx402_core::SolanaPaymentProcessor;
+///
+/// let processor = SolanaPaymentProcessor::with_rpc_urls(
+///     &["https://rpc.example.com", "https://api.mainnet-beta.solana.com"],
+///     None,
+/// );
+/// ```
+pub struct FailoverBackend {
+    endpoints: Vec<Endpoint>,
+    cooldown: Duration,
+}
+
+impl FailoverBackend {
+    /// Create a backend without endpoints; add them with [`endpoint`](Self::endpoint)
+    pub fn new() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+
+    /// Create a backend over RPC URLs, in order of preference
+    pub fn from_urls(rpc_urls: &[&str], commitment: Option<CommitmentConfig>) -> Self {
+        let commitment = commitment.unwrap_or(CommitmentConfig::confirmed());
+        rpc_urls.iter().fold(Self::new(), |backend, url| {
+            backend.endpoint(
+                url,
+                Arc::new(RpcClient::new_with_commitment(url.to_string(), commitment)),
+            )
+        })
+    }
+
+    /// Add a lower-priority endpoint
+    pub fn endpoint(mut self, label: &str, backend: Arc<dyn RpcBackend>) -> Self {
+        self.endpoints.push(Endpoint {
+            label: label.to_string(),
+            backend,
+            state: Mutex::default(),
+        });
+        self
+    }
+
+    /// Skip a failed endpoint for `cooldown` (default [`DEFAULT_COOLDOWN`])
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Current health of every endpoint, in priority order
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        let now = Instant::now();
+        self.endpoints
+            .iter()
+            .map(|endpoint| {
+                let healthy = endpoint.is_healthy(now);
+                EndpointHealth {
+                    label: endpoint.label.clone(),
+                    consecutive_failures: endpoint.state().consecutive_failures,
+                    healthy,
+                }
+            })
+            .collect()
+    }
+
+    /// Endpoints to try: healthy ones by priority, then the cooling-down ones
+    fn order(&self) -> Vec<&Endpoint> {
+        let now = Instant::now();
+        let (healthy, cooling): (Vec<_>, Vec<_>) =
+            self.endpoints.iter().partition(|e| e.is_healthy(now));
+        healthy.into_iter().chain(cooling).collect()
+    }
+
+    /// Run `call` against endpoints until one answers
+    async fn call<T, F, Fut>(&self, call: F) -> ClientResult<T>
+    where
+        F: Fn(Arc<dyn RpcBackend>) -> Fut,
+        Fut: Future<Output = ClientResult<T>>,
+    {
+        let mut last_error = None;
+        for endpoint in self.order() {
+            match call(endpoint.backend.clone()).await {
+                Err(e) if is_failover_error(&e) => {
+                    endpoint.record_failure(self.cooldown);
+                    last_error = Some(e);
+                }
+                result => {
+                    endpoint.record_success();
+                    return result;
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(no_endpoints))
+    }
+
+    /// Broadcast the transaction signed `signature` through `send`, failing over
+    /// without re-sending it once an endpoint has seen it land
+    async fn broadcast<F, Fut>(&self, signature: Signature, send: F) -> ClientResult<Signature>
+    where
+        F: Fn(Arc<dyn RpcBackend>) -> Fut,
+        Fut: Future<Output = ClientResult<Signature>>,
+    {
+        let mut last_error = None;
+
+        for (attempt, endpoint) in self.order().into_iter().enumerate() {
+            // A previous endpoint may have broadcast it before failing
+            if attempt > 0 {
+                if let Ok(Some(status)) = endpoint
+                    .backend
+                    .get_signature_status(&signature, false)
+                    .await
+                {
+                    endpoint.record_success();
+                    return match status.err {
+                        Some(err) => Err(ClientErrorKind::TransactionError(err).into()),
+                        None => Ok(signature),
+                    };
+                }
+            }
+
+            match send(endpoint.backend.clone()).await {
+                Err(e) if is_failover_error(&e) => {
+                    endpoint.record_failure(self.cooldown);
+                    last_error = Some(e);
+                }
+                result => {
+                    endpoint.record_success();
+                    return result;
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(no_endpoints))
+    }
+}
+
+impl Default for FailoverBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `error` is the endpoint's fault rather than the request's
+fn is_failover_error(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Io(_) => true,
+        // No status means the request never completed (connect error, timeout)
+        ClientErrorKind::Reqwest(e) => e
+            .status()
+            .is_none_or(|status| status.is_server_error() || status.as_u16() == 429),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            *code == NODE_UNHEALTHY
+        }
+        _ => false,
+    }
+}
+
+fn no_endpoints() -> ClientError {
+    ClientErrorKind::Custom("No RPC endpoints configured".to_string()).into()
+}
+
+#[async_trait]
+impl RpcBackend for FailoverBackend {
+    async fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Option<Account>> {
+        let pubkey = *pubkey;
+        self.call(|b| async move { b.get_account(&pubkey).await })
+            .await
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> ClientResult<u64> {
+        let pubkey = *pubkey;
+        self.call(|b| async move { b.get_balance(&pubkey).await })
+            .await
+    }
+
+    async fn get_token_account_balance(&self, token_account: &Pubkey) -> ClientResult<u64> {
+        let token_account = *token_account;
+        self.call(|b| async move { b.get_token_account_balance(&token_account).await })
+            .await
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64> {
+        self.call(|b| async move { b.get_minimum_balance_for_rent_exemption(data_len).await })
+            .await
+    }
+
+    async fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+        self.call(|b| async move { b.get_latest_blockhash().await })
+            .await
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> ClientResult<Vec<u64>> {
+        let accounts = accounts.to_vec();
+        self.call(|b| {
+            let accounts = accounts.clone();
+            async move { b.get_recent_prioritization_fees(&accounts).await }
+        })
+        .await
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> ClientResult<u64> {
+        self.call(|b| {
+            let message = message.clone();
+            async move { b.get_fee_for_message(&message).await }
+        })
+        .await
+    }
+
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature> {
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        self.broadcast(signature, |b| {
+            let transaction = transaction.clone();
+            async move { b.send_and_confirm_transaction(&transaction).await }
+        })
+        .await
+    }
+
+    async fn send_and_confirm_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<Signature> {
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        self.broadcast(signature, |b| {
+            let transaction = transaction.clone();
+            async move { b.send_and_confirm_versioned_transaction(&transaction).await }
+        })
+        .await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<RpcSimulateTransactionResult> {
+        self.call(|b| {
+            let transaction = transaction.clone();
+            async move { b.simulate_transaction(&transaction).await }
+        })
+        .await
+    }
+
+    async fn get_slot(&self) -> ClientResult<u64> {
+        self.call(|b| async move { b.get_slot().await }).await
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+        search_transaction_history: bool,
+    ) -> ClientResult<Option<TransactionStatus>> {
+        let signature = *signature;
+        self.call(|b| async move {
+            b.get_signature_status(&signature, search_transaction_history)
+                .await
+        })
+        .await
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+        encoding: UiTransactionEncoding,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        let signature = *signature;
+        self.call(|b| async move { b.get_transaction(&signature, encoding).await })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::{MockRpcBackend, TransactionFixture};
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn unreachable() -> ClientError {
+        std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "connection refused").into()
+    }
+
+    #[tokio::test]
+    async fn test_reads_fail_over_to_secondary() {
+        let wallet = Pubkey::new_unique();
+        let primary = Arc::new(MockRpcBackend::new().with_balance(wallet, 1));
+        let secondary = Arc::new(MockRpcBackend::new().with_balance(wallet, 2));
+        primary.fail_next("get_balance", unreachable());
+
+        let backend = FailoverBackend::new()
+            .endpoint("primary", primary.clone())
+            .endpoint("secondary", secondary.clone());
+
+        assert_eq!(backend.get_balance(&wallet).await.unwrap(), 2);
+        assert_eq!(
+            backend.health(),
+            vec![
+                EndpointHealth {
+                    label: "primary".to_string(),
+                    consecutive_failures: 1,
+                    healthy: false,
+                },
+                EndpointHealth {
+                    label: "secondary".to_string(),
+                    consecutive_failures: 0,
+                    healthy: true,
+                },
+            ]
+        );
+
+        // The failed primary is skipped while it cools down
+        assert_eq!(backend.get_balance(&wallet).await.unwrap(), 2);
+        assert_eq!(primary.call_count("get_balance"), 1);
+
+        // Errors about the request itself are not retried elsewhere
+        let missing = Signature::new_unique();
+        assert!(backend
+            .get_transaction(&missing, UiTransactionEncoding::JsonParsed)
+            .await
+            .is_err());
+        assert_eq!(primary.call_count("get_transaction"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_is_not_repeated_after_landing() {
+        let payer = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        );
+        let signature = transaction.signatures[0];
+
+        // The primary broadcast the transaction but failed before confirming it
+        let primary = Arc::new(MockRpcBackend::new());
+        primary.fail_next("send_and_confirm_transaction", unreachable());
+        let secondary =
+            Arc::new(MockRpcBackend::new().with_fixture(TransactionFixture::new(signature)));
+
+        let backend = FailoverBackend::new()
+            .endpoint("primary", primary.clone())
+            .endpoint("secondary", secondary.clone());
+
+        assert_eq!(
+            backend
+                .send_and_confirm_transaction(&transaction)
+                .await
+                .unwrap(),
+            signature
+        );
+        assert!(secondary.sent_transactions().is_empty());
+
+        // A transaction that never landed is sent to the secondary
+        let other = Transaction::new_signed_with_payer(
+            &[],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::new_unique(),
+        );
+        let backend = FailoverBackend::new()
+            .endpoint("primary", primary.clone())
+            .endpoint("secondary", secondary.clone());
+        primary.fail_next("send_and_confirm_transaction", unreachable());
+        backend.send_and_confirm_transaction(&other).await.unwrap();
+        assert_eq!(secondary.sent_transactions(), vec![other]);
+    }
+}
@@ -0,0 +1,1067 @@
+//! Programmable in-memory [`RpcBackend`] for tests
+
+use async_trait::async_trait;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcError,
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::CompiledInstruction,
+    message::VersionedMessage,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Signature,
+    system_program,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, TransactionConfirmationStatus, TransactionStatus,
+    UiTransactionEncoding,
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use super::RpcBackend;
+use crate::transfer::MEMO_PROGRAM_ID;
+
+/// Fee `get_fee_for_message` charges per required signature
+pub const MOCK_LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// In-memory RPC backend with scripted chain state
+///
+/// Accounts, balances, and transactions are registered up front with the
+/// `with_*` builders. Individual calls can be made to fail with
+/// [`MockRpcBackend::fail_next`], and every call and broadcast transaction is
+/// recorded for later assertions. Transaction fees are
+/// [`MOCK_LAMPORTS_PER_SIGNATURE`] per signature, without priority fees.
+#[derive(Default)]
+pub struct MockRpcBackend {
+    state: Mutex<MockState>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[derive(Default)]
+struct MockState {
+    accounts: HashMap<Pubkey, Account>,
+    transactions: HashMap<Signature, VecDeque<serde_json::Value>>,
+    pruned: HashSet<Signature>,
+    blockhash: Hash,
+    prioritization_fees: Vec<u64>,
+    simulation_error: Option<(TransactionError, Vec<String>)>,
+    slot: u64,
+    rent: Rent,
+    failures: HashMap<&'static str, VecDeque<ClientError>>,
+    sent: Vec<Transaction>,
+    sent_versioned: Vec<VersionedTransaction>,
+    confirm_sent: bool,
+    calls: Vec<&'static str>,
+    latency: Option<Duration>,
+    status_sequence: Vec<TransactionConfirmationStatus>,
+    status_polls: HashMap<Signature, usize>,
+}
+
+impl MockRpcBackend {
+    /// Create an empty mock backend
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an account
+    pub fn with_account(self, pubkey: Pubkey, account: Account) -> Self {
+        self.lock().accounts.insert(pubkey, account);
+        self
+    }
+
+    /// Register a system-owned wallet holding `lamports`
+    pub fn with_balance(self, pubkey: Pubkey, lamports: u64) -> Self {
+        let account = Account::new(lamports, 0, &system_program::id());
+        self.with_account(pubkey, account)
+    }
+
+    /// Register an SPL token mint with the given decimals
+    pub fn with_mint(self, mint: Pubkey, decimals: u8) -> Self {
+        let account = self.lock().mint_account(decimals, spl_token::id());
+        self.with_account(mint, account)
+    }
+
+    /// Register a Token-2022 mint, without extensions, with the given decimals
+    pub fn with_token_2022_mint(self, mint: Pubkey, decimals: u8) -> Self {
+        let account = self.lock().mint_account(decimals, spl_token_2022::id());
+        self.with_account(mint, account)
+    }
+
+    /// Register a Token-2022 mint whose transfer-fee extension withholds
+    /// `fee_basis_points` of each transfer, up to `maximum_fee`
+    pub fn with_transfer_fee_mint(
+        self,
+        mint: Pubkey,
+        decimals: u8,
+        fee_basis_points: u16,
+        maximum_fee: u64,
+    ) -> Self {
+        use spl_token_2022::{
+            extension::{
+                transfer_fee::{TransferFee, TransferFeeConfig},
+                BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+            },
+            state::Mint,
+        };
+
+        let len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .expect("valid mint extensions");
+        let mut data = vec![0; len];
+        let mut state =
+            StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).expect("mint length");
+        let fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: maximum_fee.into(),
+            transfer_fee_basis_points: fee_basis_points.into(),
+        };
+        let config = state
+            .init_extension::<TransferFeeConfig>(true)
+            .expect("room for the extension");
+        config.older_transfer_fee = fee;
+        config.newer_transfer_fee = fee;
+        state.base = Mint {
+            decimals,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().expect("uninitialized mint");
+
+        let lamports = self.lock().rent.minimum_balance(data.len());
+        self.with_account(
+            mint,
+            Account {
+                lamports,
+                data,
+                owner: spl_token_2022::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+    }
+
+    /// Register an initialized SPL token account holding `amount` of `mint`
+    pub fn with_token_account(
+        self,
+        address: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    ) -> Self {
+        self.with_program_token_account(address, mint, owner, amount, spl_token::id())
+    }
+
+    /// Register the associated token account of `owner` for `mint` holding `amount`
+    pub fn with_associated_token_account(self, owner: Pubkey, mint: Pubkey, amount: u64) -> Self {
+        let address = spl_associated_token_account::get_associated_token_address(&owner, &mint);
+        self.with_token_account(address, mint, owner, amount)
+    }
+
+    /// Register the Token-2022 associated token account of `owner` for `mint` holding `amount`
+    pub fn with_associated_token_2022_account(
+        self,
+        owner: Pubkey,
+        mint: Pubkey,
+        amount: u64,
+    ) -> Self {
+        let program = spl_token_2022::id();
+        let address = spl_associated_token_account::get_associated_token_address_with_program_id(
+            &owner, &mint, &program,
+        );
+        self.with_program_token_account(address, mint, owner, amount, program)
+    }
+
+    /// Register a token account owned by the token program `program`
+    fn with_program_token_account(
+        self,
+        address: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+        program: Pubkey,
+    ) -> Self {
+        let state = spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account::pack(state, &mut data).expect("valid token account state");
+        let lamports = self.lock().rent.minimum_balance(data.len());
+        self.with_account(
+            address,
+            Account {
+                lamports,
+                data,
+                owner: program,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+    }
+
+    /// Register a confirmed transaction returned by `get_transaction`
+    ///
+    /// Registering the same signature several times queues the responses;
+    /// the last one is repeated once the queue is drained.
+    pub fn with_transaction(
+        self,
+        signature: Signature,
+        transaction: EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Self {
+        let json = serde_json::to_value(transaction).expect("serializable transaction");
+        self.with_transaction_json(signature, json)
+    }
+
+    /// Register a confirmed transaction from its JSON-RPC `getTransaction` result
+    pub fn with_transaction_json(self, signature: Signature, json: serde_json::Value) -> Self {
+        self.lock()
+            .transactions
+            .entry(signature)
+            .or_default()
+            .push_back(json);
+        self
+    }
+
+    /// Register a transaction built with [`TransactionFixture`]
+    ///
+    /// Mints it transfers that aren't registered yet are added with the
+    /// decimals of the fixture's transfers.
+    pub fn with_fixture(self, fixture: TransactionFixture) -> Self {
+        self.lock().register_mints(&fixture);
+        self.with_transaction_json(fixture.signature(), fixture.to_json())
+    }
+
+    /// Drop `signature` from the recent status cache
+    ///
+    /// `get_signature_status` then only finds it when searching the
+    /// transaction history, as for a finalized transaction older than the
+    /// node's recent-status window.
+    pub fn with_pruned_status(self, signature: Signature) -> Self {
+        self.lock().pruned.insert(signature);
+        self
+    }
+
+    /// Serve broadcast token transfers back from `get_transaction`
+    ///
+    /// Every transaction sent through `send_and_confirm_transaction` is
+    /// registered as a confirmed [`TransactionFixture`] containing its SPL
+    /// token transfers, so a payment made through one processor can be
+    /// verified by another sharing this backend. Each send also advances
+    /// the latest blockhash.
+    pub fn with_confirmed_sends(self) -> Self {
+        self.lock().confirm_sent = true;
+        self
+    }
+
+    /// Report known signatures as reaching each of `statuses` in turn
+    ///
+    /// Each `get_signature_status` call for a signature moves it one step
+    /// along, staying at the last status once reached. Signatures are
+    /// reported `Finalized` straight away by default.
+    pub fn with_status_sequence(self, statuses: Vec<TransactionConfirmationStatus>) -> Self {
+        self.lock().status_sequence = statuses;
+        self
+    }
+
+    /// Set the blockhash returned by `get_latest_blockhash`
+    pub fn with_blockhash(self, blockhash: Hash) -> Self {
+        self.lock().blockhash = blockhash;
+        self
+    }
+
+    /// Set the fees returned by `get_recent_prioritization_fees`, one per slot
+    pub fn with_prioritization_fees(self, fees: Vec<u64>) -> Self {
+        self.lock().prioritization_fees = fees;
+        self
+    }
+
+    /// Make `simulate_transaction` report `error` with program `logs`
+    ///
+    /// Simulations succeed without logs by default.
+    pub fn with_simulation_error(self, error: TransactionError, logs: Vec<String>) -> Self {
+        self.lock().simulation_error = Some((error, logs));
+        self
+    }
+
+    /// Make every call wait `latency` before answering
+    ///
+    /// Calls waiting at the same time are counted by
+    /// [`max_concurrent_calls`](Self::max_concurrent_calls).
+    pub fn with_latency(self, latency: Duration) -> Self {
+        self.lock().latency = Some(latency);
+        self
+    }
+
+    /// Set the slot returned by `get_slot`
+    pub fn with_slot(self, slot: u64) -> Self {
+        self.set_slot(slot);
+        self
+    }
+
+    /// Advance (or rewind) the cluster to `slot`
+    pub fn set_slot(&self, slot: u64) {
+        self.lock().slot = slot;
+    }
+
+    /// Make the next call to `method` fail with `error`
+    ///
+    /// `method` is the trait method name, e.g. `"get_latest_blockhash"`.
+    pub fn fail_next(&self, method: &'static str, error: ClientError) {
+        self.lock()
+            .failures
+            .entry(method)
+            .or_default()
+            .push_back(error);
+    }
+
+    /// Transactions broadcast through `send_and_confirm_transaction`
+    pub fn sent_transactions(&self) -> Vec<Transaction> {
+        self.lock().sent.clone()
+    }
+
+    /// Transactions broadcast through `send_and_confirm_versioned_transaction`
+    pub fn sent_versioned_transactions(&self) -> Vec<VersionedTransaction> {
+        self.lock().sent_versioned.clone()
+    }
+
+    /// Number of times `method` has been called
+    pub fn call_count(&self, method: &str) -> usize {
+        self.lock().calls.iter().filter(|m| **m == method).count()
+    }
+
+    /// Most calls that have been waiting out [`with_latency`](Self::with_latency) at once
+    pub fn max_concurrent_calls(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn begin(
+        &self,
+        method: &'static str,
+    ) -> ClientResult<std::sync::MutexGuard<'_, MockState>> {
+        let latency = self.lock().latency;
+        if let Some(latency) = latency {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(latency).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
+        let mut state = self.lock();
+        state.calls.push(method);
+        if let Some(error) = state.failures.get_mut(method).and_then(VecDeque::pop_front) {
+            return Err(error);
+        }
+        Ok(state)
+    }
+}
+
+impl MockState {
+    /// Account data of an initialized mint with `decimals`, owned by `program`
+    ///
+    /// Token-2022 mints without extensions share SPL Token's layout.
+    fn mint_account(&self, decimals: u8, program: Pubkey) -> Account {
+        let state = spl_token::state::Mint {
+            decimals,
+            is_initialized: true,
+            ..Default::default()
+        };
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint::pack(state, &mut data).expect("valid mint state");
+        Account {
+            lamports: self.rent.minimum_balance(data.len()),
+            data,
+            owner: program,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    /// Register the mints transferred in `fixture` that aren't known yet
+    fn register_mints(&mut self, fixture: &TransactionFixture) {
+        for transfer in &fixture.transfers {
+            if !self.accounts.contains_key(&transfer.mint) {
+                let account = self.mint_account(transfer.decimals, spl_token::id());
+                self.accounts.insert(transfer.mint, account);
+            }
+        }
+    }
+
+    /// Describe the token and SOL transfers in a sent message as a confirmed fixture
+    ///
+    /// `keys` are the message's account keys, fee payer first.
+    fn fixture_for(
+        &self,
+        signature: Signature,
+        keys: &[Pubkey],
+        instructions: &[CompiledInstruction],
+    ) -> TransactionFixture {
+        use solana_sdk::{
+            program_utils::limited_deserialize, system_instruction::SystemInstruction,
+        };
+        // Token-2022 encodes the transfers decoded here the same way
+        use spl_token::instruction::TokenInstruction;
+
+        let mut fixture = TransactionFixture::new(signature);
+        if let Some(fee_payer) = keys.first() {
+            fixture = fixture.fee_payer(*fee_payer);
+        }
+
+        // Token accounts created in the same transaction aren't in `accounts` yet
+        let mut created = HashMap::new();
+        for instruction in instructions {
+            let program = keys[usize::from(instruction.program_id_index)];
+            let account = |i: usize| keys[usize::from(instruction.accounts[i])];
+            if program == spl_associated_token_account::id() && instruction.accounts.len() >= 3 {
+                created.insert(account(1), account(2));
+            }
+        }
+
+        for instruction in instructions {
+            let program = keys[usize::from(instruction.program_id_index)];
+            if program == MEMO_PROGRAM_ID {
+                if let Ok(memo) = std::str::from_utf8(&instruction.data) {
+                    fixture = fixture.memo(memo);
+                }
+                continue;
+            }
+            if program == system_program::id() {
+                if let (Ok(SystemInstruction::Transfer { lamports }), Some(index)) = (
+                    limited_deserialize(&instruction.data),
+                    instruction.accounts.get(1),
+                ) {
+                    let recipient = keys[usize::from(*index)];
+                    fixture = fixture.sol_transfer(recipient, lamports);
+                }
+                continue;
+            }
+            if program != spl_token::id() && program != spl_token_2022::id() {
+                continue;
+            }
+            let account = |i: usize| {
+                instruction
+                    .accounts
+                    .get(i)
+                    .map(|index| keys[usize::from(*index)])
+            };
+            let (destination, amount, decimals) = match TokenInstruction::unpack(&instruction.data)
+            {
+                Ok(TokenInstruction::TransferChecked { amount, decimals }) => {
+                    (account(2), amount, Some(decimals))
+                }
+                Ok(TokenInstruction::Transfer { amount }) => (account(1), amount, None),
+                _ => continue,
+            };
+            let Some(destination) = destination else {
+                continue;
+            };
+
+            let token_account = self
+                .accounts
+                .get(&destination)
+                .and_then(|account| spl_token::state::Account::unpack(&account.data).ok());
+            let owner = token_account
+                .map(|a| a.owner)
+                .or_else(|| created.get(&destination).copied());
+            let mint = match TokenInstruction::unpack(&instruction.data) {
+                Ok(TokenInstruction::TransferChecked { .. }) => account(1),
+                _ => token_account.map(|a| a.mint),
+            };
+            if let (Some(owner), Some(mint)) = (owner, mint) {
+                let decimals = decimals
+                    .or_else(|| {
+                        let account = self.accounts.get(&mint)?;
+                        Some(spl_token::state::Mint::unpack(&account.data).ok()?.decimals)
+                    })
+                    .unwrap_or(6);
+                fixture = fixture.transfer_with_decimals(mint, owner, amount, decimals);
+            }
+        }
+
+        fixture
+    }
+}
+
+/// Builder for `getTransaction` results served by [`MockRpcBackend`]
+///
+/// Produces the `jsonParsed` JSON-RPC shape of a confirmed legacy transaction
+/// so tests can describe on-chain outcomes without a live cluster.
+#[derive(Debug, Clone)]
+pub struct TransactionFixture {
+    signature: Signature,
+    slot: u64,
+    block_time: Option<i64>,
+    fee_payer: Pubkey,
+    err: Option<serde_json::Value>,
+    transfers: Vec<FixtureTransfer>,
+    sol_transfers: Vec<(Pubkey, u64)>,
+    memos: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct FixtureTransfer {
+    mint: Pubkey,
+    recipient: Pubkey,
+    amount: u64,
+    fee: u64,
+    /// Whether the instruction reports `fee`, as `transferCheckedWithFee` does
+    reports_fee: bool,
+    decimals: u8,
+    via_program: Option<Pubkey>,
+}
+
+impl TransactionFixture {
+    /// Create a successful transaction confirmed now
+    pub fn new(signature: Signature) -> Self {
+        Self {
+            signature,
+            slot: 1,
+            block_time: Some(chrono::Utc::now().timestamp()),
+            fee_payer: Pubkey::new_unique(),
+            err: None,
+            transfers: Vec::new(),
+            sol_transfers: Vec::new(),
+            memos: Vec::new(),
+        }
+    }
+
+    /// Signature of the transaction
+    pub fn signature(&self) -> Signature {
+        self.signature
+    }
+
+    /// Set the fee payer, which also signs and funds every transfer
+    pub fn fee_payer(mut self, fee_payer: Pubkey) -> Self {
+        self.fee_payer = fee_payer;
+        self
+    }
+
+    /// Set the slot the transaction landed in and its block time (Unix seconds)
+    pub fn confirmed_at(mut self, slot: u64, block_time: Option<i64>) -> Self {
+        self.slot = slot;
+        self.block_time = block_time;
+        self
+    }
+
+    /// Mark the transaction as failed on-chain
+    pub fn failed(mut self) -> Self {
+        self.err = Some(serde_json::json!({ "InstructionError": [0, { "Custom": 1 }] }));
+        self
+    }
+
+    /// Add a top-level `transferChecked` of `amount` (6 decimals) to `recipient`'s token account
+    pub fn transfer(self, mint: Pubkey, recipient: Pubkey, amount: u64) -> Self {
+        self.transfer_with_decimals(mint, recipient, amount, 6)
+    }
+
+    /// Add a top-level `transferChecked` of `amount` in a token with `decimals`
+    pub fn transfer_with_decimals(
+        mut self,
+        mint: Pubkey,
+        recipient: Pubkey,
+        amount: u64,
+        decimals: u8,
+    ) -> Self {
+        self.transfers.push(FixtureTransfer {
+            mint,
+            recipient,
+            amount,
+            fee: 0,
+            reports_fee: false,
+            decimals,
+            via_program: None,
+        });
+        self
+    }
+
+    /// Add a top-level `transferCheckedWithFee` of `amount` (6 decimals) from
+    /// which a transfer-fee mint withholds `fee`
+    ///
+    /// The recipient's token balance only grows by `amount - fee`.
+    pub fn transfer_with_fee(
+        mut self,
+        mint: Pubkey,
+        recipient: Pubkey,
+        amount: u64,
+        fee: u64,
+    ) -> Self {
+        self.transfers.push(FixtureTransfer {
+            mint,
+            recipient,
+            amount,
+            fee,
+            reports_fee: true,
+            decimals: 6,
+            via_program: None,
+        });
+        self
+    }
+
+    /// Add a top-level `transferChecked` of `amount` (6 decimals) from which a
+    /// transfer-fee mint withholds `fee`, without the instruction reporting it
+    ///
+    /// As with [`transfer_with_fee`](Self::transfer_with_fee), the
+    /// recipient's token balance only grows by `amount - fee`.
+    pub fn transfer_withholding_fee(
+        mut self,
+        mint: Pubkey,
+        recipient: Pubkey,
+        amount: u64,
+        fee: u64,
+    ) -> Self {
+        self.transfers.push(FixtureTransfer {
+            mint,
+            recipient,
+            amount,
+            fee,
+            reports_fee: false,
+            decimals: 6,
+            via_program: None,
+        });
+        self
+    }
+
+    /// Add a `transfer` made by `program` through a cross-program invocation
+    ///
+    /// The top-level instruction is an opaque call to `program`; the token
+    /// transfer only appears among the inner instructions.
+    pub fn cpi_transfer(
+        mut self,
+        program: Pubkey,
+        mint: Pubkey,
+        recipient: Pubkey,
+        amount: u64,
+    ) -> Self {
+        self.transfers.push(FixtureTransfer {
+            mint,
+            recipient,
+            amount,
+            fee: 0,
+            reports_fee: false,
+            decimals: 6,
+            via_program: Some(program),
+        });
+        self
+    }
+
+    /// Add a top-level System program `transfer` of `lamports` to `recipient`
+    pub fn sol_transfer(mut self, recipient: Pubkey, lamports: u64) -> Self {
+        self.sol_transfers.push((recipient, lamports));
+        self
+    }
+
+    /// Add a top-level SPL Memo instruction
+    pub fn memo(mut self, memo: &str) -> Self {
+        self.memos.push(memo.to_string());
+        self
+    }
+
+    /// Render the `getTransaction` JSON-RPC result
+    pub fn to_json(&self) -> serde_json::Value {
+        let status = match &self.err {
+            Some(err) => serde_json::json!({ "Err": err }),
+            None => serde_json::json!({ "Ok": null }),
+        };
+
+        let token_program = spl_token::id().to_string();
+        let mut keys = vec![self.fee_payer];
+        let mut key_index = |key: Pubkey| match keys.iter().position(|k| *k == key) {
+            Some(index) => index,
+            None => {
+                keys.push(key);
+                keys.len() - 1
+            }
+        };
+
+        let mut writable = vec![self.fee_payer];
+        let mut instructions = Vec::new();
+        let mut inner_instructions = Vec::new();
+        let mut pre_token_balances = Vec::new();
+        let mut post_token_balances = Vec::new();
+        let mut received: Vec<(usize, u64)> = Vec::new();
+
+        for transfer in &self.transfers {
+            let source = spl_associated_token_account::get_associated_token_address(
+                &self.fee_payer,
+                &transfer.mint,
+            );
+            let destination = spl_associated_token_account::get_associated_token_address(
+                &transfer.recipient,
+                &transfer.mint,
+            );
+            writable.extend([source, destination]);
+            key_index(source);
+            let destination_index = key_index(destination);
+            key_index(transfer.mint);
+            key_index(spl_token::id());
+
+            let ui_amount = |amount: u64| {
+                let ui = amount as f64 / 10f64.powi(i32::from(transfer.decimals));
+                serde_json::json!({
+                    "amount": amount.to_string(),
+                    "decimals": transfer.decimals,
+                    "uiAmount": ui,
+                    "uiAmountString": ui.to_string()
+                })
+            };
+            let token_balance = |amount: u64| {
+                serde_json::json!({
+                    "accountIndex": destination_index,
+                    "mint": transfer.mint.to_string(),
+                    "uiTokenAmount": ui_amount(amount),
+                    "owner": transfer.recipient.to_string(),
+                    "programId": token_program
+                })
+            };
+            // Several transfers into one account share its balance entries
+            match received
+                .iter()
+                .position(|(index, _)| *index == destination_index)
+            {
+                Some(position) => {
+                    received[position].1 += transfer.amount - transfer.fee;
+                    post_token_balances[position] = token_balance(received[position].1);
+                }
+                None => {
+                    let amount = transfer.amount - transfer.fee;
+                    received.push((destination_index, amount));
+                    pre_token_balances.push(token_balance(0));
+                    post_token_balances.push(token_balance(amount));
+                }
+            }
+
+            match transfer.via_program {
+                None => {
+                    let mut info = serde_json::json!({
+                        "source": source.to_string(),
+                        "destination": destination.to_string(),
+                        "mint": transfer.mint.to_string(),
+                        "authority": self.fee_payer.to_string(),
+                        "tokenAmount": ui_amount(transfer.amount)
+                    });
+                    let instruction_type = if transfer.reports_fee {
+                        info["feeAmount"] = ui_amount(transfer.fee);
+                        "transferCheckedWithFee"
+                    } else {
+                        "transferChecked"
+                    };
+                    instructions.push(serde_json::json!({
+                        "program": "spl-token",
+                        "programId": token_program,
+                        "parsed": { "type": instruction_type, "info": info },
+                        "stackHeight": null
+                    }));
+                }
+                Some(program) => {
+                    key_index(program);
+                    inner_instructions.push(serde_json::json!({
+                        "index": instructions.len(),
+                        "instructions": [{
+                            "program": "spl-token",
+                            "programId": token_program,
+                            "parsed": {
+                                "type": "transfer",
+                                "info": {
+                                    "source": source.to_string(),
+                                    "destination": destination.to_string(),
+                                    "authority": self.fee_payer.to_string(),
+                                    "amount": transfer.amount.to_string()
+                                }
+                            },
+                            "stackHeight": 2
+                        }]
+                    }));
+                    instructions.push(serde_json::json!({
+                        "programId": program.to_string(),
+                        "accounts": [
+                            source.to_string(),
+                            destination.to_string(),
+                            self.fee_payer.to_string(),
+                            token_program
+                        ],
+                        "data": "",
+                        "stackHeight": null
+                    }));
+                }
+            }
+        }
+
+        let mut lamports_received: Vec<(usize, u64)> = Vec::new();
+        for (recipient, lamports) in &self.sol_transfers {
+            writable.push(*recipient);
+            lamports_received.push((key_index(*recipient), *lamports));
+            key_index(system_program::id());
+            instructions.push(serde_json::json!({
+                "program": "system",
+                "programId": system_program::id().to_string(),
+                "parsed": {
+                    "type": "transfer",
+                    "info": {
+                        "source": self.fee_payer.to_string(),
+                        "destination": recipient.to_string(),
+                        "lamports": lamports
+                    }
+                },
+                "stackHeight": null
+            }));
+        }
+
+        for memo in &self.memos {
+            key_index(MEMO_PROGRAM_ID);
+            instructions.push(serde_json::json!({
+                "program": "spl-memo",
+                "programId": MEMO_PROGRAM_ID.to_string(),
+                "parsed": memo,
+                "stackHeight": null
+            }));
+        }
+
+        let account_keys: Vec<serde_json::Value> = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                serde_json::json!({
+                    "pubkey": key.to_string(),
+                    "writable": writable.contains(key),
+                    "signer": index == 0,
+                    "source": "transaction"
+                })
+            })
+            .collect();
+        let balances = vec![1_000_000_000u64; keys.len()];
+        let mut post_balances = balances.clone();
+        post_balances[0] -= 5000;
+        for (index, lamports) in lamports_received {
+            post_balances[0] -= lamports;
+            post_balances[index] += lamports;
+        }
+
+        serde_json::json!({
+            "slot": self.slot,
+            "blockTime": self.block_time,
+            "transaction": {
+                "signatures": [self.signature.to_string()],
+                "message": {
+                    "accountKeys": account_keys,
+                    "recentBlockhash": Hash::default().to_string(),
+                    "instructions": instructions
+                }
+            },
+            "meta": {
+                "err": self.err,
+                "status": status,
+                "fee": 5000,
+                "preBalances": balances,
+                "postBalances": post_balances,
+                "innerInstructions": inner_instructions,
+                "logMessages": [],
+                "preTokenBalances": pre_token_balances,
+                "postTokenBalances": post_token_balances,
+                "rewards": []
+            }
+        })
+    }
+}
+
+fn not_found(message: String) -> ClientError {
+    ClientErrorKind::RpcError(RpcError::ForUser(message)).into()
+}
+
+#[async_trait]
+impl RpcBackend for MockRpcBackend {
+    async fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Option<Account>> {
+        let state = self.begin("get_account").await?;
+        Ok(state.accounts.get(pubkey).cloned())
+    }
+
+    async fn get_balance(&self, pubkey: &Pubkey) -> ClientResult<u64> {
+        let state = self.begin("get_balance").await?;
+        Ok(state.accounts.get(pubkey).map_or(0, |a| a.lamports))
+    }
+
+    async fn get_token_account_balance(&self, token_account: &Pubkey) -> ClientResult<u64> {
+        let state = self.begin("get_token_account_balance").await?;
+        let account = state
+            .accounts
+            .get(token_account)
+            .ok_or_else(|| not_found(format!("AccountNotFound: pubkey={}", token_account)))?;
+        spl_token::state::Account::unpack(&account.data)
+            .map(|a| a.amount)
+            .map_err(|e| not_found(format!("Invalid token account {}: {}", token_account, e)))
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64> {
+        let state = self.begin("get_minimum_balance_for_rent_exemption").await?;
+        Ok(state.rent.minimum_balance(data_len))
+    }
+
+    async fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+        let state = self.begin("get_latest_blockhash").await?;
+        Ok(state.blockhash)
+    }
+
+    async fn get_recent_prioritization_fees(&self, _accounts: &[Pubkey]) -> ClientResult<Vec<u64>> {
+        let state = self.begin("get_recent_prioritization_fees").await?;
+        Ok(state.prioritization_fees.clone())
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> ClientResult<u64> {
+        let _state = self.begin("get_fee_for_message").await?;
+        Ok(MOCK_LAMPORTS_PER_SIGNATURE * u64::from(message.header().num_required_signatures))
+    }
+
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature> {
+        let mut state = self.begin("send_and_confirm_transaction").await?;
+        state.sent.push(transaction.clone());
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+
+        if state.confirm_sent {
+            let message = &transaction.message;
+            let fixture =
+                state.fixture_for(signature, &message.account_keys, &message.instructions);
+            state.register_mints(&fixture);
+            let json = fixture.to_json();
+            state
+                .transactions
+                .entry(signature)
+                .or_default()
+                .push_back(json);
+            // The chain moves on, so an identical payment gets a new signature
+            state.blockhash = Hash::new_unique();
+        }
+        Ok(signature)
+    }
+
+    async fn send_and_confirm_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> ClientResult<Signature> {
+        let mut state = self.begin("send_and_confirm_versioned_transaction").await?;
+        state.sent_versioned.push(transaction.clone());
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+
+        // Keys loaded from lookup tables can't be resolved, so only messages
+        // listing all their keys are served back
+        let message = &transaction.message;
+        let lookups = message.address_table_lookups().unwrap_or_default();
+        if state.confirm_sent && lookups.is_empty() {
+            let fixture = state.fixture_for(
+                signature,
+                message.static_account_keys(),
+                message.instructions(),
+            );
+            state.register_mints(&fixture);
+            let json = fixture.to_json();
+            state
+                .transactions
+                .entry(signature)
+                .or_default()
+                .push_back(json);
+            state.blockhash = Hash::new_unique();
+        }
+        Ok(signature)
+    }
+
+    async fn simulate_transaction(
+        &self,
+        _transaction: &VersionedTransaction,
+    ) -> ClientResult<RpcSimulateTransactionResult> {
+        let state = self.begin("simulate_transaction").await?;
+        let (err, logs) = state.simulation_error.clone().unzip();
+        Ok(RpcSimulateTransactionResult {
+            err,
+            logs: Some(logs.unwrap_or_default()),
+            accounts: None,
+            units_consumed: None,
+            return_data: None,
+            inner_instructions: None,
+            replacement_blockhash: None,
+        })
+    }
+
+    async fn get_slot(&self) -> ClientResult<u64> {
+        Ok(self.begin("get_slot").await?.slot)
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+        search_transaction_history: bool,
+    ) -> ClientResult<Option<TransactionStatus>> {
+        let mut state = self.begin("get_signature_status").await?;
+        if state.pruned.contains(signature) && !search_transaction_history {
+            return Ok(None);
+        }
+
+        // Statuses are derived from the registered `getTransaction` result
+        let Some(json) = state.transactions.get(signature).and_then(VecDeque::front) else {
+            return Ok(None);
+        };
+        let err: Option<TransactionError> = serde_json::from_value(json["meta"]["err"].clone())?;
+        let slot = json["slot"].as_u64().unwrap_or_default();
+        let polls = state.status_polls.entry(*signature).or_default();
+        let step = *polls;
+        *polls += 1;
+        let confirmation_status = match state.status_sequence.len() {
+            0 => TransactionConfirmationStatus::Finalized,
+            n => state.status_sequence[step.min(n - 1)].clone(),
+        };
+        // Nodes only stop counting confirmations once a slot is rooted
+        let confirmations = match confirmation_status {
+            TransactionConfirmationStatus::Finalized => None,
+            _ => Some(1),
+        };
+        Ok(Some(TransactionStatus {
+            slot,
+            confirmations,
+            status: match &err {
+                Some(err) => Err(err.clone()),
+                None => Ok(()),
+            },
+            err,
+            confirmation_status: Some(confirmation_status),
+        }))
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+        _encoding: UiTransactionEncoding,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        let mut state = self.begin("get_transaction").await?;
+        let queue = state
+            .transactions
+            .get_mut(signature)
+            .ok_or_else(|| not_found(format!("Transaction {} not found", signature)))?;
+        let json = match queue.len() {
+            0 => return Err(not_found(format!("Transaction {} not found", signature))),
+            1 => queue[0].clone(),
+            _ => queue.pop_front().expect("non-empty queue"),
+        };
+        Ok(serde_json::from_value(json)?)
+    }
+}
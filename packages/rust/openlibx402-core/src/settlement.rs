@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+use uuid::Uuid;
+
+use crate::{
+    errors::{X402Error, X402Result},
+    models::PaymentAuthorization,
+};
+
+/// Path prefix under which servers expose settlements for polling
+pub const SETTLE_PATH: &str = "/settle";
+
+/// State of an asynchronous settlement
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementStatus {
+    /// Payment confirmation or the paid work is still in progress
+    Pending,
+
+    /// Payment is confirmed and the result is available
+    Complete,
+
+    /// Payment or work failed; no result will be produced
+    Failed,
+}
+
+/// Two-step settlement of a paid request
+///
+/// The server issues a settlement when it accepts a payment authorization
+/// and starts the paid work. The client polls `/settle/{settlement_id}`
+/// until the settlement is complete, which requires both the payment to be
+/// confirmed and the work to be done.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Settlement {
+    /// Unique settlement identifier
+    pub settlement_id: String,
+
+    /// Payment ID of the authorization being settled
+    pub payment_id: String,
+
+    /// Transaction signature of the payment
+    pub signature: String,
+
+    /// Current status
+    pub status: SettlementStatus,
+
+    /// Whether the payment has been confirmed on-chain
+    pub payment_confirmed: bool,
+
+    /// Result of the paid work, once complete
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+
+    /// Failure reason, if failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// When the settlement was issued
+    pub created_at: DateTime<Utc>,
+
+    /// When the settlement last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Settlement {
+    /// Check if the settlement is still pending
+    pub fn is_pending(&self) -> bool {
+        self.status == SettlementStatus::Pending
+    }
+
+    /// Path the client polls for this settlement, e.g. `/settle/{id}`
+    pub fn poll_path(&self) -> String {
+        format!("{}/{}", SETTLE_PATH, self.settlement_id)
+    }
+
+    /// Parse settlement from JSON string
+    pub fn from_json(json: &str) -> X402Result<Self> {
+        serde_json::from_str(json)
+            .map_err(|e| X402Error::Serialization(format!("Failed to parse settlement: {}", e)))
+    }
+
+    fn refresh_status(&mut self) {
+        if self.status == SettlementStatus::Pending
+            && self.payment_confirmed
+            && self.result.is_some()
+        {
+            self.status = SettlementStatus::Complete;
+        }
+        self.updated_at = Utc::now();
+    }
+}
+
+/// In-memory registry of settlements issued by a server
+///
+/// Cloning the tracker shares the same underlying registry, so it can be
+/// stored in framework state and handed to background tasks.
+#[derive(Debug, Clone, Default)]
+pub struct SettlementTracker {
+    settlements: Arc<RwLock<HashMap<String, Settlement>>>,
+}
+
+impl SettlementTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issue a pending settlement for an accepted payment authorization
+    pub fn issue(&self, authorization: &PaymentAuthorization) -> Settlement {
+        let now = Utc::now();
+        let settlement = Settlement {
+            settlement_id: Uuid::new_v4().to_string(),
+            payment_id: authorization.payment_id.clone(),
+            signature: authorization.signature.clone(),
+            status: SettlementStatus::Pending,
+            payment_confirmed: false,
+            result: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.write()
+            .insert(settlement.settlement_id.clone(), settlement.clone());
+        settlement
+    }
+
+    /// Look up a settlement by ID
+    pub fn get(&self, settlement_id: &str) -> Option<Settlement> {
+        self.read().get(settlement_id).cloned()
+    }
+
+    /// Record that the payment has been confirmed on-chain
+    pub fn confirm_payment(&self, settlement_id: &str) -> X402Result<Settlement> {
+        self.update(settlement_id, |s| s.payment_confirmed = true)
+    }
+
+    /// Record the result of the paid work
+    pub fn complete(
+        &self,
+        settlement_id: &str,
+        result: serde_json::Value,
+    ) -> X402Result<Settlement> {
+        self.update(settlement_id, |s| s.result = Some(result))
+    }
+
+    /// Mark the settlement as failed
+    pub fn fail(&self, settlement_id: &str, error: &str) -> X402Result<Settlement> {
+        self.update(settlement_id, |s| {
+            s.status = SettlementStatus::Failed;
+            s.error = Some(error.to_string());
+        })
+    }
+
+    /// Remove a settlement once the client has collected it
+    pub fn remove(&self, settlement_id: &str) -> Option<Settlement> {
+        self.write().remove(settlement_id)
+    }
+
+    fn update(
+        &self,
+        settlement_id: &str,
+        f: impl FnOnce(&mut Settlement),
+    ) -> X402Result<Settlement> {
+        let mut settlements = self.write();
+        let settlement = settlements.get_mut(settlement_id).ok_or_else(|| {
+            X402Error::InvalidPaymentAuthorization(format!("Unknown settlement: {}", settlement_id))
+        })?;
+        f(settlement);
+        settlement.refresh_status();
+        Ok(settlement.clone())
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Settlement>> {
+        self.settlements.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Settlement>> {
+        self.settlements.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_authorization() -> PaymentAuthorization {
+        PaymentAuthorization::new(
+            "payment123".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_issue_poll_pending_poll_complete() {
+        let tracker = SettlementTracker::new();
+        let issued = tracker.issue(&test_authorization());
+        assert!(issued.is_pending());
+        assert_eq!(issued.payment_id, "payment123");
+        assert_eq!(
+            issued.poll_path(),
+            format!("/settle/{}", issued.settlement_id)
+        );
+
+        // Work finishes before the payment confirms: still pending
+        tracker
+            .complete(&issued.settlement_id, serde_json::json!({ "answer": 42 }))
+            .unwrap();
+        let polled = tracker.get(&issued.settlement_id).unwrap();
+        assert!(polled.is_pending());
+
+        tracker.confirm_payment(&issued.settlement_id).unwrap();
+        let polled = tracker.get(&issued.settlement_id).unwrap();
+        assert_eq!(polled.status, SettlementStatus::Complete);
+        assert_eq!(polled.result, Some(serde_json::json!({ "answer": 42 })));
+    }
+
+    #[test]
+    fn test_failed_settlement() {
+        let tracker = SettlementTracker::new();
+        let issued = tracker.issue(&test_authorization());
+
+        let failed = tracker.fail(&issued.settlement_id, "work crashed").unwrap();
+        assert_eq!(failed.status, SettlementStatus::Failed);
+
+        // A failed settlement never completes
+        tracker.confirm_payment(&issued.settlement_id).unwrap();
+        let completed = tracker
+            .complete(&issued.settlement_id, serde_json::json!(null))
+            .unwrap();
+        assert_eq!(completed.status, SettlementStatus::Failed);
+
+        assert!(tracker.confirm_payment("unknown").is_err());
+    }
+}
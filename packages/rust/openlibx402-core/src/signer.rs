@@ -0,0 +1,44 @@
+//! Signing payments without holding the payer's private key
+//!
+//! [`SolanaPaymentProcessor`](crate::SolanaPaymentProcessor) signs payment
+//! transactions through the [`TransactionSigner`] trait. A [`Keypair`] signs
+//! locally; implement the trait to sign with a hardware wallet, a browser
+//! wallet or a remote key management service instead.
+
+use async_trait::async_trait;
+use solana_sdk::{
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+};
+
+use crate::errors::{X402Error, X402Result};
+
+/// Signer of payment transactions
+///
+/// Payments are built with [`pubkey`](Self::pubkey) as both the fee payer
+/// and the owner of the token account paid from, so a single signature over
+/// the message authorizes them.
+#[async_trait]
+pub trait TransactionSigner: Send + Sync {
+    /// Public key of the account paying
+    fn pubkey(&self) -> Pubkey;
+
+    /// Sign the serialized `message`
+    ///
+    /// The message is legacy or v0 depending on the processor's
+    /// [`TransactionVersion`](crate::TransactionVersion).
+    async fn sign(&self, message: &VersionedMessage) -> X402Result<Signature>;
+}
+
+#[async_trait]
+impl TransactionSigner for Keypair {
+    fn pubkey(&self) -> Pubkey {
+        Signer::pubkey(self)
+    }
+
+    async fn sign(&self, message: &VersionedMessage) -> X402Result<Signature> {
+        self.try_sign_message(&message.serialize())
+            .map_err(|e| X402Error::Blockchain(format!("Failed to sign transaction: {}", e)))
+    }
+}
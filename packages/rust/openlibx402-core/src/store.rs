@@ -0,0 +1,565 @@
+//! Shared state for issued challenges and used nonces
+//!
+//! A server that issues a [`PaymentRequest`] must later recognize it when the
+//! client pays, and must refuse a nonce it has already accepted. With a single
+//! instance that state can live in memory ([`MemoryStore`]); behind a load
+//! balancer every instance needs the same view, so the state sits behind the
+//! [`ChallengeStore`] and [`NonceStore`] traits. Servers that replay the
+//! result of a paid request to a retrying client keep it in a [`GrantStore`].
+//! Payers that must not pay twice for one `payment_id` record what they
+//! broadcast in a [`PaymentSignatureStore`]. With the `redis` feature,
+//! `RedisStore` shares all of it through Redis.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{errors::X402Result, models::PaymentRequest};
+
+/// Record of nonces that have already been used
+#[async_trait]
+pub trait NonceStore: Send + Sync {
+    /// Record `nonce` as used until `expires_at`
+    ///
+    /// Returns `false` if the nonce was already recorded and has not expired,
+    /// i.e. the payment is being replayed.
+    async fn check_and_record(&self, nonce: &str, expires_at: DateTime<Utc>) -> X402Result<bool>;
+}
+
+/// Payment requests issued to clients, keyed by `payment_id`
+#[async_trait]
+pub trait ChallengeStore: Send + Sync {
+    /// Remember an issued payment request until it expires
+    async fn save(&self, request: &PaymentRequest) -> X402Result<()>;
+
+    /// Look up an unexpired payment request by `payment_id`
+    async fn get(&self, payment_id: &str) -> X402Result<Option<PaymentRequest>>;
+
+    /// Forget a payment request, e.g. once it has been paid
+    async fn remove(&self, payment_id: &str) -> X402Result<()>;
+}
+
+/// Response served for a paid request, kept so retries get the same result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grant {
+    /// `payment_id` of the authorization that paid for the request
+    pub payment_id: String,
+
+    /// Transaction signature of that authorization
+    pub signature: String,
+
+    /// HTTP status code
+    pub status: u16,
+
+    /// Response headers, in order
+    pub headers: Vec<(String, String)>,
+
+    /// Response body
+    pub body: Vec<u8>,
+
+    /// When the grant is forgotten
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Grant {
+    /// Whether this grant was issued to an authorization with `signature`
+    pub fn matches(&self, signature: &str) -> bool {
+        self.signature == signature
+    }
+}
+
+/// Results of completed paid requests, keyed by `payment_id`
+#[async_trait]
+pub trait GrantStore: Send + Sync {
+    /// Remember a grant until its `expires_at`
+    async fn save_grant(&self, grant: &Grant) -> X402Result<()>;
+
+    /// Look up an unexpired grant by `payment_id`
+    async fn get_grant(&self, payment_id: &str) -> X402Result<Option<Grant>>;
+}
+
+/// Signatures of payments broadcast by a payer, keyed by `payment_id`
+///
+/// Lets a retried `create_payment` find the transaction an earlier attempt
+/// sent instead of paying again.
+#[async_trait]
+pub trait PaymentSignatureStore: Send + Sync {
+    /// Remember that `payment_id` was sent as `signature` until `expires_at`
+    ///
+    /// Replaces any signature recorded before for `payment_id`.
+    async fn save_payment_signature(
+        &self,
+        payment_id: &str,
+        signature: &str,
+        expires_at: DateTime<Utc>,
+    ) -> X402Result<()>;
+
+    /// Look up the unexpired signature recorded for `payment_id`
+    async fn get_payment_signature(&self, payment_id: &str) -> X402Result<Option<String>>;
+
+    /// Forget the signature recorded for `payment_id`, e.g. once it can no longer land
+    async fn remove_payment_signature(&self, payment_id: &str) -> X402Result<()>;
+}
+
+/// Contents of a [`MemoryStore`], for moving state between processes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StoreSnapshot {
+    /// Used nonces and when they expire
+    pub nonces: HashMap<String, DateTime<Utc>>,
+
+    /// Issued, unexpired payment requests
+    pub challenges: Vec<PaymentRequest>,
+}
+
+/// In-process [`NonceStore`], [`ChallengeStore`], [`GrantStore`] and
+/// [`PaymentSignatureStore`]
+///
+/// The default store for single-instance deployments. Cloning shares the
+/// underlying state. Expired entries are dropped as the store is used.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    nonces: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
+    challenges: Arc<RwLock<HashMap<String, PaymentRequest>>>,
+    grants: Arc<RwLock<HashMap<String, Grant>>>,
+    payment_signatures: Arc<RwLock<HashMap<String, RecordedSignature>>>,
+}
+
+/// Payment signature held by a [`MemoryStore`]
+#[derive(Debug, Clone)]
+struct RecordedSignature {
+    signature: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy out all unexpired entries
+    pub fn export(&self) -> StoreSnapshot {
+        let now = Utc::now();
+        StoreSnapshot {
+            nonces: read(&self.nonces)
+                .iter()
+                .filter(|(_, expires_at)| **expires_at > now)
+                .map(|(nonce, expires_at)| (nonce.clone(), *expires_at))
+                .collect(),
+            challenges: read(&self.challenges)
+                .values()
+                .filter(|request| !request.is_expired())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Merge entries from a snapshot into this store
+    ///
+    /// Entries that have expired since the snapshot was taken are skipped.
+    pub fn import(&self, snapshot: StoreSnapshot) {
+        let now = Utc::now();
+        write(&self.nonces).extend(
+            snapshot
+                .nonces
+                .into_iter()
+                .filter(|(_, expires_at)| *expires_at > now),
+        );
+        write(&self.challenges).extend(
+            snapshot
+                .challenges
+                .into_iter()
+                .filter(|request| !request.is_expired())
+                .map(|request| (request.payment_id.clone(), request)),
+        );
+    }
+}
+
+#[async_trait]
+impl NonceStore for MemoryStore {
+    async fn check_and_record(&self, nonce: &str, expires_at: DateTime<Utc>) -> X402Result<bool> {
+        let now = Utc::now();
+        let mut nonces = write(&self.nonces);
+        nonces.retain(|_, expires_at| *expires_at > now);
+
+        if nonces.contains_key(nonce) {
+            return Ok(false);
+        }
+        nonces.insert(nonce.to_string(), expires_at);
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl ChallengeStore for MemoryStore {
+    async fn save(&self, request: &PaymentRequest) -> X402Result<()> {
+        let mut challenges = write(&self.challenges);
+        challenges.retain(|_, request| !request.is_expired());
+        challenges.insert(request.payment_id.clone(), request.clone());
+        Ok(())
+    }
+
+    async fn get(&self, payment_id: &str) -> X402Result<Option<PaymentRequest>> {
+        Ok(read(&self.challenges)
+            .get(payment_id)
+            .filter(|request| !request.is_expired())
+            .cloned())
+    }
+
+    async fn remove(&self, payment_id: &str) -> X402Result<()> {
+        write(&self.challenges).remove(payment_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl GrantStore for MemoryStore {
+    async fn save_grant(&self, grant: &Grant) -> X402Result<()> {
+        let now = Utc::now();
+        let mut grants = write(&self.grants);
+        grants.retain(|_, grant| grant.expires_at > now);
+        grants.insert(grant.payment_id.clone(), grant.clone());
+        Ok(())
+    }
+
+    async fn get_grant(&self, payment_id: &str) -> X402Result<Option<Grant>> {
+        Ok(read(&self.grants)
+            .get(payment_id)
+            .filter(|grant| grant.expires_at > Utc::now())
+            .cloned())
+    }
+}
+
+#[async_trait]
+impl PaymentSignatureStore for MemoryStore {
+    async fn save_payment_signature(
+        &self,
+        payment_id: &str,
+        signature: &str,
+        expires_at: DateTime<Utc>,
+    ) -> X402Result<()> {
+        let now = Utc::now();
+        let mut signatures = write(&self.payment_signatures);
+        signatures.retain(|_, recorded| recorded.expires_at > now);
+        signatures.insert(
+            payment_id.to_string(),
+            RecordedSignature {
+                signature: signature.to_string(),
+                expires_at,
+            },
+        );
+        Ok(())
+    }
+
+    async fn get_payment_signature(&self, payment_id: &str) -> X402Result<Option<String>> {
+        Ok(read(&self.payment_signatures)
+            .get(payment_id)
+            .filter(|recorded| recorded.expires_at > Utc::now())
+            .map(|recorded| recorded.signature.clone()))
+    }
+
+    async fn remove_payment_signature(&self, payment_id: &str) -> X402Result<()> {
+        write(&self.payment_signatures).remove(payment_id);
+        Ok(())
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> std::sync::RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>) -> std::sync::RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(feature = "redis")]
+pub use redis_store::RedisStore;
+
+#[cfg(feature = "redis")]
+mod redis_store {
+    use async_trait::async_trait;
+    use chrono::{DateTime, Utc};
+    use redis::{aio::ConnectionManager, AsyncCommands, SetExpiry, SetOptions};
+
+    use super::{ChallengeStore, Grant, GrantStore, NonceStore, PaymentSignatureStore};
+    use crate::{
+        errors::{X402Error, X402Result},
+        models::PaymentRequest,
+    };
+
+    /// [`NonceStore`], [`ChallengeStore`], [`GrantStore`] and
+    /// [`PaymentSignatureStore`] shared through Redis
+    ///
+    /// Every instance pointed at the same Redis (and key prefix) sees the
+    /// challenges and nonces recorded by the others. Entries expire in Redis
+    /// at their deadline.
+    #[derive(Clone)]
+    pub struct RedisStore {
+        connection: ConnectionManager,
+        prefix: String,
+    }
+
+    impl RedisStore {
+        /// Connect to Redis at `url`, e.g. `redis://127.0.0.1/`
+        pub async fn connect(url: &str) -> X402Result<Self> {
+            let client = redis::Client::open(url).map_err(|e| {
+                X402Error::Configuration(format!("Invalid Redis URL {}: {}", url, e))
+            })?;
+            let connection = ConnectionManager::new(client).await.map_err(redis_error)?;
+            Ok(Self {
+                connection,
+                prefix: "x402".to_string(),
+            })
+        }
+
+        /// Namespace keys under `prefix` (default `x402`)
+        pub fn with_prefix(mut self, prefix: &str) -> Self {
+            self.prefix = prefix.to_string();
+            self
+        }
+
+        fn key(&self, kind: &str, id: &str) -> String {
+            format!("{}:{}:{}", self.prefix, kind, id)
+        }
+    }
+
+    /// Expiry at `deadline`, at least a second from now
+    fn expire_at(deadline: DateTime<Utc>) -> SetExpiry {
+        let earliest = Utc::now().timestamp() + 1;
+        SetExpiry::EXAT(deadline.timestamp().max(earliest) as u64)
+    }
+
+    fn redis_error(e: redis::RedisError) -> X402Error {
+        X402Error::Network(format!("Redis error: {}", e))
+    }
+
+    #[async_trait]
+    impl NonceStore for RedisStore {
+        async fn check_and_record(
+            &self,
+            nonce: &str,
+            expires_at: DateTime<Utc>,
+        ) -> X402Result<bool> {
+            let options = SetOptions::default()
+                .conditional_set(redis::ExistenceCheck::NX)
+                .with_expiration(expire_at(expires_at));
+            let recorded: Option<String> = self
+                .connection
+                .clone()
+                .set_options(self.key("nonce", nonce), 1, options)
+                .await
+                .map_err(redis_error)?;
+            Ok(recorded.is_some())
+        }
+    }
+
+    #[async_trait]
+    impl ChallengeStore for RedisStore {
+        async fn save(&self, request: &PaymentRequest) -> X402Result<()> {
+            let options = SetOptions::default().with_expiration(expire_at(request.expires_at));
+            self.connection
+                .clone()
+                .set_options::<_, _, ()>(
+                    self.key("challenge", &request.payment_id),
+                    request.to_json()?,
+                    options,
+                )
+                .await
+                .map_err(redis_error)
+        }
+
+        async fn get(&self, payment_id: &str) -> X402Result<Option<PaymentRequest>> {
+            let json: Option<String> = self
+                .connection
+                .clone()
+                .get(self.key("challenge", payment_id))
+                .await
+                .map_err(redis_error)?;
+            json.map(|json| PaymentRequest::from_json(&json))
+                .transpose()
+        }
+
+        async fn remove(&self, payment_id: &str) -> X402Result<()> {
+            self.connection
+                .clone()
+                .del::<_, ()>(self.key("challenge", payment_id))
+                .await
+                .map_err(redis_error)
+        }
+    }
+
+    #[async_trait]
+    impl GrantStore for RedisStore {
+        async fn save_grant(&self, grant: &Grant) -> X402Result<()> {
+            let options = SetOptions::default().with_expiration(expire_at(grant.expires_at));
+            self.connection
+                .clone()
+                .set_options::<_, _, ()>(
+                    self.key("grant", &grant.payment_id),
+                    serde_json::to_string(grant)?,
+                    options,
+                )
+                .await
+                .map_err(redis_error)
+        }
+
+        async fn get_grant(&self, payment_id: &str) -> X402Result<Option<Grant>> {
+            let json: Option<String> = self
+                .connection
+                .clone()
+                .get(self.key("grant", payment_id))
+                .await
+                .map_err(redis_error)?;
+            Ok(json.map(|json| serde_json::from_str(&json)).transpose()?)
+        }
+    }
+
+    #[async_trait]
+    impl PaymentSignatureStore for RedisStore {
+        async fn save_payment_signature(
+            &self,
+            payment_id: &str,
+            signature: &str,
+            expires_at: DateTime<Utc>,
+        ) -> X402Result<()> {
+            let options = SetOptions::default().with_expiration(expire_at(expires_at));
+            self.connection
+                .clone()
+                .set_options::<_, _, ()>(self.key("payment", payment_id), signature, options)
+                .await
+                .map_err(redis_error)
+        }
+
+        async fn get_payment_signature(&self, payment_id: &str) -> X402Result<Option<String>> {
+            self.connection
+                .clone()
+                .get(self.key("payment", payment_id))
+                .await
+                .map_err(redis_error)
+        }
+
+        async fn remove_payment_signature(&self, payment_id: &str) -> X402Result<()> {
+            self.connection
+                .clone()
+                .del::<_, ()>(self.key("payment", payment_id))
+                .await
+                .map_err(redis_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn request(payment_id: &str, expires_in: i64) -> PaymentRequest {
+        PaymentRequest::builder()
+            .amount("0.10")
+            .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+            .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+            .network("solana-devnet")
+            .expires_at(Utc::now() + Duration::seconds(expires_in))
+            .nonce(format!("nonce_{}", payment_id))
+            .payment_id(payment_id.to_string())
+            .resource("/data")
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_nonce_replay_is_detected() {
+        let store = MemoryStore::new();
+        let expires_at = Utc::now() + Duration::seconds(300);
+
+        assert!(store.check_and_record("n1", expires_at).await.unwrap());
+        assert!(!store.check_and_record("n1", expires_at).await.unwrap());
+
+        // Expired nonces are forgotten
+        let past = Utc::now() - Duration::seconds(1);
+        assert!(store.check_and_record("n2", past).await.unwrap());
+        assert!(store.check_and_record("n2", expires_at).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_export_import_moves_state_between_instances() {
+        let first = MemoryStore::new();
+        first.save(&request("pay_1", 300)).await.unwrap();
+        first.save(&request("pay_old", -1)).await.unwrap();
+        first
+            .check_and_record("used", Utc::now() + Duration::seconds(300))
+            .await
+            .unwrap();
+
+        let snapshot: StoreSnapshot =
+            serde_json::from_str(&serde_json::to_string(&first.export()).unwrap()).unwrap();
+        let second = MemoryStore::new();
+        second.import(snapshot);
+
+        assert!(second.get("pay_1").await.unwrap().is_some());
+        assert!(second.get("pay_old").await.unwrap().is_none());
+        assert!(!second
+            .check_and_record("used", Utc::now() + Duration::seconds(300))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_grants_expire() {
+        let store = MemoryStore::new();
+        let grant = |payment_id: &str, expires_in| Grant {
+            payment_id: payment_id.to_string(),
+            signature: "sig".to_string(),
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/plain".to_string())],
+            body: b"report".to_vec(),
+            expires_at: Utc::now() + Duration::seconds(expires_in),
+        };
+        store.save_grant(&grant("pay_1", 300)).await.unwrap();
+        store.save_grant(&grant("pay_old", -1)).await.unwrap();
+
+        let saved = store.get_grant("pay_1").await.unwrap().unwrap();
+        assert!(saved.matches("sig"));
+        assert_eq!(saved.body, b"report");
+        assert!(store.get_grant("pay_old").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_payment_signatures_are_replaced_removed_and_expire() {
+        let store = MemoryStore::new();
+        let expires_in = |seconds| Utc::now() + Duration::seconds(seconds);
+        store
+            .save_payment_signature("pay_1", "sig_1", expires_in(300))
+            .await
+            .unwrap();
+        store
+            .save_payment_signature("pay_1", "sig_2", expires_in(300))
+            .await
+            .unwrap();
+        store
+            .save_payment_signature("pay_old", "sig_3", expires_in(-1))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_payment_signature("pay_1").await.unwrap(),
+            Some("sig_2".to_string())
+        );
+        assert!(store
+            .get_payment_signature("pay_old")
+            .await
+            .unwrap()
+            .is_none());
+
+        store.remove_payment_signature("pay_1").await.unwrap();
+        assert!(store
+            .get_payment_signature("pay_1")
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,419 @@
+//! Token transfer extraction from confirmed transactions
+//!
+//! Verification reads transfers from the RPC's `jsonParsed` encoding rather
+//! than matching raw instructions. The parsed form covers transfers made
+//! directly, through another program via CPI (inner instructions), and by
+//! either the SPL Token or Token-2022 program, as well as native SOL
+//! transfers by the System program. Memos attached with the SPL Memo program
+//! are read the same way.
+
+use solana_sdk::{pubkey, pubkey::Pubkey};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+    EncodedTransaction, UiInstruction, UiMessage, UiParsedInstruction, UiTransactionTokenBalance,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use std::str::FromStr;
+
+/// Program name the RPC uses for both the SPL Token and Token-2022 programs
+const SPL_TOKEN_PROGRAM_NAME: &str = "spl-token";
+
+/// Program name the RPC uses for the System program
+const SYSTEM_PROGRAM_NAME: &str = "system";
+
+/// Program name the RPC uses for the SPL Memo program
+const MEMO_PROGRAM_NAME: &str = "spl-memo";
+
+/// SPL Memo program (v2)
+pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// A token transfer found in a confirmed transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenTransfer {
+    /// Token program that executed the transfer
+    pub program_id: String,
+
+    /// Source token account
+    pub source: String,
+
+    /// Destination token account
+    pub destination: String,
+
+    /// Signing authority of the source account, if reported
+    pub authority: Option<String>,
+
+    /// Token mint, from the instruction or the destination's token balance
+    pub mint: Option<String>,
+
+    /// Owner of the destination token account, from its token balance
+    pub destination_owner: Option<String>,
+
+    /// Amount transferred in the token's smallest unit
+    pub amount: u64,
+
+    /// Part of `amount` withheld by a Token-2022 transfer-fee mint
+    pub fee: u64,
+
+    /// Token decimals, if reported
+    pub decimals: Option<u8>,
+
+    /// Whether the transfer was made by a cross-program invocation
+    pub inner: bool,
+}
+
+impl TokenTransfer {
+    /// Amount the destination actually received, after transfer fees
+    pub fn net_amount(&self) -> u64 {
+        self.amount.saturating_sub(self.fee)
+    }
+
+    /// Check whether this transfer pays `recipient` in `mint`
+    ///
+    /// The destination's owner is taken from the transaction's token balances
+    /// when available; otherwise the destination must be the recipient's
+    /// associated token account under the executing token program.
+    pub fn pays(&self, recipient: &str, mint: &str) -> bool {
+        if self.mint.as_deref() != Some(mint) {
+            return false;
+        }
+
+        if let Some(owner) = &self.destination_owner {
+            return owner == recipient;
+        }
+
+        match (
+            Pubkey::from_str(recipient),
+            Pubkey::from_str(mint),
+            Pubkey::from_str(&self.program_id),
+        ) {
+            (Ok(recipient), Ok(mint), Ok(program_id)) => {
+                get_associated_token_address_with_program_id(&recipient, &mint, &program_id)
+                    .to_string()
+                    == self.destination
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A native SOL transfer found in a confirmed transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolTransfer {
+    /// Source wallet
+    pub source: String,
+
+    /// Destination wallet
+    pub destination: String,
+
+    /// Amount transferred in lamports
+    pub lamports: u64,
+
+    /// Whether the transfer was made by a cross-program invocation
+    pub inner: bool,
+}
+
+/// Fee payer of a transaction, i.e. its first account key
+///
+/// Works with both `jsonParsed` and raw JSON encodings.
+pub fn fee_payer(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> Option<String> {
+    match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui) => match &ui.message {
+            UiMessage::Parsed(message) => message.account_keys.first().map(|a| a.pubkey.clone()),
+            UiMessage::Raw(message) => message.account_keys.first().cloned(),
+        },
+        _ => None,
+    }
+}
+
+/// Accounts that signed a transaction
+///
+/// Works with both `jsonParsed` and raw JSON encodings.
+pub fn signers(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<String> {
+    match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui) => match &ui.message {
+            UiMessage::Parsed(message) => message
+                .account_keys
+                .iter()
+                .filter(|a| a.signer)
+                .map(|a| a.pubkey.clone())
+                .collect(),
+            UiMessage::Raw(message) => message
+                .account_keys
+                .iter()
+                .take(usize::from(message.header.num_required_signatures))
+                .cloned()
+                .collect(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Memos attached to a transaction fetched with `jsonParsed` encoding
+///
+/// Only top-level SPL Memo instructions are read, in order.
+pub fn memos(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<String> {
+    let message = match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui) => match &ui.message {
+            UiMessage::Parsed(message) => message,
+            UiMessage::Raw(_) => return Vec::new(),
+        },
+        _ => return Vec::new(),
+    };
+
+    message
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed))
+                if parsed.program == MEMO_PROGRAM_NAME =>
+            {
+                parsed.parsed.as_str().map(str::to_string)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Net change of `owner`'s balance in `mint` over a transaction
+///
+/// Compares the post and pre token balances in the transaction meta for every
+/// token account `owner` holds in `mint`, in the token's smallest unit. An
+/// account created by the transaction counts as starting from zero. Returns
+/// `None` if the meta reports no balance for `owner` in `mint`.
+pub fn token_balance_change(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    owner: &str,
+    mint: &str,
+) -> Option<i128> {
+    let meta = transaction.transaction.meta.as_ref()?;
+    let balances = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| {
+        let balances = match balances {
+            OptionSerializer::Some(balances) => balances.as_slice(),
+            _ => &[],
+        };
+        balances
+            .iter()
+            .filter(|b| {
+                b.mint == mint && matches!(&b.owner, OptionSerializer::Some(o) if o == owner)
+            })
+            .map(|b| {
+                let amount = b.ui_token_amount.amount.parse::<u64>().unwrap_or_default();
+                (b.account_index, i128::from(amount))
+            })
+            .collect::<std::collections::HashMap<_, _>>()
+    };
+
+    let pre = balances(&meta.pre_token_balances);
+    let post = balances(&meta.post_token_balances);
+    if pre.is_empty() && post.is_empty() {
+        return None;
+    }
+
+    let change = post
+        .iter()
+        .map(|(index, amount)| amount - pre.get(index).copied().unwrap_or_default())
+        .sum::<i128>()
+        - pre
+            .iter()
+            .filter(|(index, _)| !post.contains_key(index))
+            .map(|(_, amount)| amount)
+            .sum::<i128>();
+    Some(change)
+}
+
+/// Net change of `owner`'s SOL balance over a transaction, in lamports
+///
+/// Read from the pre and post balances in the transaction meta. Returns
+/// `None` if `owner` is not one of the transaction's accounts.
+pub fn lamport_balance_change(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    owner: &str,
+) -> Option<i128> {
+    let index = match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui) => match &ui.message {
+            UiMessage::Parsed(message) => {
+                message.account_keys.iter().position(|a| a.pubkey == owner)
+            }
+            UiMessage::Raw(message) => message.account_keys.iter().position(|a| a == owner),
+        },
+        _ => None,
+    }?;
+    let meta = transaction.transaction.meta.as_ref()?;
+    let pre = meta.pre_balances.get(index)?;
+    let post = meta.post_balances.get(index)?;
+    Some(i128::from(*post) - i128::from(*pre))
+}
+
+/// Extract every native SOL transfer from a transaction fetched with `jsonParsed` encoding
+///
+/// Ordered like [`token_transfers`].
+pub fn sol_transfers(transaction: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<SolTransfer> {
+    let message = match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui) => match &ui.message {
+            UiMessage::Parsed(message) => message,
+            UiMessage::Raw(_) => return Vec::new(),
+        },
+        _ => return Vec::new(),
+    };
+
+    let mut transfers: Vec<SolTransfer> = message
+        .instructions
+        .iter()
+        .filter_map(|instruction| parse_sol_transfer(instruction, false))
+        .collect();
+
+    let meta = transaction.transaction.meta.as_ref();
+    if let Some(OptionSerializer::Some(inner)) = meta.map(|m| m.inner_instructions.as_ref()) {
+        transfers.extend(
+            inner
+                .iter()
+                .flat_map(|group| group.instructions.iter())
+                .filter_map(|instruction| parse_sol_transfer(instruction, true)),
+        );
+    }
+
+    transfers
+}
+
+fn parse_sol_transfer(instruction: &UiInstruction, inner: bool) -> Option<SolTransfer> {
+    let parsed = match instruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => parsed,
+        _ => return None,
+    };
+    if parsed.program != SYSTEM_PROGRAM_NAME {
+        return None;
+    }
+    if !matches!(
+        parsed.parsed.get("type")?.as_str()?,
+        "transfer" | "transferWithSeed"
+    ) {
+        return None;
+    }
+
+    let info = parsed.parsed.get("info")?;
+    let field = |name: &str| info.get(name).and_then(|v| v.as_str()).map(str::to_string);
+    Some(SolTransfer {
+        source: field("source")?,
+        destination: field("destination")?,
+        lamports: info.get("lamports")?.as_u64()?,
+        inner,
+    })
+}
+
+/// Extract every token transfer from a transaction fetched with `jsonParsed` encoding
+///
+/// Top-level instructions are listed first, followed by inner instructions in
+/// the order the RPC reports them. Transactions fetched with another encoding
+/// yield no transfers.
+pub fn token_transfers(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Vec<TokenTransfer> {
+    let message = match &transaction.transaction.transaction {
+        EncodedTransaction::Json(ui) => match &ui.message {
+            UiMessage::Parsed(message) => message,
+            UiMessage::Raw(_) => return Vec::new(),
+        },
+        _ => return Vec::new(),
+    };
+    let account_keys: Vec<&str> = message
+        .account_keys
+        .iter()
+        .map(|account| account.pubkey.as_str())
+        .collect();
+
+    let meta = transaction.transaction.meta.as_ref();
+    let post_token_balances = match meta.map(|m| m.post_token_balances.as_ref()) {
+        Some(OptionSerializer::Some(balances)) => balances.as_slice(),
+        _ => &[],
+    };
+    let balance_of = |account: &str| {
+        let index = account_keys.iter().position(|key| *key == account)?;
+        post_token_balances
+            .iter()
+            .find(|balance| usize::from(balance.account_index) == index)
+    };
+
+    let mut transfers: Vec<TokenTransfer> = message
+        .instructions
+        .iter()
+        .filter_map(|instruction| parse_transfer(instruction, false, &balance_of))
+        .collect();
+
+    if let Some(OptionSerializer::Some(inner)) = meta.map(|m| m.inner_instructions.as_ref()) {
+        transfers.extend(
+            inner
+                .iter()
+                .flat_map(|group| group.instructions.iter())
+                .filter_map(|instruction| parse_transfer(instruction, true, &balance_of)),
+        );
+    }
+
+    transfers
+}
+
+fn parse_transfer<'a>(
+    instruction: &UiInstruction,
+    inner: bool,
+    balance_of: &impl Fn(&str) -> Option<&'a UiTransactionTokenBalance>,
+) -> Option<TokenTransfer> {
+    let parsed = match instruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => parsed,
+        _ => return None,
+    };
+    if parsed.program != SPL_TOKEN_PROGRAM_NAME {
+        return None;
+    }
+
+    let instruction_type = parsed.parsed.get("type")?.as_str()?;
+    if !matches!(
+        instruction_type,
+        "transfer" | "transferChecked" | "transferCheckedWithFee"
+    ) {
+        return None;
+    }
+
+    let info = parsed.parsed.get("info")?;
+    let field = |name: &str| info.get(name).and_then(|v| v.as_str()).map(str::to_string);
+
+    let source = field("source")?;
+    let destination = field("destination")?;
+    let token_amount = info.get("tokenAmount");
+    let amount = token_amount
+        .and_then(|t| t.get("amount"))
+        .or_else(|| info.get("amount"))?
+        .as_str()?
+        .parse::<u64>()
+        .ok()?;
+
+    let fee = info
+        .get("feeAmount")
+        .and_then(|f| f.get("amount"))
+        .and_then(|a| a.as_str())
+        .and_then(|a| a.parse::<u64>().ok())
+        .unwrap_or_default();
+
+    let destination_balance = balance_of(&destination);
+    let mint = field("mint").or_else(|| destination_balance.map(|b| b.mint.clone()));
+    let destination_owner = destination_balance.and_then(|b| match &b.owner {
+        OptionSerializer::Some(owner) => Some(owner.clone()),
+        _ => None,
+    });
+    let decimals = token_amount
+        .and_then(|t| t.get("decimals"))
+        .and_then(|d| d.as_u64())
+        .and_then(|d| u8::try_from(d).ok())
+        .or_else(|| destination_balance.map(|b| b.ui_token_amount.decimals));
+
+    Some(TokenTransfer {
+        program_id: parsed.program_id.clone(),
+        source,
+        destination,
+        authority: field("authority").or_else(|| field("multisigAuthority")),
+        mint,
+        destination_owner,
+        amount,
+        fee,
+        decimals,
+        inner,
+    })
+}
@@ -0,0 +1,236 @@
+use solana_sdk::pubkey::Pubkey;
+use std::{str::FromStr, time::Duration};
+
+use crate::{
+    errors::{X402Error, X402Result},
+    escrow::EscrowProgram,
+};
+
+/// Basis points in 100%
+const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Approximate time between Solana slots, used when a block time is unavailable
+pub const SLOT_DURATION: Duration = Duration::from_millis(400);
+
+/// Server-side policy applied when verifying a payment
+///
+/// The defaults accept only payments of at least the exact required amount.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerificationOptions {
+    /// Accept payments up to this many basis points below the required amount
+    ///
+    /// Useful when prices come from a drifting feed, e.g. `50` accepts a
+    /// payment of 0.995 when 1.0 was requested. Must be at most 10,000.
+    pub amount_tolerance_bps: u16,
+
+    /// Reject payments confirmed less than this long ago
+    ///
+    /// Reduces reorg risk on high-value endpoints. The age comes from the
+    /// transaction's block time, or from its slot distance to the current slot
+    /// when no block time is reported. Too-fresh payments fail verification
+    /// with a hint of how long to wait before retrying.
+    pub min_confirmation_age: Option<Duration>,
+
+    /// Reject payments confirmed more than this long ago
+    ///
+    /// Keeps old, unrelated transfers from being presented as fresh payments.
+    /// The age is measured as for `min_confirmation_age`.
+    pub max_confirmation_age: Option<Duration>,
+
+    /// Allowance for the cluster's block times disagreeing with the local clock
+    ///
+    /// Both age bounds are widened by this much, so a payment whose age is
+    /// within the skew of a bound is given the benefit of the doubt.
+    pub clock_skew: Duration,
+
+    /// Also accept deposits into the recipient's escrow with this payment program
+    pub escrow: Option<EscrowProgram>,
+
+    /// Reject payments whose transaction wasn't signed by the authorization's `public_key`
+    ///
+    /// Off by default: payments from an SPL Token multisig name the multisig
+    /// account, which never signs itself.
+    pub require_payer_signature: bool,
+
+    /// Reject payments whose transaction doesn't carry the authorization's
+    /// `payment_id` as an SPL Memo
+    ///
+    /// Pairs with payers built `with_payment_id_memo`, so every transfer can
+    /// be reconciled with the challenge it paid.
+    pub require_payment_id_memo: bool,
+
+    /// Require the recipient to receive the full amount after Token-2022
+    /// transfer fees
+    ///
+    /// By default the amount sent counts, so transfer-fee mints' fees are
+    /// borne by the recipient. With this set the payer bears them and must
+    /// send the amount plus the fee, which payers built with this library
+    /// don't add.
+    pub require_amount_after_transfer_fees: bool,
+}
+
+impl VerificationOptions {
+    /// Set the amount tolerance in basis points
+    pub fn with_amount_tolerance_bps(mut self, bps: u16) -> Self {
+        self.amount_tolerance_bps = bps;
+        self
+    }
+
+    /// Require payments to be at least `age` old
+    pub fn with_min_confirmation_age(mut self, age: Duration) -> Self {
+        self.min_confirmation_age = Some(age);
+        self
+    }
+
+    /// Require payments to be at most `age` old
+    pub fn with_max_confirmation_age(mut self, age: Duration) -> Self {
+        self.max_confirmation_age = Some(age);
+        self
+    }
+
+    /// Widen the age bounds by `skew` to allow for clock differences
+    pub fn with_clock_skew(mut self, skew: Duration) -> Self {
+        self.clock_skew = skew;
+        self
+    }
+
+    /// Require the payer named in the authorization to have signed the payment
+    pub fn with_payer_signature_required(mut self) -> Self {
+        self.require_payer_signature = true;
+        self
+    }
+
+    /// Require the payment to carry its `payment_id` as a memo
+    pub fn with_payment_id_memo_required(mut self) -> Self {
+        self.require_payment_id_memo = true;
+        self
+    }
+
+    /// Require the amount to arrive in full after transfer fees
+    pub fn with_amount_after_transfer_fees_required(mut self) -> Self {
+        self.require_amount_after_transfer_fees = true;
+        self
+    }
+
+    /// Whether payments' confirmation age needs to be checked
+    pub fn checks_confirmation_age(&self) -> bool {
+        self.min_confirmation_age.is_some() || self.max_confirmation_age.is_some()
+    }
+
+    /// Accept deposits into the recipient's escrow with `program`
+    pub fn with_escrow(mut self, program: EscrowProgram) -> Self {
+        self.escrow = Some(program);
+        self
+    }
+
+    /// Owners whose token receipts count as paying `recipient`
+    ///
+    /// The recipient itself, followed by its escrow PDA when an escrow
+    /// program is configured.
+    pub fn payees(&self, recipient: &str) -> Vec<String> {
+        let mut payees = vec![recipient.to_string()];
+        if let (Some(escrow), Ok(recipient)) = (&self.escrow, Pubkey::from_str(recipient)) {
+            payees.push(escrow.escrow_address(&recipient).to_string());
+        }
+        payees
+    }
+
+    /// Check that a payment confirmed `age` ago is neither too fresh nor too old
+    ///
+    /// Both bounds allow for `clock_skew`.
+    pub fn check_confirmation_age(&self, signature: &str, age: Duration) -> X402Result<()> {
+        if let Some(minimum) = self.min_confirmation_age {
+            let skewed = age.saturating_add(self.clock_skew);
+            if skewed < minimum {
+                let wait = minimum - skewed;
+                return Err(X402Error::PaymentVerification(format!(
+                    "Transaction {} confirmed {}s ago, less than the required {}s; retry in {}s",
+                    signature,
+                    age.as_secs(),
+                    minimum.as_secs(),
+                    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+                )));
+            }
+        }
+        if let Some(maximum) = self.max_confirmation_age {
+            if age > maximum.saturating_add(self.clock_skew) {
+                return Err(X402Error::PaymentVerification(format!(
+                    "Transaction {} confirmed {}s ago, more than the allowed {}s",
+                    signature,
+                    age.as_secs(),
+                    maximum.as_secs()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Smallest amount accepted for `required`, in the token's smallest unit
+    ///
+    /// Computes `required * (1 - tolerance)`, rounded up so the tolerance is
+    /// never exceeded.
+    pub fn minimum_accepted(&self, required: u64) -> X402Result<u64> {
+        let bps = u64::from(self.amount_tolerance_bps);
+        if bps > BPS_DENOMINATOR {
+            return Err(X402Error::Configuration(format!(
+                "amount_tolerance_bps must be at most {}, got {}",
+                BPS_DENOMINATOR, bps
+            )));
+        }
+
+        let minimum = (u128::from(required) * u128::from(BPS_DENOMINATOR - bps))
+            .div_ceil(u128::from(BPS_DENOMINATOR));
+        Ok(minimum as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimum_accepted() {
+        let exact = VerificationOptions::default();
+        assert_eq!(exact.minimum_accepted(100_000).unwrap(), 100_000);
+
+        let tolerant = VerificationOptions::default().with_amount_tolerance_bps(50);
+        assert_eq!(tolerant.minimum_accepted(100_000).unwrap(), 99_500);
+        assert_eq!(tolerant.minimum_accepted(1).unwrap(), 1);
+
+        let invalid = VerificationOptions::default().with_amount_tolerance_bps(10_001);
+        assert!(invalid.minimum_accepted(100_000).is_err());
+    }
+
+    #[test]
+    fn test_confirmation_age_bounds_allow_clock_skew() {
+        let secs = Duration::from_secs;
+        let strict = VerificationOptions::default()
+            .with_min_confirmation_age(secs(20))
+            .with_max_confirmation_age(secs(60));
+        let skewed = strict.clone().with_clock_skew(secs(5));
+
+        // Just inside the bounds either way
+        for options in [&strict, &skewed] {
+            assert!(options.check_confirmation_age("sig", secs(20)).is_ok());
+            assert!(options.check_confirmation_age("sig", secs(60)).is_ok());
+        }
+
+        // Just outside them only with the skew allowance
+        assert!(strict.check_confirmation_age("sig", secs(16)).is_err());
+        assert!(skewed.check_confirmation_age("sig", secs(16)).is_ok());
+        assert!(strict.check_confirmation_age("sig", secs(64)).is_err());
+        assert!(skewed.check_confirmation_age("sig", secs(64)).is_ok());
+
+        // Beyond the allowance
+        let err = skewed
+            .check_confirmation_age("sig", secs(14))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("retry in 1s"), "{}", err);
+        let err = skewed
+            .check_confirmation_age("sig", secs(66))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("more than the allowed 60s"), "{}", err);
+    }
+}
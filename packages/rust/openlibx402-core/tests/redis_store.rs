@@ -0,0 +1,83 @@
+//! Cross-instance tests for `RedisStore`
+//!
+//! Run against a disposable Redis with
+//! `REDIS_URL=redis://127.0.0.1/ cargo test -p openlibx402-core --features redis`.
+//! The tests are skipped when `REDIS_URL` is unset.
+#![cfg(feature = "redis")]
+
+use chrono::{Duration, Utc};
+use openlibx402_core::{ChallengeStore, Grant, GrantStore, NonceStore, PaymentRequest, RedisStore};
+
+/// Two independent connections standing in for two server instances
+async fn instances() -> Option<(RedisStore, RedisStore)> {
+    let Ok(url) = std::env::var("REDIS_URL") else {
+        eprintln!("REDIS_URL not set, skipping Redis store test");
+        return None;
+    };
+    let prefix = format!("x402-test-{}", uuid::Uuid::new_v4());
+    let first = RedisStore::connect(&url)
+        .await
+        .unwrap()
+        .with_prefix(&prefix);
+    let second = RedisStore::connect(&url)
+        .await
+        .unwrap()
+        .with_prefix(&prefix);
+    Some((first, second))
+}
+
+#[tokio::test]
+async fn test_challenge_issued_on_one_instance_is_visible_on_another() {
+    let Some((issuer, verifier)) = instances().await else {
+        return;
+    };
+    let request = PaymentRequest::builder()
+        .amount("0.10")
+        .asset_address("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+        .payment_address("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU")
+        .network("solana-devnet")
+        .nonce("nonce_1")
+        .payment_id("pay_1")
+        .resource("/data")
+        .build()
+        .unwrap();
+
+    issuer.save(&request).await.unwrap();
+    assert_eq!(verifier.get("pay_1").await.unwrap(), Some(request));
+
+    verifier.remove("pay_1").await.unwrap();
+    assert!(issuer.get("pay_1").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_nonce_used_on_one_instance_is_rejected_on_another() {
+    let Some((first, second)) = instances().await else {
+        return;
+    };
+    let expires_at = Utc::now() + Duration::seconds(300);
+
+    assert!(first.check_and_record("nonce_1", expires_at).await.unwrap());
+    assert!(!second
+        .check_and_record("nonce_1", expires_at)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn test_grant_saved_on_one_instance_is_served_by_another() {
+    let Some((first, second)) = instances().await else {
+        return;
+    };
+    let grant = Grant {
+        payment_id: "pay_1".to_string(),
+        signature: "sig_1".to_string(),
+        status: 200,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: br#"{"report":1}"#.to_vec(),
+        expires_at: Utc::now() + Duration::seconds(300),
+    };
+
+    first.save_grant(&grant).await.unwrap();
+    assert_eq!(second.get_grant("pay_1").await.unwrap(), Some(grant));
+    assert!(second.get_grant("pay_2").await.unwrap().is_none());
+}
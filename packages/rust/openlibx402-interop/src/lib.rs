@@ -0,0 +1,221 @@
+//! Cross-crate compatibility harness for OpenLibx402
+//!
+//! The client and the Actix and Rocket integrations each implement their own
+//! half of the protocol: challenge encoding, header encoding, amount handling
+//! and verification. This crate serves the same paid endpoint from both
+//! frameworks on localhost and lets the tests in `tests/` drive them with the
+//! real client, so drift between the crates shows up as a failing test naming
+//! the side that diverged.
+//!
+//! All parties share one [`Chain`], a mock RPC backend on which payments sent
+//! by the client are confirmed and visible to the servers' verifiers.
+
+use actix_web::{web, App, HttpResponse, HttpServer};
+use openlibx402_client::X402Client;
+use openlibx402_core::{rpc::mock::MockRpcBackend, SolanaPaymentProcessor};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use std::{
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Price of the paid endpoint, in token units
+pub const PRICE: &str = "0.10";
+
+/// Path of the paid endpoint on every server
+pub const PAID_PATH: &str = "/premium";
+
+/// Path of a paid endpoint on the Actix server that serves retries the
+/// response their payment was first accepted with
+pub const CACHED_PATH: &str = "/premium/cached";
+
+/// Body served once a payment is accepted
+pub const CONTENT: &str = "premium content";
+
+/// Paths sold together as a bundle by the Actix server
+pub const BUNDLE_PATHS: [&str; 3] = ["/reports/daily", "/reports/weekly", "/reports/monthly"];
+
+/// Price of the whole bundle, in token units
+pub const BUNDLE_PRICE: &str = "0.25";
+
+/// Mock chain shared by the paying client and the verifying servers
+pub struct Chain {
+    pub backend: Arc<MockRpcBackend>,
+    pub mint: Pubkey,
+    pub recipient: Pubkey,
+    payer: Keypair,
+}
+
+impl Chain {
+    /// Create a chain on which the payer holds plenty of tokens
+    pub fn new() -> Self {
+        Self::with_latency(Duration::ZERO)
+    }
+
+    /// Like [`Chain::new`], with every RPC call taking `latency`
+    ///
+    /// Slow calls keep a payment in flight while concurrent requests arrive.
+    pub fn with_latency(latency: Duration) -> Self {
+        let payer = Keypair::new();
+        let mint = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let backend = Arc::new(
+            MockRpcBackend::new()
+                .with_latency(latency)
+                .with_confirmed_sends()
+                .with_mint(mint, 6)
+                .with_balance(payer.pubkey(), 1_000_000_000)
+                .with_associated_token_account(payer.pubkey(), mint, 1_000_000_000)
+                .with_associated_token_account(recipient, mint, 0),
+        );
+
+        Self {
+            backend,
+            mint,
+            recipient,
+            payer,
+        }
+    }
+
+    /// Processor talking to this chain
+    pub fn processor(&self) -> SolanaPaymentProcessor {
+        SolanaPaymentProcessor::with_backend(self.backend.clone(), None)
+    }
+
+    /// Client paying from the funded payer on this chain
+    pub fn client(&self) -> X402Client {
+        self.client_with(|processor| processor)
+    }
+
+    /// Like [`Chain::client`], paying through a `configure`d processor
+    pub fn client_with(
+        &self,
+        configure: impl FnOnce(SolanaPaymentProcessor) -> SolanaPaymentProcessor,
+    ) -> X402Client {
+        X402Client::with_payment_processor(self.payer.insecure_clone(), configure(self.processor()))
+    }
+
+    /// Number of payment transactions broadcast so far
+    pub fn payments(&self) -> usize {
+        self.backend.sent_transactions().len()
+    }
+}
+
+impl Default for Chain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A server running on localhost
+#[derive(Debug, Clone)]
+pub struct Server {
+    /// Framework name, used to label assertion failures
+    pub name: &'static str,
+    pub base_url: String,
+}
+
+impl Server {
+    /// Absolute URL of `path` on this server
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+/// Serve [`PAID_PATH`], [`CACHED_PATH`] and the [`BUNDLE_PATHS`] bundle through `X402Protection` on an Actix server
+pub async fn spawn_actix(chain: &Chain) -> Server {
+    use openlibx402_actix::{PaymentRequirement, X402Config, X402Protection};
+
+    let protection = X402Protection::builder()
+        .config(X402Config {
+            payment_address: chain.recipient.to_string(),
+            token_mint: chain.mint.to_string(),
+            ..Default::default()
+        })
+        .route(PAID_PATH, PaymentRequirement::new(PRICE))
+        .route(
+            CACHED_PATH,
+            PaymentRequirement::new(PRICE).with_cached_result(),
+        )
+        .bundle(PaymentRequirement::new(BUNDLE_PRICE).with_bundle(&BUNDLE_PATHS))
+        .payment_processor(chain.processor())
+        .build()
+        .expect("valid protection config");
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("bind actix listener");
+    let addr = listener.local_addr().expect("actix listener address");
+    let server = HttpServer::new(move || {
+        let content = || async { HttpResponse::Ok().body(CONTENT) };
+        BUNDLE_PATHS.iter().fold(
+            App::new()
+                .wrap(protection.clone())
+                .route(PAID_PATH, web::get().to(content))
+                .route(CACHED_PATH, web::get().to(content)),
+            |app, path| app.route(path, web::get().to(content)),
+        )
+    })
+    .workers(1)
+    .disable_signals()
+    .listen(listener)
+    .expect("listen on actix listener")
+    .run();
+    tokio::spawn(server);
+
+    Server {
+        name: "actix",
+        base_url: format!("http://{}", addr),
+    }
+}
+
+/// Paid Rocket endpoint; the guard verifies the payment against the fairing's price
+#[rocket::get("/premium")]
+fn rocket_premium(_payment: openlibx402_rocket::PaymentGuard) -> &'static str {
+    CONTENT
+}
+
+/// Serve [`PAID_PATH`] through `X402Fairing` and `PaymentGuard` on a Rocket server
+pub async fn spawn_rocket(chain: &Chain) -> Server {
+    use openlibx402_rocket::{PaymentRequirement, PricingTable, X402Config, X402Fairing};
+
+    // Rocket binds its own listener, so reserve a free port for it
+    let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .and_then(|listener| listener.local_addr())
+        .expect("reserve rocket port")
+        .port();
+    let config = rocket::Config {
+        address: Ipv4Addr::LOCALHOST.into(),
+        port,
+        log_level: rocket::config::LogLevel::Off,
+        ..rocket::Config::debug_default()
+    };
+
+    let rocket = rocket::custom(config)
+        .manage(X402Config {
+            payment_address: chain.recipient.to_string(),
+            token_mint: chain.mint.to_string(),
+            ..Default::default()
+        })
+        .manage(chain.processor())
+        .attach(X402Fairing::new(
+            PricingTable::new().route(PAID_PATH, PaymentRequirement::new(PRICE)),
+        ))
+        .mount("/", rocket::routes![rocket_premium]);
+    tokio::spawn(rocket.launch());
+
+    let addr = (Ipv4Addr::LOCALHOST, port);
+    for _ in 0..100 {
+        if TcpStream::connect(addr).is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    Server {
+        name: "rocket",
+        base_url: format!("http://{}:{}", Ipv4Addr::LOCALHOST, port),
+    }
+}
@@ -0,0 +1,228 @@
+//! End-to-end contract between the client and both server integrations
+//!
+//! Each step of the payment flow is checked separately so a failure names
+//! the side that diverged: the server's challenge, the client's parsing or
+//! payment, the header encoding, or the server's verification.
+
+use openlibx402_client::{PaymentAuthorization, SolanaPaymentProcessor, X402AutoClient};
+use openlibx402_interop::{
+    spawn_actix, spawn_rocket, Chain, Server, BUNDLE_PATHS, BUNDLE_PRICE, CACHED_PATH, CONTENT,
+    PAID_PATH, PRICE,
+};
+use std::time::Duration;
+
+/// Walk one payment through `server` step by step
+async fn check_manual_flow(chain: &Chain, server: &Server) {
+    let client = chain.client();
+    let url = server.url(PAID_PATH);
+
+    let challenge = client.get(&url).await.unwrap();
+    assert_eq!(
+        challenge.status(),
+        402,
+        "{}: server did not challenge an unpaid request",
+        server.name
+    );
+
+    let request = client
+        .parse_payment_request(challenge)
+        .await
+        .unwrap_or_else(|e| {
+            panic!(
+                "{}: client could not parse the challenge: {}",
+                server.name, e
+            )
+        });
+    assert_eq!(
+        (
+            request.max_amount_required.as_str(),
+            request.payment_address.as_str(),
+            request.asset_address.as_str(),
+            request.resource.as_str(),
+        ),
+        (
+            PRICE,
+            chain.recipient.to_string().as_str(),
+            chain.mint.to_string().as_str(),
+            PAID_PATH,
+        ),
+        "{}: server challenge does not describe the configured price",
+        server.name
+    );
+
+    let authorization = client
+        .create_payment(&request)
+        .await
+        .unwrap_or_else(|e| panic!("{}: client could not pay the challenge: {}", server.name, e));
+    let header = authorization
+        .to_header_value()
+        .unwrap_or_else(|e| panic!("client could not encode its authorization: {}", e));
+    assert_eq!(
+        PaymentAuthorization::from_header_value(&header)
+            .ok()
+            .as_ref(),
+        Some(&authorization),
+        "client authorization header does not round-trip"
+    );
+
+    let paid = client.get_with_auth(&url, &authorization).await.unwrap();
+    let status = paid.status();
+    let body = paid.text().await.unwrap();
+    assert_eq!(
+        (status.as_u16(), body.as_str()),
+        (200, CONTENT),
+        "{}: server did not accept the client's payment",
+        server.name
+    );
+}
+
+/// Let the auto client pay `server` on its own
+async fn check_auto_flow(chain: &Chain, server: &Server) {
+    let client = X402AutoClient::from_client(chain.client(), None);
+    let payments = chain.payments();
+
+    let response = client
+        .get(&server.url(PAID_PATH))
+        .await
+        .unwrap_or_else(|e| panic!("{}: auto client payment failed: {}", server.name, e));
+    assert_eq!(
+        response.status(),
+        200,
+        "{}: server refused the auto client's payment",
+        server.name
+    );
+    assert_eq!(
+        chain.payments(),
+        payments + 1,
+        "{}: auto client should pay exactly once",
+        server.name
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_actix_server_accepts_client_payments() {
+    let chain = Chain::new();
+    let server = spawn_actix(&chain).await;
+
+    check_manual_flow(&chain, &server).await;
+    check_auto_flow(&chain, &server).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rocket_server_accepts_client_payments() {
+    let chain = Chain::new();
+    let server = spawn_rocket(&chain).await;
+
+    check_manual_flow(&chain, &server).await;
+    check_auto_flow(&chain, &server).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_servers_issue_equivalent_challenges() {
+    let chain = Chain::new();
+    let client = chain.client();
+
+    let mut requests = Vec::new();
+    for server in [spawn_actix(&chain).await, spawn_rocket(&chain).await] {
+        let response = client.get(&server.url(PAID_PATH)).await.unwrap();
+        let request = client
+            .parse_payment_request(response)
+            .await
+            .unwrap_or_else(|e| {
+                panic!(
+                    "{}: client could not parse the challenge: {}",
+                    server.name, e
+                )
+            });
+        requests.push((server.name, request));
+    }
+
+    let (actix, rocket) = (&requests[0].1, &requests[1].1);
+    assert_eq!(
+        (
+            &actix.max_amount_required,
+            &actix.payment_address,
+            &actix.asset_address,
+            &actix.network,
+            &actix.resource,
+        ),
+        (
+            &rocket.max_amount_required,
+            &rocket.payment_address,
+            &rocket.asset_address,
+            &rocket.network,
+            &rocket.resource,
+        ),
+        "actix and rocket challenges disagree for the same configuration"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_one_bundle_payment_unlocks_every_resource() {
+    let chain = Chain::new();
+    let server = spawn_actix(&chain).await;
+    let client = X402AutoClient::from_client(chain.client(), None);
+
+    for path in BUNDLE_PATHS {
+        let response = client.get(&server.url(path)).await.unwrap();
+        assert_eq!(response.status(), 200, "bundle resource {} refused", path);
+        assert_eq!(response.text().await.unwrap(), CONTENT);
+    }
+    assert_eq!(chain.payments(), 1, "the bundle should be paid once");
+
+    // The payment covers each resource once
+    let manual = chain.client();
+    let response = manual.get(&server.url(BUNDLE_PATHS[0])).await.unwrap();
+    let request = manual.parse_payment_request(response).await.unwrap();
+    assert_eq!(request.max_amount_required, BUNDLE_PRICE);
+    assert_eq!(request.resources, BUNDLE_PATHS);
+    let authorization = manual.create_payment(&request).await.unwrap();
+    let first = manual
+        .get_with_auth(&server.url(BUNDLE_PATHS[1]), &authorization)
+        .await
+        .unwrap();
+    assert_eq!(first.status(), 200);
+    let again = manual
+        .get_with_auth(&server.url(BUNDLE_PATHS[1]), &authorization)
+        .await
+        .unwrap();
+    assert_eq!(again.status(), 409);
+    let unlisted = manual
+        .get_with_auth(&server.url(PAID_PATH), &authorization)
+        .await
+        .unwrap();
+    assert_ne!(unlisted.status(), 200);
+}
+
+/// Request `url` ten times at once, expecting each to be served
+async fn get_concurrently(client: &X402AutoClient, url: &str) {
+    let requests = (0..10).map(|_| client.get(url));
+    for response in futures_util::future::join_all(requests).await {
+        let response = response.unwrap();
+        assert_eq!(response.status(), 200, "{} refused a payment", url);
+        assert_eq!(response.text().await.unwrap(), CONTENT);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_requests_pay_as_the_server_requires() {
+    // Slow RPC calls keep the first payment in flight while the others get their 402
+    let chain = Chain::with_latency(Duration::from_millis(50));
+    let server = spawn_actix(&chain).await;
+    // Payments of the same amount under one blockhash are the same
+    // transaction unless something sets them apart
+    let client = chain.client_with(SolanaPaymentProcessor::with_payment_id_memo);
+    let client = X402AutoClient::from_client(client, None);
+
+    // A result cached for the first payment is served for the others
+    get_concurrently(&client, &server.url(CACHED_PATH)).await;
+    assert_eq!(
+        chain.payments(),
+        1,
+        "concurrent requests should share one payment"
+    );
+
+    // Otherwise the shared payment is refused as a replay and each pays its own
+    get_concurrently(&client, &server.url(PAID_PATH)).await;
+    assert_eq!(chain.payments(), 11, "each request should pay once");
+}
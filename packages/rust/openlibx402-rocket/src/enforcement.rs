@@ -0,0 +1,528 @@
+use openlibx402_core::{PaymentAuthorization, PaymentRequest, PRICE_HEADER};
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    http::{uri::Origin, Method, Status},
+    request::{FromRequest, Outcome, Request},
+    response::status::Custom,
+    serde::json::{Json, Value},
+    Build, Data, Response, Rocket,
+};
+
+use crate::{
+    create_payment_request, pricing::PricingTable, PaymentRequiredResponse, PaymentRequirement,
+    X402Config,
+};
+
+/// Internal route unpaid requests are rerouted to
+const CHALLENGE_PATH: &str = "/__x402/payment-required";
+
+/// Internal route `HEAD` requests to paid paths are rerouted to
+const PRICE_PATH: &str = "/__x402/price";
+
+/// Fairing that enforces a [`PricingTable`] on every request
+///
+/// Requests to paid paths without a well-formed `X-Payment-Authorization`
+/// header are rerouted, before any handler runs, to an internal route that
+/// answers with a 402 [`PaymentRequiredResponse`]. Requires a managed
+/// [`X402Config`]; launch fails without one.
+///
+/// The requirement of the matched path is also made available to
+/// [`PaymentGuard`](crate::PaymentGuard), which verifies payments against it,
+/// and a payment the guard rejects is answered with a fresh 402 challenge.
+///
+/// Every response on a paid path carries an `X-Payment-Price` header. `HEAD`
+/// requests to paid paths are answered with an empty 200 carrying just that
+/// header, so crawlers can learn prices without being challenged.
+#[derive(Debug, Clone)]
+pub struct X402Fairing {
+    pricing: PricingTable,
+}
+
+impl X402Fairing {
+    /// Create a fairing charging according to `pricing`
+    pub fn new(pricing: PricingTable) -> Self {
+        Self { pricing }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for X402Fairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "X402 Payment Enforcement",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        if rocket.state::<X402Config>().is_none() {
+            rocket::error!("X402Fairing requires a managed X402Config");
+            return Err(rocket);
+        }
+        Ok(rocket
+            .mount("/", rocket::routes![payment_required, price_probe])
+            .register("/", rocket::catchers![rejected_payment]))
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let path = req.uri().path().to_string();
+        let requirement = match self.pricing.requirement_for(&path) {
+            Some(requirement) => requirement,
+            None => return,
+        };
+        let config = match req.rocket().state::<X402Config>() {
+            Some(config) => config,
+            None => return,
+        };
+        req.local_cache(|| AdvertisedPrice(Some(config.advertised_price(requirement))));
+        req.local_cache(|| RouteRequirement(Some(requirement.clone())));
+
+        if req.method() == Method::Head {
+            req.set_uri(Origin::parse(PRICE_PATH).expect("valid price path"));
+            return;
+        }
+
+        let authorized = req
+            .headers()
+            .get_one(&config.authorization_header)
+            .is_some_and(|h| PaymentAuthorization::from_header_value(h).is_ok());
+        if authorized {
+            return;
+        }
+
+        let payment_request = create_payment_request(config, requirement, &path);
+        req.local_cache(|| PendingChallenge(Some(payment_request)));
+        req.set_method(Method::Get);
+        req.set_uri(Origin::parse(CHALLENGE_PATH).expect("valid challenge path"));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if let Some(price) = &req.local_cache(|| AdvertisedPrice(None)).0 {
+            res.set_raw_header(PRICE_HEADER, price.clone());
+        }
+    }
+}
+
+/// `X-Payment-Price` value recorded by [`X402Fairing`] for a paid path
+struct AdvertisedPrice(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r AdvertisedPrice {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let price = req.local_cache(|| AdvertisedPrice(None));
+        match price.0 {
+            Some(_) => Outcome::Success(price),
+            None => Outcome::Forward(Status::NotFound),
+        }
+    }
+}
+
+/// Fresh challenge for a payment [`PaymentGuard`](crate::PaymentGuard) rejected
+#[rocket::catch(402)]
+fn rejected_payment(req: &Request<'_>) -> Result<PaymentRequiredResponse, Custom<Json<Value>>> {
+    match req.local_cache(|| PendingChallenge(None)).0.clone() {
+        Some(payment_request) => Ok(PaymentRequiredResponse { payment_request }),
+        None => Err(Custom(
+            Status::PaymentRequired,
+            Json(serde_json::json!({ "error": "Payment required" })),
+        )),
+    }
+}
+
+#[rocket::head("/__x402/price")]
+fn price_probe(_price: &AdvertisedPrice) {}
+
+/// Payment request prepared for a rerouted or rejected request
+pub(crate) struct PendingChallenge(pub(crate) Option<PaymentRequest>);
+
+/// Requirement [`X402Fairing`] matched for the request's path
+pub(crate) struct RouteRequirement(pub(crate) Option<PaymentRequirement>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r PendingChallenge {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let pending = req.local_cache(|| PendingChallenge(None));
+        match pending.0 {
+            Some(_) => Outcome::Success(pending),
+            None => Outcome::Forward(Status::NotFound),
+        }
+    }
+}
+
+#[rocket::get("/__x402/payment-required")]
+fn payment_required(pending: &PendingChallenge) -> Option<PaymentRequiredResponse> {
+    pending
+        .0
+        .clone()
+        .map(|payment_request| PaymentRequiredResponse { payment_request })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PaymentRequirement;
+    use rocket::local::blocking::Client;
+
+    #[rocket::get("/health")]
+    fn health() -> &'static str {
+        "ok"
+    }
+
+    #[rocket::get("/unlisted")]
+    fn unlisted() -> &'static str {
+        "paid content"
+    }
+
+    fn test_client(pricing: PricingTable) -> Client {
+        let config = X402Config {
+            payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            auto_verify: false,
+            ..Default::default()
+        };
+        let rocket = rocket::build()
+            .manage(config)
+            .attach(X402Fairing::new(pricing))
+            .mount("/", rocket::routes![health, unlisted]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn test_deny_by_default_requires_payment_for_unlisted_route() {
+        let client = test_client(
+            PricingTable::new()
+                .free("/health")
+                .paid_by_default(PaymentRequirement::new("0.01")),
+        );
+
+        let response = client.get("/health").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get("/unlisted").dispatch();
+        assert_eq!(response.status(), Status::PaymentRequired);
+        let payment_request: PaymentRequest = response.into_json().unwrap();
+        assert_eq!(payment_request.max_amount_required, "0.01");
+        assert_eq!(payment_request.resource, "/unlisted");
+    }
+
+    #[rocket::get("/paid")]
+    fn paid(_payment: crate::PaymentGuard) -> &'static str {
+        "paid content"
+    }
+
+    #[rocket::get("/premium")]
+    fn premium(_payment: crate::PaymentGuard) -> &'static str {
+        "premium content"
+    }
+
+    #[test]
+    fn test_unpaid_request_gets_challenge_from_route_requirement() {
+        let rocket = rocket::build()
+            .manage(X402Config {
+                payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+                token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+                auto_verify: false,
+                ..Default::default()
+            })
+            .attach(X402Fairing::new(PricingTable::new().route(
+                "/premium",
+                PaymentRequirement::new("0.10").with_description("Premium content"),
+            )))
+            .mount("/", rocket::routes![premium]);
+        let client = Client::tracked(rocket).unwrap();
+
+        let response = client.get("/premium?page=2").dispatch();
+        assert_eq!(response.status(), Status::PaymentRequired);
+        let payment_request: PaymentRequest = response.into_json().unwrap();
+        assert_eq!(payment_request.max_amount_required, "0.10");
+        assert_eq!(payment_request.resource, "/premium");
+        assert_eq!(
+            payment_request.description.as_deref(),
+            Some("Premium content")
+        );
+        assert_eq!(
+            payment_request.payment_address,
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU"
+        );
+
+        // Clients that can't read a JSON body get the challenge in a header
+        let response = client
+            .get("/premium")
+            .header(rocket::http::Header::new("Accept", "text/plain"))
+            .dispatch();
+        assert_eq!(response.status(), Status::PaymentRequired);
+        let encoded = response
+            .headers()
+            .get_one(openlibx402_core::PAYMENT_REQUEST_HEADER)
+            .unwrap();
+        let payment_request = PaymentRequest::from_base64(encoded).unwrap();
+        assert_eq!(payment_request.resource, "/premium");
+    }
+
+    #[test]
+    fn test_guard_verifies_against_route_requirement() {
+        use openlibx402_core::{
+            rpc::mock::{MockRpcBackend, TransactionFixture},
+            SolanaPaymentProcessor,
+        };
+        use solana_sdk::{pubkey::Pubkey, signature::Signature};
+        use std::sync::Arc;
+
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (full, short) = (Signature::new_unique(), Signature::new_unique());
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(full).transfer(mint, recipient, 100_000))
+            .with_fixture(TransactionFixture::new(short).transfer(mint, recipient, 50_000));
+        let rocket = rocket::build()
+            .manage(X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            })
+            .manage(SolanaPaymentProcessor::with_backend(
+                Arc::new(backend),
+                None,
+            ))
+            .attach(X402Fairing::new(
+                PricingTable::new().route("/paid", PaymentRequirement::new("0.10")),
+            ))
+            .mount("/", rocket::routes![paid]);
+        let client = Client::tracked(rocket).unwrap();
+        let pay = |signature: &Signature, amount: &str| {
+            let header = PaymentAuthorization::new(
+                "pay_1".to_string(),
+                amount.to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            )
+            .to_header_value()
+            .unwrap();
+            client
+                .get("/paid")
+                .header(rocket::http::Header::new("X-Payment-Authorization", header))
+                .dispatch()
+        };
+
+        let response = pay(&full, "0.10");
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().unwrap(), "paid content");
+
+        // Claiming less than the route's price doesn't lower it, and the
+        // client gets a fresh challenge to retry with
+        for (signature, amount) in [(&short, "0.05"), (&Signature::new_unique(), "0.10")] {
+            let response = pay(signature, amount);
+            assert_eq!(response.status(), Status::PaymentRequired);
+            let payment_request: PaymentRequest = response.into_json().unwrap();
+            assert_eq!(payment_request.max_amount_required, "0.10");
+            assert_eq!(payment_request.resource, "/paid");
+        }
+    }
+
+    #[test]
+    fn test_guard_records_only_the_paying_signature() {
+        use openlibx402_core::{
+            rpc::mock::{MockRpcBackend, TransactionFixture},
+            MemoryStore, NonceStore, SolanaPaymentProcessor,
+        };
+        use solana_sdk::{pubkey::Pubkey, signature::Signature};
+        use std::sync::Arc;
+
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (own, other) = (Signature::new_unique(), Signature::new_unique());
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(own).transfer(mint, recipient, 100_000))
+            .with_fixture(TransactionFixture::new(other).transfer(mint, recipient, 100_000));
+        let nonces: Arc<dyn NonceStore> = Arc::new(MemoryStore::new());
+        let rocket = rocket::build()
+            .manage(X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            })
+            .manage(SolanaPaymentProcessor::with_backend(
+                Arc::new(backend),
+                None,
+            ))
+            .manage(nonces)
+            .attach(X402Fairing::new(
+                PricingTable::new().route("/paid", PaymentRequirement::new("0.10")),
+            ))
+            .mount("/", rocket::routes![paid]);
+        let client = Client::tracked(rocket).unwrap();
+        let pay = |payment_id: &str, signature: &Signature, extra: Vec<String>| {
+            let header = PaymentAuthorization::new(
+                payment_id.to_string(),
+                "0.10".to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            )
+            .with_signatures(extra)
+            .to_header_value()
+            .unwrap();
+            client
+                .get("/paid")
+                .header(rocket::http::Header::new("X-Payment-Authorization", header))
+                .dispatch()
+                .status()
+        };
+
+        // Listing someone else's transfer as an extra candidate doesn't spend it
+        assert_eq!(pay("pay_1", &own, vec![other.to_string()]), Status::Ok);
+        assert_eq!(pay("pay_2", &other, vec![]), Status::Ok);
+
+        assert_eq!(pay("pay_3", &other, vec![]), Status::Conflict);
+    }
+
+    #[test]
+    fn test_guard_records_metrics() {
+        use openlibx402_core::{
+            rpc::mock::{MockRpcBackend, TransactionFixture},
+            MemoryStore, MetricsRecorder, NonceStore, SolanaPaymentProcessor,
+        };
+        use solana_sdk::{pubkey::Pubkey, signature::Signature};
+        use std::{
+            sync::{Arc, Mutex},
+            time::Duration,
+        };
+
+        #[derive(Default)]
+        struct RecordingMetrics {
+            verifications: Mutex<usize>,
+            collected: Mutex<Vec<(String, u64)>>,
+            failures: Mutex<Vec<&'static str>>,
+        }
+
+        impl MetricsRecorder for RecordingMetrics {
+            fn record_verification(&self, _latency: Duration) {
+                *self.verifications.lock().unwrap() += 1;
+            }
+
+            fn record_payment_collected(&self, mint: &str, amount: u64) {
+                self.collected
+                    .lock()
+                    .unwrap()
+                    .push((mint.to_string(), amount));
+            }
+
+            fn record_failure(&self, _operation: &'static str, reason: &'static str) {
+                self.failures.lock().unwrap().push(reason);
+            }
+        }
+
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (full, short) = (Signature::new_unique(), Signature::new_unique());
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(full).transfer(mint, recipient, 100_000))
+            .with_fixture(TransactionFixture::new(short).transfer(mint, recipient, 50_000));
+        let metrics = Arc::new(RecordingMetrics::default());
+        let recorder: Arc<dyn MetricsRecorder> = metrics.clone();
+        let nonces: Arc<dyn NonceStore> = Arc::new(MemoryStore::new());
+        let rocket = rocket::build()
+            .manage(X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            })
+            .manage(
+                SolanaPaymentProcessor::with_backend(Arc::new(backend), None)
+                    .with_metrics(recorder.clone()),
+            )
+            .manage(recorder)
+            .manage(nonces)
+            .attach(X402Fairing::new(
+                PricingTable::new().route("/paid", PaymentRequirement::new("0.10")),
+            ))
+            .mount("/", rocket::routes![paid]);
+        let client = Client::tracked(rocket).unwrap();
+        let pay = |payment_id: &str, signature: &Signature, amount: &str| {
+            let header = PaymentAuthorization::new(
+                payment_id.to_string(),
+                amount.to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            )
+            .to_header_value()
+            .unwrap();
+            client
+                .get("/paid")
+                .header(rocket::http::Header::new("X-Payment-Authorization", header))
+                .dispatch()
+                .status()
+        };
+
+        assert_eq!(pay("pay_1", &full, "0.10"), Status::Ok);
+        assert_eq!(*metrics.verifications.lock().unwrap(), 1);
+        assert_eq!(
+            *metrics.collected.lock().unwrap(),
+            vec![(mint.to_string(), 100_000)]
+        );
+        assert!(metrics.failures.lock().unwrap().is_empty());
+
+        assert_eq!(pay("pay_2", &short, "0.05"), Status::PaymentRequired);
+        assert_eq!(pay("pay_1", &full, "0.10"), Status::Conflict);
+        // A new payment ID doesn't make the same transfer count twice
+        assert_eq!(pay("pay_3", &full, "0.10"), Status::Conflict);
+        // Replays still verify on-chain before their signature is refused
+        assert_eq!(*metrics.verifications.lock().unwrap(), 3);
+        assert_eq!(
+            *metrics.failures.lock().unwrap(),
+            vec![
+                "PAYMENT_VERIFICATION_FAILED",
+                "PAYMENT_REPLAYED",
+                "PAYMENT_REPLAYED"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_responses_advertise_price() {
+        let client =
+            test_client(PricingTable::new().route("/unlisted", PaymentRequirement::new("0.10")));
+
+        let response = client.get("/unlisted").dispatch();
+        assert_eq!(response.status(), Status::PaymentRequired);
+        assert_eq!(response.headers().get_one(PRICE_HEADER), Some("0.10 USDC"));
+
+        // Probing the priced route is free and still advertises its price
+        let response = client.head("/unlisted").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one(PRICE_HEADER), Some("0.10 USDC"));
+
+        let response = client.get("/health").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one(PRICE_HEADER), None);
+
+        // The internal route is not reachable directly
+        let response = client.head(PRICE_PATH).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_opt_in_leaves_unlisted_routes_free() {
+        let client =
+            test_client(PricingTable::new().route("/health", PaymentRequirement::new("0.10")));
+
+        let response = client.get("/unlisted").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get("/health").dispatch();
+        assert_eq!(response.status(), Status::PaymentRequired);
+
+        // The internal route is not reachable directly
+        let response = client.get(CHALLENGE_PATH).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
@@ -24,6 +24,7 @@
 //!         network: "solana-devnet".to_string(),
 //!         rpc_url: None,
 //!         auto_verify: true,
+//!         ..Default::default()
 //!     };
 //!
 //!     rocket::build()
@@ -36,15 +37,31 @@
 //! ```
 
 use chrono::{Duration, Utc};
-use openlibx402_core::{PaymentAuthorization, PaymentRequest, X402Error};
+use openlibx402_core::{
+    metrics, ChallengeFormat, MetricsRecorder, NonceStore, PassAuthorization, PassIssuer,
+    PaymentAuthorization, PaymentIdGenerator, PaymentProblem, PaymentRequest, RentExemptionReport,
+    Settlement, SettlementTracker, SolanaPaymentProcessor, UuidGenerator, VerificationOptions,
+    X402Error, X402Result, AUTHORIZATION_HEADER, DEFAULT_EXPIRES_IN, PASS_HEADER,
+    PAYMENT_REQUEST_HEADER,
+};
 use rocket::{
-    http::Status,
+    fairing::{self, Fairing, Info, Kind},
+    http::{ContentType, Status},
     request::{FromRequest, Outcome, Request},
     response::Responder,
     serde::json::Json,
+    Build, Rocket,
 };
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use std::sync::Arc;
+
+pub mod enforcement;
+pub mod pricing;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_support;
+
+pub use enforcement::X402Fairing;
+pub use pricing::PricingTable;
 
 /// Global X402 configuration
 #[derive(Debug, Clone)]
@@ -61,8 +78,159 @@ pub struct X402Config {
     /// Optional custom RPC URL
     pub rpc_url: Option<String>,
 
+    /// Commitment payments must reach to be accepted: `processed`,
+    /// `confirmed` or `finalized` (default: confirmed)
+    pub commitment: Option<String>,
+
     /// Whether to verify payments on-chain
     pub auto_verify: bool,
+
+    /// Accept payments up to this many basis points below the required amount (default: 0)
+    pub amount_tolerance_bps: u16,
+
+    /// Token symbol shown in the `X-Payment-Price` header (default: "USDC")
+    pub token_symbol: String,
+
+    /// Source of payment IDs for issued payment requests (default: UUIDv4)
+    pub payment_ids: Arc<dyn PaymentIdGenerator>,
+
+    /// Facilitator advertised to payers for settling payments (default: none)
+    pub facilitator_url: Option<String>,
+
+    /// Header payers send authorizations in (default: `X-Payment-Authorization`)
+    pub authorization_header: String,
+
+    /// Oldest authorization accepted, by its `timestamp` (default: the
+    /// requirement's `expires_in`)
+    pub max_authorization_age: Option<std::time::Duration>,
+}
+
+impl Default for X402Config {
+    fn default() -> Self {
+        Self {
+            payment_address: String::new(),
+            token_mint: String::new(),
+            network: "solana-devnet".to_string(),
+            rpc_url: None,
+            commitment: None,
+            auto_verify: true,
+            amount_tolerance_bps: 0,
+            token_symbol: "USDC".to_string(),
+            payment_ids: Arc::new(UuidGenerator),
+            facilitator_url: None,
+            authorization_header: AUTHORIZATION_HEADER.to_string(),
+            max_authorization_age: None,
+        }
+    }
+}
+
+impl X402Config {
+    /// Create a payment processor for the configured network, RPC URL and
+    /// commitment
+    ///
+    /// Fails with a configuration error if `commitment` isn't a known level.
+    pub fn payment_processor(&self) -> X402Result<SolanaPaymentProcessor> {
+        let rpc_url = self
+            .rpc_url
+            .as_deref()
+            .unwrap_or_else(|| SolanaPaymentProcessor::default_rpc_url(&self.network));
+        let commitment = self
+            .commitment
+            .as_deref()
+            .map(SolanaPaymentProcessor::parse_commitment)
+            .transpose()?;
+        Ok(SolanaPaymentProcessor::new(rpc_url, commitment))
+    }
+
+    /// Value of the `X-Payment-Price` header for `requirement`, e.g. `0.10 USDC`
+    pub fn advertised_price(&self, requirement: &PaymentRequirement) -> String {
+        format!("{} {}", requirement.amount, self.token_symbol)
+    }
+
+    /// Verification policy derived from this configuration
+    pub fn verification_options(&self) -> VerificationOptions {
+        VerificationOptions::default().with_amount_tolerance_bps(self.amount_tolerance_bps)
+    }
+
+    /// Oldest authorization accepted for `requirement`
+    ///
+    /// `max_authorization_age` if set, otherwise how long the requirement's
+    /// payment requests stay payable ([`DEFAULT_EXPIRES_IN`] without one).
+    pub fn authorization_age_limit(
+        &self,
+        requirement: Option<&PaymentRequirement>,
+    ) -> std::time::Duration {
+        self.max_authorization_age
+            .unwrap_or_else(|| requirement_ttl(requirement))
+    }
+
+    /// Check that the recipient wallet and its token account are rent-exempt
+    ///
+    /// With `strict` set a recipient that isn't rent-exempt is an error;
+    /// otherwise the problems are listed in the report's `warnings()`.
+    pub async fn check_rent_exemption(&self, strict: bool) -> X402Result<RentExemptionReport> {
+        self.payment_processor()?
+            .ensure_rent_exemption(&self.payment_address, &self.token_mint, strict)
+            .await
+    }
+}
+
+/// Fairing that checks the recipient is rent-exempt when Rocket ignites
+///
+/// Reads the managed `X402Config`. In strict mode a failed check aborts
+/// launch; otherwise problems are logged as warnings through Rocket's logger.
+#[derive(Debug, Clone, Default)]
+pub struct RentExemptionCheck {
+    pub strict: bool,
+}
+
+impl RentExemptionCheck {
+    /// Warn about recipients that aren't rent-exempt
+    pub fn warn() -> Self {
+        Self { strict: false }
+    }
+
+    /// Abort launch if the recipient isn't rent-exempt
+    pub fn strict() -> Self {
+        Self { strict: true }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RentExemptionCheck {
+    fn info(&self) -> Info {
+        Info {
+            name: "X402 Rent Exemption Check",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.state::<X402Config>() {
+            Some(config) => config.clone(),
+            None => {
+                rocket::warn!("X402Config is not managed; skipping rent exemption check");
+                return Ok(rocket);
+            }
+        };
+
+        match config.check_rent_exemption(self.strict).await {
+            Ok(report) => {
+                for warning in report.warnings() {
+                    rocket::warn!("{}", warning);
+                }
+                Ok(rocket)
+            }
+            Err(e) if self.strict => {
+                rocket::error!("rent exemption check failed: {}", e);
+                Err(rocket)
+            }
+            Err(e) => {
+                rocket::warn!("rent exemption check failed: {}", e);
+                Ok(rocket)
+            }
+        }
+    }
 }
 
 /// Payment requirement configuration for a specific endpoint
@@ -101,7 +269,52 @@ impl PaymentRequirement {
     }
 }
 
+/// How long payment requests for `requirement` stay payable
+fn requirement_ttl(requirement: Option<&PaymentRequirement>) -> std::time::Duration {
+    requirement.map_or(DEFAULT_EXPIRES_IN, |requirement| {
+        std::time::Duration::from_secs(requirement.expires_in.max(0) as u64)
+    })
+}
+
+/// How long used payments are remembered by default
+pub const DEFAULT_REPLAY_WINDOW: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
 /// Request guard that enforces payment requirements
+///
+/// When the managed `X402Config` has `auto_verify` set, the authorization must
+/// pay the configured recipient and token, and its transfer is verified
+/// on-chain through a managed `SolanaPaymentProcessor` (or one built from the
+/// config) before the route runs. The expected amount is the requirement
+/// [`X402Fairing`] matched for the path; without the fairing the guard
+/// answers 500 rather than trusting the authorization's own amount. A
+/// rejected payment is answered with 402 and a fresh payment request to
+/// retry with.
+///
+/// Authorizations older than the config's
+/// [`authorization_age_limit`](X402Config::authorization_age_limit) are
+/// refused the same way, before any verification, so a settled payment can't
+/// be presented again after its payment request has expired. Without a
+/// managed config the limit is the matched requirement's `expires_in`.
+///
+/// When Rocket manages an `Arc<dyn NonceStore>`, the signature of the
+/// transaction that paid (the authorization's `signature` without
+/// `auto_verify`) is recorded there for [`DEFAULT_REPLAY_WINDOW`] and a reused
+/// one is rejected with 409 Conflict, whatever `payment_id` it comes with.
+/// Manage a shared store such as `RedisStore` to reject replays across server
+/// instances:
+///
+/// ```rust,no_run
+/// use openlibx402_core::{MemoryStore, NonceStore};
+/// use std::sync::Arc;
+///
+/// let store: Arc<dyn NonceStore> = Arc::new(MemoryStore::new());
+/// let rocket = rocket::build().manage(store);
+/// ```
+///
+/// When Rocket manages an `Arc<dyn MetricsRecorder>`, verifications through
+/// a processor built from the config and refused replays are reported to it.
+/// A managed `SolanaPaymentProcessor` reports to the recorder installed with
+/// its own [`with_metrics`](SolanaPaymentProcessor::with_metrics).
 pub struct PaymentGuard {
     pub authorization: PaymentAuthorization,
 }
@@ -112,29 +325,180 @@ impl<'r> FromRequest<'r> for PaymentGuard {
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         // Get payment authorization header
-        let auth_header = match req.headers().get_one("X-Payment-Authorization") {
+        let header_name = req
+            .rocket()
+            .state::<X402Config>()
+            .map_or(AUTHORIZATION_HEADER, |config| {
+                config.authorization_header.as_str()
+            });
+        let auth_header = match req.headers().get_one(header_name) {
             Some(h) => h,
             None => {
                 // No payment provided, return 402 with payment request
-                return Outcome::Error((
-                    Status::PaymentRequired,
-                    X402Error::PaymentRequired("Payment authorization required".to_string()),
-                ));
+                return reject(X402Error::PaymentRequired(format!(
+                    "Payment authorization required in the {} header",
+                    header_name
+                )));
             }
         };
 
         // Parse authorization
-        match PaymentAuthorization::from_header_value(auth_header) {
-            Ok(auth) => {
-                // TODO: Verify payment if auto_verify is enabled
-                Outcome::Success(PaymentGuard { authorization: auth })
+        let authorization = match PaymentAuthorization::from_header_value(auth_header) {
+            Ok(auth) => auth,
+            Err(e) => return reject(e),
+        };
+
+        let requirement = req
+            .local_cache(|| enforcement::RouteRequirement(None))
+            .0
+            .clone();
+        let config = req.rocket().state::<X402Config>();
+        let max_age = match config {
+            Some(config) => config.authorization_age_limit(requirement.as_ref()),
+            None => requirement_ttl(requirement.as_ref()),
+        };
+        if let Err(e) = authorization.check_age(max_age) {
+            if let (Some(config), Some(requirement)) = (config, &requirement) {
+                let payment_request =
+                    create_payment_request(config, requirement, req.uri().path().as_str());
+                req.local_cache(|| enforcement::PendingChallenge(Some(payment_request)));
+            }
+            return reject(e);
+        }
+
+        let mut paid_by = authorization.signature.clone();
+        if let Some(config) = config.filter(|c| c.auto_verify) {
+            // The claimed amount is the client's word, so there is nothing to verify against
+            let Some(expected) = requirement.as_ref().map(|r| r.amount.as_str()) else {
+                return reject(X402Error::Configuration(
+                    "auto_verify needs X402Fairing to price the route".to_string(),
+                ));
+            };
+            let verified = match req.rocket().state::<SolanaPaymentProcessor>() {
+                Some(processor) => {
+                    verify_authorization(config, processor, &authorization, expected).await
+                }
+                None => {
+                    let mut processor = match config.payment_processor() {
+                        Ok(processor) => processor,
+                        Err(e) => return reject(e),
+                    };
+                    if let Some(metrics) = req.rocket().state::<Arc<dyn MetricsRecorder>>() {
+                        processor = processor.with_metrics(metrics.clone());
+                    }
+                    verify_authorization(config, &processor, &authorization, expected).await
+                }
+            };
+            match verified {
+                Ok(signature) => paid_by = signature,
+                Err(e) => {
+                    if let Some(requirement) = &requirement {
+                        let payment_request =
+                            create_payment_request(config, requirement, req.uri().path().as_str());
+                        req.local_cache(|| enforcement::PendingChallenge(Some(payment_request)));
+                    }
+                    return reject(e);
+                }
+            }
+        }
+
+        if let Some(nonces) = req.rocket().state::<Arc<dyn NonceStore>>() {
+            let retain_until =
+                Utc::now() + Duration::from_std(DEFAULT_REPLAY_WINDOW).unwrap_or(Duration::MAX);
+            match nonces.check_and_record(&paid_by, retain_until).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    if let Some(metrics) = req.rocket().state::<Arc<dyn MetricsRecorder>>() {
+                        metrics.record_failure(metrics::VERIFY_PAYMENT, "PAYMENT_REPLAYED");
+                    }
+                    return reject(X402Error::PaymentReplayed(format!(
+                        "Transaction {} was already used",
+                        paid_by
+                    )));
+                }
+                Err(e) => return reject(e),
+            }
+        }
+
+        Outcome::Success(PaymentGuard { authorization })
+    }
+}
+
+/// Fail a guard with `error`, answering its HTTP status
+fn reject<T>(error: X402Error) -> Outcome<T, X402Error> {
+    let status = Status::from_code(error.http_status()).unwrap_or(Status::InternalServerError);
+    Outcome::Error((status, error))
+}
+
+/// Verify `authorization` pays `config`'s recipient `expected_amount`
+///
+/// Returns the candidate signature that made the payment.
+async fn verify_authorization(
+    config: &X402Config,
+    processor: &SolanaPaymentProcessor,
+    authorization: &PaymentAuthorization,
+    expected_amount: &str,
+) -> X402Result<String> {
+    if authorization.payment_address != config.payment_address
+        || authorization.asset_address != config.token_mint
+    {
+        return Err(X402Error::PaymentVerification(
+            "Payment was not made to this server's address and token".to_string(),
+        ));
+    }
+
+    processor
+        .verify_payment_signature(
+            authorization,
+            expected_amount,
+            &config.verification_options(),
+        )
+        .await
+}
+
+/// Request guard that admits requests presenting a valid pass
+///
+/// Checks the `X-Payment-Pass` header against the managed `PassIssuer`: the
+/// signature, the expiry and that the pass covers the request path. No RPC
+/// call is made.
+pub struct PassGuard {
+    pub pass: PassAuthorization,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PassGuard {
+    type Error = X402Error;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let issuer = match req.rocket().state::<PassIssuer>() {
+            Some(issuer) => issuer,
+            None => {
+                return reject(X402Error::Configuration(
+                    "PassGuard requires a managed PassIssuer".to_string(),
+                ))
             }
-            Err(e) => Outcome::Error((Status::BadRequest, e)),
+        };
+        let header = match req.headers().get_one(PASS_HEADER) {
+            Some(h) => h,
+            None => return reject(X402Error::PaymentRequired("Pass required".to_string())),
+        };
+
+        let pass = match PassAuthorization::from_header_value(header) {
+            Ok(pass) => pass,
+            Err(e) => return reject(e),
+        };
+        match issuer.verify(&pass, req.uri().path().as_str()) {
+            Ok(()) => Outcome::Success(PassGuard { pass }),
+            Err(e) => reject(e),
         }
     }
 }
 
 /// Response type for 402 Payment Required
+///
+/// The challenge is negotiated from the request's `Accept` header: plain JSON
+/// (default), `application/problem+json`, or an empty body with the
+/// base64-encoded request in the `X-Payment-Request` header.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PaymentRequiredResponse {
     pub payment_request: PaymentRequest,
@@ -142,8 +506,62 @@ pub struct PaymentRequiredResponse {
 
 impl<'r, 'o: 'r> Responder<'r, 'o> for PaymentRequiredResponse {
     fn respond_to(self, req: &'r Request<'_>) -> rocket::response::Result<'o> {
-        rocket::response::Response::build_from(Json(self.payment_request).respond_to(req)?)
+        match ChallengeFormat::from_accept(req.headers().get_one("Accept")) {
+            ChallengeFormat::Json => {
+                rocket::response::Response::build_from(Json(self.payment_request).respond_to(req)?)
+                    .status(Status::PaymentRequired)
+                    .ok()
+            }
+            ChallengeFormat::ProblemJson => rocket::response::Response::build_from(
+                Json(PaymentProblem::new(self.payment_request)).respond_to(req)?,
+            )
+            .header(ContentType::new("application", "problem+json"))
             .status(Status::PaymentRequired)
+            .ok(),
+            ChallengeFormat::HeaderOnly => {
+                let encoded = self
+                    .payment_request
+                    .to_base64()
+                    .map_err(|_| Status::InternalServerError)?;
+                rocket::response::Response::build()
+                    .raw_header(PAYMENT_REQUEST_HEADER, encoded)
+                    .status(Status::PaymentRequired)
+                    .ok()
+            }
+        }
+    }
+}
+
+/// Response type for asynchronous settlements
+///
+/// Pending settlements respond with 202 Accepted and a `Location` header
+/// pointing at their `/settle/{id}` endpoint; complete or failed settlements
+/// respond with 200 OK. Return it from the paid route after issuing the
+/// settlement, and from the `/settle/<id>` route via
+/// [`SettlementResponse::lookup`], which yields 404 for unknown IDs.
+#[derive(Debug)]
+pub struct SettlementResponse(pub Settlement);
+
+impl SettlementResponse {
+    /// Look up a settlement for a `/settle/<id>` poll
+    pub fn lookup(tracker: &SettlementTracker, settlement_id: &str) -> Option<Self> {
+        tracker.get(settlement_id).map(Self)
+    }
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for SettlementResponse {
+    fn respond_to(self, req: &'r Request<'_>) -> rocket::response::Result<'o> {
+        let settlement = self.0;
+        let status = if settlement.is_pending() {
+            Status::Accepted
+        } else {
+            Status::Ok
+        };
+        let location = settlement.poll_path();
+
+        rocket::response::Response::build_from(Json(settlement).respond_to(req)?)
+            .raw_header("Location", location)
+            .status(status)
             .ok()
     }
 }
@@ -154,24 +572,23 @@ pub fn create_payment_request(
     requirement: &PaymentRequirement,
     resource: &str,
 ) -> PaymentRequest {
-    let expires_at = Utc::now() + Duration::seconds(requirement.expires_in);
-    let payment_id = Uuid::new_v4().to_string();
-    let nonce = Uuid::new_v4().to_string();
-
-    let mut request = PaymentRequest::new(
-        requirement.amount.clone(),
-        config.token_mint.clone(),
-        config.payment_address.clone(),
-        config.network.clone(),
-        expires_at,
-        nonce,
-        payment_id,
-        resource.to_string(),
-    );
+    let mut request = PaymentRequest::builder()
+        .amount(requirement.amount.as_str())
+        .asset_address(config.token_mint.as_str())
+        .payment_address(config.payment_address.as_str())
+        .network(config.network.as_str())
+        .expires_at(Utc::now() + Duration::seconds(requirement.expires_in))
+        .payment_id(config.payment_ids.generate())
+        .resource(resource)
+        .build()
+        .expect("request with all required fields");
 
     if let Some(desc) = &requirement.description {
         request = request.with_description(desc.clone());
     }
+    if let Some(url) = &config.facilitator_url {
+        request = request.with_facilitator_url(url.clone());
+    }
 
     request
 }
@@ -210,6 +627,7 @@ mod tests {
             network: "solana-devnet".to_string(),
             rpc_url: None,
             auto_verify: false,
+            ..Default::default()
         };
 
         let requirement = PaymentRequirement::new("0.10");
@@ -219,4 +637,368 @@ mod tests {
         assert_eq!(request.payment_address, config.payment_address);
         assert_eq!(request.asset_address, config.token_mint);
     }
+
+    #[rocket::get("/premium")]
+    fn premium() -> PaymentRequiredResponse {
+        let config = X402Config {
+            payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            network: "solana-devnet".to_string(),
+            rpc_url: None,
+            auto_verify: false,
+            ..Default::default()
+        };
+        PaymentRequiredResponse {
+            payment_request: create_payment_request(
+                &config,
+                &PaymentRequirement::new("0.10"),
+                "/premium",
+            ),
+        }
+    }
+
+    #[rocket::get("/reports/<_name>")]
+    fn report(_name: &str, guard: PassGuard) -> String {
+        guard.pass.pass_id
+    }
+
+    #[rocket::get("/paid")]
+    fn paid(_payment: PaymentGuard) -> &'static str {
+        "paid content"
+    }
+
+    #[rocket::get("/settle/<id>")]
+    fn settle(id: &str, tracker: &rocket::State<SettlementTracker>) -> Option<SettlementResponse> {
+        SettlementResponse::lookup(tracker, id)
+    }
+
+    fn test_client() -> rocket::local::blocking::Client {
+        let rocket = rocket::build().mount("/", rocket::routes![premium]);
+        rocket::local::blocking::Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn test_negotiate_json() {
+        let client = test_client();
+        for accept in [None, Some("application/json"), Some("*/*")] {
+            let mut req = client.get("/premium");
+            if let Some(accept) = accept {
+                req = req.header(rocket::http::Header::new("Accept", accept));
+            }
+            let response = req.dispatch();
+            assert_eq!(response.status(), Status::PaymentRequired);
+            assert_eq!(response.content_type(), Some(ContentType::JSON));
+            let request: PaymentRequest = response.into_json().unwrap();
+            assert_eq!(request.max_amount_required, "0.10");
+        }
+    }
+
+    #[test]
+    fn test_negotiate_problem_json() {
+        let client = test_client();
+        let response = client
+            .get("/premium")
+            .header(rocket::http::Header::new(
+                "Accept",
+                "application/problem+json",
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::PaymentRequired);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "problem+json"))
+        );
+        let problem: PaymentProblem = response.into_json().unwrap();
+        assert_eq!(problem.status, 402);
+        assert_eq!(problem.payment_request.resource, "/premium");
+    }
+
+    #[test]
+    fn test_negotiate_header_only() {
+        let client = test_client();
+        let response = client
+            .get("/premium")
+            .header(rocket::http::Header::new("Accept", "text/plain"))
+            .dispatch();
+        assert_eq!(response.status(), Status::PaymentRequired);
+        let encoded = response
+            .headers()
+            .get_one(PAYMENT_REQUEST_HEADER)
+            .unwrap()
+            .to_string();
+        let request = PaymentRequest::from_base64(&encoded).unwrap();
+        assert_eq!(request.resource, "/premium");
+        assert!(response.into_string().unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn test_settlement_poll() {
+        let tracker = SettlementTracker::new();
+        let issued = tracker.issue(&PaymentAuthorization::new(
+            "payment123".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "signature".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        ));
+        let rocket = rocket::build()
+            .manage(tracker.clone())
+            .mount("/", rocket::routes![settle]);
+        let client = rocket::local::blocking::Client::tracked(rocket).unwrap();
+
+        let response = client.get(issued.poll_path()).dispatch();
+        assert_eq!(response.status(), Status::Accepted);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some(issued.poll_path().as_str())
+        );
+
+        tracker.confirm_payment(&issued.settlement_id).unwrap();
+        tracker
+            .complete(&issued.settlement_id, serde_json::json!("done"))
+            .unwrap();
+        let response = client.get(issued.poll_path()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let settlement: Settlement = response.into_json().unwrap();
+        assert_eq!(settlement.result, Some(serde_json::json!("done")));
+
+        let response = client.get("/settle/unknown").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_payment_guard_rejects_replay() {
+        let store: Arc<dyn NonceStore> = Arc::new(openlibx402_core::MemoryStore::new());
+        let rocket = rocket::build()
+            .manage(store)
+            .mount("/", rocket::routes![paid]);
+        let client = rocket::local::blocking::Client::tracked(rocket).unwrap();
+        let pay = |payment_id: &str, signature: &str| {
+            let header = PaymentAuthorization::new(
+                payment_id.to_string(),
+                "0.10".to_string(),
+                "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            )
+            .to_header_value()
+            .unwrap();
+            client
+                .get("/paid")
+                .header(rocket::http::Header::new("X-Payment-Authorization", header))
+                .dispatch()
+                .status()
+        };
+
+        assert_eq!(pay("pay_1", "signature_1"), Status::Ok);
+        assert_eq!(pay("pay_1", "signature_1"), Status::Conflict);
+        // The same transfer presented under a fresh payment ID is still a replay
+        assert_eq!(pay("pay_2", "signature_1"), Status::Conflict);
+        assert_eq!(pay("pay_2", "signature_2"), Status::Ok);
+    }
+
+    #[test]
+    fn test_payment_guard_reads_configured_header() {
+        let rocket = rocket::build()
+            .manage(X402Config {
+                auto_verify: false,
+                authorization_header: "X-PAYMENT".to_string(),
+                ..Default::default()
+            })
+            .mount("/", rocket::routes![paid]);
+        let client = rocket::local::blocking::Client::tracked(rocket).unwrap();
+        let header = PaymentAuthorization::new(
+            "pay_1".to_string(),
+            "0.10".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            "solana-devnet".to_string(),
+            "signature".to_string(),
+            "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+        )
+        .to_header_value()
+        .unwrap();
+        let pay = |name: &'static str| {
+            client
+                .get("/paid")
+                .header(rocket::http::Header::new(name, header.clone()))
+                .dispatch()
+                .status()
+        };
+
+        assert_eq!(pay("X-PAYMENT"), Status::Ok);
+        // A payment in the default header is missing, not malformed
+        assert_eq!(pay(AUTHORIZATION_HEADER), Status::PaymentRequired);
+    }
+
+    #[test]
+    fn test_payment_guard_rejects_stale_authorization() {
+        let client = |config: X402Config| {
+            let rocket = rocket::build()
+                .manage(config)
+                .mount("/", rocket::routes![paid]);
+            rocket::local::blocking::Client::tracked(rocket).unwrap()
+        };
+        let pay = |client: &rocket::local::blocking::Client, age: i64| {
+            let mut authorization = PaymentAuthorization::new(
+                "pay_1".to_string(),
+                "0.10".to_string(),
+                "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+                "solana-devnet".to_string(),
+                "signature".to_string(),
+                "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            );
+            authorization.timestamp = Utc::now() - Duration::seconds(age);
+            client
+                .get("/paid")
+                .header(rocket::http::Header::new(
+                    "X-Payment-Authorization",
+                    authorization.to_header_value().unwrap(),
+                ))
+                .dispatch()
+                .status()
+        };
+        let config = X402Config {
+            auto_verify: false,
+            ..Default::default()
+        };
+
+        // By default authorizations are accepted for as long as requests stay payable
+        let default = client(config.clone());
+        assert_eq!(pay(&default, 10), Status::Ok);
+        assert_eq!(pay(&default, 600), Status::PaymentRequired);
+
+        let strict = client(X402Config {
+            max_authorization_age: Some(std::time::Duration::from_secs(60)),
+            ..config
+        });
+        assert_eq!(pay(&strict, 10), Status::Ok);
+        assert_eq!(pay(&strict, 120), Status::PaymentRequired);
+    }
+
+    #[test]
+    fn test_payment_guard_rejects_unknown_commitment() {
+        let config = X402Config {
+            payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            commitment: Some("final".to_string()),
+            ..Default::default()
+        };
+        let Err(error) = config.payment_processor() else {
+            panic!("unknown commitment accepted");
+        };
+        assert!(matches!(error, X402Error::Configuration(_)));
+        assert!(error.to_string().contains("'final'"));
+
+        // Verifying with the config's processor fails as a server error
+        let rocket = rocket::build()
+            .manage(config.clone())
+            .attach(X402Fairing::new(
+                PricingTable::new().route("/paid", PaymentRequirement::new("0.10")),
+            ))
+            .mount("/", rocket::routes![paid]);
+        let client = rocket::local::blocking::Client::tracked(rocket).unwrap();
+        let header = PaymentAuthorization::new(
+            "pay_1".to_string(),
+            "0.10".to_string(),
+            config.payment_address.clone(),
+            config.token_mint.clone(),
+            "solana-devnet".to_string(),
+            "signature".to_string(),
+            config.payment_address.clone(),
+        )
+        .to_header_value()
+        .unwrap();
+        let response = client
+            .get("/paid")
+            .header(rocket::http::Header::new(AUTHORIZATION_HEADER, header))
+            .dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+    }
+
+    #[test]
+    fn test_payment_guard_requires_route_requirement() {
+        let config = X402Config {
+            payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            ..Default::default()
+        };
+        let rocket = rocket::build()
+            .manage(config.clone())
+            .mount("/", rocket::routes![paid]);
+        let client = rocket::local::blocking::Client::tracked(rocket).unwrap();
+        let header = PaymentAuthorization::new(
+            "pay_1".to_string(),
+            "0.10".to_string(),
+            config.payment_address.clone(),
+            config.token_mint.clone(),
+            "solana-devnet".to_string(),
+            "signature".to_string(),
+            config.payment_address.clone(),
+        )
+        .to_header_value()
+        .unwrap();
+
+        // Without the fairing there is no price to verify the claimed amount against
+        let response = client
+            .get("/paid")
+            .header(rocket::http::Header::new(AUTHORIZATION_HEADER, header))
+            .dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+    }
+
+    #[test]
+    fn test_pass_guard() {
+        let issuer = PassIssuer::new(solana_sdk::signature::Keypair::new());
+        let config = X402Config {
+            payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+            ..Default::default()
+        };
+        let offer = openlibx402_core::PassRequest::new(
+            create_payment_request(&config, &PaymentRequirement::new("5.00"), "/passes"),
+            "/reports/*",
+            Duration::hours(1),
+        );
+        let pass = issuer
+            .issue(
+                &offer,
+                &PaymentAuthorization::new(
+                    offer.payment_request.payment_id.clone(),
+                    "5.00".to_string(),
+                    config.payment_address.clone(),
+                    config.token_mint.clone(),
+                    "solana-devnet".to_string(),
+                    "signature".to_string(),
+                    "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+                ),
+            )
+            .unwrap();
+        let rocket = rocket::build()
+            .manage(issuer)
+            .mount("/", rocket::routes![report]);
+        let client = rocket::local::blocking::Client::tracked(rocket).unwrap();
+        let with_pass = |path: &'static str| {
+            client
+                .get(path)
+                .header(rocket::http::Header::new(
+                    PASS_HEADER,
+                    pass.to_header_value().unwrap(),
+                ))
+                .dispatch()
+        };
+
+        let response = with_pass("/reports/q1");
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().unwrap(), pass.pass_id);
+        assert_eq!(with_pass("/reports/q2").status(), Status::Ok);
+
+        let response = client.get("/reports/q1").dispatch();
+        assert_eq!(response.status(), Status::PaymentRequired);
+    }
 }
@@ -0,0 +1,162 @@
+//! In-process payment-protected Rocket instances for downstream tests
+//!
+//! Enabled by the `test-util` feature. [`MockPayments`] stands in for the
+//! chain: it pays challenges from a funded wallet on a
+//! [`MockRpcBackend`] and verifies them against the same backend, so tests
+//! exercise the full 402 → pay → retry flow without a cluster.
+//!
+//! ```rust,no_run
+//! use openlibx402_rocket::{
+//!     test_support::{payment_rocket, MockPayments},
+//!     PaymentGuard, PaymentRequirement, PricingTable,
+//! };
+//! use rocket::local::asynchronous::Client;
+//!
+//! #[rocket::get("/premium")]
+//! fn premium(_payment: PaymentGuard) -> &'static str {
+//!     "Premium content!"
+//! }
+//!
+//! # async fn example() {
+//! let payments = MockPayments::new();
+//! let pricing = PricingTable::new().route("/premium", PaymentRequirement::new("0.10"));
+//! let rocket = payment_rocket(&payments, pricing).mount("/", rocket::routes![premium]);
+//! let client = Client::tracked(rocket).await.unwrap();
+//! # }
+//! ```
+
+use openlibx402_core::{
+    rpc::mock::MockRpcBackend, MemoryStore, NonceStore, PaymentAuthorization, PaymentRequest,
+    SolanaPaymentProcessor, X402Result,
+};
+use rocket::{Build, Rocket};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use std::sync::Arc;
+
+use crate::{pricing::PricingTable, X402Config, X402Fairing};
+
+/// Decimals of the mock token mint
+const MOCK_TOKEN_DECIMALS: u8 = 6;
+
+/// Mock chain that pays and verifies challenges in memory
+///
+/// The recipient and token mint are fresh addresses, and the payer wallet
+/// holds enough of the token for any test.
+pub struct MockPayments {
+    backend: Arc<MockRpcBackend>,
+    payer: Keypair,
+    config: X402Config,
+}
+
+impl MockPayments {
+    /// Create a mock chain with a funded payer
+    pub fn new() -> Self {
+        let (recipient, mint, payer) = (Pubkey::new_unique(), Pubkey::new_unique(), Keypair::new());
+        let backend = MockRpcBackend::new()
+            .with_mint(mint, MOCK_TOKEN_DECIMALS)
+            .with_balance(payer.pubkey(), 1_000_000_000)
+            .with_associated_token_account(payer.pubkey(), mint, u64::MAX / 2)
+            .with_associated_token_account(recipient, mint, 0)
+            .with_confirmed_sends();
+
+        Self {
+            backend: Arc::new(backend),
+            payer,
+            config: X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Configuration managed by [`payment_rocket`]
+    pub fn config(&self) -> &X402Config {
+        &self.config
+    }
+
+    /// The mock RPC backend, for scripting failures or inspecting calls
+    pub fn backend(&self) -> &MockRpcBackend {
+        &self.backend
+    }
+
+    /// Payment processor reading the mock chain
+    pub fn processor(&self) -> SolanaPaymentProcessor {
+        SolanaPaymentProcessor::with_backend(self.backend.clone(), None)
+    }
+
+    /// Pay `request` from the funded wallet
+    ///
+    /// The transfer lands on the mock chain immediately, so the returned
+    /// authorization passes verification.
+    pub async fn pay(&self, request: &PaymentRequest) -> X402Result<PaymentAuthorization> {
+        self.processor().create_payment(request, &self.payer).await
+    }
+}
+
+impl Default for MockPayments {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build a Rocket instance charging according to `pricing`, verified against `payments`
+///
+/// The instance attaches an [`X402Fairing`] and manages the [`X402Config`],
+/// payment processor and an in-memory nonce store that
+/// [`PaymentGuard`](crate::PaymentGuard) needs. Mount routes for the paths
+/// under test.
+pub fn payment_rocket(payments: &MockPayments, pricing: PricingTable) -> Rocket<Build> {
+    let nonces: Arc<dyn NonceStore> = Arc::new(MemoryStore::new());
+    rocket::build()
+        .manage(payments.config.clone())
+        .manage(payments.processor())
+        .manage(nonces)
+        .attach(X402Fairing::new(pricing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PaymentGuard, PaymentRequirement};
+    use rocket::{
+        http::{Header, Status},
+        local::asynchronous::Client,
+    };
+
+    #[rocket::get("/premium")]
+    fn premium(payment: PaymentGuard) -> String {
+        payment.authorization.payment_id
+    }
+
+    #[rocket::async_test]
+    async fn test_payment_rocket_fixture() {
+        let payments = MockPayments::new();
+        let pricing = PricingTable::new().route("/premium", PaymentRequirement::new("0.10"));
+        let rocket = payment_rocket(&payments, pricing).mount("/", rocket::routes![premium]);
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let response = client.get("/premium").dispatch().await;
+        assert_eq!(response.status(), Status::PaymentRequired);
+        let request: PaymentRequest = response.into_json().await.unwrap();
+
+        let authorization = payments.pay(&request).await.unwrap();
+        let header = authorization.to_header_value().unwrap();
+        let paid = || {
+            client
+                .get("/premium")
+                .header(Header::new("X-Payment-Authorization", header.clone()))
+                .dispatch()
+        };
+        let response = paid().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().await.unwrap(), request.payment_id);
+
+        // The same payment is not accepted twice
+        let response = paid().await;
+        assert_eq!(response.status(), Status::Conflict);
+    }
+}
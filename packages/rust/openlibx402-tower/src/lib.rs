@@ -0,0 +1,887 @@
+//! # OpenLibx402 Tower
+//!
+//! Tower middleware for the X402 payment protocol.
+//!
+//! [`X402Layer`] protects any `tower::Service` taking `http::Request`s, so
+//! it works with Axum, Tonic, Hyper and other tower-based stacks. Priced
+//! requests without a valid payment are answered with a 402 carrying a
+//! `PaymentRequest`; paid ones reach the inner service with their
+//! [`PaymentAuthorization`] in the request extensions.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use openlibx402_tower::{PaymentRequirement, PricingTable, X402Config, X402Layer};
+//!
+//! let config = X402Config {
+//!     payment_address: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+//!     token_mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+//!     network: "solana-devnet".to_string(),
+//!     rpc_url: None,
+//!     auto_verify: true,
+//!     ..Default::default()
+//! };
+//!
+//! let pricing = PricingTable::new()
+//!     .route("/premium", PaymentRequirement::new("0.10"))
+//!     .route("/api/*", PaymentRequirement::new("0.05"));
+//!
+//! // Wrap a service with `layer.layer(service)` or `ServiceBuilder::layer`
+//! let layer = X402Layer::new(config, pricing);
+//! ```
+
+mod pricing;
+
+pub use pricing::{Pricing, PricingTable};
+
+use bytes::Bytes;
+use chrono::{Duration, Utc};
+use http::{header, request::Parts, HeaderMap, HeaderValue, Request, Response, StatusCode};
+use http_body_util::{Either, Full};
+use openlibx402_core::{
+    negotiation::PROBLEM_JSON_CONTENT_TYPE, ChallengeFormat, NonceStore, PaymentAuthorization,
+    PaymentIdGenerator, PaymentProblem, PaymentRequest, RentExemptionReport,
+    SolanaPaymentProcessor, UuidGenerator, VerificationOptions, X402Error, X402Result,
+    AUTHORIZATION_HEADER, DEFAULT_EXPIRES_IN, PAYMENT_REQUEST_HEADER,
+};
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Global X402 configuration
+#[derive(Debug, Clone)]
+pub struct X402Config {
+    /// Wallet address to receive payments
+    pub payment_address: String,
+
+    /// SPL token mint address (USDC)
+    pub token_mint: String,
+
+    /// Solana network (e.g., "solana-devnet", "solana-mainnet")
+    pub network: String,
+
+    /// Optional custom RPC URL
+    pub rpc_url: Option<String>,
+
+    /// Commitment payments must reach to be accepted: `processed`,
+    /// `confirmed` or `finalized` (default: confirmed)
+    pub commitment: Option<String>,
+
+    /// Whether to verify payments on-chain
+    pub auto_verify: bool,
+
+    /// Accept payments up to this many basis points below the required amount (default: 0)
+    pub amount_tolerance_bps: u16,
+
+    /// Token symbol shown in the `X-Payment-Price` header (default: "USDC")
+    pub token_symbol: String,
+
+    /// Source of payment IDs for issued payment requests (default: UUIDv4)
+    pub payment_ids: Arc<dyn PaymentIdGenerator>,
+
+    /// Facilitator advertised to payers for settling payments (default: none)
+    pub facilitator_url: Option<String>,
+
+    /// Header payers send authorizations in (default: `X-Payment-Authorization`)
+    pub authorization_header: String,
+
+    /// Oldest authorization accepted, by its `timestamp` (default: the
+    /// requirement's `expires_in`)
+    pub max_authorization_age: Option<std::time::Duration>,
+}
+
+impl Default for X402Config {
+    fn default() -> Self {
+        Self {
+            payment_address: String::new(),
+            token_mint: String::new(),
+            network: "solana-devnet".to_string(),
+            rpc_url: None,
+            commitment: None,
+            auto_verify: true,
+            amount_tolerance_bps: 0,
+            token_symbol: "USDC".to_string(),
+            payment_ids: Arc::new(UuidGenerator),
+            facilitator_url: None,
+            authorization_header: AUTHORIZATION_HEADER.to_string(),
+            max_authorization_age: None,
+        }
+    }
+}
+
+impl X402Config {
+    /// Create a payment processor for the configured network, RPC URL and
+    /// commitment
+    ///
+    /// Fails with a configuration error if `commitment` isn't a known level.
+    pub fn payment_processor(&self) -> X402Result<SolanaPaymentProcessor> {
+        let rpc_url = self
+            .rpc_url
+            .as_deref()
+            .unwrap_or_else(|| SolanaPaymentProcessor::default_rpc_url(&self.network));
+        let commitment = self
+            .commitment
+            .as_deref()
+            .map(SolanaPaymentProcessor::parse_commitment)
+            .transpose()?;
+        Ok(SolanaPaymentProcessor::new(rpc_url, commitment))
+    }
+
+    /// Value of the `X-Payment-Price` header for `requirement`, e.g. `0.10 USDC`
+    pub fn advertised_price(&self, requirement: &PaymentRequirement) -> String {
+        format!("{} {}", requirement.amount, self.token_symbol)
+    }
+
+    /// Verification policy derived from this configuration
+    pub fn verification_options(&self) -> VerificationOptions {
+        VerificationOptions::default().with_amount_tolerance_bps(self.amount_tolerance_bps)
+    }
+
+    /// Oldest authorization accepted for `requirement`
+    ///
+    /// `max_authorization_age` if set, otherwise how long the requirement's
+    /// payment requests stay payable ([`DEFAULT_EXPIRES_IN`] without one).
+    pub fn authorization_age_limit(
+        &self,
+        requirement: Option<&PaymentRequirement>,
+    ) -> std::time::Duration {
+        self.max_authorization_age.unwrap_or_else(|| {
+            requirement.map_or(DEFAULT_EXPIRES_IN, |requirement| {
+                std::time::Duration::from_secs(requirement.expires_in.max(0) as u64)
+            })
+        })
+    }
+
+    /// Check that the recipient wallet and its token account are rent-exempt
+    ///
+    /// Run this at startup, before binding the server, to catch recipients that
+    /// would make payments fail in production. With `strict` set a recipient
+    /// that isn't rent-exempt is an error; otherwise the problems are listed in
+    /// the report's `warnings()`.
+    pub async fn check_rent_exemption(&self, strict: bool) -> X402Result<RentExemptionReport> {
+        self.payment_processor()?
+            .ensure_rent_exemption(&self.payment_address, &self.token_mint, strict)
+            .await
+    }
+}
+
+/// Payment requirement configuration for a specific endpoint
+#[derive(Debug, Clone)]
+pub struct PaymentRequirement {
+    /// Amount required in USDC
+    pub amount: String,
+
+    /// Optional description
+    pub description: Option<String>,
+
+    /// Expiration time in seconds (default: 300)
+    pub expires_in: i64,
+}
+
+impl PaymentRequirement {
+    /// Create a new payment requirement
+    pub fn new(amount: &str) -> Self {
+        Self {
+            amount: amount.to_string(),
+            description: None,
+            expires_in: 300,
+        }
+    }
+
+    /// Set the description
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Set the expiration time
+    pub fn with_expires_in(mut self, seconds: i64) -> Self {
+        self.expires_in = seconds;
+        self
+    }
+}
+
+/// How long used payments are remembered by default
+pub const DEFAULT_REPLAY_WINDOW: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// Layer charging for requests priced by `P`
+///
+/// Requests `P` prices as free pass straight through. Priced requests without
+/// an `X-Payment-Authorization` header are answered with a 402 carrying a
+/// `PaymentRequest` for the request path, negotiated like
+/// [`payment_required_response_for`].
+///
+/// Authorizations older than the config's
+/// [`authorization_age_limit`](X402Config::authorization_age_limit) are
+/// refused with a 402, before any verification. With `auto_verify` set, the
+/// authorization must pay the configured recipient and token, and its
+/// transfer must cover the request's price on-chain; failed verification is a
+/// 400. With a nonce store (see [`X402Layer::with_nonce_store`]), a reused
+/// transaction signature is rejected with 409 Conflict, whatever `payment_id`
+/// it comes with.
+///
+/// Accepted requests reach the inner service with their
+/// [`PaymentAuthorization`] in the request extensions.
+pub struct X402Layer<P> {
+    config: Arc<X402Config>,
+    pricing: Arc<P>,
+    processor: Option<Arc<SolanaPaymentProcessor>>,
+    nonces: Option<Arc<dyn NonceStore>>,
+}
+
+impl<P: Pricing> X402Layer<P> {
+    /// Layer charging the prices `pricing` gives, verifying through a
+    /// processor built from `config`
+    pub fn new(config: X402Config, pricing: P) -> Self {
+        Self {
+            config: Arc::new(config),
+            pricing: Arc::new(pricing),
+            processor: None,
+            nonces: None,
+        }
+    }
+
+    /// Verify payments through `processor` instead of the config's RPC endpoint
+    pub fn with_payment_processor(mut self, processor: SolanaPaymentProcessor) -> Self {
+        self.processor = Some(Arc::new(processor));
+        self
+    }
+
+    /// Record the signature of each transaction that paid in `store` and
+    /// reject reuse
+    ///
+    /// Use a shared store such as `RedisStore` to reject replays across
+    /// server instances.
+    pub fn with_nonce_store(mut self, store: Arc<dyn NonceStore>) -> Self {
+        self.nonces = Some(store);
+        self
+    }
+
+    /// Configuration payments are checked against
+    pub fn config(&self) -> &X402Config {
+        &self.config
+    }
+
+    /// Check the payment on `parts` against `requirement`
+    async fn check_payment(
+        &self,
+        parts: &Parts,
+        requirement: &PaymentRequirement,
+    ) -> Result<PaymentAuthorization, PaymentError> {
+        let config = &*self.config;
+
+        // Get payment authorization header
+        let auth_header = match parts.headers.get(config.authorization_header.as_str()) {
+            Some(h) => h.to_str().map_err(|_| PaymentError::InvalidHeader)?,
+            None => {
+                let payment_request = create_payment_request(config, requirement, parts.uri.path());
+                let accept = parts
+                    .headers
+                    .get(header::ACCEPT)
+                    .and_then(|h| h.to_str().ok());
+                return Err(PaymentError::Challenge(
+                    Box::new(payment_request),
+                    ChallengeFormat::from_accept(accept),
+                ));
+            }
+        };
+
+        // Parse authorization
+        let authorization = PaymentAuthorization::from_header_value(auth_header)
+            .map_err(|e| PaymentError::InvalidAuthorization(e.to_string()))?;
+        authorization
+            .check_age(config.authorization_age_limit(Some(requirement)))
+            .map_err(|e| PaymentError::Expired(e.to_string()))?;
+
+        let mut paid_by = authorization.signature.clone();
+        if config.auto_verify {
+            let verified = match &self.processor {
+                Some(processor) => {
+                    verify_authorization(config, processor, &authorization, &requirement.amount)
+                        .await
+                }
+                None => {
+                    let processor = config
+                        .payment_processor()
+                        .map_err(PaymentError::Configuration)?;
+                    verify_authorization(config, &processor, &authorization, &requirement.amount)
+                        .await
+                }
+            };
+            paid_by = verified.map_err(|e| PaymentError::InvalidAuthorization(e.to_string()))?;
+        }
+
+        if let Some(nonces) = &self.nonces {
+            let retain_until =
+                Utc::now() + Duration::from_std(DEFAULT_REPLAY_WINDOW).unwrap_or(Duration::MAX);
+            let fresh = nonces
+                .check_and_record(&paid_by, retain_until)
+                .await
+                .map_err(PaymentError::Store)?;
+            if !fresh {
+                return Err(PaymentError::Replayed(paid_by));
+            }
+        }
+
+        Ok(authorization)
+    }
+}
+
+impl<P> Clone for X402Layer<P> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            pricing: self.pricing.clone(),
+            processor: self.processor.clone(),
+            nonces: self.nonces.clone(),
+        }
+    }
+}
+
+impl<P> fmt::Debug for X402Layer<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X402Layer")
+            .field("config", &self.config)
+            .field("nonces", &self.nonces.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, P: Pricing> Layer<S> for X402Layer<P> {
+    type Service = X402Service<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        X402Service {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service produced by [`X402Layer`]
+pub struct X402Service<S, P> {
+    inner: S,
+    layer: X402Layer<P>,
+}
+
+impl<S: Clone, P> Clone for X402Service<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug, P> fmt::Debug for X402Service<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X402Service")
+            .field("inner", &self.inner)
+            .field("layer", &self.layer)
+            .finish()
+    }
+}
+
+impl<S, P, ReqBody, ResBody> Service<Request<ReqBody>> for X402Service<S, P>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    P: Pricing,
+    ReqBody: Send + 'static,
+{
+    type Response = Response<Either<ResBody, Full<Bytes>>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        // Call the instance that was polled ready, leaving a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            if let Some(requirement) = layer.pricing.price(&parts) {
+                match layer.check_payment(&parts, &requirement).await {
+                    Ok(authorization) => {
+                        parts.extensions.insert(authorization);
+                    }
+                    Err(rejection) => return Ok(rejection.into_response().map(Either::Right)),
+                }
+            }
+
+            let response = inner.call(Request::from_parts(parts, body)).await?;
+            Ok(response.map(Either::Left))
+        })
+    }
+}
+
+/// Verify `authorization` pays `config`'s recipient at least `required`
+///
+/// Returns the candidate signature that made the payment.
+async fn verify_authorization(
+    config: &X402Config,
+    processor: &SolanaPaymentProcessor,
+    authorization: &PaymentAuthorization,
+    required: &str,
+) -> X402Result<String> {
+    if authorization.payment_address != config.payment_address
+        || authorization.asset_address != config.token_mint
+    {
+        return Err(X402Error::PaymentVerification(
+            "Payment was not made to this server's address and token".to_string(),
+        ));
+    }
+
+    processor
+        .verify_payment_signature(authorization, required, &config.verification_options())
+        .await
+}
+
+/// Why [`X402Service`] refused a request
+#[derive(Debug)]
+pub enum PaymentError {
+    /// No payment; answered with a challenge for the request's price
+    Challenge(Box<PaymentRequest>, ChallengeFormat),
+
+    InvalidHeader,
+    InvalidAuthorization(String),
+    Expired(String),
+    Replayed(String),
+
+    /// The nonce store failed
+    Store(X402Error),
+
+    /// The server's `X402Config` is invalid
+    Configuration(X402Error),
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::Challenge(..) => write!(f, "Payment required"),
+            PaymentError::InvalidHeader => write!(f, "Invalid authorization header"),
+            PaymentError::InvalidAuthorization(msg) => {
+                write!(f, "Invalid payment authorization: {}", msg)
+            }
+            PaymentError::Expired(msg) => write!(f, "{}", msg),
+            PaymentError::Replayed(signature) => {
+                write!(f, "Transaction {} was already used", signature)
+            }
+            PaymentError::Store(e) | PaymentError::Configuration(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+impl PaymentError {
+    /// Core error this rejection corresponds to
+    fn as_x402(&self) -> X402Error {
+        match self {
+            PaymentError::Challenge(..) => X402Error::PaymentRequired(self.to_string()),
+            PaymentError::InvalidHeader | PaymentError::InvalidAuthorization(_) => {
+                X402Error::InvalidPaymentAuthorization(self.to_string())
+            }
+            PaymentError::Expired(_) => X402Error::PaymentExpired(self.to_string()),
+            PaymentError::Replayed(_) => X402Error::PaymentReplayed(self.to_string()),
+            PaymentError::Store(e) | PaymentError::Configuration(e) => e.clone(),
+        }
+    }
+
+    /// Response refusing the request
+    pub fn into_response(self) -> Response<Full<Bytes>> {
+        let error = self.as_x402();
+        let status = match self {
+            PaymentError::Store(_) | PaymentError::Configuration(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => status_code(&error),
+        };
+        let message = self.to_string();
+        let body = match self {
+            PaymentError::Challenge(payment_request, format) => {
+                return challenge_response(*payment_request, format)
+            }
+            PaymentError::Expired(_) | PaymentError::Replayed(_) => serde_json::json!({
+                "error": message,
+                "code": error.code()
+            }),
+            _ => serde_json::json!({
+                "error": message
+            }),
+        };
+        json_response(status, None, &body)
+    }
+}
+
+/// Status code for a request rejected with `error`
+fn status_code(error: &X402Error) -> StatusCode {
+    StatusCode::from_u16(error.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Create a payment request for an endpoint
+pub fn create_payment_request(
+    config: &X402Config,
+    requirement: &PaymentRequirement,
+    resource: &str,
+) -> PaymentRequest {
+    let mut request = PaymentRequest::builder()
+        .amount(requirement.amount.as_str())
+        .asset_address(config.token_mint.as_str())
+        .payment_address(config.payment_address.as_str())
+        .network(config.network.as_str())
+        .expires_at(Utc::now() + Duration::seconds(requirement.expires_in))
+        .payment_id(config.payment_ids.generate())
+        .resource(resource)
+        .build()
+        .expect("request with all required fields");
+
+    if let Some(desc) = &requirement.description {
+        request = request.with_description(desc.clone());
+    }
+    if let Some(url) = &config.facilitator_url {
+        request = request.with_facilitator_url(url.clone());
+    }
+
+    request
+}
+
+/// Helper function to create a 402 Payment Required response
+pub fn payment_required_response(payment_request: PaymentRequest) -> Response<Full<Bytes>> {
+    json_response(StatusCode::PAYMENT_REQUIRED, None, &payment_request)
+}
+
+/// Create a 402 Payment Required response in the form the client asked for
+///
+/// Inspects the request's `Accept` header and responds with the payment request
+/// as plain JSON (default), `application/problem+json`, or an empty body with
+/// the base64-encoded request in the `X-Payment-Request` header.
+pub fn payment_required_response_for(
+    headers: &HeaderMap,
+    payment_request: PaymentRequest,
+) -> Response<Full<Bytes>> {
+    let accept = headers.get(header::ACCEPT).and_then(|h| h.to_str().ok());
+    challenge_response(payment_request, ChallengeFormat::from_accept(accept))
+}
+
+/// 402 carrying `payment_request` in `format`
+fn challenge_response(
+    payment_request: PaymentRequest,
+    format: ChallengeFormat,
+) -> Response<Full<Bytes>> {
+    match format {
+        ChallengeFormat::Json => payment_required_response(payment_request),
+        ChallengeFormat::ProblemJson => json_response(
+            StatusCode::PAYMENT_REQUIRED,
+            Some(PROBLEM_JSON_CONTENT_TYPE),
+            &PaymentProblem::new(payment_request),
+        ),
+        ChallengeFormat::HeaderOnly => {
+            let encoded = payment_request.to_base64().and_then(|encoded| {
+                HeaderValue::from_str(&encoded).map_err(|e| X402Error::Serialization(e.to_string()))
+            });
+            match encoded {
+                Ok(encoded) => {
+                    let mut response = Response::new(Full::default());
+                    *response.status_mut() = StatusCode::PAYMENT_REQUIRED;
+                    response
+                        .headers_mut()
+                        .insert(PAYMENT_REQUEST_HEADER, encoded);
+                    response
+                }
+                Err(e) => json_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    None,
+                    &serde_json::json!({ "error": e.to_string() }),
+                ),
+            }
+        }
+    }
+}
+
+/// `status` response with `body` serialized as JSON
+fn json_response<T: serde::Serialize>(
+    status: StatusCode,
+    content_type: Option<&'static str>,
+    body: &T,
+) -> Response<Full<Bytes>> {
+    let (status, body) = match serde_json::to_vec(body) {
+        Ok(body) => (status, body),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({ "error": e.to_string() })
+                .to_string()
+                .into_bytes(),
+        ),
+    };
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(content_type.unwrap_or("application/json")),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Method;
+    use http_body_util::BodyExt;
+    use openlibx402_core::rpc::mock::{MockRpcBackend, TransactionFixture};
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+
+    const RECIPIENT: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+    const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn test_config() -> X402Config {
+        X402Config {
+            payment_address: RECIPIENT.to_string(),
+            token_mint: MINT.to_string(),
+            auto_verify: false,
+            ..Default::default()
+        }
+    }
+
+    fn test_pricing() -> PricingTable {
+        PricingTable::new()
+            .route(
+                "/premium",
+                PaymentRequirement::new("0.10").with_description("Premium"),
+            )
+            .route("/api/*", PaymentRequirement::new("0.05"))
+    }
+
+    fn authorization(payment_id: &str, signature: &str) -> PaymentAuthorization {
+        PaymentAuthorization::new(
+            payment_id.to_string(),
+            "0.10".to_string(),
+            RECIPIENT.to_string(),
+            MINT.to_string(),
+            "solana-devnet".to_string(),
+            signature.to_string(),
+            RECIPIENT.to_string(),
+        )
+    }
+
+    fn get_request(uri: &str, authorization: Option<&PaymentAuthorization>) -> Request<()> {
+        let mut request = Request::get(uri);
+        if let Some(authorization) = authorization {
+            request = request.header(
+                AUTHORIZATION_HEADER,
+                authorization.to_header_value().unwrap(),
+            );
+        }
+        request.body(()).unwrap()
+    }
+
+    /// Call `layer` around a service answering with the payment it was given
+    async fn call<P: Pricing>(layer: &X402Layer<P>, request: Request<()>) -> (StatusCode, Vec<u8>) {
+        let handler = service_fn(|request: Request<()>| async move {
+            let payment_id = request
+                .extensions()
+                .get::<PaymentAuthorization>()
+                .map(|authorization| authorization.payment_id.clone())
+                .unwrap_or_else(|| "free".to_string());
+            Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(payment_id))))
+        });
+
+        let response = layer.layer(handler).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, body.to_vec())
+    }
+
+    #[test]
+    fn test_create_payment_request() {
+        let requirement = PaymentRequirement::new("0.10")
+            .with_description("Premium data access")
+            .with_expires_in(600);
+        let request = create_payment_request(&test_config(), &requirement, "/api/premium");
+
+        assert_eq!(request.max_amount_required, "0.10");
+        assert_eq!(request.payment_address, RECIPIENT);
+        assert_eq!(request.asset_address, MINT);
+        assert_eq!(request.resource, "/api/premium");
+        assert_eq!(request.description.as_deref(), Some("Premium data access"));
+    }
+
+    #[tokio::test]
+    async fn test_unpaid_request_gets_payment_request() {
+        let layer = X402Layer::new(test_config(), test_pricing());
+
+        for (uri, amount) in [("/premium", "0.10"), ("/api/data", "0.05")] {
+            let (status, body) = call(&layer, get_request(uri, None)).await;
+            assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+            let request: PaymentRequest = serde_json::from_slice(&body).unwrap();
+            assert_eq!(request.max_amount_required, amount);
+            assert_eq!(request.resource, uri);
+            assert_eq!(request.payment_address, RECIPIENT);
+        }
+
+        // Unpriced requests reach the service without payment
+        let (status, body) = call(&layer, get_request("/public", None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"free");
+
+        let problem = Request::get("/premium")
+            .header(header::ACCEPT, PROBLEM_JSON_CONTENT_TYPE)
+            .body(())
+            .unwrap();
+        let (status, body) = call(&layer, problem).await;
+        assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+        let problem: PaymentProblem = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.payment_request.max_amount_required, "0.10");
+
+        let malformed = Request::get("/premium")
+            .header(AUTHORIZATION_HEADER, "not base64")
+            .body(())
+            .unwrap();
+        let (status, _) = call(&layer, malformed).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_paid_request_reaches_service() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let signature = Signature::new_unique();
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(signature).transfer(mint, recipient, 100_000));
+        let layer = X402Layer::new(
+            X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            },
+            test_pricing(),
+        )
+        .with_payment_processor(SolanaPaymentProcessor::with_backend(
+            Arc::new(backend),
+            None,
+        ));
+        let paid = |uri: &str, signature: &Signature| {
+            let authorization = PaymentAuthorization::new(
+                "pay_1".to_string(),
+                "0.10".to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            );
+            get_request(uri, Some(&authorization))
+        };
+
+        let (status, body) = call(&layer, paid("/premium", &signature)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"pay_1");
+
+        let (status, body) = call(&layer, paid("/premium", &Signature::new_unique())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_replayed_and_stale_payments() {
+        let store: Arc<dyn NonceStore> = Arc::new(openlibx402_core::MemoryStore::new());
+        let layer = X402Layer::new(test_config(), test_pricing()).with_nonce_store(store);
+        let paid = |payment_id: &str, signature: &str, age: i64| {
+            let mut authorization = authorization(payment_id, signature);
+            authorization.timestamp = Utc::now() - Duration::seconds(age);
+            get_request("/premium", Some(&authorization))
+        };
+
+        let (status, _) = call(&layer, paid("pay_1", "signature_1", 0)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(&layer, paid("pay_1", "signature_1", 0)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "PAYMENT_REPLAYED");
+
+        // The same transfer presented under a fresh payment ID is still a replay
+        let (status, _) = call(&layer, paid("pay_2", "signature_1", 0)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = call(&layer, paid("pay_2", "signature_2", 600)).await;
+        assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "PAYMENT_EXPIRED");
+    }
+
+    #[tokio::test]
+    async fn test_records_only_the_paying_signature() {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (own, other) = (Signature::new_unique(), Signature::new_unique());
+        let backend = MockRpcBackend::new()
+            .with_fixture(TransactionFixture::new(own).transfer(mint, recipient, 100_000))
+            .with_fixture(TransactionFixture::new(other).transfer(mint, recipient, 100_000));
+        let store: Arc<dyn NonceStore> = Arc::new(openlibx402_core::MemoryStore::new());
+        let layer = X402Layer::new(
+            X402Config {
+                payment_address: recipient.to_string(),
+                token_mint: mint.to_string(),
+                ..Default::default()
+            },
+            test_pricing(),
+        )
+        .with_payment_processor(SolanaPaymentProcessor::with_backend(
+            Arc::new(backend),
+            None,
+        ))
+        .with_nonce_store(store);
+        let paid = |payment_id: &str, signature: &Signature, extra: Vec<String>| {
+            let authorization = PaymentAuthorization::new(
+                payment_id.to_string(),
+                "0.10".to_string(),
+                recipient.to_string(),
+                mint.to_string(),
+                "solana-devnet".to_string(),
+                signature.to_string(),
+                Pubkey::new_unique().to_string(),
+            )
+            .with_signatures(extra);
+            get_request("/premium", Some(&authorization))
+        };
+
+        // Listing someone else's transfer as an extra candidate doesn't spend it
+        let (status, _) = call(&layer, paid("pay_1", &own, vec![other.to_string()])).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&layer, paid("pay_2", &other, vec![])).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = call(&layer, paid("pay_3", &other, vec![])).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_pricing_closure() {
+        let layer = X402Layer::new(test_config(), |request: &Parts| {
+            (request.method == Method::POST).then(|| PaymentRequirement::new("0.05"))
+        });
+
+        let (status, body) = call(&layer, get_request("/items", None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"free");
+
+        let post = Request::post("/items").body(()).unwrap();
+        let (status, body) = call(&layer, post).await;
+        assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+        let request: PaymentRequest = serde_json::from_slice(&body).unwrap();
+        assert_eq!(request.max_amount_required, "0.05");
+    }
+}
@@ -0,0 +1,166 @@
+use http::request::Parts;
+use openlibx402_core::PaymentRequest;
+
+use crate::{create_payment_request, PaymentRequirement, X402Config};
+
+/// Lookup of the price [`X402Layer`](crate::X402Layer) charges for a request
+///
+/// Implemented by [`PricingTable`] and by closures taking the request's
+/// [`Parts`], so prices can depend on the method, headers or extensions as
+/// well as the path:
+///
+/// ```rust
+/// use http::{request::Parts, Method};
+/// use openlibx402_tower::{PaymentRequirement, X402Config, X402Layer};
+///
+/// // Reads are free, writes cost 0.05
+/// let layer = X402Layer::new(X402Config::default(), |request: &Parts| {
+///     (request.method != Method::GET).then(|| PaymentRequirement::new("0.05"))
+/// });
+/// ```
+pub trait Pricing: Send + Sync + 'static {
+    /// Payment requirement for `request`, or `None` if it is free
+    fn price(&self, request: &Parts) -> Option<PaymentRequirement>;
+}
+
+impl<F> Pricing for F
+where
+    F: Fn(&Parts) -> Option<PaymentRequirement> + Send + Sync + 'static,
+{
+    fn price(&self, request: &Parts) -> Option<PaymentRequirement> {
+        self(request)
+    }
+}
+
+/// Route prices for payment-protected paths
+///
+/// Patterns are exact paths (`/premium`) or prefix wildcards (`/api/*`, which
+/// matches `/api` and everything below it). When several patterns match, the
+/// most specific one wins: exact paths beat wildcards, and longer prefixes beat
+/// shorter ones.
+///
+/// By default only listed routes are paid. In deny-by-default mode (see
+/// [`PricingTable::paid_by_default`]) every route is paid unless it is
+/// explicitly marked free.
+#[derive(Debug, Clone, Default)]
+pub struct PricingTable {
+    routes: Vec<(String, Option<PaymentRequirement>)>,
+    default_requirement: Option<PaymentRequirement>,
+}
+
+impl PricingTable {
+    /// Create an empty, opt-in pricing table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Charge `requirement` for paths matching `pattern`
+    pub fn route(mut self, pattern: &str, requirement: PaymentRequirement) -> Self {
+        self.routes.push((pattern.to_string(), Some(requirement)));
+        self
+    }
+
+    /// Serve paths matching `pattern` without payment
+    pub fn free(mut self, pattern: &str) -> Self {
+        self.routes.push((pattern.to_string(), None));
+        self
+    }
+
+    /// Charge `requirement` for every path that no pattern matches
+    pub fn paid_by_default(mut self, requirement: PaymentRequirement) -> Self {
+        self.default_requirement = Some(requirement);
+        self
+    }
+
+    /// Whether unmatched paths require payment
+    pub fn default_paid(&self) -> bool {
+        self.default_requirement.is_some()
+    }
+
+    /// Payment requirement for `path`, or `None` if it is free
+    pub fn requirement_for(&self, path: &str) -> Option<&PaymentRequirement> {
+        self.routes
+            .iter()
+            .filter_map(|(pattern, requirement)| {
+                specificity(pattern, path).map(|score| (score, requirement))
+            })
+            .max_by_key(|(score, _)| *score)
+            .map_or(self.default_requirement.as_ref(), |(_, requirement)| {
+                requirement.as_ref()
+            })
+    }
+
+    /// Payment request to answer an unpaid request for `path` with
+    ///
+    /// `None` when `path` is free or matches no priced route, i.e. the
+    /// request should be served (or 404) without asking for payment.
+    pub fn payment_request_for(&self, config: &X402Config, path: &str) -> Option<PaymentRequest> {
+        self.requirement_for(path)
+            .map(|requirement| create_payment_request(config, requirement, path))
+    }
+}
+
+impl Pricing for PricingTable {
+    fn price(&self, request: &Parts) -> Option<PaymentRequirement> {
+        self.requirement_for(request.uri.path()).cloned()
+    }
+}
+
+/// How specifically `pattern` matches `path`, or `None` if it doesn't
+fn specificity(pattern: &str, path: &str) -> Option<usize> {
+    match pattern.strip_suffix("/*") {
+        Some(prefix) => {
+            let matches = path == prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'));
+            matches.then_some(prefix.len())
+        }
+        None => (pattern == path).then_some(usize::MAX),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opt_in_pricing() {
+        let pricing = PricingTable::new()
+            .route("/premium", PaymentRequirement::new("0.10"))
+            .route("/api/*", PaymentRequirement::new("0.05"))
+            .free("/api/health");
+
+        assert_eq!(pricing.requirement_for("/premium").unwrap().amount, "0.10");
+        assert_eq!(pricing.requirement_for("/api/data").unwrap().amount, "0.05");
+        assert!(pricing.requirement_for("/api/health").is_none());
+        assert!(pricing.requirement_for("/apiary").is_none());
+        assert!(pricing.requirement_for("/public").is_none());
+    }
+
+    #[test]
+    fn test_deny_by_default() {
+        let pricing = PricingTable::new()
+            .route("/premium", PaymentRequirement::new("0.10"))
+            .free("/health")
+            .paid_by_default(PaymentRequirement::new("0.01"));
+
+        assert!(pricing.default_paid());
+        assert_eq!(pricing.requirement_for("/premium").unwrap().amount, "0.10");
+        assert_eq!(pricing.requirement_for("/unlisted").unwrap().amount, "0.01");
+        assert!(pricing.requirement_for("/health").is_none());
+    }
+
+    #[test]
+    fn test_price_request_parts() {
+        let pricing = PricingTable::new().route("/premium", PaymentRequirement::new("0.10"));
+        let parts = |uri: &str| http::Request::get(uri).body(()).unwrap().into_parts().0;
+
+        // Query strings don't change the route's price
+        assert_eq!(
+            pricing.price(&parts("/premium?page=2")).unwrap().amount,
+            "0.10"
+        );
+        assert!(pricing.price(&parts("/public")).is_none());
+    }
+}